@@ -7,6 +7,7 @@ extern crate test;
 #[macro_use]
 extern crate prost_amino_derive;
 
+mod reserve;
 mod varint;
 
 use prost::Message;
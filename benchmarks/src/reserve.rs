@@ -0,0 +1,44 @@
+use test;
+
+use prost::Message;
+
+/// A message of about 100 KB, whose encoding reallocates an empty `Vec` many times.
+#[derive(Clone, PartialEq, Message)]
+pub struct Large {
+    #[prost_amino(string, repeated, tag = "1")]
+    pub names: Vec<String>,
+    #[prost_amino(int64, repeated, tag = "2")]
+    pub values: Vec<i64>,
+}
+
+fn large() -> Large {
+    Large {
+        names: (0..2000).map(|i| format!("name {}", i)).collect(),
+        values: (0..10_000).collect(),
+    }
+}
+
+/// Benchmark encoding into a new `Vec`, which grows as the fields are written.
+#[bench]
+fn encode_large_growing(b: &mut test::Bencher) {
+    let message = large();
+    b.iter(|| {
+        let mut buf = Vec::new();
+        message.encode(&mut buf).unwrap();
+        test::black_box(buf);
+    });
+    b.bytes = message.encoded_len() as u64;
+}
+
+/// Benchmark encoding into a new `Vec` with `encode_append`, which reserves the encoded length
+/// up front.
+#[bench]
+fn encode_large_reserved(b: &mut test::Bencher) {
+    let message = large();
+    b.iter(|| {
+        let mut buf = Vec::new();
+        message.encode_append(&mut buf);
+        test::black_box(buf);
+    });
+    b.bytes = message.encoded_len() as u64;
+}
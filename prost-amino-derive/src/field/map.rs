@@ -33,7 +33,31 @@ fn fake_scalar(ty: scalar::Ty) -> scalar::Field {
         ty,
         kind,
         tag: 0, // Not used here
+        amino_name: None,
         amino_prefix: vec![],
+        wire: None,
+        max_len: None,
+        cow: false,
+        delta: false,
+        stream: false,
+        default_unknown: false,
+        closed: false,
+        trim: false,
+        ascii: false,
+        zstd: false,
+        shared: false,
+        fixed_or_var: false,
+        varint_width: None,
+        varint_codec: None,
+        epoch: false,
+        conversion: None,
+        range: None,
+        checked: false,
+        clamp: None,
+        dedup: false,
+        sorted: false,
+        big_endian: false,
+        chunk: None,
     }
 }
 
@@ -168,17 +192,17 @@ impl Field {
             ValueTy::Scalar(scalar::Ty::Enumeration(ref ty)) => {
                 let default = quote!(#ty::default() as i32);
                 quote! {
-                    _prost::encoding::#module::merge_with_default(#km, _prost::encoding::int32::merge,
+                    _prost::encoding::#module::merge_with_default(wire_type, #km, _prost::encoding::int32::merge,
                                                                   #default, &mut #ident, buf)
                 }
             }
             ValueTy::Scalar(ref value_ty) => {
                 let val_mod = value_ty.module();
                 let vm = quote!(_prost::encoding::#val_mod::merge);
-                quote!(_prost::encoding::#module::merge(#km, #vm, &mut #ident, buf))
+                quote!(_prost::encoding::#module::merge(wire_type, #km, #vm, &mut #ident, buf))
             }
             ValueTy::Message => {
-                quote!(_prost::encoding::#module::merge(#km, _prost::encoding::message::merge,
+                quote!(_prost::encoding::#module::merge(wire_type, #km, _prost::encoding::message::merge,
                                                         &mut #ident, buf))
             }
         }
@@ -211,10 +235,54 @@ impl Field {
         }
     }
 
+    /// Returns a statement which passes the key and then the value of each entry to `visitor`.
+    pub fn visit(&self, ident: TokenStream, name: &str) -> TokenStream {
+        let tag = self.tag;
+        let key = self.key_ty.scalar_value(quote!(key));
+        let visit_value = match self.value_ty {
+            ValueTy::Scalar(ref value_ty) => {
+                let value = value_ty.scalar_value(quote!(value));
+                quote!(visitor.visit_scalar(#tag, #name, #value);)
+            }
+            ValueTy::Message => quote!(visitor.visit_message(#tag, #name, value);),
+        };
+        quote! {
+            for (key, value) in &#ident {
+                visitor.visit_map_key(#tag, #name, #key);
+                #visit_value
+            }
+        }
+    }
+
+    /// Returns an expression which evaluates to the Amino JSON object of the map, keyed by the
+    /// keys as strings.
+    pub fn json(&self, ident: TokenStream) -> TokenStream {
+        let value = match self.value_ty {
+            ValueTy::Scalar(ref value_ty) => value_ty.json_value(quote!(value)),
+            ValueTy::Message => quote!(_prost::encoding::json::AminoJson::to_json(value)),
+        };
+        quote! {
+            _prost::serde_json::Value::Object(
+                #ident.iter().map(|(key, value)| (key.to_string(), #value)).collect(),
+            )
+        }
+    }
+
+    /// Returns an expression which evaluates to `true` if the map is empty.
+    pub fn is_default(&self, ident: TokenStream) -> TokenStream {
+        quote!(#ident.is_empty())
+    }
+
     pub fn clear(&self, ident: TokenStream) -> TokenStream {
         quote!(#ident.clear())
     }
 
+    /// Returns a statement which merges the entries of `other` into the map, overwriting
+    /// existing keys.
+    pub fn merge_from(&self, ident: TokenStream, other: TokenStream) -> TokenStream {
+        quote!(#ident.extend(#other);)
+    }
+
     /// Returns methods to embed in the message.
     pub fn methods(&self, ident: &Ident) -> Option<TokenStream> {
         if let ValueTy::Scalar(scalar::Ty::Enumeration(ref ty)) = self.value_ty {
@@ -11,16 +11,38 @@ use super::compute_disfix;
 pub struct Field {
     pub label: Label,
     pub tag: u32,
+    /// The registered amino name of the field type, if any.
+    pub amino_name: Option<String>,
     // this is to be able to de/encode registered type aliases:
     pub amino_prefix: Vec<u8>,
+    /// Whether the field is a oneof variant, which must be encoded even when empty.
+    pub oneof: bool,
+    /// Whether the field holds a `Duration`, which is validated when decoded.
+    pub duration: bool,
+    /// Whether the message is framed by start group and end group keys, instead of a length
+    /// delimiter.
+    pub group: bool,
+    /// Whether the fields of the message are encoded directly in the enclosing message, with
+    /// their own tags, instead of as a single field.
+    pub flatten: bool,
+    /// Whether the field holds a `Lazy` message, which is decoded when it is first accessed.
+    pub lazy: bool,
+    /// Whether the fields of the message are written directly in the Amino JSON object of the
+    /// enclosing message, instead of as a nested object.
+    pub json_inline: bool,
 }
 
 impl Field {
     pub fn new(attrs: &[Meta], inferred_tag: Option<u32>) -> Result<Option<Field>, Error> {
         let mut message = false;
+        let mut duration = false;
+        let mut group = false;
         let mut label = None;
         let mut tag = None;
         let mut boxed = false;
+        let mut flatten = false;
+        let mut lazy = false;
+        let mut json_inline = false;
         let mut amino_name = None;
 
         let mut unknown_attrs = Vec::new();
@@ -28,8 +50,20 @@ impl Field {
         for attr in attrs {
             if word_attr("message", attr) {
                 set_bool(&mut message, "duplicate message attribute")?;
+            } else if word_attr("duration", attr) {
+                set_bool(&mut duration, "duplicate duration attribute")?;
+            } else if word_attr("group", attr) {
+                set_bool(&mut group, "duplicate group attribute")?;
             } else if word_attr("boxed", attr) {
+                // A boxed field holds a `Box<M>`, e.g. of a recursive message type, which
+                // implements `Message` by delegating to `M`, so it is encoded like any other.
                 set_bool(&mut boxed, "duplicate boxed attribute")?;
+            } else if word_attr("flatten", attr) {
+                set_bool(&mut flatten, "duplicate flatten attribute")?;
+            } else if word_attr("lazy", attr) {
+                set_bool(&mut lazy, "duplicate lazy attribute")?;
+            } else if word_attr("json_inline", attr) {
+                set_bool(&mut json_inline, "duplicate json_inline attribute")?;
             } else if let Some(t) = tag_attr(attr)? {
                 set_option(&mut tag, t, "duplicate tag attributes")?;
             } else if let Some(l) = Label::from_attr(attr) {
@@ -41,8 +75,10 @@ impl Field {
             }
         }
 
-        if !message {
-            return Ok(None);
+        match (message, duration, group) {
+            (false, false, false) => return Ok(None),
+            (true, false, false) | (false, true, false) | (false, false, true) => (),
+            _ => bail!("message, duration and group attributes may not be combined"),
         }
 
         match unknown_attrs.len() {
@@ -54,13 +90,68 @@ impl Field {
             _ => bail!("unknown attributes for message field: {:?}", unknown_attrs),
         }
 
+        // A lazy field holds the encoded fields of an unregistered message, which are copied as a
+        // single length-delimited value.
+        if lazy {
+            if !message {
+                bail!("lazy attribute may only be used on message fields");
+            }
+            if boxed || flatten || amino_name.is_some() {
+                bail!("lazy message fields may not be boxed, flattened or have an amino_name");
+            }
+        }
+
+        // An inlined message is written as part of the JSON object of the enclosing message, so
+        // there is at most one of it, and it has no name of its own to be wrapped with.
+        if json_inline {
+            if !message || lazy {
+                bail!("json_inline attribute may only be used on message fields");
+            }
+            if label == Some(Label::Repeated) || amino_name.is_some() {
+                bail!("json_inline message fields may not be repeated or have an amino_name");
+            }
+        }
+
+        // A flattened message has no tag or label of its own; its fields are dispatched by the
+        // tags of the message type, which must not collide with those of the enclosing message.
+        if flatten {
+            if !message {
+                bail!("flatten attribute may only be used on message fields");
+            }
+            if tag.is_some() {
+                bail!("flattened message fields may not have a tag attribute");
+            }
+            if label.is_some() {
+                bail!("flattened message fields may not have a label attribute");
+            }
+            if boxed || amino_name.is_some() {
+                bail!("flattened message fields may not be boxed or have an amino_name");
+            }
+            return Ok(Some(Field {
+                label: Label::Required,
+                tag: 0,
+                amino_prefix: vec![],
+                oneof: false,
+                duration,
+                group,
+                flatten,
+                lazy,
+                json_inline,
+                amino_name,
+            }));
+        }
+
         let tag = match tag.or(inferred_tag) {
             Some(tag) => tag,
             None => bail!("message field is missing a tag attribute"),
         };
 
+        if group && amino_name.is_some() {
+            bail!("group fields may not have an amino_name");
+        }
+
         let amino_prefix: Vec<u8> = match amino_name {
-            Some(n) => {
+            Some(ref n) => {
                 let (_dis, pre) = compute_disfix(n.as_str());
                 pre
             }
@@ -71,6 +162,13 @@ impl Field {
             label: label.unwrap_or(Label::Optional),
             tag: tag,
             amino_prefix: amino_prefix,
+            oneof: false,
+            duration,
+            group,
+            flatten,
+            lazy,
+            json_inline,
+            amino_name,
         }))
     }
 
@@ -82,69 +180,247 @@ impl Field {
                     attr.path().into_token_stream()
                 );
             }
+            if field.group {
+                bail!("invalid group attribute on oneof field");
+            }
+            if field.flatten {
+                bail!("invalid flatten attribute on oneof field");
+            }
+            if field.json_inline {
+                bail!("invalid json_inline attribute on oneof field");
+            }
             field.label = Label::Required;
+            field.oneof = true;
             Ok(Some(field))
         } else {
             Ok(None)
         }
     }
 
+    /// Returns the encoding module used for the field.
+    fn module(&self) -> TokenStream {
+        if self.duration {
+            quote!(_prost::encoding::duration)
+        } else if self.group {
+            quote!(_prost::encoding::group)
+        } else if self.lazy {
+            quote!(_prost::encoding::lazy)
+        } else {
+            quote!(_prost::encoding::message)
+        }
+    }
+
     pub fn encode(&self, ident: TokenStream) -> TokenStream {
+        let module = self.module();
         let tag = self.tag;
         let amino_prefix = &self.amino_prefix;
+        if self.flatten {
+            return quote!(_prost::Message::encode_raw(&#ident, buf););
+        }
         match self.label {
+            // An optional message is encoded whenever it is set, even if it is empty, so that
+            // `Some(Default::default())` and `None` are distinct on the wire.
             Label::Optional => quote! {
                 if let Some(ref msg) = #ident {
-                    _prost::encoding::message::encode(#tag, msg, buf);
+                    #module::encode(#tag, msg, buf);
                 }
             },
-            Label::Required => quote! {
+            // Amino writes the prefix of a registered message even when it is empty, so only
+            // empty unregistered messages are skipped.
+            Label::Required if self.oneof || !amino_prefix.is_empty() => quote! {
                 let pre = vec![#(#amino_prefix),*];
                 buf.put(pre.as_ref());
-                _prost::encoding::message::encode(#tag, &#ident, buf);
+                #module::encode(#tag, &#ident, buf);
+            },
+            Label::Required => quote! {
+                if _prost::encoding::message::is_encoded(&#ident) {
+                    #module::encode(#tag, &#ident, buf);
+                }
             },
             Label::Repeated => quote! {
                 for msg in &#ident {
                     let pre = vec![#(#amino_prefix),*];
                     buf.put(pre.as_ref());
-                    _prost::encoding::message::encode(#tag, msg, buf);
+                    #module::encode(#tag, msg, buf);
                 }
             },
         }
     }
 
     pub fn merge(&self, ident: TokenStream) -> TokenStream {
+        let module = self.module();
+        // The end of a group is marked by a key with the tag of the field.
+        let tag = Some(self.tag).filter(|_| self.group).into_iter();
         match self.label {
             Label::Optional => quote! {
-                _prost::encoding::message::merge(wire_type,
+                #module::merge(#(#tag,)* wire_type,
                                                  #ident.get_or_insert_with(Default::default),
                                                  buf)
             },
             Label::Required => quote! {
-                _prost::encoding::message::merge(wire_type, &mut #ident, buf)
+                #module::merge(#(#tag,)* wire_type, &mut #ident, buf)
             },
             Label::Repeated => quote! {
-                _prost::encoding::message::merge_repeated(wire_type, &mut #ident, buf)
+                #module::merge_repeated(#(#tag,)* wire_type, &mut #ident, buf)
+            },
+        }
+    }
+
+    /// Returns an expression which evaluates to the result of merging a message decoded from a
+    /// `Bytes` into the field, sharing the buffer with the `shared` fields of the message.
+    pub fn merge_shared(&self, ident: TokenStream) -> TokenStream {
+        if self.duration || self.group || self.lazy {
+            return self.merge(ident);
+        }
+        match self.label {
+            Label::Optional => quote! {
+                _prost::encoding::message::merge_shared(wire_type,
+                                                        #ident.get_or_insert_with(Default::default),
+                                                        buf)
+            },
+            Label::Required => quote! {
+                _prost::encoding::message::merge_shared(wire_type, &mut #ident, buf)
+            },
+            Label::Repeated => quote! {
+                _prost::encoding::message::merge_repeated_shared(wire_type, &mut #ident, buf)
             },
         }
     }
 
     pub fn encoded_len(&self, ident: TokenStream) -> TokenStream {
+        let module = self.module();
         let tag = self.tag;
-        let pl: usize = self.amino_prefix.len();
+        // A registered message type counts its own prefix, but the prefix of the field's
+        // `amino_name` is written ahead of each non-optional value by `encode`.
+        let pl = Some(self.amino_prefix.len())
+            .filter(|&len| len > 0)
+            .into_iter();
+        if self.flatten {
+            return quote!(_prost::Message::encoded_len(&#ident));
+        }
         match self.label {
             Label::Optional => quote! {
-                #ident.as_ref().map_or(0, |msg| _prost::encoding::message::encoded_len(#tag, msg) + #pl)
+                #ident.as_ref().map_or(0, |msg| #module::encoded_len(#tag, msg))
+            },
+            Label::Required if self.oneof || !self.amino_prefix.is_empty() => quote! {
+                #module::encoded_len(#tag, &#ident) #(+ #pl)*
             },
             Label::Required => quote! {
-                _prost::encoding::message::encoded_len(#tag, &#ident)
+                if _prost::encoding::message::is_encoded(&#ident) {
+                    #module::encoded_len(#tag, &#ident)
+                } else {
+                    0
+                }
             },
             Label::Repeated => quote! {
-                _prost::encoding::message::encoded_len_repeated(#tag, &#ident)
+                #module::encoded_len_repeated(#tag, &#ident) #(+ #pl * #ident.len())*
             },
         }
     }
 
+    /// Returns an expression which evaluates to `true` if the field holds its default value.
+    pub fn is_default(&self, ident: TokenStream) -> TokenStream {
+        match self.label {
+            Label::Optional => quote!(#ident.is_none()),
+            Label::Required => quote!(_prost::Message::is_empty(&#ident)),
+            Label::Repeated => quote!(#ident.is_empty()),
+        }
+    }
+
+    /// Returns a statement which merges the message in `other` into the field, recursing into
+    /// messages which are set in both, and returns any error merging them.
+    pub fn merge_from(&self, ident: TokenStream, other: TokenStream) -> TokenStream {
+        match self.label {
+            Label::Optional => quote! {
+                if let ::std::option::Option::Some(msg) = #other {
+                    match #ident {
+                        ::std::option::Option::Some(ref mut value) => {
+                            _prost::Message::merge_from(value, msg)?
+                        }
+                        ::std::option::Option::None => {
+                            #ident = ::std::option::Option::Some(msg)
+                        }
+                    }
+                }
+            },
+            Label::Required => quote!(_prost::Message::merge_from(&mut #ident, #other)?;),
+            Label::Repeated => quote!(#ident.extend(#other);),
+        }
+    }
+
+    pub fn validate(&self, ident: TokenStream) -> Option<TokenStream> {
+        if self.duration {
+            return None;
+        }
+        Some(match self.label {
+            Label::Optional => quote! {
+                match #ident {
+                    ::std::option::Option::Some(ref msg) => _prost::Message::validate(msg),
+                    ::std::option::Option::None => ::std::result::Result::Ok(()),
+                }
+            },
+            Label::Required => quote!(_prost::Message::validate(&#ident)),
+            Label::Repeated => quote! {
+                #ident.iter().enumerate().try_for_each(|(index, msg)| {
+                    _prost::Message::validate(msg).map_err(|mut error| {
+                        error.push_index(index);
+                        error
+                    })
+                })
+            },
+        })
+    }
+
+    /// Returns a statement which passes each value of the field to `visitor`. The fields of a
+    /// flattened message are visited directly.
+    pub fn visit(&self, ident: TokenStream, name: &str) -> TokenStream {
+        let tag = self.tag;
+        if self.flatten {
+            return quote!(_prost::Message::visit_fields(&#ident, visitor););
+        }
+        match self.label {
+            Label::Optional => quote! {
+                if let ::std::option::Option::Some(ref value) = #ident {
+                    visitor.visit_message(#tag, #name, value);
+                }
+            },
+            Label::Required => quote!(visitor.visit_message(#tag, #name, &#ident);),
+            Label::Repeated => quote! {
+                for value in &#ident {
+                    visitor.visit_message(#tag, #name, value);
+                }
+            },
+        }
+    }
+
+    /// Returns an expression which evaluates to the Amino JSON value of the field, or a statement
+    /// which writes the fields of a `json_inline` field into `object`.
+    pub fn json(&self, ident: TokenStream) -> Result<TokenStream, Error> {
+        if self.duration || self.lazy {
+            bail!("duration and lazy message fields have no Amino JSON form");
+        }
+        let to_json = quote!(_prost::encoding::json::AminoJson::to_json);
+        let write_fields = quote!(_prost::encoding::json::AminoJson::write_fields);
+        Ok(match self.label {
+            Label::Optional if self.json_inline => quote! {
+                if let ::std::option::Option::Some(ref value) = #ident {
+                    #write_fields(value, object);
+                }
+            },
+            Label::Required if self.json_inline => quote!(#write_fields(&#ident, object);),
+            Label::Optional => quote! {
+                match #ident {
+                    ::std::option::Option::Some(ref value) => #to_json(value),
+                    ::std::option::Option::None => _prost::serde_json::Value::Null,
+                }
+            },
+            Label::Required => quote!(#to_json(&#ident)),
+            Label::Repeated => quote! {
+                _prost::serde_json::Value::Array(#ident.iter().map(#to_json).collect())
+            },
+        })
+    }
+
     pub fn clear(&self, ident: TokenStream) -> TokenStream {
         match self.label {
             Label::Optional => quote!(#ident = ::std::option::Option::None),
@@ -15,7 +15,7 @@ use super::compute_disfix;
 #[derive(Clone)]
 pub enum Field {
     /// A scalar field.
-    Scalar(scalar::Field),
+    Scalar(Box<scalar::Field>),
     /// A message field.
     Message(message::Field),
     /// A map field.
@@ -30,12 +30,16 @@ impl Field {
     /// If the meta items are invalid, an error will be returned.
     /// If the field should be ignored, `None` is returned.
     pub fn new(attrs: Vec<Attribute>, inferred_tag: Option<u32>) -> Result<Option<Field>, Error> {
-        let attrs = prost_attrs(attrs)?;
+        // `deprecated_wire` only affects how the message encodes the field.
+        let attrs = prost_attrs(attrs)?
+            .into_iter()
+            .filter(|attr| !word_attr("deprecated_wire", attr))
+            .collect::<Vec<_>>();
 
         // TODO: check for ignore attribute.
 
         let field = if let Some(field) = scalar::Field::new(&attrs, inferred_tag)? {
-            Field::Scalar(field)
+            Field::Scalar(Box::new(field))
         } else if let Some(field) = message::Field::new(&attrs, inferred_tag)? {
             Field::Message(field)
         } else if let Some(field) = map::Field::new(&attrs, inferred_tag)? {
@@ -59,7 +63,7 @@ impl Field {
         // TODO: check for ignore attribute.
 
         let field = if let Some(field) = scalar::Field::new_oneof(&attrs)? {
-            Field::Scalar(field)
+            Field::Scalar(Box::new(field))
         } else if let Some(field) = message::Field::new_oneof(&attrs)? {
             Field::Message(field)
         } else if let Some(field) = map::Field::new_oneof(&attrs)? {
@@ -74,6 +78,7 @@ impl Field {
     pub fn tags(&self) -> Vec<u32> {
         match *self {
             Field::Scalar(ref scalar) => vec![scalar.tag],
+            Field::Message(ref message) if message.flatten => vec![],
             Field::Message(ref message) => vec![message.tag],
             Field::Map(ref map) => vec![map.tag],
             Field::Oneof(ref oneof) => oneof.tags.clone(),
@@ -111,7 +116,252 @@ impl Field {
         }
     }
 
+    /// Returns an expression which evaluates to `true` if the field holds its default value.
+    pub fn is_default(&self, ident: TokenStream) -> TokenStream {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.is_default(ident),
+            Field::Message(ref message) => message.is_default(ident),
+            Field::Map(ref map) => map.is_default(ident),
+            Field::Oneof(ref oneof) => oneof.is_default(ident),
+        }
+    }
+
+    /// Returns an expression which evaluates to the result of merging a value decoded from a
+    /// `&'a [u8]` slice into the field.
+    pub fn merge_borrowed(&self, ident: TokenStream) -> TokenStream {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.merge_borrowed(ident),
+            _ => self.merge(ident),
+        }
+    }
+
+    /// Returns an expression which evaluates to the result of merging a value decoded from a
+    /// `Bytes` into the field.
+    pub fn merge_shared(&self, ident: TokenStream) -> TokenStream {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.merge_shared(ident),
+            Field::Message(ref message) => message.merge_shared(ident),
+            _ => self.merge(ident),
+        }
+    }
+
+    /// Returns `true` if the field is a message which may be registered, i.e. whose prefix is
+    /// given by its type rather than by an `amino_name` attribute. Whether its type is
+    /// registered, i.e. has a non-empty prefix, is only known when it is compiled.
+    pub fn is_registered_message(&self) -> bool {
+        match *self {
+            Field::Message(ref message) => {
+                !message.duration && !message.lazy && message.amino_prefix.is_empty()
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the field is a plain or required scalar field, whose presence is tracked
+    /// by a message with the `hazzers` attribute.
+    pub fn is_singular_scalar(&self) -> bool {
+        match *self {
+            Field::Scalar(ref scalar) => matches!(
+                scalar.kind,
+                scalar::Kind::Plain(..) | scalar::Kind::Required(..)
+            ),
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the field is compared with `BitEq` by messages with the `bit_eq`
+    /// attribute: `float` and `double` scalar fields, maps with `float` or `double` values, and
+    /// oneof fields.
+    pub fn is_bit_eq(&self) -> bool {
+        let is_float = |ty: &scalar::Ty| *ty == scalar::Ty::Float || *ty == scalar::Ty::Double;
+        match *self {
+            Field::Scalar(ref scalar) => is_float(&scalar.ty),
+            Field::Map(ref map) => match map.value_ty {
+                map::ValueTy::Scalar(ref ty) => is_float(ty),
+                map::ValueTy::Message => false,
+            },
+            Field::Oneof(..) => true,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the field is a flattened message, whose fields are encoded with their own
+    /// tags in the enclosing message.
+    pub fn is_flattened(&self) -> bool {
+        match *self {
+            Field::Message(ref message) => message.flatten,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the field is a `cow` field, which borrows from a decoded slice.
+    pub fn is_cow(&self) -> bool {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.cow,
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the field may share the buffer passed to `decode_from_bytes`, i.e. if it
+    /// is a `shared` field or a message which may have `shared` fields.
+    pub fn is_shared(&self) -> bool {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.shared,
+            Field::Message(ref message) => {
+                !message.duration && !message.group && !message.flatten && !message.lazy
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the registered amino name and prefix of the field type, if any.
+    pub fn amino_name(&self) -> Option<(&str, &[u8])> {
+        let (name, prefix) = match *self {
+            Field::Scalar(ref scalar) => (&scalar.amino_name, &scalar.amino_prefix),
+            Field::Message(ref message) => (&message.amino_name, &message.amino_prefix),
+            _ => return None,
+        };
+        name.as_ref().map(|name| (&name[..], &prefix[..]))
+    }
+
+    /// Returns a `FieldDescriptor` expression for each tag of the field, whose Rust type is
+    /// `field_ty`.
+    pub fn descriptors(&self, name: &str, field_ty: &syn::Type) -> Vec<(u32, TokenStream)> {
+        let type_name = match *self {
+            Field::Scalar(ref scalar) => match scalar.ty {
+                scalar::Ty::Enumeration(ref path) => path_name(path),
+                _ => String::new(),
+            },
+            Field::Message(_) => inner_type_name(field_ty),
+            Field::Map(ref map) => {
+                let value = match map.value_ty {
+                    map::ValueTy::Scalar(scalar::Ty::Enumeration(ref path)) => path_name(path),
+                    map::ValueTy::Scalar(ref ty) => ty.as_str().to_owned(),
+                    map::ValueTy::Message => inner_type_name(field_ty),
+                };
+                format!("map<{}, {}>", map.key_ty.as_str(), value)
+            }
+            Field::Oneof(ref oneof) => path_name(&oneof.ty),
+        };
+        let amino_name = self.amino_name().map_or("", |(name, _)| name);
+        let mut default = None;
+        let (ty, label, amino_prefix) = match *self {
+            Field::Scalar(ref scalar) => {
+                // The default of an `epoch` field is the unix epoch, encoded as zero, that of an
+                // `as` field is given by its type, and that of a `json` field is `null`.
+                let parsed = |value: &scalar::DefaultValue| {
+                    if scalar.epoch {
+                        Some(quote!(Int(0)))
+                    } else if let Some(conversion) = scalar.conversion {
+                        Some(conversion.parsed_default())
+                    } else if scalar.ty == scalar::Ty::Json {
+                        Some(quote!(Str("null")))
+                    } else {
+                        value.parsed(&scalar.ty)
+                    }
+                };
+                let label = match scalar.kind {
+                    scalar::Kind::Plain(ref value) | scalar::Kind::Optional(ref value) => {
+                        default = parsed(value);
+                        quote!(Optional)
+                    }
+                    scalar::Kind::Required(ref value) => {
+                        default = parsed(value);
+                        quote!(Required)
+                    }
+                    scalar::Kind::Repeated
+                    | scalar::Kind::Packed
+                    | scalar::Kind::OptionalRepeated => {
+                        quote!(Repeated)
+                    }
+                };
+                (scalar.ty.as_str(), label, &scalar.amino_prefix[..])
+            }
+            Field::Message(ref message) => {
+                let label = match message.label {
+                    Label::Optional => quote!(Optional),
+                    Label::Required => quote!(Required),
+                    Label::Repeated => quote!(Repeated),
+                };
+                let ty = if message.duration {
+                    "duration"
+                } else if message.group {
+                    "group"
+                } else {
+                    "message"
+                };
+                (ty, label, &message.amino_prefix[..])
+            }
+            Field::Map(_) => ("map", quote!(Repeated), &[][..]),
+            Field::Oneof(_) => ("oneof", quote!(Optional), &[][..]),
+        };
+        let default = match default {
+            Some(default) => quote!(::std::option::Option::Some(_prost::ParsedDefault::#default)),
+            None => quote!(::std::option::Option::None),
+        };
+        self.tags()
+            .into_iter()
+            .map(|tag| {
+                let descriptor = quote! {
+                    _prost::FieldDescriptor {
+                        tag: #tag,
+                        name: #name,
+                        ty: #ty,
+                        label: _prost::Label::#label,
+                        amino_prefix: &[#(#amino_prefix),*],
+                        amino_name: #amino_name,
+                        type_name: #type_name,
+                        default: #default,
+                    }
+                };
+                (tag, descriptor)
+            })
+            .collect()
+    }
+
+    /// Returns a statement which merges the field of `other` into the field.
+    pub fn merge_from(&self, ident: TokenStream, other: TokenStream) -> TokenStream {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.merge_from(ident, other),
+            Field::Message(ref message) => message.merge_from(ident, other),
+            Field::Map(ref map) => map.merge_from(ident, other),
+            Field::Oneof(ref oneof) => oneof.merge_from(ident, other),
+        }
+    }
+
+    /// Returns a statement which completes the merge of the field once every field of the message
+    /// has been merged, or `None` if there is nothing to complete.
+    pub fn finish_merge(&self, ident: TokenStream) -> Option<TokenStream> {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.finish_merge(ident),
+            // The fields of a flattened message are merged by the enclosing message.
+            Field::Message(ref message) if message.flatten => {
+                Some(quote!(_prost::Message::finish_merge(&mut #ident);))
+            }
+            _ => None,
+        }
+    }
+
     /// Returns a statement which clears the field.
+    /// Returns an expression which evaluates to the Amino JSON value of the field, or for a
+    /// `json_inline` field, a statement which writes its fields into `object`.
+    pub fn json(&self, ident: TokenStream) -> Result<TokenStream, Error> {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.json(ident),
+            Field::Message(ref message) => message.json(ident),
+            Field::Map(ref map) => Ok(map.json(ident)),
+            Field::Oneof(..) => bail!("oneof fields have no Amino JSON form"),
+        }
+    }
+
+    /// Returns true if the field is a message field with the `json_inline` attribute.
+    pub fn is_json_inline(&self) -> bool {
+        match *self {
+            Field::Message(ref message) => message.json_inline,
+            _ => false,
+        }
+    }
+
     pub fn clear(&self, ident: TokenStream) -> TokenStream {
         match *self {
             Field::Scalar(ref scalar) => scalar.clear(ident),
@@ -121,6 +371,49 @@ impl Field {
         }
     }
 
+    /// Returns a statement which passes each value of the field, named `name`, to `visitor`.
+    pub fn visit(&self, ident: TokenStream, name: &str) -> TokenStream {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.visit(ident, name),
+            Field::Message(ref message) => message.visit(ident, name),
+            Field::Map(ref map) => map.visit(ident, name),
+            Field::Oneof(ref oneof) => oneof.visit(ident),
+        }
+    }
+
+    /// Returns a statement which pushes the tag, name and encoded length of the field to
+    /// `breakdown`. A flattened message pushes each of its own fields, and a oneof its set
+    /// variant, named by the variant.
+    pub fn size_breakdown(&self, ident: TokenStream, name: &str) -> TokenStream {
+        let tag = match *self {
+            Field::Scalar(ref scalar) => scalar.tag,
+            Field::Message(ref message) if message.flatten => {
+                return quote!(breakdown.extend(_prost::Message::size_breakdown(&#ident)););
+            }
+            Field::Message(ref message) => message.tag,
+            Field::Map(ref map) => map.tag,
+            Field::Oneof(..) => {
+                return quote! {
+                    if let ::std::option::Option::Some(ref oneof) = #ident {
+                        breakdown.push(oneof.size_breakdown());
+                    }
+                };
+            }
+        };
+        let encoded_len = self.encoded_len(ident);
+        quote!(breakdown.push((#tag, #name, #encoded_len));)
+    }
+
+    /// Returns an expression which validates the field, or `None` if every value of the field is
+    /// valid.
+    pub fn validate(&self, ident: TokenStream) -> Option<TokenStream> {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.validate(ident),
+            Field::Message(ref message) => message.validate(ident),
+            _ => None,
+        }
+    }
+
     pub fn default(&self) -> TokenStream {
         match *self {
             Field::Scalar(ref scalar) => scalar.default(),
@@ -160,6 +453,15 @@ impl Field {
             _ => None,
         }
     }
+
+    /// Returns the return type of the accessor named after the field among its `methods`, if one
+    /// is generated.
+    pub fn getter_ty(&self) -> Option<TokenStream> {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.getter_ty(),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -212,8 +514,16 @@ impl fmt::Display for Label {
     }
 }
 
-/// Get the items belonging to the 'prost' list attribute, e.g. `#[prost(foo, bar="baz")]`.
+/// Returns the `prost_amino` attributes of a field. An automatically assigned `tag = "auto"` is
+/// left out, so that the field uses the tag chosen for it by the message.
 pub(super) fn prost_attrs(attrs: Vec<Attribute>) -> Result<Vec<Meta>, Error> {
+    Ok(all_prost_attrs(attrs)?
+        .into_iter()
+        .filter(|attr| !auto_tag_attr(attr) && !attr.path().is_ident("alias"))
+        .collect())
+}
+
+fn all_prost_attrs(attrs: Vec<Attribute>) -> Result<Vec<Meta>, Error> {
     Ok(attrs
         .iter()
         .flat_map(Attribute::parse_meta)
@@ -290,7 +600,7 @@ fn bool_attr(key: &str, attr: &Meta) -> Result<Option<bool>, Error> {
 }
 
 /// Checks if an attribute matches a word.
-fn word_attr(key: &str, attr: &Meta) -> bool {
+pub(super) fn word_attr(key: &str, attr: &Meta) -> bool {
     if let Meta::Path(ref path) = *attr {
         path.is_ident(key)
     } else {
@@ -298,6 +608,46 @@ fn word_attr(key: &str, attr: &Meta) -> bool {
     }
 }
 
+/// Returns `true` if the attributes request an automatically assigned tag with `tag = "auto"`.
+pub fn is_auto_tag(attrs: &[Attribute]) -> Result<bool, Error> {
+    Ok(all_prost_attrs(attrs.to_vec())?.iter().any(auto_tag_attr))
+}
+
+/// Returns `true` if the attributes mark a field as `deprecated_wire`, which is decoded but never
+/// encoded.
+pub fn is_deprecated_wire(attrs: &[Attribute]) -> Result<bool, Error> {
+    Ok(all_prost_attrs(attrs.to_vec())?
+        .iter()
+        .any(|attr| word_attr("deprecated_wire", attr)))
+}
+
+/// Returns the former names of a field given by its `alias = ".."` attributes.
+pub fn alias_attrs(attrs: &[Attribute]) -> Result<Vec<Ident>, Error> {
+    all_prost_attrs(attrs.to_vec())?
+        .iter()
+        .filter(|attr| attr.path().is_ident("alias"))
+        .map(|attr| match *attr {
+            Meta::NameValue(MetaNameValue {
+                lit: Lit::Str(ref lit),
+                ..
+            }) => lit.parse::<Ident>().map_err(Error::from),
+            _ => bail!("invalid alias attribute: {:?}", attr),
+        })
+        .collect()
+}
+
+/// Checks if an attribute is `tag = "auto"`.
+fn auto_tag_attr(attr: &Meta) -> bool {
+    match *attr {
+        Meta::NameValue(MetaNameValue {
+            ref path,
+            lit: Lit::Str(ref lit),
+            ..
+        }) => path.is_ident("tag") && lit.value() == "auto",
+        _ => false,
+    }
+}
+
 pub(super) fn tag_attr(attr: &Meta) -> Result<Option<u32>, Error> {
     if !attr.path().is_ident("tag") {
         return Ok(None);
@@ -325,6 +675,41 @@ pub(super) fn tag_attr(attr: &Meta) -> Result<Option<u32>, Error> {
     }
 }
 
+/// Unpacks an attribute into a (key, integer) pair, returning the integer value.
+/// If the key doesn't match the attribute, `None` is returned.
+pub(super) fn u32_attr(key: &str, attr: &Meta) -> Result<Option<u32>, Error> {
+    if !attr.path().is_ident(key) {
+        return Ok(None);
+    }
+    match *attr {
+        Meta::NameValue(ref meta_name_value) => match meta_name_value.lit {
+            Lit::Str(ref lit) => lit
+                .value()
+                .parse::<u32>()
+                .map_err(Error::from)
+                .map(Option::Some),
+            Lit::Int(ref lit) => Ok(Some(lit.base10_parse()?)),
+            _ => bail!("invalid {} attribute: {:?}", key, attr),
+        },
+        _ => bail!("invalid {} attribute: {:?}", key, attr),
+    }
+}
+
+/// Unpacks an attribute into a (key, string) pair, returning the string value.
+/// If the key doesn't match the attribute, `None` is returned.
+pub(super) fn str_attr(key: &str, attr: &Meta) -> Result<Option<String>, Error> {
+    if !attr.path().is_ident(key) {
+        return Ok(None);
+    }
+    match *attr {
+        Meta::NameValue(MetaNameValue {
+            lit: Lit::Str(ref lit),
+            ..
+        }) => Ok(Some(lit.value())),
+        _ => bail!("invalid {} attribute: {:?}", key, attr),
+    }
+}
+
 fn amino_name_attr(attr: &Meta) -> Result<Option<String>, Error> {
     if !attr.path().is_ident("amino_name") {
         return Ok(None);
@@ -339,7 +724,12 @@ fn amino_name_attr(attr: &Meta) -> Result<Option<String>, Error> {
 }
 
 fn tags_attr(attr: &Meta) -> Result<Option<Vec<u32>>, Error> {
-    if !attr.path().is_ident("tags") {
+    u32_list_attr("tags", attr)
+}
+
+/// Parses a list of integers, given either as a list or as a comma separated string.
+pub(super) fn u32_list_attr(key: &str, attr: &Meta) -> Result<Option<Vec<u32>>, Error> {
+    if !attr.path().is_ident(key) {
         return Ok(None);
     }
     match *attr {
@@ -349,7 +739,7 @@ fn tags_attr(attr: &Meta) -> Result<Option<Vec<u32>>, Error> {
                 if let NestedMeta::Lit(Lit::Int(ref lit)) = *item {
                     tags.push(lit.base10_parse()?);
                 } else {
-                    bail!("invalid tag attribute: {:?}", attr);
+                    bail!("invalid {} attribute: {:?}", key, attr);
                 }
             }
             Ok(Some(tags))
@@ -363,6 +753,36 @@ fn tags_attr(attr: &Meta) -> Result<Option<Vec<u32>>, Error> {
             .map(|s| s.trim().parse::<u32>().map_err(Error::from))
             .collect::<Result<Vec<u32>, _>>()
             .map(Some),
-        _ => bail!("invalid tag attribute: {:?}", attr),
+        _ => bail!("invalid {} attribute: {:?}", key, attr),
+    }
+}
+
+/// Returns the name of the last segment of the path, e.g. `Level` for `levels::Level`.
+fn path_name(path: &syn::Path) -> String {
+    path.segments
+        .last()
+        .map_or_else(String::new, |segment| segment.ident.to_string())
+}
+
+/// Returns the name of the type held by a field, looking through the last type argument of
+/// `Option`, `Box`, `Vec` and maps, e.g. `Header` for `Option<Box<Header>>`.
+fn inner_type_name(ty: &syn::Type) -> String {
+    let path = match *ty {
+        syn::Type::Path(ref path) => &path.path,
+        _ => return String::new(),
+    };
+    let segment = match path.segments.last() {
+        Some(segment) => segment,
+        None => return String::new(),
+    };
+    if let syn::PathArguments::AngleBracketed(ref args) = segment.arguments {
+        let inner = args.args.iter().rev().find_map(|arg| match *arg {
+            syn::GenericArgument::Type(ref inner) => Some(inner),
+            _ => None,
+        });
+        if let Some(inner) = inner {
+            return inner_type_name(inner);
+        }
     }
+    segment.ident.to_string()
 }
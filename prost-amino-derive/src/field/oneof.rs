@@ -92,7 +92,30 @@ impl Field {
         }
     }
 
+    /// Returns a statement which passes the set variant, if any, to `visitor`.
+    pub fn visit(&self, ident: TokenStream) -> TokenStream {
+        quote! {
+            if let ::std::option::Option::Some(ref oneof) = #ident {
+                oneof.visit_fields(visitor);
+            }
+        }
+    }
+
+    /// Returns an expression which evaluates to `true` if no oneof variant is set.
+    pub fn is_default(&self, ident: TokenStream) -> TokenStream {
+        quote!(#ident.is_none())
+    }
+
     pub fn clear(&self, ident: TokenStream) -> TokenStream {
         quote!(#ident = ::std::option::Option::None)
     }
+
+    /// Returns a statement which replaces the oneof with the variant set in `other`, if any.
+    pub fn merge_from(&self, ident: TokenStream, other: TokenStream) -> TokenStream {
+        quote! {
+            if #other.is_some() {
+                #ident = #other;
+            }
+        }
+    }
 }
@@ -1,12 +1,17 @@
+use std::cmp::Ordering;
 use std::fmt;
 
 use failure::Error;
-use proc_macro2::{Span, TokenStream};
-use quote;
+use proc_macro2::{Literal, Span, TokenStream, TokenTree};
+use quote::{self, ToTokens};
 use std::convert::TryFrom;
-use syn::{parse_str, Ident, Lit, LitByteStr, Meta, MetaList, MetaNameValue, NestedMeta, Path};
+use syn::{
+    parse_str, Expr, Ident, Lit, LitByteStr, Meta, MetaList, MetaNameValue, NestedMeta, Path,
+};
 
-use field::{amino_name_attr, bool_attr, set_option, tag_attr, Label};
+use field::{
+    amino_name_attr, bool_attr, set_option, str_attr, tag_attr, u32_attr, word_attr, Label,
+};
 
 use super::compute_disfix;
 
@@ -16,8 +21,65 @@ pub struct Field {
     pub ty: Ty,
     pub kind: Kind,
     pub tag: u32,
+    /// The registered amino name of the field type, if any.
+    pub amino_name: Option<String>,
     // this is to be able to de/encode registered type aliases:
     pub amino_prefix: Vec<u8>,
+    /// Overrides the codec selected by `ty`, e.g. to encode an `int64` as a `fixed64`.
+    pub wire: Option<Ty>,
+    /// The maximum length of a decoded `string` or `bytes` value.
+    pub max_len: Option<u32>,
+    /// Whether a `string` or `bytes` field is held in a `Cow`, which may borrow when decoding.
+    pub cow: bool,
+    /// Whether a packed `sint32` or `sint64` field stores deltas between consecutive values.
+    pub delta: bool,
+    /// Whether a `decode_<field>_stream` method is generated for a repeated field.
+    pub stream: bool,
+    /// Whether the accessor of an enumeration field returns `None` for an unknown value, rather
+    /// than the default.
+    pub default_unknown: bool,
+    /// Whether decoding an enumeration field fails on a value which is not a variant of the
+    /// enumeration.
+    pub closed: bool,
+    /// Whether leading and trailing whitespace is stripped from a decoded `string` value.
+    pub trim: bool,
+    /// Whether a decoded `string` value is rejected unless it is ASCII.
+    pub ascii: bool,
+    /// Whether the value of a `bytes` field is compressed with zstd on the wire.
+    pub zstd: bool,
+    /// Whether a `bytes` field is held in a `Bytes`, which shares the buffer passed to
+    /// `decode_from_bytes`.
+    pub shared: bool,
+    /// Whether a `bytes` field also accepts values with a `fixed32` or `fixed64` encoding when
+    /// decoding. Values are always encoded as length-delimited.
+    pub fixed_or_var: bool,
+    /// The number of bytes a varint value is padded to with redundant continuation bits.
+    pub varint_width: Option<u32>,
+    /// The `VarintCodec` which encodes and decodes the values of a varint field in place of the
+    /// standard varint encoding.
+    pub varint_codec: Option<Path>,
+    /// Whether a `fixed64` or `sfixed64` field holds a `SystemTime`, encoded as nanoseconds since
+    /// the unix epoch.
+    pub epoch: bool,
+    /// The std type which the field holds in place of its Protobuf type, given by the `as`
+    /// attribute.
+    pub conversion: Option<Conversion>,
+    /// The inclusive range of the values of an integer field, checked by `validate`.
+    pub range: Option<(i128, i128)>,
+    /// Whether the `range` of the field is also checked when decoding.
+    pub checked: bool,
+    /// The inclusive range which decoded values of an integer field are clamped to.
+    pub clamp: Option<(i128, i128)>,
+    /// Whether values of a repeated field which equal a value before them are dropped when
+    /// decoding.
+    pub dedup: bool,
+    /// Whether the values of a repeated field are encoded in ascending order, regardless of
+    /// their order in the field.
+    pub sorted: bool,
+    /// Whether a fixed width field is encoded in big-endian byte order.
+    pub big_endian: bool,
+    /// The maximum number of values in each length-delimited block of a packed field.
+    pub chunk: Option<u32>,
 }
 
 impl Field {
@@ -28,12 +90,94 @@ impl Field {
         let mut default = None;
         let mut tag = None;
         let mut amino_name = None;
+        let mut wire = None;
+        let mut max_len = None;
+        let mut cow = None;
+        let mut optional_repeated = None;
+        let mut delta = None;
+        let mut default_fn = None;
+        let mut default_expr = None;
+        let mut stream = None;
+        let mut default_unknown = None;
+        let mut closed = None;
+        let mut trim = None;
+        let mut ascii = None;
+        let mut shared = None;
+        let mut zstd = None;
+        let mut fixed_or_var = None;
+        let mut varint_width = None;
+        let mut varint_codec = None;
+        let mut epoch = None;
+        let mut conversion = None;
+        let mut range = None;
+        let mut checked = None;
+        let mut clamp = None;
+        let mut dedup = None;
+        let mut sorted = None;
+        let mut endian = None;
+        let mut chunk = None;
 
         let mut unknown_attrs = Vec::new();
 
         for attr in attrs {
             if let Some(t) = Ty::from_attr(attr)? {
                 set_option(&mut ty, t, "duplicate type attributes")?;
+            } else if let Some(w) = Wire::from_attr(attr)? {
+                set_option(&mut wire, w, "duplicate wire attributes")?;
+            } else if let Some(e) = str_attr("endian", attr)? {
+                set_option(&mut endian, e, "duplicate endian attributes")?;
+            } else if let Some(r) = str_attr("range", attr)? {
+                set_option(&mut range, r, "duplicate range attributes")?;
+            } else if let Some(c) = str_attr("clamp", attr)? {
+                set_option(&mut clamp, c, "duplicate clamp attributes")?;
+            } else if let Some(d) = bool_attr("dedup", attr)? {
+                set_option(&mut dedup, d, "duplicate dedup attributes")?;
+            } else if let Some(s) = bool_attr("sorted", attr)? {
+                set_option(&mut sorted, s, "duplicate sorted attributes")?;
+            } else if let Some(c) = bool_attr("checked", attr)? {
+                set_option(&mut checked, c, "duplicate checked attributes")?;
+            } else if let Some(e) = str_attr("epoch", attr)? {
+                set_option(&mut epoch, e, "duplicate epoch attributes")?;
+            } else if let Some(c) = str_attr("as", attr)? {
+                set_option(&mut conversion, c, "duplicate as attributes")?;
+            } else if let Some(c) = u32_attr("chunk", attr)? {
+                set_option(&mut chunk, c, "duplicate chunk attributes")?;
+            } else if let Some(w) = u32_attr("varint_width", attr)? {
+                set_option(&mut varint_width, w, "duplicate varint_width attributes")?;
+            } else if let Some(c) = str_attr("varint_codec", attr)? {
+                set_option(&mut varint_codec, c, "duplicate varint_codec attributes")?;
+            } else if let Some(m) = u32_attr("max_len", attr)? {
+                set_option(&mut max_len, m, "duplicate max_len attributes")?;
+            } else if let Some(t) = bool_attr("trim", attr)? {
+                set_option(&mut trim, t, "duplicate trim attributes")?;
+            } else if let Some(a) = bool_attr("ascii", attr)? {
+                set_option(&mut ascii, a, "duplicate ascii attributes")?;
+            } else if let Some(z) = bool_attr("zstd", attr)? {
+                set_option(&mut zstd, z, "duplicate zstd attributes")?;
+            } else if let Some(f) = bool_attr("fixed_or_var", attr)? {
+                set_option(&mut fixed_or_var, f, "duplicate fixed_or_var attributes")?;
+            } else if let Some(s) = bool_attr("shared", attr)? {
+                set_option(&mut shared, s, "duplicate shared attributes")?;
+            } else if let Some(c) = bool_attr("cow", attr)? {
+                set_option(&mut cow, c, "duplicate cow attributes")?;
+            } else if let Some(o) = bool_attr("optional_repeated", attr)? {
+                set_option(
+                    &mut optional_repeated,
+                    o,
+                    "duplicate optional_repeated attributes",
+                )?;
+            } else if let Some(d) = bool_attr("delta", attr)? {
+                set_option(&mut delta, d, "duplicate delta attributes")?;
+            } else if let Some(s) = bool_attr("stream", attr)? {
+                set_option(&mut stream, s, "duplicate stream attributes")?;
+            } else if let Some(d) = bool_attr("default_unknown", attr)? {
+                set_option(
+                    &mut default_unknown,
+                    d,
+                    "duplicate default_unknown attributes",
+                )?;
+            } else if let Some(c) = bool_attr("closed", attr)? {
+                set_option(&mut closed, c, "duplicate closed attributes")?;
             } else if let Some(p) = bool_attr("packed", attr)? {
                 set_option(&mut packed, p, "duplicate packed attributes")?;
             } else if let Some(t) = tag_attr(attr)? {
@@ -44,6 +188,10 @@ impl Field {
                 set_option(&mut label, l, "duplicate label attributes")?;
             } else if let Some(d) = DefaultValue::from_attr(attr)? {
                 set_option(&mut default, d, "duplicate default attributes")?;
+            } else if let Some(f) = DefaultValue::from_fn_attr(attr)? {
+                set_option(&mut default_fn, f, "duplicate default_fn attributes")?;
+            } else if let Some(e) = DefaultValue::from_expr_attr(attr)? {
+                set_option(&mut default_expr, e, "duplicate default_expr attributes")?;
             } else {
                 unknown_attrs.push(attr);
             }
@@ -64,13 +212,87 @@ impl Field {
             None => bail!("missing tag attribute"),
         };
 
-        let has_default = default.is_some();
-        let default = default.map_or_else(
-            || Ok(DefaultValue::new(&ty)),
-            |lit| DefaultValue::from_lit(&ty, lit),
-        )?;
+        let has_default = default.is_some() || default_fn.is_some() || default_expr.is_some();
+        let is_enumeration = matches!(ty, Ty::Enumeration(..));
+        let epoch = match epoch.as_deref() {
+            None => false,
+            Some("unix_nanos") => true,
+            Some(other) => bail!("unknown epoch {:?}, expected \"unix_nanos\"", other),
+        };
+        if epoch {
+            match ty {
+                Ty::Fixed64 | Ty::Sfixed64 => (),
+                _ => bail!("epoch attribute may only be applied to fixed64 or sfixed64 fields"),
+            }
+            match label {
+                None | Some(Label::Optional) | Some(Label::Required) => (),
+                Some(Label::Repeated) => {
+                    bail!("epoch attribute may not be applied to repeated fields")
+                }
+            }
+            if has_default || wire.is_some() || amino_name.is_some() || varint_width.is_some() {
+                bail!("epoch attribute may not be combined with default, wire, amino_name or varint_width");
+            }
+        }
+        if ty == Ty::Json
+            && (default.is_some()
+                || amino_name.is_some()
+                || dedup == Some(true)
+                || sorted == Some(true))
+        {
+            bail!("json attribute may not be combined with default, amino_name, dedup or sorted");
+        }
+        let conversion = match conversion {
+            Some(name) => Some(Conversion::new(&name, &ty)?),
+            None => None,
+        };
+        if conversion.is_some() {
+            if label == Some(Label::Repeated) || optional_repeated.unwrap_or(false) {
+                bail!("as attribute may not be applied to repeated fields");
+            }
+            if has_default
+                || epoch
+                || wire.is_some()
+                || amino_name.is_some()
+                || varint_width.is_some()
+            {
+                bail!("as attribute may not be combined with default, epoch, wire, amino_name or varint_width");
+            }
+        }
+        let default = match (default, default_fn) {
+            _ if epoch => DefaultValue::Path(parse_str::<Path>("::std::time::UNIX_EPOCH")?),
+            _ if conversion.is_some() => conversion.expect("conversion").default(),
+            (Some(_), Some(_)) => bail!("default and default_fn attributes may not be combined"),
+            (Some(_), None) | (None, Some(_)) if default_expr.is_some() => {
+                bail!("default_expr attribute may not be combined with default or default_fn")
+            }
+            (None, None) if default_expr.is_some() && (is_enumeration || !ty.is_numeric()) => {
+                bail!("default_expr attribute may only be applied to numeric or bool fields")
+            }
+            (None, None) if default_expr.is_some() => {
+                DefaultValue::Expr(default_expr.expect("default_expr"))
+            }
+            (_, Some(_)) if is_enumeration => {
+                bail!("default_fn attribute may not be applied to enumeration fields")
+            }
+            (_, Some(_)) if label.is_some() && label != Some(Label::Required) => {
+                bail!("default_fn attribute may only be applied to plain or required fields")
+            }
+            (_, Some(path)) => DefaultValue::Fn(path),
+            (Some(lit), None) => DefaultValue::from_lit(&ty, lit)?,
+            (None, None) => DefaultValue::new(&ty),
+        };
 
         let kind = match (label, packed, has_default) {
+            _ if optional_repeated.unwrap_or(false) => {
+                if label.is_some() || packed.is_some() || has_default {
+                    bail!("optional_repeated fields may not have a label, packed, or default attribute");
+                }
+                if !ty.is_numeric() {
+                    bail!("optional_repeated attribute may only be applied to numeric types");
+                }
+                Kind::OptionalRepeated
+            }
             (None, Some(true), _)
             | (Some(Label::Optional), Some(true), _)
             | (Some(Label::Required), Some(true), _) => {
@@ -92,18 +314,362 @@ impl Field {
             (Some(Label::Repeated), _, false) => Kind::Repeated,
         };
         let amino_prefix: Vec<u8> = match amino_name {
-            Some(n) => {
+            Some(ref n) => {
                 let (_dis, pre) = compute_disfix(n.as_str());
                 pre
             }
             None => vec![],
         };
 
+        let wire = match wire {
+            Some(wire) => match wire.codec(&ty) {
+                Some(codec) => Some(codec),
+                None if wire == Wire::Zigzag => {
+                    bail!("zigzag attribute may only be applied to signed integer fields")
+                }
+                None => bail!(
+                    "wire attribute {:?} is not compatible with {} fields",
+                    wire,
+                    ty
+                ),
+            },
+            None => None,
+        };
+
+        if max_len.is_some() {
+            match ty {
+                Ty::String | Ty::Bytes => (),
+                _ => bail!("max_len attribute may only be applied to string or bytes fields"),
+            }
+            // The values of a stream are decoded without the limit.
+            if stream == Some(true) {
+                bail!("max_len attribute may not be combined with stream");
+            }
+            match kind {
+                Kind::Repeated | Kind::Packed => {
+                    bail!("max_len attribute may not be applied to repeated fields")
+                }
+                _ => (),
+            }
+            if !amino_prefix.is_empty() {
+                bail!("max_len attribute may not be combined with amino_name");
+            }
+        }
+
+        let cow = cow.unwrap_or(false);
+        if cow {
+            match ty {
+                Ty::String | Ty::Bytes => (),
+                _ => bail!("cow attribute may only be applied to string or bytes fields"),
+            }
+            match kind {
+                Kind::Plain(..) | Kind::Required(..) => (),
+                _ => bail!("cow attribute may not be applied to optional or repeated fields"),
+            }
+            if !amino_prefix.is_empty() || max_len.is_some() {
+                bail!("cow attribute may not be combined with amino_name or max_len");
+            }
+            if let Kind::Plain(DefaultValue::Fn(_)) | Kind::Required(DefaultValue::Fn(_)) = kind {
+                bail!("cow attribute may not be combined with default_fn");
+            }
+        }
+
+        let delta = delta.unwrap_or(false);
+        if delta {
+            match ty {
+                Ty::Sint32 | Ty::Sint64 => (),
+                _ => bail!("delta attribute may only be applied to sint32 or sint64 fields"),
+            }
+            match kind {
+                Kind::Packed => (),
+                _ => bail!("delta attribute may only be applied to packed repeated fields"),
+            }
+            if wire.is_some() || !amino_prefix.is_empty() {
+                bail!("delta attribute may not be combined with wire or amino_name");
+            }
+        }
+
+        let stream = stream.unwrap_or(false);
+        if stream {
+            match kind {
+                Kind::Repeated | Kind::Packed => (),
+                _ => bail!("stream attribute may only be applied to repeated fields"),
+            }
+            if delta || !amino_prefix.is_empty() {
+                bail!("stream attribute may not be combined with delta or amino_name");
+            }
+        }
+
+        let default_unknown = default_unknown.unwrap_or(false);
+        if default_unknown && !is_enumeration {
+            bail!("default_unknown attribute may only be applied to enumeration fields");
+        }
+
+        let closed = closed.unwrap_or(false);
+        if closed {
+            if !is_enumeration {
+                bail!("closed attribute may only be applied to enumeration fields");
+            }
+            if let Kind::OptionalRepeated = kind {
+                bail!("closed attribute may not be applied to optional_repeated fields");
+            }
+            if stream {
+                bail!("closed attribute may not be combined with stream");
+            }
+        }
+
+        let trim = trim.unwrap_or(false);
+        if trim {
+            if ty != Ty::String {
+                bail!("trim attribute may only be applied to string fields");
+            }
+            match kind {
+                Kind::Plain(..) | Kind::Optional(..) | Kind::Required(..) => (),
+                _ => bail!("trim attribute may not be applied to repeated fields"),
+            }
+            if max_len.is_some() || cow {
+                bail!("trim attribute may not be combined with max_len or cow");
+            }
+        }
+
+        let ascii = ascii.unwrap_or(false);
+        if ascii {
+            if ty != Ty::String {
+                bail!("ascii attribute may only be applied to string fields");
+            }
+            if max_len.is_some() || cow || trim || stream {
+                bail!("ascii attribute may not be combined with max_len, cow, trim or stream");
+            }
+        }
+
+        let shared = shared.unwrap_or(false);
+        if shared {
+            if ty != Ty::Bytes {
+                bail!("shared attribute may only be applied to bytes fields");
+            }
+            match kind {
+                Kind::Plain(DefaultValue::Bytes(ref value))
+                | Kind::Required(DefaultValue::Bytes(ref value))
+                    if value.is_empty() => {}
+                Kind::Plain(..) | Kind::Required(..) => {
+                    bail!("shared attribute may not be combined with default or default_fn")
+                }
+                _ => bail!("shared attribute may not be applied to optional or repeated fields"),
+            }
+            if !amino_prefix.is_empty() || wire.is_some() || max_len.is_some() || cow {
+                bail!("shared attribute may not be combined with amino_name, wire, max_len or cow");
+            }
+        }
+
+        let zstd = zstd.unwrap_or(false);
+        if zstd {
+            if ty != Ty::Bytes {
+                bail!("zstd attribute may only be applied to bytes fields");
+            }
+            if let Kind::OptionalRepeated = kind {
+                bail!("zstd attribute may not be applied to optional_repeated fields");
+            }
+            if !amino_prefix.is_empty() || wire.is_some() || cow || shared {
+                bail!("zstd attribute may not be combined with amino_name, wire, cow or shared");
+            }
+        }
+
+        let fixed_or_var = fixed_or_var.unwrap_or(false);
+        if fixed_or_var {
+            if ty != Ty::Bytes {
+                bail!("fixed_or_var attribute may only be applied to bytes fields");
+            }
+            if !amino_prefix.is_empty()
+                || wire.is_some()
+                || max_len.is_some()
+                || cow
+                || shared
+                || zstd
+                || stream
+            {
+                bail!("fixed_or_var attribute may not be combined with amino_name, wire, max_len, cow, shared, zstd or stream");
+            }
+        }
+
+        if let Some(width) = varint_width {
+            let min = match ty.max_varint_len() {
+                Some(min) => min,
+                None => bail!("varint_width attribute may only be applied to varint fields"),
+            };
+            if width < min {
+                bail!(
+                    "varint_width {} is too narrow for {} fields, which may take {} bytes",
+                    width,
+                    ty,
+                    min
+                );
+            }
+            if width > 10 {
+                bail!("varint_width may not exceed 10 bytes");
+            }
+            match kind {
+                Kind::Plain(..) | Kind::Optional(..) | Kind::Required(..) => (),
+                _ => bail!("varint_width attribute may not be applied to repeated fields"),
+            }
+            if wire.is_some() || !amino_prefix.is_empty() {
+                bail!("varint_width attribute may not be combined with wire or amino_name");
+            }
+        }
+
+        let varint_codec = match varint_codec {
+            Some(codec) => Some(parse_str::<Path>(&codec)?),
+            None => None,
+        };
+        if varint_codec.is_some() {
+            if ty.max_varint_len().is_none() {
+                bail!("varint_codec attribute may only be applied to varint fields");
+            }
+            match kind {
+                Kind::Plain(..) | Kind::Optional(..) | Kind::Required(..) => (),
+                _ => bail!("varint_codec attribute may not be applied to repeated fields"),
+            }
+            if wire.is_some()
+                || !amino_prefix.is_empty()
+                || varint_width.is_some()
+                || conversion.is_some()
+            {
+                bail!("varint_codec attribute may not be combined with wire, amino_name, varint_width or as");
+            }
+        }
+
+        let range = match range {
+            Some(range) => {
+                let bounds = parse_range("range", &range, &ty)?;
+                if let Kind::OptionalRepeated = kind {
+                    bail!("range attribute may not be applied to optional_repeated fields");
+                }
+                if epoch {
+                    bail!("range attribute may not be combined with epoch");
+                }
+                Some(bounds)
+            }
+            None => None,
+        };
+        let clamp = match clamp {
+            Some(clamp) => {
+                let bounds = parse_range("clamp", &clamp, &ty)?;
+                if let Kind::OptionalRepeated = kind {
+                    bail!("clamp attribute may not be applied to optional_repeated fields");
+                }
+                if epoch || checked == Some(true) || stream {
+                    bail!("clamp attribute may not be combined with epoch, checked or stream");
+                }
+                Some(bounds)
+            }
+            None => None,
+        };
+        let checked = checked.unwrap_or(false);
+        if checked && range.is_none() {
+            bail!("checked attribute requires a range attribute");
+        }
+        if checked && stream {
+            bail!("checked attribute may not be combined with stream");
+        }
+
+        if conversion.is_some()
+            && (max_len.is_some()
+                || cow
+                || shared
+                || zstd
+                || range.is_some()
+                || clamp.is_some()
+                || endian.is_some())
+        {
+            bail!("as attribute may not be combined with max_len, cow, shared, zstd, range, clamp or endian");
+        }
+
+        let dedup = dedup.unwrap_or(false);
+        if dedup {
+            match kind {
+                Kind::Repeated | Kind::Packed => (),
+                _ => bail!("dedup attribute may only be applied to repeated fields"),
+            }
+            if ty == Ty::Float || ty == Ty::Double {
+                bail!("dedup attribute may not be applied to float or double fields, which are not Eq");
+            }
+            if shared {
+                bail!("dedup attribute may not be combined with shared");
+            }
+        }
+
+        let sorted = sorted.unwrap_or(false);
+        if sorted {
+            match kind {
+                Kind::Repeated | Kind::Packed => (),
+                _ => bail!("sorted attribute may only be applied to repeated fields"),
+            }
+            if ty == Ty::Float || ty == Ty::Double {
+                bail!("sorted attribute may not be applied to float or double fields, which are not Ord");
+            }
+        }
+
+        let big_endian = match endian.as_deref() {
+            None | Some("little") => false,
+            Some("big") => true,
+            Some(other) => bail!("unknown endian {:?}, expected \"little\" or \"big\"", other),
+        };
+        if endian.is_some() {
+            match *wire.as_ref().unwrap_or(&ty) {
+                Ty::Float
+                | Ty::Double
+                | Ty::Fixed32
+                | Ty::Fixed64
+                | Ty::Sfixed32
+                | Ty::Sfixed64 => {}
+                _ => bail!("endian attribute may only be applied to fixed width fields"),
+            }
+            if !amino_prefix.is_empty() || epoch {
+                bail!("endian attribute may not be combined with amino_name or epoch");
+            }
+        }
+
+        if let Some(chunk) = chunk {
+            if chunk == 0 {
+                bail!("chunk attribute must be at least 1");
+            }
+            match kind {
+                Kind::Packed => (),
+                _ => bail!("chunk attribute may only be applied to packed repeated fields"),
+            }
+            if delta {
+                bail!("chunk attribute may not be combined with delta");
+            }
+        }
+
         Ok(Some(Field {
             ty: ty,
             kind: kind,
             tag: tag,
             amino_prefix: amino_prefix,
+            wire,
+            max_len,
+            cow,
+            delta,
+            stream,
+            default_unknown,
+            closed,
+            trim,
+            ascii,
+            zstd,
+            shared,
+            fixed_or_var,
+            varint_width,
+            varint_codec,
+            epoch,
+            conversion,
+            range,
+            checked,
+            clamp,
+            dedup,
+            sorted,
+            big_endian,
+            chunk,
+            amino_name,
         }))
     }
 
@@ -116,15 +682,55 @@ impl Field {
                 }
                 Kind::Optional(..) => bail!("invalid optional attribute on oneof field"),
                 Kind::Required(..) => bail!("invalid required attribute on oneof field"),
-                Kind::Packed | Kind::Repeated => bail!("invalid repeated attribute on oneof field"),
+                Kind::Packed | Kind::Repeated | Kind::OptionalRepeated => {
+                    bail!("invalid repeated attribute on oneof field")
+                }
             }
         } else {
             Ok(None)
         }
     }
 
+    /// Returns the encoding module used for the field, honoring any wire override.
+    fn module(&self) -> Ident {
+        if self.cow {
+            return Ident::new(&format!("cow_{}", self.ty.module()), Span::call_site());
+        }
+        if self.shared {
+            return Ident::new("shared_bytes", Span::call_site());
+        }
+        if self.zstd {
+            return Ident::new("zstd_bytes", Span::call_site());
+        }
+        if self.epoch {
+            return Ident::new("unix_nanos", Span::call_site());
+        }
+        if let Some(conversion) = self.conversion {
+            return conversion.module();
+        }
+        let module = self.wire.as_ref().unwrap_or(&self.ty).module();
+        if self.big_endian {
+            return Ident::new(&format!("{}_be", module), Span::call_site());
+        }
+        module
+    }
+
     pub fn encode(&self, ident: TokenStream) -> TokenStream {
-        let module = self.ty.module();
+        if self.sorted {
+            let encode = self.encode_values(quote!(values));
+            return quote! {
+                {
+                    let values = _prost::encoding::sorted(&#ident);
+                    #encode
+                }
+            };
+        }
+        self.encode_values(ident)
+    }
+
+    /// Returns a statement which encodes the values of the field in their order in `ident`.
+    fn encode_values(&self, ident: TokenStream) -> TokenStream {
+        let module = self.module();
         let encode_fn = match self.kind {
             Kind::Plain(..) | Kind::Optional(..) | Kind::Required(..) => {
                 if self.amino_prefix.len() > 0 {
@@ -134,24 +740,77 @@ impl Field {
                 }
             }
             Kind::Repeated => quote!(encode_repeated),
-            Kind::Packed => quote!(encode_packed),
+            Kind::Packed if self.delta => quote!(encode_delta),
+            Kind::Packed | Kind::OptionalRepeated => quote!(encode_packed),
         };
         let encode_fn = quote!(_prost::encoding::#module::#encode_fn);
         let tag = self.tag;
 
+        if let Some(ref codec) = self.varint_codec {
+            let encode_fn = quote!(_prost::encoding::#module::encode_with_codec::<#codec, _>);
+            return match self.kind {
+                Kind::Plain(ref default) => {
+                    let differs = default.differs(&ident);
+                    quote! {
+                        if #differs {
+                            #encode_fn(#tag, &#ident, buf);
+                        }
+                    }
+                }
+                Kind::Optional(..) => quote! {
+                    if let ::std::option::Option::Some(ref value) = #ident {
+                        #encode_fn(#tag, value, buf);
+                    }
+                },
+                _ => quote!(#encode_fn(#tag, &#ident, buf);),
+            };
+        }
+
+        if let Some(width) = self.varint_width {
+            let width = width as usize;
+            let encode_fn = quote!(_prost::encoding::#module::encode_padded);
+            return match self.kind {
+                Kind::Plain(ref default) => {
+                    let differs = default.differs(&ident);
+                    quote! {
+                        if #differs {
+                            #encode_fn(#tag, &#ident, #width, buf);
+                        }
+                    }
+                }
+                Kind::Optional(..) => quote! {
+                    if let ::std::option::Option::Some(ref value) = #ident {
+                        #encode_fn(#tag, value, #width, buf);
+                    }
+                },
+                _ => quote!(#encode_fn(#tag, &#ident, #width, buf);),
+            };
+        }
+
+        // Each chunk is encoded as a separate occurrence of the field, which are concatenated
+        // when decoded.
+        if let Some(chunk) = self.chunk {
+            let chunk = chunk as usize;
+            return quote! {
+                for values in #ident.chunks(#chunk) {
+                    #encode_fn(#tag, values, buf);
+                }
+            };
+        }
+
         match self.kind {
             Kind::Plain(ref default) => {
-                let default = default.typed();
+                let differs = default.differs(&ident);
                 if self.amino_prefix.len() > 0 {
                     let pre = &self.amino_prefix;
                     quote! {
-                        if #ident != #default {
+                        if #differs {
                             #encode_fn(#tag, &#ident, &vec![#(#pre),*], buf);
                         }
                     }
                 } else {
                     quote! {
-                        if #ident != #default {
+                        if #differs {
                             #encode_fn(#tag, &#ident, buf);
                         }
                     }
@@ -165,17 +824,204 @@ impl Field {
             Kind::Required(..) | Kind::Repeated | Kind::Packed => quote! {
                 #encode_fn(#tag, &#ident, buf);
             },
+            // An empty list is encoded as an empty packed field, to distinguish it from `None`.
+            Kind::OptionalRepeated => quote! {
+                if let ::std::option::Option::Some(ref values) = #ident {
+                    if values.is_empty() {
+                        _prost::encoding::encode_key(#tag, _prost::encoding::WireType::LengthDelimited, buf);
+                        _prost::encoding::encode_varint(0, buf);
+                    } else {
+                        #encode_fn(#tag, values, buf);
+                    }
+                }
+            },
         }
     }
 
     /// Returns an expression which evaluates to the result of merging a decoded
-    /// scalar value into the field.
+    /// scalar value into the field, and checking that it is within the range of a `checked`
+    /// field.
     pub fn merge(&self, ident: TokenStream) -> TokenStream {
-        let module = self.ty.module();
+        self.merge_values(ident)
+    }
+
+    /// Returns a statement which drops the duplicate values of a `dedup` field once the message
+    /// has been merged, or `None` if the field is not deduplicated.
+    pub fn finish_merge(&self, ident: TokenStream) -> Option<TokenStream> {
+        if self.dedup {
+            Some(quote!(_prost::encoding::dedup_repeated(&mut #ident);))
+        } else {
+            None
+        }
+    }
+
+    /// Returns an expression which evaluates to the result of merging a decoded value into the
+    /// field, clamped or checked against its range.
+    fn merge_values(&self, ident: TokenStream) -> TokenStream {
+        if self.closed {
+            return self.merge_closed(ident);
+        }
+        if let Some((min, max)) = self.clamp {
+            return self.merge_clamped(ident, min, max);
+        }
+        let (min, max) = match self.range {
+            Some((min, max)) if self.checked => {
+                (Literal::i128_unsuffixed(min), Literal::i128_unsuffixed(max))
+            }
+            _ => return self.merge_unchecked(ident),
+        };
+        match self.kind {
+            Kind::Plain(..) | Kind::Required(..) => {
+                let merge = self.merge_unchecked(ident.clone());
+                quote! {
+                    #merge.and_then(|()| _prost::encoding::check_range(#ident, #min, #max))
+                }
+            }
+            Kind::Optional(..) => {
+                let merge = self.merge_unchecked(ident.clone());
+                quote! {
+                    #merge.and_then(|()| #ident.map_or(::std::result::Result::Ok(()), |value| {
+                        _prost::encoding::check_range(value, #min, #max)
+                    }))
+                }
+            }
+            _ => {
+                let merge = self.merge_unchecked(quote!((*values)));
+                quote! {
+                    _prost::encoding::merge_repeated_checked(&mut #ident, #min, #max, |values| #merge)
+                }
+            }
+        }
+    }
+
+    /// Returns an expression which evaluates to the result of merging a decoded value into a
+    /// `closed` enumeration field, and checking that it is a variant of the enumeration.
+    fn merge_closed(&self, ident: TokenStream) -> TokenStream {
+        let tag = self.tag;
+        let from_i32 = match self.ty {
+            Ty::Enumeration(ref ty) => {
+                let ty = resolve_path(ty);
+                quote!(#ty::from_i32)
+            }
+            _ => unreachable!("closed field is not an enumeration"),
+        };
+        match self.kind {
+            Kind::Plain(..) | Kind::Required(..) => {
+                let merge = self.merge_unchecked(ident.clone());
+                quote! {
+                    #merge.and_then(|()| _prost::encoding::check_enum_value(#tag, #ident, #from_i32))
+                }
+            }
+            Kind::Optional(..) => {
+                let merge = self.merge_unchecked(ident.clone());
+                quote! {
+                    #merge.and_then(|()| #ident.map_or(::std::result::Result::Ok(()), |value| {
+                        _prost::encoding::check_enum_value(#tag, value, #from_i32)
+                    }))
+                }
+            }
+            _ => {
+                let merge = self.merge_unchecked(quote!((*values)));
+                quote! {
+                    _prost::encoding::merge_repeated_closed(&mut #ident, #tag, #from_i32, |values| #merge)
+                }
+            }
+        }
+    }
+
+    /// Returns an expression which evaluates to the result of merging a decoded scalar value
+    /// into the field, moved to the nearest bound of the `clamp` range if it is outside it.
+    fn merge_clamped(&self, ident: TokenStream, min: i128, max: i128) -> TokenStream {
+        let (min, max) = (Literal::i128_unsuffixed(min), Literal::i128_unsuffixed(max));
+        match self.kind {
+            Kind::Plain(..) | Kind::Required(..) => {
+                let merge = self.merge_unchecked(ident.clone());
+                quote! {
+                    #merge.map(|()| #ident = _prost::encoding::clamp(#ident, #min, #max))
+                }
+            }
+            Kind::Optional(..) => {
+                let merge = self.merge_unchecked(ident.clone());
+                quote! {
+                    #merge.map(|()| if let ::std::option::Option::Some(ref mut value) = #ident {
+                        *value = _prost::encoding::clamp(*value, #min, #max);
+                    })
+                }
+            }
+            _ => {
+                let merge = self.merge_unchecked(quote!((*values)));
+                quote! {
+                    _prost::encoding::merge_repeated_clamped(&mut #ident, #min, #max, |values| #merge)
+                }
+            }
+        }
+    }
+
+    fn merge_unchecked(&self, ident: TokenStream) -> TokenStream {
+        let module = self.module();
         let merge_fn = match self.kind {
             Kind::Plain(..) | Kind::Optional(..) | Kind::Required(..) => quote!(merge),
-            Kind::Repeated | Kind::Packed => quote!(merge_repeated),
+            Kind::Packed if self.delta => quote!(merge_delta),
+            Kind::Repeated | Kind::Packed | Kind::OptionalRepeated => quote!(merge_repeated),
         };
+        if let Some(max_len) = self.max_len {
+            let max_len = max_len as usize;
+            let value = match self.kind {
+                Kind::Optional(..) => quote!(#ident.get_or_insert_with(Default::default)),
+                _ => quote!(&mut #ident),
+            };
+            return quote! {
+                _prost::encoding::#module::merge_max_len(wire_type, #value, #max_len, buf)
+            };
+        }
+        if self.trim {
+            let value = match self.kind {
+                Kind::Optional(..) => quote!(#ident.get_or_insert_with(Default::default)),
+                _ => quote!(&mut #ident),
+            };
+            return quote! {
+                _prost::encoding::string::merge_trimmed(wire_type, #value, buf)
+            };
+        }
+        if self.ascii {
+            return match self.kind {
+                Kind::Plain(..) | Kind::Required(..) => quote! {
+                    _prost::encoding::string::merge_ascii(wire_type, &mut #ident, buf)
+                },
+                Kind::Optional(..) => quote! {
+                    _prost::encoding::string::merge_ascii(wire_type,
+                                                          #ident.get_or_insert_with(Default::default),
+                                                          buf)
+                },
+                _ => quote! {
+                    _prost::encoding::string::merge_repeated_ascii(wire_type, &mut #ident, buf)
+                },
+            };
+        }
+        if let Some(ref codec) = self.varint_codec {
+            let merge_fn = quote!(_prost::encoding::#module::merge_with_codec::<#codec, _>);
+            return match self.kind {
+                Kind::Optional(..) => quote! {
+                    #merge_fn(wire_type, #ident.get_or_insert_with(Default::default), buf)
+                },
+                _ => quote!(#merge_fn(wire_type, &mut #ident, buf)),
+            };
+        }
+        if self.fixed_or_var {
+            return match self.kind {
+                Kind::Plain(..) | Kind::Required(..) => quote! {
+                    _prost::encoding::bytes::merge_fixed_or_var(wire_type, &mut #ident, buf)
+                },
+                Kind::Optional(..) => quote! {
+                    _prost::encoding::bytes::merge_fixed_or_var(wire_type,
+                                                                #ident.get_or_insert_with(Default::default),
+                                                                buf)
+                },
+                _ => quote! {
+                    _prost::encoding::bytes::merge_repeated_fixed_or_var(wire_type, &mut #ident, buf)
+                },
+            };
+        }
         let is_registered = self.amino_prefix.len() > 0;
         let decode_with_prefix = is_registered && module.to_string() == "bytes";
         let merge_fn = if decode_with_prefix {
@@ -196,7 +1042,17 @@ impl Field {
                     }
                 }
             }
-            Kind::Optional(..) => quote! {
+            // `SystemTime` and the `as` types have no `Default`, so an unset field starts at the
+            // default of the field, e.g. the unix epoch.
+            Kind::Optional(ref default) if self.epoch || self.conversion.is_some() => {
+                let default = default.owned();
+                quote! {
+                    #merge_fn(wire_type,
+                              #ident.get_or_insert(#default),
+                              buf)
+                }
+            }
+            Kind::Optional(..) | Kind::OptionalRepeated => quote! {
                 #merge_fn(wire_type,
                           #ident.get_or_insert_with(Default::default),
                           buf)
@@ -204,38 +1060,237 @@ impl Field {
         }
     }
 
-    /// Returns an expression which evaluates to the encoded length of the field.
-    pub fn encoded_len(&self, ident: TokenStream) -> TokenStream {
-        let module = self.ty.module();
-        let encoded_len_fn = match self.kind {
-            Kind::Plain(..) | Kind::Optional(..) | Kind::Required(..) => quote!(encoded_len),
-            Kind::Repeated => quote!(encoded_len_repeated),
-            Kind::Packed => quote!(encoded_len_packed),
-        };
-        let encoded_len_fn = quote!(_prost::encoding::#module::#encoded_len_fn);
-        let tag = self.tag;
-        let is_amino_prefixed = self.amino_prefix.len() > 0;
+    /// Returns an expression which evaluates to the encoded length of the field.
+    pub fn encoded_len(&self, ident: TokenStream) -> TokenStream {
+        let module = self.module();
+        let encoded_len_fn = match self.kind {
+            Kind::Plain(..) | Kind::Optional(..) | Kind::Required(..) => quote!(encoded_len),
+            Kind::Repeated => quote!(encoded_len_repeated),
+            Kind::Packed if self.delta => quote!(encoded_len_delta),
+            Kind::Packed | Kind::OptionalRepeated => quote!(encoded_len_packed),
+        };
+        let encoded_len_fn = quote!(_prost::encoding::#module::#encoded_len_fn);
+        let tag = self.tag;
+
+        if let Some(ref codec) = self.varint_codec {
+            let encoded_len_fn =
+                quote!(_prost::encoding::#module::encoded_len_with_codec::<#codec>);
+            return match self.kind {
+                Kind::Plain(ref default) => {
+                    let differs = default.differs(&ident);
+                    quote! {
+                        if #differs {
+                            #encoded_len_fn(#tag, &#ident)
+                        } else {
+                            0
+                        }
+                    }
+                }
+                Kind::Optional(..) => quote! {
+                    #ident.as_ref().map_or(0, |value| #encoded_len_fn(#tag, value))
+                },
+                _ => quote!(#encoded_len_fn(#tag, &#ident)),
+            };
+        }
+
+        if let Some(width) = self.varint_width {
+            let width = width as usize;
+            let encoded_len_fn = quote!(_prost::encoding::#module::encoded_len_padded);
+            return match self.kind {
+                Kind::Plain(ref default) => {
+                    let differs = default.differs(&ident);
+                    quote! {
+                        if #differs {
+                            #encoded_len_fn(#tag, &#ident, #width)
+                        } else {
+                            0
+                        }
+                    }
+                }
+                Kind::Optional(..) => quote! {
+                    #ident.as_ref().map_or(0, |value| #encoded_len_fn(#tag, value, #width))
+                },
+                _ => quote!(#encoded_len_fn(#tag, &#ident, #width)),
+            };
+        }
+
+        if let Some(chunk) = self.chunk {
+            let chunk = chunk as usize;
+            // The values are chunked in the order they are encoded, since the length of a block
+            // depends on which values share it.
+            let values = if self.sorted {
+                quote!(_prost::encoding::sorted(&#ident))
+            } else {
+                quote!(#ident)
+            };
+            return quote! {
+                #values.chunks(#chunk).map(|values| #encoded_len_fn(#tag, values)).sum::<usize>()
+            };
+        }
+
+        match self.kind {
+            Kind::Plain(ref default) => {
+                let differs = default.differs(&ident);
+                let encoded_len = if self.amino_prefix.is_empty() || module != "bytes" {
+                    quote!(#encoded_len_fn(#tag, &#ident))
+                } else {
+                    let pre = &self.amino_prefix;
+                    quote! {
+                        _prost::encoding::bytes::encoded_len_with_prefix(#tag, &#ident, &vec![#(#pre),*])
+                    }
+                };
+                quote! {
+                    if #differs {
+                        #encoded_len
+                    } else {
+                        0
+                    }
+                }
+            }
+            Kind::Optional(..) => quote! {
+                #ident.as_ref().map_or(0, |value| #encoded_len_fn(#tag, value))
+            },
+            Kind::Required(..) | Kind::Repeated | Kind::Packed => quote! {
+                #encoded_len_fn(#tag, &#ident)
+            },
+            Kind::OptionalRepeated => quote! {
+                #ident.as_ref().map_or(0, |values| if values.is_empty() {
+                    _prost::encoding::key_len(#tag) + 1
+                } else {
+                    #encoded_len_fn(#tag, values)
+                })
+            },
+        }
+    }
+
+    /// Returns a statement which passes each value of the field to `visitor`.
+    pub fn visit(&self, ident: TokenStream, name: &str) -> TokenStream {
+        let tag = self.tag;
+        let value = if self.epoch {
+            quote! {
+                _prost::ScalarValue::Int(_prost::encoding::unix_nanos::saturating_nanos(value))
+            }
+        } else if let Some(conversion) = self.conversion {
+            conversion.scalar_value(quote!(value))
+        } else {
+            self.ty.scalar_value(quote!(value))
+        };
+        let visit = quote!(visitor.visit_scalar(#tag, #name, #value););
+        match self.kind {
+            Kind::Plain(..) | Kind::Required(..) => quote! {
+                {
+                    let value = &#ident;
+                    #visit
+                }
+            },
+            Kind::Optional(..) => quote! {
+                if let ::std::option::Option::Some(ref value) = #ident {
+                    #visit
+                }
+            },
+            Kind::Repeated | Kind::Packed => quote! {
+                for value in &#ident {
+                    #visit
+                }
+            },
+            Kind::OptionalRepeated => quote! {
+                if let ::std::option::Option::Some(ref values) = #ident {
+                    for value in values {
+                        #visit
+                    }
+                }
+            },
+        }
+    }
+
+    /// Returns an expression which evaluates to the Amino JSON value of the field.
+    pub fn json(&self, ident: TokenStream) -> Result<TokenStream, Error> {
+        if self.epoch || self.conversion.is_some() {
+            bail!("epoch and as fields have no Amino JSON form");
+        }
+        let value = self.ty.json_value(quote!(value));
+        Ok(match self.kind {
+            Kind::Plain(..) | Kind::Required(..) => quote! {
+                {
+                    let value = &#ident;
+                    #value
+                }
+            },
+            Kind::Optional(..) => quote! {
+                match #ident {
+                    ::std::option::Option::Some(ref value) => #value,
+                    ::std::option::Option::None => _prost::serde_json::Value::Null,
+                }
+            },
+            Kind::Repeated | Kind::Packed => quote! {
+                _prost::serde_json::Value::Array(#ident.iter().map(|value| #value).collect())
+            },
+            Kind::OptionalRepeated => quote! {
+                match #ident {
+                    ::std::option::Option::Some(ref values) => _prost::serde_json::Value::Array(
+                        values.iter().map(|value| #value).collect(),
+                    ),
+                    ::std::option::Option::None => _prost::serde_json::Value::Null,
+                }
+            },
+        })
+    }
+
+    /// Returns an expression which evaluates to `true` if the field holds its default value.
+    pub fn is_default(&self, ident: TokenStream) -> TokenStream {
+        match self.kind {
+            Kind::Plain(ref default) | Kind::Required(ref default) => {
+                let differs = default.differs(&ident);
+                quote!(!(#differs))
+            }
+            Kind::Optional(_) | Kind::OptionalRepeated => quote!(#ident.is_none()),
+            Kind::Repeated | Kind::Packed => quote!(#ident.is_empty()),
+        }
+    }
+
+    /// Returns an expression which evaluates to the result of merging a value decoded from a
+    /// `&'a [u8]` into the field, borrowing from the slice for `cow` fields.
+    pub fn merge_borrowed(&self, ident: TokenStream) -> TokenStream {
+        if self.cow {
+            let module = self.module();
+            quote!(_prost::encoding::#module::merge_borrowed(wire_type, &mut #ident, buf))
+        } else {
+            self.merge(ident)
+        }
+    }
+
+    /// Returns an expression which evaluates to the result of merging a value decoded from a
+    /// `Bytes` into the field, sharing the buffer for `shared` fields.
+    pub fn merge_shared(&self, ident: TokenStream) -> TokenStream {
+        if self.shared {
+            quote!(_prost::encoding::shared_bytes::merge_shared(wire_type, &mut #ident, buf))
+        } else {
+            self.merge(ident)
+        }
+    }
 
+    /// Returns a statement which merges the value in `other` into the field. Values which differ
+    /// from the default overwrite the field, and repeated values are appended.
+    pub fn merge_from(&self, ident: TokenStream, other: TokenStream) -> TokenStream {
         match self.kind {
-            Kind::Plain(ref default) => {
-                let default = default.typed();
+            Kind::Plain(ref default) | Kind::Required(ref default) => {
+                let differs = default.differs(&other);
                 quote! {
-                    if #ident != #default {
-                        if #is_amino_prefixed {
-                            #encoded_len_fn(#tag, &#ident) + 5
-                        } else {
-                            #encoded_len_fn(#tag, &#ident)
-                        }
-                    } else {
-                        0
+                    if #differs {
+                        #ident = #other;
                     }
                 }
             }
-            Kind::Optional(..) => quote! {
-                #ident.as_ref().map_or(0, |value| #encoded_len_fn(#tag, value))
+            Kind::Optional(_) => quote! {
+                if #other.is_some() {
+                    #ident = #other;
+                }
             },
-            Kind::Required(..) | Kind::Repeated | Kind::Packed => quote! {
-                #encoded_len_fn(#tag, &#ident)
+            Kind::Repeated | Kind::Packed => quote!(#ident.extend(#other);),
+            Kind::OptionalRepeated => quote! {
+                if let ::std::option::Option::Some(values) = #other {
+                    #ident.get_or_insert_with(::std::vec::Vec::new).extend(values);
+                }
             },
         }
     }
@@ -243,13 +1298,22 @@ impl Field {
     pub fn clear(&self, ident: TokenStream) -> TokenStream {
         match self.kind {
             Kind::Plain(ref default) | Kind::Required(ref default) => {
-                let default = default.typed();
-                match self.ty {
-                    Ty::String | Ty::Bytes => quote!(#ident.clear()),
-                    _ => quote!(#ident = #default),
+                if self.cow {
+                    let default = default.borrowed();
+                    return quote!(#ident = #default);
+                }
+                match *default {
+                    DefaultValue::String(ref value) if value.is_empty() => quote!(#ident.clear()),
+                    DefaultValue::Bytes(ref value) if value.is_empty() => quote!(#ident.clear()),
+                    _ => {
+                        let default = default.owned();
+                        quote!(#ident = #default)
+                    }
                 }
             }
-            Kind::Optional(_) => quote!(#ident = ::std::option::Option::None),
+            Kind::Optional(_) | Kind::OptionalRepeated => {
+                quote!(#ident = ::std::option::Option::None)
+            }
             Kind::Repeated | Kind::Packed => quote!(#ident.clear()),
         }
     }
@@ -257,8 +1321,12 @@ impl Field {
     /// Returns an expression which evaluates to the default value of the field.
     pub fn default(&self) -> TokenStream {
         match self.kind {
+            Kind::Plain(ref value) | Kind::Required(ref value) if self.cow => value.borrowed(),
+            Kind::Plain(..) | Kind::Required(..) if self.shared => {
+                quote!(_prost::bytes::Bytes::new())
+            }
             Kind::Plain(ref value) | Kind::Required(ref value) => value.owned(),
-            Kind::Optional(_) => quote!(::std::option::Option::None),
+            Kind::Optional(_) | Kind::OptionalRepeated => quote!(::std::option::Option::None),
             Kind::Repeated | Kind::Packed => quote!(::std::vec::Vec::new()),
         }
     }
@@ -266,11 +1334,12 @@ impl Field {
     /// An inner debug wrapper, around the base type.
     fn debug_inner(&self, wrap_name: TokenStream) -> TokenStream {
         if let Ty::Enumeration(ref ty) = self.ty {
+            let ty = resolve_path(ty);
             quote! {
                 struct #wrap_name<'a>(&'a i32);
                 impl<'a> ::std::fmt::Debug for #wrap_name<'a> {
                     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
-                        match super::#ty::from_i32(*self.0) {
+                        match #ty::from_i32(*self.0) {
                             None => ::std::fmt::Debug::fmt(&self.0, f),
                             Some(en) => ::std::fmt::Debug::fmt(&en, f),
                         }
@@ -287,7 +1356,13 @@ impl Field {
     /// Returns a fragment for formatting the field `ident` in `Debug`.
     pub fn debug(&self, wrapper_name: TokenStream) -> TokenStream {
         let wrapper = self.debug_inner(quote!(Inner));
-        let inner_ty = self.ty.rust_type();
+        let inner_ty = if self.epoch {
+            quote!(::std::time::SystemTime)
+        } else if let Some(conversion) = self.conversion {
+            conversion.rust_type()
+        } else {
+            self.ty.rust_type()
+        };
         match self.kind {
             Kind::Plain(_) | Kind::Required(_) => self.debug_inner(wrapper_name),
             Kind::Optional(_) => quote! {
@@ -314,60 +1389,273 @@ impl Field {
                     }
                 }
             }
+            Kind::OptionalRepeated => quote! {
+                struct #wrapper_name<'a>(&'a ::std::option::Option<::std::vec::Vec<#inner_ty>>);
+                impl<'a> ::std::fmt::Debug for #wrapper_name<'a> {
+                    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                        #wrapper
+                        let values = self.0.as_ref().map(|values| {
+                            values.iter().map(Inner).collect::<::std::vec::Vec<_>>()
+                        });
+                        ::std::fmt::Debug::fmt(&values, f)
+                    }
+                }
+            },
+        }
+    }
+
+    /// Returns an expression which checks that a required `string` or `bytes` field is not
+    /// empty, or that the time of an `epoch` field is in range. Presence on the wire is
+    /// unaffected: an empty required field is still encoded.
+    pub fn validate(&self, ident: TokenStream) -> Option<TokenStream> {
+        if self.epoch {
+            return Some(match self.kind {
+                Kind::Optional(..) => quote! {
+                    match #ident {
+                        ::std::option::Option::Some(ref time) => _prost::encoding::unix_nanos::check(time),
+                        ::std::option::Option::None => ::std::result::Result::Ok(()),
+                    }
+                },
+                _ => quote!(_prost::encoding::unix_nanos::check(&#ident)),
+            });
+        }
+        match (&self.kind, &self.ty) {
+            (&Kind::Required(..), &Ty::String) | (&Kind::Required(..), &Ty::Bytes) => {
+                Some(quote! {
+                    if #ident.is_empty() {
+                        ::std::result::Result::Err(_prost::ValidationError::new("required field is empty"))
+                    } else {
+                        ::std::result::Result::Ok(())
+                    }
+                })
+            }
+            _ => self.validate_range(ident),
         }
     }
 
+    /// Returns an expression which checks that the values of a field with a `range` are within
+    /// it.
+    fn validate_range(&self, ident: TokenStream) -> Option<TokenStream> {
+        let (min, max) = match self.range {
+            Some((min, max)) => (Literal::i128_unsuffixed(min), Literal::i128_unsuffixed(max)),
+            None => return None,
+        };
+        Some(match self.kind {
+            Kind::Plain(..) | Kind::Required(..) => {
+                quote!(_prost::encoding::validate_range(#ident, #min, #max))
+            }
+            Kind::Optional(..) => quote! {
+                #ident.map_or(::std::result::Result::Ok(()), |value| {
+                    _prost::encoding::validate_range(value, #min, #max)
+                })
+            },
+            _ => quote! {
+                #ident.iter().enumerate().try_for_each(|(index, &value)| {
+                    _prost::encoding::validate_range(value, #min, #max).map_err(|mut error| {
+                        error.push_index(index);
+                        error
+                    })
+                })
+            },
+        })
+    }
+
     /// Returns methods to embed in the message.
     pub fn methods(&self, ident: &Ident) -> Option<TokenStream> {
+        let methods = self.accessor_methods(ident);
+        if !self.stream {
+            return methods;
+        }
+
+        let stream = Ident::new(&format!("decode_{}_stream", ident), Span::call_site());
+        let ty = self.ty.rust_type();
+        let tag = self.tag;
+        let module = self.module();
+        let wire_type = self.wire.as_ref().unwrap_or(&self.ty).wire_type();
+        Some(quote! {
+            #methods
+
+            /// Decodes the values of the field from an encoded message one at a time, without
+            /// collecting them or decoding the other fields.
+            pub fn #stream<B>(
+                buf: B,
+            ) -> impl ::std::iter::Iterator<Item = ::std::result::Result<#ty, _prost::DecodeError>>
+            where B: _prost::bytes::Buf {
+                _prost::encoding::RepeatedStream::new(
+                    #tag,
+                    _prost::encoding::WireType::#wire_type,
+                    _prost::encoding::#module::merge,
+                    buf,
+                )
+            }
+        })
+    }
+
+    /// Returns the return type of the accessor named after the field, if one is generated.
+    pub fn getter_ty(&self) -> Option<TokenStream> {
+        if let Ty::Enumeration(ref ty) = self.ty {
+            let ty = resolve_path(ty);
+            match self.kind {
+                Kind::Plain(_) | Kind::Required(_) | Kind::Optional(_) if self.default_unknown => {
+                    Some(quote!(::std::option::Option<#ty>))
+                }
+                Kind::Plain(_) | Kind::Required(_) | Kind::Optional(_) => Some(quote!(#ty)),
+                Kind::Repeated | Kind::Packed if self.default_unknown => Some(quote! {
+                    ::std::iter::Map<::std::iter::Cloned<::std::slice::Iter<i32>>,
+                                     fn(i32) -> Option<#ty>>
+                }),
+                Kind::Repeated | Kind::Packed => Some(quote! {
+                    ::std::iter::FilterMap<::std::iter::Cloned<::std::slice::Iter<i32>>,
+                                           fn(i32) -> Option<#ty>>
+                }),
+                Kind::OptionalRepeated => None,
+            }
+        } else if let Kind::Optional(_) = self.kind {
+            Some(if self.epoch {
+                quote!(::std::time::SystemTime)
+            } else if let Some(conversion) = self.conversion {
+                conversion.rust_type()
+            } else {
+                self.ty.rust_ref_type()
+            })
+        } else {
+            None
+        }
+    }
+
+    fn accessor_methods(&self, ident: &Ident) -> Option<TokenStream> {
         let set = Ident::new(&format!("set_{}", ident), Span::call_site());
         let push = Ident::new(&format!("push_{}", ident), Span::call_site());
+        let extend = Ident::new(&format!("extend_{}", ident), Span::call_site());
+        let getter_ty = self.getter_ty();
         if let Ty::Enumeration(ref ty) = self.ty {
+            let ty = resolve_path(ty);
             Some(match self.kind {
+                Kind::Plain(_) | Kind::Required(_) if self.default_unknown => {
+                    quote! {
+                        pub fn #ident(&self) -> #getter_ty {
+                            #ty::from_i32(self.#ident)
+                        }
+
+                        pub fn #set(&mut self, value: #ty) {
+                            self.#ident = value as i32;
+                        }
+                    }
+                }
+                Kind::Optional(ref default) if self.default_unknown => {
+                    quote! {
+                        pub fn #ident(&self) -> #getter_ty {
+                            match self.#ident {
+                                ::std::option::Option::Some(value) => #ty::from_i32(value),
+                                ::std::option::Option::None => ::std::option::Option::Some(#default),
+                            }
+                        }
+
+                        pub fn #set(&mut self, value: #ty) {
+                            self.#ident = ::std::option::Option::Some(value as i32);
+                        }
+                    }
+                }
                 Kind::Plain(ref default) | Kind::Required(ref default) => {
                     quote! {
-                        pub fn #ident(&self) -> super::#ty {
-                            super::#ty::from_i32(self.#ident).unwrap_or(super::#default)
+                        pub fn #ident(&self) -> #getter_ty {
+                            #ty::from_i32(self.#ident).unwrap_or(#default)
                         }
 
-                        pub fn #set(&mut self, value: super::#ty) {
+                        pub fn #set(&mut self, value: #ty) {
                             self.#ident = value as i32;
                         }
                     }
                 }
                 Kind::Optional(ref default) => {
                     quote! {
-                        pub fn #ident(&self) -> super::#ty {
-                            self.#ident.and_then(super::#ty::from_i32).unwrap_or(super::#default)
+                        pub fn #ident(&self) -> #getter_ty {
+                            self.#ident.and_then(#ty::from_i32).unwrap_or(#default)
                         }
 
-                        pub fn #set(&mut self, value: super::#ty) {
+                        pub fn #set(&mut self, value: #ty) {
                             self.#ident = ::std::option::Option::Some(value as i32);
                         }
                     }
                 }
+                Kind::Repeated | Kind::Packed if self.default_unknown => {
+                    quote! {
+                        pub fn #ident(&self) -> #getter_ty {
+                            self.#ident.iter().cloned().map(#ty::from_i32)
+                        }
+                        pub fn #push(&mut self, value: #ty) {
+                            self.#ident.push(value as i32);
+                        }
+                        pub fn #extend<I>(&mut self, values: I)
+                        where I: ::std::iter::IntoIterator<Item = #ty> {
+                            self.#ident.extend(values.into_iter().map(|value| value as i32));
+                        }
+                    }
+                }
                 Kind::Repeated | Kind::Packed => {
                     quote! {
-                        pub fn #ident(&self) -> ::std::iter::FilterMap<::std::iter::Cloned<::std::slice::Iter<i32>>,
-                                                                       fn(i32) -> Option<super::#ty>> {
-                            self.#ident.iter().cloned().filter_map(super::#ty::from_i32)
+                        pub fn #ident(&self) -> #getter_ty {
+                            self.#ident.iter().cloned().filter_map(#ty::from_i32)
                         }
-                        pub fn #push(&mut self, value: super::#ty) {
+                        pub fn #push(&mut self, value: #ty) {
                             self.#ident.push(value as i32);
                         }
+                        pub fn #extend<I>(&mut self, values: I)
+                        where I: ::std::iter::IntoIterator<Item = #ty> {
+                            self.#ident.extend(values.into_iter().map(|value| value as i32));
+                        }
+                    }
+                }
+                Kind::OptionalRepeated => quote! {
+                    pub fn #push(&mut self, value: #ty) {
+                        self.#ident.get_or_insert_with(::std::vec::Vec::new).push(value as i32);
+                    }
+                    pub fn #extend<I>(&mut self, values: I)
+                    where I: ::std::iter::IntoIterator<Item = #ty> {
+                        self.#ident
+                            .get_or_insert_with(::std::vec::Vec::new)
+                            .extend(values.into_iter().map(|value| value as i32));
                     }
+                },
+            })
+        } else if let Kind::Repeated | Kind::Packed = self.kind {
+            let ty = self.ty.rust_type();
+            Some(quote! {
+                pub fn #push(&mut self, value: #ty) {
+                    self.#ident.push(value);
+                }
+                pub fn #extend<I>(&mut self, values: I)
+                where I: ::std::iter::IntoIterator<Item = #ty> {
+                    self.#ident.extend(values);
+                }
+            })
+        } else if let Kind::OptionalRepeated = self.kind {
+            let ty = self.ty.rust_type();
+            Some(quote! {
+                pub fn #push(&mut self, value: #ty) {
+                    self.#ident.get_or_insert_with(::std::vec::Vec::new).push(value);
+                }
+                pub fn #extend<I>(&mut self, values: I)
+                where I: ::std::iter::IntoIterator<Item = #ty> {
+                    self.#ident.get_or_insert_with(::std::vec::Vec::new).extend(values);
                 }
             })
         } else if let Kind::Optional(ref default) = self.kind {
-            let ty = self.ty.rust_ref_type();
-
-            let match_some = if self.ty.is_numeric() {
+            let match_some = if self.ty.is_numeric() || self.conversion.is_some() {
                 quote!(::std::option::Option::Some(val) => val,)
+            } else if self.ty == Ty::Json {
+                quote!(::std::option::Option::Some(ref val) => val,)
             } else {
                 quote!(::std::option::Option::Some(ref val) => &val[..],)
             };
+            let default = if self.ty == Ty::Json {
+                quote!(&#default)
+            } else {
+                quote!(#default)
+            };
 
             Some(quote! {
-                pub fn #ident(&self) -> #ty {
+                pub fn #ident(&self) -> #getter_ty {
                     match self.#ident {
                         #match_some
                         ::std::option::Option::None => #default,
@@ -380,6 +1668,35 @@ impl Field {
     }
 }
 
+/// Parses the inclusive `min..=max` range of a `range` or `clamp` attribute, which must be within
+/// the bounds of the integer type of the field.
+fn parse_range(key: &str, range: &str, ty: &Ty) -> Result<(i128, i128), Error> {
+    let (type_min, type_max) = match ty.int_bounds() {
+        Some(bounds) => bounds,
+        None => bail!("{} attribute may only be applied to integer fields", key),
+    };
+    let bounds = match range.find("..=") {
+        Some(i) => (range[..i].trim().parse(), range[i + 3..].trim().parse()),
+        None => bail!(
+            "invalid {} attribute {:?}, expected \"min..=max\"",
+            key,
+            range
+        ),
+    };
+    let (min, max): (i128, i128) = match bounds {
+        (Ok(min), Ok(max)) => (min, max),
+        _ => bail!(
+            "invalid {} attribute {:?}, expected \"min..=max\"",
+            key,
+            range
+        ),
+    };
+    if min > max || min < type_min || max > type_max {
+        bail!("invalid {} attribute {:?} for {} fields", key, range, ty);
+    }
+    Ok((min, max))
+}
+
 /// A scalar protobuf field type.
 #[derive(Clone, PartialEq, Eq)]
 pub enum Ty {
@@ -398,6 +1715,8 @@ pub enum Ty {
     Bool,
     String,
     Bytes,
+    /// A `serde_json::Value`, encoded as a JSON string.
+    Json,
     Enumeration(Path),
 }
 
@@ -419,6 +1738,7 @@ impl Ty {
             Meta::Path(ref name) if name.is_ident("bool") => Ty::Bool,
             Meta::Path(ref name) if name.is_ident("string") => Ty::String,
             Meta::Path(ref name) if name.is_ident("bytes") => Ty::Bytes,
+            Meta::Path(ref name) if name.is_ident("json") => Ty::Json,
             Meta::NameValue(MetaNameValue {
                 ref path,
                 lit: Lit::Str(ref l),
@@ -464,6 +1784,7 @@ impl Ty {
             "bool" => Ty::Bool,
             "string" => Ty::String,
             "bytes" => Ty::Bytes,
+            "json" => Ty::Json,
             s if s.len() > enumeration_len && &s[..enumeration_len] == "enumeration" => {
                 let s = &s[enumeration_len..].trim();
                 match s.chars().next() {
@@ -500,15 +1821,54 @@ impl Ty {
             Ty::Bool => "bool",
             Ty::String => "string",
             Ty::Bytes => "bytes",
+            Ty::Json => "json",
             Ty::Enumeration(..) => "enum",
         }
     }
 
     // TODO: rename to 'owned_type'.
+    /// Returns an expression which converts `value`, a reference to a value of the type, to a
+    /// `ScalarValue`.
+    pub fn scalar_value(&self, value: TokenStream) -> TokenStream {
+        match *self {
+            Ty::Int32 | Ty::Int64 | Ty::Sint32 | Ty::Sint64 | Ty::Sfixed32 | Ty::Sfixed64 => {
+                quote!(_prost::ScalarValue::Int(i64::from(*#value)))
+            }
+            Ty::Uint32 | Ty::Uint64 | Ty::Fixed32 | Ty::Fixed64 => {
+                quote!(_prost::ScalarValue::Uint(u64::from(*#value)))
+            }
+            Ty::Float | Ty::Double => quote!(_prost::ScalarValue::Float(f64::from(*#value))),
+            Ty::Bool => quote!(_prost::ScalarValue::Bool(*#value)),
+            Ty::String => quote!(_prost::ScalarValue::Str(&#value[..])),
+            Ty::Bytes => quote!(_prost::ScalarValue::Bytes(&#value[..])),
+            Ty::Json => quote!(_prost::ScalarValue::Str(&#value.to_string())),
+            Ty::Enumeration(_) => quote!(_prost::ScalarValue::Enum(*#value)),
+        }
+    }
+
+    /// Returns an expression which converts `value`, a reference to a value of the type, to its
+    /// Amino JSON value. 64-bit integers are written as strings, since JSON numbers may not hold
+    /// them exactly, and `bytes` as base64.
+    pub fn json_value(&self, value: TokenStream) -> TokenStream {
+        match *self {
+            Ty::Int64 | Ty::Uint64 | Ty::Sint64 | Ty::Fixed64 | Ty::Sfixed64 => {
+                quote!(_prost::serde_json::Value::String(#value.to_string()))
+            }
+            Ty::Bool => quote!(_prost::serde_json::Value::Bool(*#value)),
+            Ty::String => quote!(_prost::serde_json::Value::String(#value.to_string())),
+            Ty::Bytes => quote! {
+                _prost::serde_json::Value::String(_prost::encoding::json::base64(&#value[..]))
+            },
+            Ty::Json => quote!(::std::clone::Clone::clone(#value)),
+            _ => quote!(_prost::serde_json::Value::from(*#value)),
+        }
+    }
+
     pub fn rust_type(&self) -> TokenStream {
         match *self {
             Ty::String => quote!(::std::string::String),
             Ty::Bytes => quote!(::std::vec::Vec<u8>),
+            Ty::Json => quote!(_prost::serde_json::Value),
             _ => self.rust_ref_type(),
         }
     }
@@ -531,10 +1891,22 @@ impl Ty {
             Ty::Bool => quote!(bool),
             Ty::String => quote!(&str),
             Ty::Bytes => quote!(&[u8]),
+            Ty::Json => quote!(&_prost::serde_json::Value),
             Ty::Enumeration(..) => quote!(i32),
         }
     }
 
+    /// Returns the wire type of a single encoded value of the type.
+    pub fn wire_type(&self) -> Ident {
+        let wire_type = match *self {
+            Ty::Double | Ty::Fixed64 | Ty::Sfixed64 => "SixtyFourBit",
+            Ty::Float | Ty::Fixed32 | Ty::Sfixed32 => "ThirtyTwoBit",
+            Ty::String | Ty::Bytes | Ty::Json => "LengthDelimited",
+            _ => "Varint",
+        };
+        Ident::new(wire_type, Span::call_site())
+    }
+
     pub fn module(&self) -> Ident {
         match *self {
             Ty::Enumeration(..) => Ident::new("int32", Span::call_site()),
@@ -542,9 +1914,32 @@ impl Ty {
         }
     }
 
-    /// Returns true if the scalar type is length delimited (i.e., `string` or `bytes`).
+    /// Returns the maximum encoded length of a varint value of the type, or `None` if the type is
+    /// not encoded as a varint. Negative `int32` values are sign extended to ten bytes.
+    pub fn max_varint_len(&self) -> Option<u32> {
+        match *self {
+            Ty::Bool => Some(1),
+            Ty::Uint32 | Ty::Sint32 => Some(5),
+            Ty::Int32 | Ty::Int64 | Ty::Uint64 | Ty::Sint64 | Ty::Enumeration(..) => Some(10),
+            _ => None,
+        }
+    }
+
+    /// Returns the minimum and maximum values of an integer type, or `None` if the type is not an
+    /// integer.
+    pub fn int_bounds(&self) -> Option<(i128, i128)> {
+        match *self {
+            Ty::Int32 | Ty::Sint32 | Ty::Sfixed32 => Some((i32::MIN.into(), i32::MAX.into())),
+            Ty::Int64 | Ty::Sint64 | Ty::Sfixed64 => Some((i64::MIN.into(), i64::MAX.into())),
+            Ty::Uint32 | Ty::Fixed32 => Some((0, u32::MAX.into())),
+            Ty::Uint64 | Ty::Fixed64 => Some((0, u64::MAX.into())),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the scalar type is length delimited (i.e., `string`, `bytes` or `json`).
     pub fn is_numeric(&self) -> bool {
-        *self != Ty::String && *self != Ty::Bytes
+        *self != Ty::String && *self != Ty::Bytes && *self != Ty::Json
     }
 }
 
@@ -560,6 +1955,166 @@ impl fmt::Display for Ty {
     }
 }
 
+/// A wire encoding override for a numeric scalar field, e.g. `wire = "fixed"`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Wire {
+    /// Variable length encoding, as used by `int32`, `uint64`, etc.
+    Varint,
+    /// Fixed width encoding, as used by `fixed32`, `sfixed64`, etc.
+    Fixed,
+    /// Zigzag variable length encoding, as used by `sint32` and `sint64`.
+    Zigzag,
+}
+
+impl Wire {
+    pub fn from_attr(attr: &Meta) -> Result<Option<Wire>, Error> {
+        if word_attr("zigzag", attr) || word_attr("signed_varint", attr) {
+            return Ok(Some(Wire::Zigzag));
+        }
+        if !attr.path().is_ident("wire") {
+            return Ok(None);
+        }
+        match *attr {
+            Meta::NameValue(MetaNameValue {
+                lit: Lit::Str(ref lit),
+                ..
+            }) => match lit.value().trim() {
+                "varint" => Ok(Some(Wire::Varint)),
+                "fixed" => Ok(Some(Wire::Fixed)),
+                "zigzag" => Ok(Some(Wire::Zigzag)),
+                other => bail!("invalid wire attribute: {}", other),
+            },
+            _ => bail!("invalid wire attribute: {:?}", attr),
+        }
+    }
+
+    /// Returns the scalar type whose codec encodes values of `ty` with this wire encoding, or
+    /// `None` if the encoding can not represent the type.
+    pub fn codec(self, ty: &Ty) -> Option<Ty> {
+        let codec = match (self, ty) {
+            (Wire::Varint, &Ty::Int32) | (Wire::Varint, &Ty::Sfixed32) => Ty::Int32,
+            (Wire::Varint, &Ty::Int64) | (Wire::Varint, &Ty::Sfixed64) => Ty::Int64,
+            (Wire::Varint, &Ty::Uint32) | (Wire::Varint, &Ty::Fixed32) => Ty::Uint32,
+            (Wire::Varint, &Ty::Uint64) | (Wire::Varint, &Ty::Fixed64) => Ty::Uint64,
+            (Wire::Varint, &Ty::Sint32) => Ty::Sint32,
+            (Wire::Varint, &Ty::Sint64) => Ty::Sint64,
+            (Wire::Varint, &Ty::Enumeration(..)) => Ty::Int32,
+            (Wire::Fixed, &Ty::Int32)
+            | (Wire::Fixed, &Ty::Sint32)
+            | (Wire::Fixed, &Ty::Sfixed32) => Ty::Sfixed32,
+            (Wire::Fixed, &Ty::Int64)
+            | (Wire::Fixed, &Ty::Sint64)
+            | (Wire::Fixed, &Ty::Sfixed64) => Ty::Sfixed64,
+            (Wire::Fixed, &Ty::Uint32) | (Wire::Fixed, &Ty::Fixed32) => Ty::Fixed32,
+            (Wire::Fixed, &Ty::Uint64) | (Wire::Fixed, &Ty::Fixed64) => Ty::Fixed64,
+            (Wire::Fixed, &Ty::Enumeration(..)) => Ty::Sfixed32,
+            (Wire::Zigzag, &Ty::Int32)
+            | (Wire::Zigzag, &Ty::Sint32)
+            | (Wire::Zigzag, &Ty::Sfixed32) => Ty::Sint32,
+            (Wire::Zigzag, &Ty::Int64)
+            | (Wire::Zigzag, &Ty::Sint64)
+            | (Wire::Zigzag, &Ty::Sfixed64) => Ty::Sint64,
+            _ => return None,
+        };
+        Some(codec)
+    }
+}
+
+/// A std type which a scalar field holds in place of its Protobuf type, converted when the field
+/// is encoded and decoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Conversion {
+    /// An `Ipv4Addr`, held by a `fixed32` or `sfixed32` field.
+    Ipv4Addr,
+    /// An `Ipv6Addr`, held by a `bytes` field of 16 octets.
+    Ipv6Addr,
+    /// A `u16`, such as a port, held by a `uint32` field.
+    U16,
+}
+
+impl Conversion {
+    /// Returns the conversion to the type named by an `as` attribute, checking that a field of
+    /// type `ty` has the width of the type on the wire.
+    pub fn new(name: &str, ty: &Ty) -> Result<Conversion, Error> {
+        let (conversion, fits) = match name {
+            "Ipv4Addr" => (
+                Conversion::Ipv4Addr,
+                *ty == Ty::Fixed32 || *ty == Ty::Sfixed32,
+            ),
+            "Ipv6Addr" => (Conversion::Ipv6Addr, *ty == Ty::Bytes),
+            "u16" => (Conversion::U16, *ty == Ty::Uint32),
+            _ => bail!(
+                "unknown as type {:?}, expected \"Ipv4Addr\", \"Ipv6Addr\" or \"u16\"",
+                name
+            ),
+        };
+        if !fits {
+            let expected = match conversion {
+                Conversion::Ipv4Addr => "fixed32 or sfixed32",
+                Conversion::Ipv6Addr => "bytes",
+                Conversion::U16 => "uint32",
+            };
+            bail!(
+                "as type {} may only be applied to {} fields, not {}",
+                name,
+                expected,
+                ty
+            );
+        }
+        Ok(conversion)
+    }
+
+    /// Returns the encoding module of the type.
+    fn module(self) -> Ident {
+        let module = match self {
+            Conversion::Ipv4Addr => "ipv4_addr",
+            Conversion::Ipv6Addr => "ipv6_addr",
+            Conversion::U16 => "port",
+        };
+        Ident::new(module, Span::call_site())
+    }
+
+    pub fn rust_type(self) -> TokenStream {
+        match self {
+            Conversion::Ipv4Addr => quote!(::std::net::Ipv4Addr),
+            Conversion::Ipv6Addr => quote!(::std::net::Ipv6Addr),
+            Conversion::U16 => quote!(u16),
+        }
+    }
+
+    /// Returns the value of the type which is encoded as the default of the Protobuf type.
+    fn default(self) -> DefaultValue {
+        match self {
+            Conversion::Ipv4Addr => DefaultValue::Path(
+                parse_str::<Path>("::std::net::Ipv4Addr::UNSPECIFIED").expect("path"),
+            ),
+            Conversion::Ipv6Addr => DefaultValue::Path(
+                parse_str::<Path>("::std::net::Ipv6Addr::UNSPECIFIED").expect("path"),
+            ),
+            Conversion::U16 => DefaultValue::Expr(quote!(0)),
+        }
+    }
+
+    /// Returns the `ParsedDefault` variant describing the encoded default of the type.
+    pub fn parsed_default(self) -> TokenStream {
+        match self {
+            Conversion::Ipv4Addr | Conversion::U16 => quote!(Uint(0)),
+            Conversion::Ipv6Addr => quote!(Bytes(&[0; 16])),
+        }
+    }
+
+    /// Returns the `ScalarValue` of a reference to a value of the type, as encoded.
+    fn scalar_value(self, value: TokenStream) -> TokenStream {
+        match self {
+            Conversion::Ipv4Addr => {
+                quote!(_prost::ScalarValue::Uint(u64::from(u32::from(*#value))))
+            }
+            Conversion::Ipv6Addr => quote!(_prost::ScalarValue::Bytes(&#value.octets()[..])),
+            Conversion::U16 => quote!(_prost::ScalarValue::Uint(u64::from(*#value))),
+        }
+    }
+}
+
 /// Scalar Protobuf field types.
 #[derive(Clone, Debug)]
 pub enum Kind {
@@ -573,6 +2128,24 @@ pub enum Kind {
     Repeated,
     /// A packed repeated scalar field.
     Packed,
+    /// An optional packed repeated scalar field, which distinguishes an empty list from unset.
+    OptionalRepeated,
+}
+
+/// Returns the path of an enumeration type or default function as seen from the module holding
+/// the generated code.
+/// Relative paths are resolved from the parent module, while absolute and `crate::` paths are
+/// used as written.
+pub fn resolve_path(path: &Path) -> TokenStream {
+    if path.leading_colon.is_some() {
+        return quote!(#path);
+    }
+    let mut segments = path.segments.iter();
+    match segments.next() {
+        Some(first) if first.ident == "crate" => quote!(#path),
+        Some(first) if first.ident == "self" => quote!(super #(::#segments)*),
+        _ => quote!(super::#path),
+    }
 }
 
 /// Scalar Protobuf field default value.
@@ -589,6 +2162,10 @@ pub enum DefaultValue {
     Bytes(Vec<u8>),
     Enumeration(TokenStream),
     Path(Path),
+    /// A function which is called to produce the default value.
+    Fn(Path),
+    /// A constant expression, which is evaluated when the message is compiled.
+    Expr(TokenStream),
 }
 
 impl DefaultValue {
@@ -602,6 +2179,32 @@ impl DefaultValue {
         }
     }
 
+    pub fn from_fn_attr(attr: &Meta) -> Result<Option<Path>, Error> {
+        if !attr.path().is_ident("default_fn") {
+            return Ok(None);
+        }
+        match *attr {
+            Meta::NameValue(MetaNameValue {
+                lit: Lit::Str(ref lit),
+                ..
+            }) => Ok(Some(parse_str::<Path>(&lit.value())?)),
+            _ => bail!("invalid default_fn attribute: {:?}", attr),
+        }
+    }
+
+    pub fn from_expr_attr(attr: &Meta) -> Result<Option<TokenStream>, Error> {
+        if !attr.path().is_ident("default_expr") {
+            return Ok(None);
+        }
+        match *attr {
+            Meta::NameValue(MetaNameValue {
+                lit: Lit::Str(ref lit),
+                ..
+            }) => Ok(Some(parse_str::<Expr>(&lit.value())?.into_token_stream())),
+            _ => bail!("invalid default_expr attribute: {:?}", attr),
+        }
+    }
+
     pub fn from_lit(ty: &Ty, lit: Lit) -> Result<DefaultValue, Error> {
         let is_i32 = *ty == Ty::Int32 || *ty == Ty::Sint32 || *ty == Ty::Sfixed32;
         let is_i64 = *ty == Ty::Int64 || *ty == Ty::Sint64 || *ty == Ty::Sfixed64;
@@ -609,30 +2212,18 @@ impl DefaultValue {
         let is_u32 = *ty == Ty::Uint32 || *ty == Ty::Fixed32;
         let is_u64 = *ty == Ty::Uint64 || *ty == Ty::Fixed64;
 
-        let empty_or_is = |expected, actual: &str| expected == actual || actual.is_empty();
+        check_suffix(ty, &lit)?;
 
         let default = match lit {
-            Lit::Int(ref lit) if is_i32 && empty_or_is("i32", lit.suffix()) => {
-                DefaultValue::I32(lit.base10_parse()?)
-            }
-            Lit::Int(ref lit) if is_i64 && empty_or_is("i64", lit.suffix()) => {
-                DefaultValue::I64(lit.base10_parse()?)
-            }
-            Lit::Int(ref lit) if is_u32 && empty_or_is("u32", lit.suffix()) => {
-                DefaultValue::U32(lit.base10_parse()?)
-            }
-            Lit::Int(ref lit) if is_u64 && empty_or_is("u64", lit.suffix()) => {
-                DefaultValue::U64(lit.base10_parse()?)
-            }
+            Lit::Int(ref lit) if is_i32 => DefaultValue::I32(lit.base10_parse()?),
+            Lit::Int(ref lit) if is_i64 => DefaultValue::I64(lit.base10_parse()?),
+            Lit::Int(ref lit) if is_u32 => DefaultValue::U32(lit.base10_parse()?),
+            Lit::Int(ref lit) if is_u64 => DefaultValue::U64(lit.base10_parse()?),
 
-            Lit::Float(ref lit) if *ty == Ty::Float && empty_or_is("f32", lit.suffix()) => {
-                DefaultValue::F32(lit.base10_parse()?)
-            }
+            Lit::Float(ref lit) if *ty == Ty::Float => DefaultValue::F32(lit.base10_parse()?),
             Lit::Int(ref lit) if *ty == Ty::Float => DefaultValue::F32(lit.base10_parse()?),
 
-            Lit::Float(ref lit) if *ty == Ty::Double && empty_or_is("f64", lit.suffix()) => {
-                DefaultValue::F64(lit.base10_parse()?)
-            }
+            Lit::Float(ref lit) if *ty == Ty::Double => DefaultValue::F64(lit.base10_parse()?),
             Lit::Int(ref lit) if *ty == Ty::Double => DefaultValue::F64(lit.base10_parse()?),
 
             Lit::Bool(ref lit) if *ty == Ty::Bool => DefaultValue::Bool(lit.value),
@@ -643,8 +2234,18 @@ impl DefaultValue {
                 let value = lit.value();
                 let value = value.trim();
 
+                // Booleans may also be written as `yes`/`no` or `on`/`off`, in any case.
+                if *ty == Ty::Bool {
+                    return match &value.to_lowercase()[..] {
+                        "true" | "yes" | "on" => Ok(DefaultValue::Bool(true)),
+                        "false" | "no" | "off" => Ok(DefaultValue::Bool(false)),
+                        _ => bail!("invalid bool default value: {}", quote!(#value)),
+                    };
+                }
+
                 if let Ty::Enumeration(ref path) = *ty {
                     let variant = Ident::new(value, Span::call_site());
+                    let path = resolve_path(path);
                     return Ok(DefaultValue::Enumeration(quote!(#path::#variant)));
                 }
 
@@ -653,16 +2254,16 @@ impl DefaultValue {
                     match value {
                         "inf" => {
                             return Ok(DefaultValue::Path(parse_str::<Path>(
-                                "::core::f32::INFINITY",
+                                "::std::f32::INFINITY",
                             )?));
                         }
                         "-inf" => {
                             return Ok(DefaultValue::Path(parse_str::<Path>(
-                                "::core::f32::NEG_INFINITY",
+                                "::std::f32::NEG_INFINITY",
                             )?));
                         }
                         "nan" => {
-                            return Ok(DefaultValue::Path(parse_str::<Path>("::core::f32::NAN")?));
+                            return Ok(DefaultValue::Path(parse_str::<Path>("::std::f32::NAN")?));
                         }
                         _ => (),
                     }
@@ -671,16 +2272,16 @@ impl DefaultValue {
                     match value {
                         "inf" => {
                             return Ok(DefaultValue::Path(parse_str::<Path>(
-                                "::core::f64::INFINITY",
+                                "::std::f64::INFINITY",
                             )?));
                         }
                         "-inf" => {
                             return Ok(DefaultValue::Path(parse_str::<Path>(
-                                "::core::f64::NEG_INFINITY",
+                                "::std::f64::NEG_INFINITY",
                             )?));
                         }
                         "nan" => {
-                            return Ok(DefaultValue::Path(parse_str::<Path>("::core::f64::NAN")?));
+                            return Ok(DefaultValue::Path(parse_str::<Path>("::std::f64::NAN")?));
                         }
                         _ => (),
                     }
@@ -689,36 +2290,33 @@ impl DefaultValue {
                 // Rust doesn't have a negative literals, so they have to be parsed specially.
                 if value.starts_with('-') {
                     if let Ok(lit) = syn::parse_str::<Lit>(&value[1..]) {
+                        check_suffix(ty, &lit)?;
                         match lit {
-                            Lit::Int(ref lit) if is_i32 && empty_or_is("i32", lit.suffix()) => {
+                            Lit::Int(ref lit) if is_i32 => {
                                 // Initially parse into an i64, so that i32::MIN does not overflow.
                                 let value: i64 = -lit.base10_parse()?;
                                 return Ok(i32::try_from(value).map(DefaultValue::I32)?);
                             }
 
-                            Lit::Int(ref lit) if is_i64 && empty_or_is("i64", lit.suffix()) => {
+                            Lit::Int(ref lit) if is_i64 => {
                                 // Initially parse into an i128, so that i64::MIN does not overflow.
                                 let value: i128 = -lit.base10_parse()?;
                                 return Ok(i64::try_from(value).map(DefaultValue::I64)?);
                             }
 
-                            Lit::Float(ref lit)
-                                if *ty == Ty::Float && empty_or_is("f32", lit.suffix()) =>
-                            {
+                            Lit::Float(ref lit) if *ty == Ty::Float => {
                                 return Ok(DefaultValue::F32(-lit.base10_parse()?));
                             }
 
-                            Lit::Float(ref lit)
-                                if *ty == Ty::Double && empty_or_is("f64", lit.suffix()) =>
-                            {
+                            Lit::Float(ref lit) if *ty == Ty::Double => {
                                 return Ok(DefaultValue::F64(-lit.base10_parse()?));
                             }
 
-                            Lit::Int(ref lit) if *ty == Ty::Float && lit.suffix().is_empty() => {
+                            Lit::Int(ref lit) if *ty == Ty::Float => {
                                 return Ok(DefaultValue::F32(-lit.base10_parse()?));
                             }
 
-                            Lit::Int(ref lit) if *ty == Ty::Double && lit.suffix().is_empty() => {
+                            Lit::Int(ref lit) if *ty == Ty::Double => {
                                 return Ok(DefaultValue::F64(-lit.base10_parse()?));
                             }
 
@@ -751,8 +2349,12 @@ impl DefaultValue {
             Ty::Bool => DefaultValue::Bool(false),
             Ty::String => DefaultValue::String(String::new()),
             Ty::Bytes => DefaultValue::Bytes(Vec::new()),
+            Ty::Json => DefaultValue::Path(
+                parse_str::<Path>("_prost::serde_json::Value::Null").expect("path"),
+            ),
             Ty::Enumeration(ref path) => {
-                return DefaultValue::Enumeration(quote!(#path::default()))
+                let path = resolve_path(path);
+                return DefaultValue::Enumeration(quote!(#path::default()));
             }
         }
     }
@@ -766,22 +2368,183 @@ impl DefaultValue {
             DefaultValue::Bytes(ref value) if value.is_empty() => quote!(::std::vec::Vec::new()),
             DefaultValue::Bytes(ref value) => {
                 let lit = LitByteStr::new(value, Span::call_site());
-                quote!(#lit.to_owned())
+                quote!(#lit.to_vec())
             }
 
             ref other => other.typed(),
         }
     }
 
+    /// Returns a `Cow::Borrowed` expression for a `string` or `bytes` default.
+    pub fn borrowed(&self) -> TokenStream {
+        match *self {
+            DefaultValue::String(ref value) => quote!(::std::borrow::Cow::Borrowed(#value)),
+            DefaultValue::Bytes(ref value) => {
+                let lit = LitByteStr::new(value, Span::call_site());
+                quote!(::std::borrow::Cow::Borrowed(&#lit[..]))
+            }
+            _ => unreachable!("only string and bytes fields may be held in a Cow"),
+        }
+    }
+
+    /// Returns the `ParsedDefault` variant describing the default of a field of type `ty`, or
+    /// `None` for the implicit default of an enumeration, whose variant is not known.
+    pub fn parsed(&self, ty: &Ty) -> Option<TokenStream> {
+        Some(match *self {
+            DefaultValue::F64(value) => quote!(Float(#value)),
+            DefaultValue::F32(value) => {
+                let value = f64::from(value);
+                quote!(Float(#value))
+            }
+            DefaultValue::I32(value) => {
+                let value = i64::from(value);
+                quote!(Int(#value))
+            }
+            DefaultValue::I64(value) => quote!(Int(#value)),
+            DefaultValue::U32(value) => {
+                let value = u64::from(value);
+                quote!(Uint(#value))
+            }
+            DefaultValue::U64(value) => quote!(Uint(#value)),
+            DefaultValue::Bool(value) => quote!(Bool(#value)),
+            DefaultValue::String(ref value) => quote!(Str(#value)),
+            DefaultValue::Bytes(ref value) => {
+                let lit = LitByteStr::new(value, Span::call_site());
+                quote!(Bytes(#lit))
+            }
+            // An explicit default ends with the variant, and the implicit one with `default()`.
+            DefaultValue::Enumeration(ref value) => match value.clone().into_iter().last() {
+                Some(TokenTree::Ident(variant)) => {
+                    let variant = variant.to_string();
+                    quote!(Enum(#variant))
+                }
+                _ => return None,
+            },
+            DefaultValue::Path(ref path) => quote!(Float(#path as f64)),
+            DefaultValue::Fn(ref path) => {
+                let segments = path
+                    .segments
+                    .iter()
+                    .map(|segment| segment.ident.to_string())
+                    .collect::<Vec<_>>()
+                    .join("::");
+                let path = match path.leading_colon {
+                    Some(_) => format!("::{}", segments),
+                    None => segments,
+                };
+                quote!(Fn(#path))
+            }
+            // The descriptor is a constant, so the expression is evaluated when it is compiled.
+            // It is given the type of the field before it is widened.
+            DefaultValue::Expr(_) => {
+                let rust_ty = ty.rust_type();
+                let value = quote!({
+                    let value: #rust_ty = #self;
+                    value
+                });
+                match *ty {
+                    Ty::Bool => quote!(Bool(#value)),
+                    Ty::Uint32 | Ty::Uint64 | Ty::Fixed32 | Ty::Fixed64 => {
+                        quote!(Uint(#value as u64))
+                    }
+                    Ty::Float | Ty::Double => quote!(Float(#value as f64)),
+                    _ => quote!(Int(#value as i64)),
+                }
+            }
+        })
+    }
+
     pub fn typed(&self) -> TokenStream {
         if let DefaultValue::Enumeration(_) = *self {
-            quote!(super::#self as i32)
+            quote!(#self as i32)
         } else {
             quote!(#self)
         }
     }
+
+    /// Returns an expression which evaluates to `true` if the field value differs from the
+    /// default. Empty `string` and `bytes` defaults check `is_empty`, and other `bytes`
+    /// defaults compare lengths before contents. Fields with a default function are always
+    /// considered to differ, so that they are always encoded.
+    pub fn differs(&self, ident: &TokenStream) -> TokenStream {
+        match *self {
+            DefaultValue::Fn(_) => quote!(true),
+            DefaultValue::String(ref value) if value.is_empty() => quote!(!#ident.is_empty()),
+            DefaultValue::Bytes(ref value) if value.is_empty() => quote!(!#ident.is_empty()),
+            DefaultValue::Bytes(ref value) => {
+                let len = value.len();
+                quote!((#ident.len() != #len || #ident[..] != #self[..]))
+            }
+            _ => {
+                let default = self.typed();
+                quote!(#ident != #default)
+            }
+        }
+    }
+}
+
+impl DefaultValue {
+    /// Returns the position of the variant in the declaration order, used to order values of
+    /// different kinds.
+    fn rank(&self) -> u8 {
+        match *self {
+            DefaultValue::F64(_) => 0,
+            DefaultValue::F32(_) => 1,
+            DefaultValue::I32(_) => 2,
+            DefaultValue::I64(_) => 3,
+            DefaultValue::U32(_) => 4,
+            DefaultValue::U64(_) => 5,
+            DefaultValue::Bool(_) => 6,
+            DefaultValue::String(_) => 7,
+            DefaultValue::Bytes(_) => 8,
+            DefaultValue::Enumeration(_) => 9,
+            DefaultValue::Path(_) => 10,
+            DefaultValue::Fn(_) => 11,
+            DefaultValue::Expr(_) => 12,
+        }
+    }
+}
+
+/// Default values are totally ordered so that codegen never depends on an unstable order. Floats
+/// are compared with `total_cmp`, and enumeration variants and paths by their token text.
+impl Ord for DefaultValue {
+    fn cmp(&self, other: &DefaultValue) -> Ordering {
+        match (self, other) {
+            (DefaultValue::F64(a), DefaultValue::F64(b)) => a.total_cmp(b),
+            (DefaultValue::F32(a), DefaultValue::F32(b)) => a.total_cmp(b),
+            (DefaultValue::I32(a), DefaultValue::I32(b)) => a.cmp(b),
+            (DefaultValue::I64(a), DefaultValue::I64(b)) => a.cmp(b),
+            (DefaultValue::U32(a), DefaultValue::U32(b)) => a.cmp(b),
+            (DefaultValue::U64(a), DefaultValue::U64(b)) => a.cmp(b),
+            (DefaultValue::Bool(a), DefaultValue::Bool(b)) => a.cmp(b),
+            (DefaultValue::String(a), DefaultValue::String(b)) => a.cmp(b),
+            (DefaultValue::Bytes(a), DefaultValue::Bytes(b)) => a.cmp(b),
+            (DefaultValue::Enumeration(a), DefaultValue::Enumeration(b))
+            | (DefaultValue::Expr(a), DefaultValue::Expr(b)) => a.to_string().cmp(&b.to_string()),
+            (DefaultValue::Path(a), DefaultValue::Path(b))
+            | (DefaultValue::Fn(a), DefaultValue::Fn(b)) => a
+                .to_token_stream()
+                .to_string()
+                .cmp(&b.to_token_stream().to_string()),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for DefaultValue {
+    fn partial_cmp(&self, other: &DefaultValue) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for DefaultValue {
+    fn eq(&self, other: &DefaultValue) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
 }
 
+impl Eq for DefaultValue {}
+
 impl quote::ToTokens for DefaultValue {
     fn to_tokens(&self, tokens: &mut TokenStream) {
         match *self {
@@ -798,6 +2561,145 @@ impl quote::ToTokens for DefaultValue {
             }
             DefaultValue::Enumeration(ref value) => value.to_tokens(tokens),
             DefaultValue::Path(ref value) => value.to_tokens(tokens),
+            DefaultValue::Fn(ref path) => {
+                let path = resolve_path(path);
+                quote!(#path()).to_tokens(tokens)
+            }
+            // An inline constant, whose type is inferred from the field.
+            DefaultValue::Expr(ref expr) => quote!(const { #expr }).to_tokens(tokens),
         }
     }
 }
+
+/// Checks that an explicit suffix of a numeric default literal matches the type of the field,
+/// e.g. that a `uint32` default is not written as `5i64`. Unsuffixed literals are accepted.
+fn check_suffix(ty: &Ty, lit: &Lit) -> Result<(), Error> {
+    let suffix = match *lit {
+        Lit::Int(ref lit) => lit.suffix(),
+        Lit::Float(ref lit) => lit.suffix(),
+        _ => return Ok(()),
+    };
+    let expected = match *ty {
+        Ty::Int32 | Ty::Sint32 | Ty::Sfixed32 => "i32",
+        Ty::Int64 | Ty::Sint64 | Ty::Sfixed64 => "i64",
+        Ty::Uint32 | Ty::Fixed32 => "u32",
+        Ty::Uint64 | Ty::Fixed64 => "u64",
+        Ty::Float => "f32",
+        Ty::Double => "f64",
+        _ => return Ok(()),
+    };
+    if !suffix.is_empty() && suffix != expected {
+        bail!(
+            "invalid default value: {} has suffix {}, but {} fields require {} or no suffix",
+            quote!(#lit),
+            suffix,
+            ty,
+            expected
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_values_are_ordered() {
+        let mut values = vec![
+            DefaultValue::String("b".to_owned()),
+            DefaultValue::F64(::std::f64::NAN),
+            DefaultValue::I32(2),
+            DefaultValue::String("a".to_owned()),
+            DefaultValue::F64(-0.0),
+            DefaultValue::I32(-1),
+        ];
+        values.sort();
+        assert_eq!(
+            values,
+            vec![
+                DefaultValue::F64(-0.0),
+                DefaultValue::F64(::std::f64::NAN),
+                DefaultValue::I32(-1),
+                DefaultValue::I32(2),
+                DefaultValue::String("a".to_owned()),
+                DefaultValue::String("b".to_owned()),
+            ]
+        );
+    }
+
+    fn parse_default(ty: Ty, lit: &str) -> Result<DefaultValue, Error> {
+        DefaultValue::from_lit(&ty, parse_str::<Lit>(lit).unwrap())
+    }
+
+    #[test]
+    fn default_suffixes() {
+        assert_eq!(
+            parse_default(Ty::Int32, "5i32").unwrap(),
+            DefaultValue::I32(5)
+        );
+        assert_eq!(
+            parse_default(Ty::Uint64, "5u64").unwrap(),
+            DefaultValue::U64(5)
+        );
+        assert_eq!(
+            parse_default(Ty::Double, "1.5f64").unwrap(),
+            DefaultValue::F64(1.5)
+        );
+        assert_eq!(
+            parse_default(Ty::Int64, "\"-5i64\"").unwrap(),
+            DefaultValue::I64(-5)
+        );
+
+        assert_eq!(parse_default(Ty::Int64, "5").unwrap(), DefaultValue::I64(5));
+        assert_eq!(
+            parse_default(Ty::Float, "5").unwrap(),
+            DefaultValue::F32(5.0)
+        );
+        assert_eq!(
+            parse_default(Ty::Sint32, "\"-5\"").unwrap(),
+            DefaultValue::I32(-5)
+        );
+
+        let error = parse_default(Ty::Int64, "5u32").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid default value: 5u32 has suffix u32, but int64 fields require i64 or no suffix"
+        );
+        assert!(parse_default(Ty::Int32, "5isize").is_err());
+        assert!(parse_default(Ty::Float, "5u32").is_err());
+        assert!(parse_default(Ty::Double, "1.5f32").is_err());
+        assert!(parse_default(Ty::Int64, "\"-5i32\"").is_err());
+    }
+
+    #[test]
+    fn bool_string_defaults() {
+        assert_eq!(
+            parse_default(Ty::Bool, "true").unwrap(),
+            DefaultValue::Bool(true)
+        );
+        assert_eq!(
+            parse_default(Ty::Bool, "\"false\"").unwrap(),
+            DefaultValue::Bool(false)
+        );
+        assert_eq!(
+            parse_default(Ty::Bool, "\"yes\"").unwrap(),
+            DefaultValue::Bool(true)
+        );
+        assert_eq!(
+            parse_default(Ty::Bool, "\"On\"").unwrap(),
+            DefaultValue::Bool(true)
+        );
+        assert_eq!(
+            parse_default(Ty::Bool, "\"OFF\"").unwrap(),
+            DefaultValue::Bool(false)
+        );
+        assert_eq!(
+            parse_default(Ty::Bool, "\" no \"").unwrap(),
+            DefaultValue::Bool(false)
+        );
+
+        let error = parse_default(Ty::Bool, "\"maybe\"").unwrap_err();
+        assert_eq!(error.to_string(), "invalid bool default value: \"maybe\"");
+    }
+}
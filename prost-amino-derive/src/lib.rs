@@ -13,10 +13,11 @@ extern crate failure;
 #[macro_use]
 extern crate quote;
 
+use std::collections::BTreeSet;
+
 use failure::Error;
 use itertools::Itertools;
-use proc_macro::TokenStream;
-use proc_macro2::Span;
+use proc_macro2::{Span, TokenStream};
 use sha2::{Digest, Sha256};
 use syn::punctuated::Punctuated;
 use syn::{
@@ -28,10 +29,60 @@ mod field;
 
 use field::Field;
 
+/// The smallest and largest valid field tags.
+const MIN_TAG: u32 = 1;
+const MAX_TAG: u32 = (1 << 29) - 1;
+
 fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
-    let input: DeriveInput = syn::parse(input)?;
+    let input: DeriveInput = syn::parse2(input)?;
 
     let top_level_attrs: Vec<syn::Attribute> = input.attrs;
+
+    let mut tag_prefix = None;
+    let mut reserved_tags = None;
+    let mut reserved_names = None;
+    let mut transparent = false;
+    let mut hazzers = false;
+    let mut hash_encoded = false;
+    let mut bit_eq = false;
+    let mut builder = false;
+    let mut amino_json = false;
+    for attr in field::prost_attrs(top_level_attrs.clone())? {
+        if let Some(p) = field::u32_attr("tag_prefix", &attr)? {
+            field::set_option(&mut tag_prefix, p, "duplicate tag_prefix attributes")?;
+        } else if let Some(r) = field::u32_list_attr("reserved_tags", &attr)? {
+            field::set_option(&mut reserved_tags, r, "duplicate reserved_tags attributes")?;
+        } else if let Some(r) = field::str_attr("reserved_names", &attr)? {
+            field::set_option(
+                &mut reserved_names,
+                r,
+                "duplicate reserved_names attributes",
+            )?;
+        } else if field::word_attr("transparent", &attr) {
+            field::set_bool(&mut transparent, "duplicate transparent attributes")?;
+        } else if field::word_attr("hazzers", &attr) {
+            field::set_bool(&mut hazzers, "duplicate hazzers attributes")?;
+        } else if field::word_attr("hash_encoded", &attr) {
+            field::set_bool(&mut hash_encoded, "duplicate hash_encoded attributes")?;
+        } else if field::word_attr("bit_eq", &attr) {
+            field::set_bool(&mut bit_eq, "duplicate bit_eq attributes")?;
+        } else if field::word_attr("builder", &attr) {
+            field::set_bool(&mut builder, "duplicate builder attributes")?;
+        } else if field::word_attr("amino_json", &attr) {
+            field::set_bool(&mut amino_json, "duplicate amino_json attributes")?;
+        } else {
+            bail!("unknown message attribute: {:?}", attr);
+        }
+    }
+    let tag_prefix = tag_prefix.unwrap_or(0);
+    let reserved_tags = reserved_tags.unwrap_or_default();
+    let reserved_names = reserved_names.unwrap_or_default();
+    let reserved_names = reserved_names
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .collect::<Vec<_>>();
+
     let amino_name_attrs: Vec<syn::Attribute> = top_level_attrs
         .into_iter()
         .filter(|a| a.path.is_ident("amino_name"))
@@ -70,6 +121,9 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
         }
     };
 
+    let json_name = amino_name
+        .as_ref()
+        .map(|name| name.trim_matches('"').to_owned());
     let prefix: Option<Vec<u8>> = {
         match amino_name {
             Some(mut reg) => {
@@ -85,7 +139,7 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
     };
 
     let comp_prefix = match prefix {
-        Some(p) => {
+        Some(ref p) => {
             quote! {
                 // add prefix bytes for registered types:
                 let pre = vec![#(#p),*];
@@ -95,7 +149,31 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
         None => quote!(),
     };
 
+    // Registered messages are framed by a length delimiter and their prefix, both when decoded
+    // on their own and when nested in a field of another message.
+    let registered_methods = match prefix {
+        Some(ref p) => quote! {
+            fn amino_prefix() -> &'static [u8] {
+                &[#(#p),*]
+            }
+
+            fn merge<B>(&mut self, mut buf: B) -> ::std::result::Result<(), _prost::DecodeError>
+            where B: _prost::bytes::Buf {
+                while buf.has_remaining() {
+                    _prost::encoding::message::merge(
+                        _prost::encoding::WireType::LengthDelimited,
+                        self,
+                        &mut buf,
+                    )?;
+                }
+                ::std::result::Result::Ok(())
+            }
+        },
+        None => quote!(),
+    };
+
     let ident = input.ident;
+    let vis = input.vis;
 
     let variant_data = match input.data {
         Data::Struct(variant_data) => variant_data,
@@ -103,11 +181,29 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
         Data::Union(..) => bail!("Message can not be derived for a union"),
     };
 
-    if !input.generics.params.is_empty() || input.generics.where_clause.is_some() {
+    // Lifetime parameters are allowed so that `cow` fields may borrow from decoded slices.
+    let generics = input.generics;
+    if generics.type_params().next().is_some()
+        || generics.const_params().next().is_some()
+        || generics.where_clause.is_some()
+    {
         bail!("Message may not be derived for generic type");
     }
+    if generics.lifetimes().count() > 1 {
+        bail!("Message may be derived for types with at most one lifetime parameter");
+    }
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+
+    if let Fields::Unnamed(..) = variant_data.fields {
+        if builder {
+            bail!(
+                "builder may only be derived for message {} with named fields",
+                ident
+            );
+        }
+    }
 
-    let fields = match variant_data {
+    let fields: Vec<syn::Field> = match variant_data {
         DataStruct {
             fields: Fields::Named(FieldsNamed { named: fields, .. }),
             ..
@@ -125,73 +221,485 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
         } => Vec::new(),
     };
 
-    let mut next_tag: u32 = 0;
-    let mut fields = fields
-        .into_iter()
-        .enumerate()
-        .flat_map(|(idx, field)| {
-            let field_ident = field
-                .ident
-                .unwrap_or_else(|| Ident::new(&idx.to_string(), Span::call_site()));
-            match Field::new(field.attrs, Some(next_tag)) {
-                Ok(Some(field)) => {
-                    next_tag = field.tags().iter().max().map(|t| t + 1).unwrap_or(next_tag);
-                    Some(Ok((field_ident, field)))
-                }
-                Ok(None) => None,
-                Err(err) => Some(Err(
-                    err.context(format!("invalid message field {}.{}", ident, field_ident))
-                )),
+    if transparent {
+        if is_registered
+            || tag_prefix != 0
+            || !reserved_tags.is_empty()
+            || !reserved_names.is_empty()
+            || hazzers
+            || hash_encoded
+            || bit_eq
+            || builder
+            || amino_json
+        {
+            bail!(
+                "transparent message {} may not have an amino_name, tag_prefix, reserved_tags, \
+                 reserved_names, hazzers, hash_encoded, bit_eq, builder or amino_json",
+                ident
+            );
+        }
+        if fields.len() != 1 {
+            bail!("transparent message {} must have exactly one field", ident);
+        }
+        if !field::prost_attrs(fields[0].attrs.clone())?.is_empty() {
+            bail!(
+                "the field of transparent message {} may not have attributes",
+                ident
+            );
+        }
+        let field_ident = match fields[0].ident {
+            Some(ref field_ident) => quote!(#field_ident),
+            None => quote!(0),
+        };
+        return Ok(try_transparent(
+            &ident,
+            &field_ident,
+            &fields[0].ty,
+            impl_generics,
+            ty_generics,
+        ));
+    }
+
+    // Fields without an explicit tag are numbered by position, offset by the tag prefix and
+    // skipping reserved tags. Fields with `tag = "auto"` are assigned afterwards, in declaration
+    // order, the lowest tags which are neither reserved nor used by any other field.
+    let first_tag: u32 = match tag_prefix.checked_add(1) {
+        Some(tag) => tag,
+        None => bail!(
+            "message {} has an invalid tag_prefix: {}",
+            ident,
+            tag_prefix
+        ),
+    };
+    let mut next_tag = first_tag;
+    let mut numbered_fields = Vec::new();
+    let mut auto_fields = Vec::new();
+    let mut aliases = Vec::new();
+    let mut deprecated_wire = Vec::new();
+    let mut field_tys = Vec::new();
+    let mut presence = None;
+    for (idx, field) in fields.into_iter().enumerate() {
+        let field_ident = field
+            .ident
+            .unwrap_or_else(|| Ident::new(&idx.to_string(), Span::call_site()));
+        let context = format!("invalid message field {}.{}", ident, field_ident);
+        if reserved_names.iter().any(|name| field_ident == name) {
+            bail!(
+                "message {} has a field {} whose name is reserved",
+                ident,
+                field_ident
+            );
+        }
+        // The presence bits are held by a field of the message, which is not encoded.
+        let attrs = field::prost_attrs(field.attrs.clone())?;
+        if attrs.iter().any(|attr| field::word_attr("presence", attr)) {
+            if attrs.len() != 1 {
+                bail!("{}: presence fields may not have other attributes", context);
             }
-        })
-        .collect::<Result<Vec<(Ident, Field)>, failure::Context<String>>>()?;
+            field::set_option(&mut presence, field_ident, "duplicate presence fields")?;
+            continue;
+        }
+        field_tys.push((field_ident.clone(), field.ty.clone()));
+        // A renamed field keeps deprecated accessors under each of its former names.
+        for alias in field::alias_attrs(&field.attrs).map_err(|err| err.context(context.clone()))? {
+            aliases.push((field_ident.clone(), alias));
+        }
+        // A retired field is still decoded, for compatibility, but is never encoded.
+        if field::is_deprecated_wire(&field.attrs).map_err(|err| err.context(context.clone()))? {
+            deprecated_wire.push(field_ident.clone());
+        }
+        if field::is_auto_tag(&field.attrs).map_err(|err| err.context(context.clone()))? {
+            auto_fields.push((idx, field_ident, field.attrs));
+            continue;
+        }
+        while reserved_tags.contains(&next_tag) {
+            next_tag += 1;
+        }
+        let field = Field::new(field.attrs, Some(next_tag)).map_err(|err| err.context(context))?;
+        if let Some(field) = field {
+            next_tag = field.tags().iter().max().map(|t| t + 1).unwrap_or(next_tag);
+            numbered_fields.push((idx, field_ident, field));
+        }
+    }
+    let mut used_tags = numbered_fields
+        .iter()
+        .flat_map(|(_, _, field)| field.tags())
+        .chain(reserved_tags.iter().cloned())
+        .collect::<BTreeSet<u32>>();
+    let mut auto_tag = first_tag;
+    for (idx, field_ident, attrs) in auto_fields {
+        while used_tags.contains(&auto_tag) {
+            auto_tag += 1;
+        }
+        let context = format!("invalid message field {}.{}", ident, field_ident);
+        let field = Field::new(attrs, Some(auto_tag)).map_err(|err| err.context(context))?;
+        if let Some(field) = field {
+            used_tags.extend(field.tags());
+            numbered_fields.push((idx, field_ident, field));
+        }
+    }
+    let presence = match (hazzers, presence) {
+        (true, Some(presence)) => Some(presence),
+        (true, None) => bail!(
+            "message {} has the hazzers attribute, but no `#[prost_amino(presence)]` u64 field",
+            ident
+        ),
+        (false, Some(presence)) => bail!(
+            "message {} has a presence field {}, but not the hazzers attribute",
+            ident,
+            presence
+        ),
+        (false, None) => None,
+    };
+    numbered_fields.sort_by_key(|&(idx, _, _)| idx);
+    let mut fields = numbered_fields
+        .into_iter()
+        .map(|(_, field_ident, field)| (field_ident, field))
+        .collect::<Vec<(Ident, Field)>>();
 
     // We want Debug to be in declaration order
     let unsorted_fields = fields.clone();
 
+    // Each singular scalar field of a message with hazzers has a presence bit, in declaration
+    // order, which is set when a value of the field is decoded.
+    let presence_bits = match presence {
+        Some(_) => unsorted_fields
+            .iter()
+            .filter(|(_, field)| field.is_singular_scalar())
+            .map(|(field_ident, _)| field_ident.clone())
+            .collect::<Vec<_>>(),
+        None => Vec::new(),
+    };
+    if presence_bits.len() > 64 {
+        bail!(
+            "message {} has more than 64 fields with presence bits",
+            ident
+        );
+    }
+    let presence_mask = |field_ident: &Ident| {
+        presence_bits
+            .iter()
+            .position(|bit_ident| bit_ident == field_ident)
+            .map(|bit| {
+                let bit = bit as u32;
+                quote!((1u64 << #bit))
+            })
+    };
+    let set_presence =
+        |field_ident: &Ident, merge: TokenStream| match (&presence, presence_mask(field_ident)) {
+            (Some(presence), Some(mask)) => quote!(#merge.map(|()| self.#presence |= #mask)),
+            _ => merge,
+        };
+
     // Sort the fields by tag number so that fields will be encoded in tag order.
     // TODO: This encodes oneof fields in the position of their lowest tag,
     // regardless of the currently occupied variant, is that consequential?
     // See: https://developers.google.com/protocol-buffers/docs/encoding#order
-    fields.sort_by_key(|&(_, ref field)| field.tags().into_iter().min().unwrap());
+    // Flattened message fields have no tags of their own, so they are sorted last, and their
+    // fields are interleaved with those of the message by tag when encoded.
+    fields.sort_by_key(|&(_, ref field)| field.tags().into_iter().min().unwrap_or(u32::MAX));
     let fields = fields;
 
-    let mut tags = fields
+    let mut field_tags = fields
         .iter()
-        .flat_map(|&(_, ref field)| field.tags())
+        .flat_map(|(field_ident, field)| {
+            field.tags().into_iter().map(move |tag| (tag, field_ident))
+        })
         .collect::<Vec<_>>();
-    let num_tags = tags.len();
-    tags.sort();
-    tags.dedup();
-    if tags.len() != num_tags {
-        bail!("message {} has fields with duplicate tags", ident);
+    field_tags.sort_by_key(|&(tag, _)| tag);
+    if let Some(pair) = field_tags.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+        bail!(
+            "message {} has fields {} and {} with the same tag {}",
+            ident,
+            pair[0].1,
+            pair[1].1,
+            pair[0].0
+        );
+    }
+    // Streams decode the fields directly, without the framing of a registered message.
+    if is_registered {
+        if let Some((field_ident, _)) = fields.iter().find(|(_, field)| match field {
+            Field::Scalar(field) => field.stream,
+            _ => false,
+        }) {
+            bail!(
+                "message {} is registered, so its field {} may not have a stream attribute",
+                ident,
+                field_ident
+            );
+        }
+    }
+    if let Some((tag, field_ident)) = field_tags
+        .iter()
+        .find(|(tag, _)| reserved_tags.contains(tag))
+    {
+        bail!(
+            "message {} has a field {} with reserved tag {}",
+            ident,
+            field_ident,
+            tag
+        );
+    }
+    let tags = field_tags.iter().map(|&(tag, _)| tag).collect::<Vec<_>>();
+    if let Some(&tag) = tags
+        .iter()
+        .find(|&&tag| !(MIN_TAG..=MAX_TAG).contains(&tag))
+    {
+        bail!(
+            "message {} has a field with an invalid tag: {} (must be between {} and {})",
+            ident,
+            tag,
+            MIN_TAG,
+            MAX_TAG
+        );
+    }
+
+    // Distinct registered names with the same prefix could not be told apart when decoding.
+    let registered = fields
+        .iter()
+        .filter_map(|(field_ident, field)| field.amino_name().map(|name| (field_ident, name)))
+        .collect::<Vec<_>>();
+    for (i, &(field_ident, (name, prefix))) in registered.iter().enumerate() {
+        for &(other_ident, (other_name, other_prefix)) in &registered[i + 1..] {
+            if prefix == other_prefix && name != other_name {
+                bail!(
+                    "message {} has fields {} and {} whose amino names {:?} and {:?} \
+                     have the same prefix {:02x?}",
+                    ident,
+                    field_ident,
+                    other_ident,
+                    name,
+                    other_name,
+                    prefix
+                );
+            }
+        }
     }
 
     // Put impls in a special module, so that 'extern crate' can be used.
     let module = Ident::new(&format!("{}_MESSAGE", ident), Span::call_site());
 
-    let encoded_len = fields
+    let encoded_fields = fields
         .iter()
+        .filter(|(field_ident, _)| !deprecated_wire.contains(field_ident));
+    let encoded_len = encoded_fields
+        .clone()
         .map(|&(ref field_ident, ref field)| field.encoded_len(quote!(self.#field_ident)));
     let encoded_len2 = encoded_len.clone();
 
-    let encode = fields
+    // Each field is encoded by `FieldTags::encode_field` at its lowest tag, so that the fields of
+    // flattened messages can be interleaved with those of the message by tag.
+    let encoded_tags = encoded_fields
+        .clone()
+        .filter(|(_, field)| !field.is_flattened())
+        .map(|(_, field)| field.tags().into_iter().min().expect("field tag"))
+        .collect::<Vec<_>>();
+    let encode_arms = encoded_fields
+        .filter(|(_, field)| !field.is_flattened())
+        .zip(&encoded_tags)
+        .map(|((field_ident, field), tag)| {
+            let encode = field.encode(quote!(self.#field_ident));
+            quote!(#tag => { #encode })
+        })
+        .collect::<Vec<_>>();
+
+    // The tags of a flattened message field are given by its type, and must not collide with the
+    // tags of the message, nor with those of any other flattened field.
+    let flattened = fields
+        .iter()
+        .filter(|(_, field)| field.is_flattened())
+        .map(|(field_ident, _)| {
+            let field_ty = field_tys
+                .iter()
+                .find(|&(ident, _)| ident == field_ident)
+                .map(|(_, ty)| ty)
+                .expect("field type");
+            (field_ident, field_ty)
+        })
+        .collect::<Vec<_>>();
+    let flattened_checks = flattened
         .iter()
-        .map(|&(ref field_ident, ref field)| field.encode(quote!(self.#field_ident)));
-
-    let merge = fields.iter().map(|&(ref field_ident, ref field)| {
-        let merge = field.merge(quote!(self.#field_ident));
-        let tags = field
-            .tags()
-            .into_iter()
-            .map(|tag| quote!(#tag))
-            .intersperse(quote!(|));
-        quote!(#(#tags)* => #merge.map_err(|mut error| {
-            error.push(STRUCT_NAME, stringify!(#field_ident));
-            error
-        }),)
+        .enumerate()
+        .flat_map(|(i, &(field_ident, ty))| {
+            let ident = &ident;
+            let msg = format!(
+                "message {} has a flattened field {} whose tags collide with its own tags",
+                ident, field_ident
+            );
+            let own = quote! {
+                const _: () = assert!(
+                    !_prost::encoding::tags_overlap(
+                        &[#(#tags),*],
+                        <#ty as _prost::encoding::FieldTags>::FIELD_TAGS,
+                    ),
+                    #msg
+                );
+            };
+            let others = flattened[i + 1..]
+                .iter()
+                .map(move |&(other_ident, other_ty)| {
+                    let msg = format!(
+                        "message {} has flattened fields {} and {} whose tags collide",
+                        ident, field_ident, other_ident
+                    );
+                    quote! {
+                        const _: () = assert!(
+                            !_prost::encoding::tags_overlap(
+                                <#ty as _prost::encoding::FieldTags>::FIELD_TAGS,
+                                <#other_ty as _prost::encoding::FieldTags>::FIELD_TAGS,
+                            ),
+                            #msg
+                        );
+                    }
+                });
+            ::std::iter::once(own).chain(others)
+        });
+    let registered_checks = flattened.iter().map(|&(field_ident, ty)| {
+        let msg = format!(
+            "message {} has a flattened field {} whose type is registered",
+            ident, field_ident
+        );
+        quote! {
+            const _: () = assert!(!<#ty as _prost::encoding::FieldTags>::REGISTERED, #msg);
+        }
+    });
+    let flattened_tys = flattened.iter().map(|&(_, ty)| ty).collect::<Vec<_>>();
+    let field_tags = if flattened.is_empty() {
+        quote!(&[#(#tags),*])
+    } else {
+        let own_len = tags.len();
+        quote!({
+            const LEN: usize =
+                #own_len #(+ <#flattened_tys as _prost::encoding::FieldTags>::FIELD_TAGS.len())*;
+            const TAGS: [u32; LEN] = _prost::encoding::flattened_tags(
+                &[#(#tags),*],
+                &[#(<#flattened_tys as _prost::encoding::FieldTags>::FIELD_TAGS),*],
+            );
+            &TAGS
+        })
+    };
+    let encode = if flattened.is_empty() {
+        quote!(#(_prost::encoding::FieldTags::encode_field(self, #encoded_tags, buf);)*)
+    } else {
+        quote! {
+            for &tag in <Self as _prost::encoding::FieldTags>::FIELD_TAGS {
+                _prost::encoding::FieldTags::encode_field(self, tag, buf);
+            }
+        }
+    };
+    let encode_flattened = flattened.iter().map(|&(field_ident, ty)| {
+        quote! {
+            if <#ty as _prost::encoding::FieldTags>::FIELD_TAGS.contains(&tag) {
+                _prost::encoding::FieldTags::encode_field(&self.#field_ident, tag, buf);
+            }
+        }
     });
 
+    let merge_flattened = flattened.iter().map(|&(field_ident, ty)| {
+        quote! {
+            if <#ty as _prost::encoding::FieldTags>::FIELD_TAGS.contains(&tag) {
+                return _prost::encoding::merge_field_with_key(tag, wire_type, &mut self.#field_ident, buf)
+                    .map_err(|mut error| {
+                        error.push(STRUCT_NAME, stringify!(#field_ident));
+                        error
+                    });
+            }
+        }
+    });
+    let merge_unknown = quote! {
+        _ => {
+            #(#merge_flattened)*
+            _prost::encoding::skip_field(wire_type, tag, buf, 0)
+        }
+    };
+
+    let merge = fields
+        .iter()
+        .filter(|(_, field)| !field.is_flattened())
+        .map(|(field_ident, field)| {
+            let merge = set_presence(field_ident, field.merge(quote!(self.#field_ident)));
+            merge_arm(field_ident, field, merge)
+        });
+
+    let decode_borrowed = if fields.iter().any(|(_, field)| field.is_cow()) {
+        let lifetime = match generics.lifetimes().next() {
+            Some(def) => &def.lifetime,
+            None => bail!("message {} has cow fields but no lifetime parameter", ident),
+        };
+        if is_registered {
+            bail!(
+                "message {} may not have both an amino_name and cow fields",
+                ident
+            );
+        }
+        let merge_borrowed = fields
+            .iter()
+            .filter(|(_, field)| !field.is_flattened())
+            .map(|(field_ident, field)| {
+                let merge =
+                    set_presence(field_ident, field.merge_borrowed(quote!(self.#field_ident)));
+                merge_arm(field_ident, field, merge)
+            });
+        quote! {
+            impl #impl_generics #ident #ty_generics {
+                /// Decodes an instance of the message from a slice, borrowing `cow` fields from it.
+                pub fn decode_borrowed(mut buf: &#lifetime [u8]) -> ::std::result::Result<Self, _prost::DecodeError> {
+                    let mut message = <Self as ::std::default::Default>::default();
+                    while !buf.is_empty() {
+                        message.merge_field_borrowed(&mut buf)?;
+                    }
+                    _prost::Message::finish_merge(&mut message);
+                    ::std::result::Result::Ok(message)
+                }
+
+                fn merge_field_borrowed(&mut self, buf: &mut &#lifetime [u8]) -> ::std::result::Result<(), _prost::DecodeError> {
+                    const STRUCT_NAME: &'static str = stringify!(#ident);
+                    let (tag, wire_type) = _prost::encoding::decode_key(buf)?;
+                    match tag {
+                        #(#merge_borrowed)*
+                        #merge_unknown
+                    }
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
+
+    let has_shared_bytes = fields
+        .iter()
+        .any(|(_, field)| matches!(field, Field::Scalar(scalar) if scalar.shared));
+    if is_registered && has_shared_bytes {
+        bail!(
+            "message {} may not have both an amino_name and shared fields",
+            ident
+        );
+    }
+    let merge_field_shared = if !is_registered && fields.iter().any(|(_, field)| field.is_shared())
+    {
+        let merge_shared = fields
+            .iter()
+            .filter(|(_, field)| !field.is_flattened())
+            .map(|(field_ident, field)| {
+                let merge =
+                    set_presence(field_ident, field.merge_shared(quote!(self.#field_ident)));
+                merge_arm(field_ident, field, merge)
+            });
+        quote! {
+            #[allow(unused_variables)]
+            fn merge_field_shared(&mut self, buf: &mut _prost::bytes::Bytes) -> ::std::result::Result<(), _prost::DecodeError> {
+                const STRUCT_NAME: &'static str = stringify!(#ident);
+                let (tag, wire_type) = _prost::encoding::decode_key(buf)?;
+                match tag {
+                    #(#merge_shared)*
+                    #merge_unknown
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
+
     let struct_name = if fields.is_empty() {
         quote!()
     } else {
@@ -205,23 +713,322 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
 
     let clear = fields
         .iter()
-        .map(|&(ref field_ident, ref field)| field.clear(quote!(self.#field_ident)));
+        .map(|&(ref field_ident, ref field)| field.clear(quote!(self.#field_ident)))
+        .chain(presence.iter().map(|presence| quote!(self.#presence = 0)));
+
+    let finish_merge = fields
+        .iter()
+        .filter_map(|(field_ident, field)| field.finish_merge(quote!(self.#field_ident)))
+        .collect::<Vec<_>>();
+    let finish_merge = if finish_merge.is_empty() {
+        quote!()
+    } else {
+        quote! {
+            fn finish_merge(&mut self) {
+                #(#finish_merge)*
+            }
+        }
+    };
+
+    let merge_from = fields
+        .iter()
+        .map(|(field_ident, field)| {
+            field.merge_from(quote!(self.#field_ident), quote!(other.#field_ident))
+        })
+        .chain(
+            presence
+                .iter()
+                .map(|presence| quote!(self.#presence |= other.#presence;)),
+        );
+
+    let is_default = fields
+        .iter()
+        .map(|(field_ident, field)| field.is_default(quote!(self.#field_ident)));
+
+    let mut descriptors = fields
+        .iter()
+        .flat_map(|(field_ident, field)| {
+            let field_ty = field_tys
+                .iter()
+                .find(|&(ident, _)| ident == field_ident)
+                .map(|(_, ty)| ty)
+                .expect("field type");
+            field.descriptors(&field_ident.to_string(), field_ty)
+        })
+        .collect::<Vec<_>>();
+    descriptors.sort_by_key(|&(tag, _)| tag);
+    let descriptors = descriptors.into_iter().map(|(_, descriptor)| descriptor);
+
+    let default = fields
+        .iter()
+        .map(|&(ref field_ident, ref field)| {
+            let value = field.default();
+            quote!(#field_ident: #value,)
+        })
+        .chain(presence.iter().map(|presence| quote!(#presence: 0,)));
+
+    let validate = fields.iter().filter_map(|(field_ident, field)| {
+        field.validate(quote!(self.#field_ident)).map(|validate| {
+            quote! {
+                #validate.map_err(|mut error| {
+                    error.push(stringify!(#ident), stringify!(#field_ident));
+                    error
+                })?;
+            }
+        })
+    });
+
+    let visit = fields.iter().map(|(field_ident, field)| {
+        field.visit(quote!(self.#field_ident), &field_ident.to_string())
+    });
+
+    let size_breakdown = fields
+        .iter()
+        .filter(|(field_ident, _)| !deprecated_wire.contains(field_ident))
+        .map(|(field_ident, field)| {
+            field.size_breakdown(quote!(self.#field_ident), &field_ident.to_string())
+        });
+
+    // The fields of flattened messages are interleaved with those of the message by tag, as they
+    // are encoded, and the set variant of a oneof is reported where the oneof is encoded, at its
+    // lowest tag.
+    let sort_breakdown = if flattened.is_empty() {
+        quote!()
+    } else {
+        let oneof_positions = fields.iter().filter_map(|(_, field)| match field {
+            Field::Oneof(oneof) => {
+                let min = oneof.tags.iter().min();
+                let tags = &oneof.tags;
+                Some(quote!(#(#tags)|* => #min,))
+            }
+            _ => None,
+        });
+        quote! {
+            breakdown.sort_by_key(|&(tag, _, _)| match tag {
+                #(#oneof_positions)*
+                tag => tag,
+            });
+        }
+    };
 
-    let default = fields.iter().map(|&(ref field_ident, ref field)| {
-        let value = field.default();
-        quote!(#field_ident: #value,)
+    let clear_methods = fields.iter().map(|(field_ident, field)| {
+        let clear_fn = Ident::new(&format!("clear_{}", field_ident), Span::call_site());
+        let clear = field.clear(quote!(self.#field_ident));
+        let clear_presence = match (&presence, presence_mask(field_ident)) {
+            (Some(presence), Some(mask)) => Some(quote!(self.#presence &= !#mask;)),
+            _ => None,
+        }
+        .into_iter();
+        quote! {
+            /// Resets the field to its default value.
+            pub fn #clear_fn(&mut self) {
+                #clear;
+                #(#clear_presence)*
+            }
+        }
+    });
+    let has_methods = presence.iter().flat_map(|presence| {
+        presence_bits.iter().map(move |field_ident| {
+            let has_fn = Ident::new(&format!("has_{}", field_ident), Span::call_site());
+            let mask = presence_mask(field_ident);
+            quote! {
+                /// Returns `true` if a value of the field has been decoded since the message was
+                /// created or cleared.
+                pub fn #has_fn(&self) -> bool {
+                    self.#presence & #mask != 0
+                }
+            }
+        })
+    });
+    // The encoded length of a registered message already counts its prefix, but not the length
+    // delimiter which precedes it.
+    let prefixed_len_method = if is_registered {
+        Some(quote! {
+            /// Returns the length of the message when encoded with its length delimiter and
+            /// amino prefix, as written by `encode`.
+            pub fn encoded_len_with_prefix(&self) -> usize {
+                let len = _prost::Message::encoded_len(self);
+                _prost::encoding::encoded_len_varint(len as u64) + len
+            }
+        })
+    } else {
+        None
+    };
+
+    // The builder starts from the default message, so fields which are not set keep their
+    // amino defaults. Setters of optional fields take the value without the `Option`.
+    let builder_ident = Ident::new(&format!("{}Builder", ident), Span::call_site());
+    let (builder_method, builder_struct) = if builder {
+        if let Some((field_ident, _)) = field_tys
+            .iter()
+            .find(|(field_ident, _)| field_ident == "build")
+        {
+            bail!(
+                "message {} has a field {}, which conflicts with the build method of its builder",
+                ident,
+                field_ident
+            );
+        }
+        let setters = field_tys.iter().map(|(field_ident, ty)| {
+            let doc = format!("Sets the `{}` field.", field_ident);
+            match option_inner_type(ty) {
+                Some(inner) => quote! {
+                    #[doc = #doc]
+                    pub fn #field_ident(mut self, value: #inner) -> Self {
+                        self.message.#field_ident = ::std::option::Option::Some(value);
+                        self
+                    }
+                },
+                None => quote! {
+                    #[doc = #doc]
+                    pub fn #field_ident(mut self, value: #ty) -> Self {
+                        self.message.#field_ident = value;
+                        self
+                    }
+                },
+            }
+        });
+        let struct_doc = format!(
+            "Builds a `{}` one field at a time, starting from its default value.",
+            ident
+        );
+        let builder_method = quote! {
+            /// Returns a builder for the message, with every field set to its default value.
+            pub fn builder() -> #builder_ident #ty_generics {
+                #builder_ident {
+                    message: ::std::default::Default::default(),
+                }
+            }
+        };
+        let builder_struct = quote! {
+            #[doc = #struct_doc]
+            #[derive(Debug)]
+            #vis struct #builder_ident #generics {
+                message: #ident #ty_generics,
+            }
+
+            #[allow(dead_code)]
+            impl #impl_generics #builder_ident #ty_generics {
+                #(#setters)*
+
+                /// Returns the built message.
+                pub fn build(self) -> #ident #ty_generics {
+                    self.message
+                }
+            }
+        };
+        (Some(builder_method), builder_struct)
+    } else {
+        (None, quote!())
+    };
+
+    // The bounds are higher-ranked so that they are not checked when the message is derived:
+    // messages with fields which are not `PartialEq` still derive, but have no `eq_ignoring`.
+    // A oneof field is skipped if any of its tags is ignored; a flattened field compares its own
+    // fields, as they are encoded with their own tags in the message, so it is bound by
+    // `EqIgnoring` rather than `PartialEq`.
+    let eq_bounds = fields
+        .iter()
+        .map(|(field_ident, field)| {
+            let field_ty = field_tys
+                .iter()
+                .find(|&(ident, _)| ident == field_ident)
+                .map(|(_, ty)| ty)
+                .expect("field type");
+            if field.is_flattened() {
+                quote!(for<'eq> #field_ty: _prost::encoding::EqIgnoring,)
+            } else {
+                quote!(for<'eq> #field_ty: ::std::cmp::PartialEq,)
+            }
+        })
+        .collect::<Vec<_>>();
+    let eqs = fields.iter().map(|(field_ident, field)| {
+        if field.is_flattened() {
+            return quote! {
+                if !_prost::encoding::EqIgnoring::eq_ignoring(
+                    &self.#field_ident,
+                    &other.#field_ident,
+                    ignored_tags,
+                ) {
+                    return false;
+                }
+            };
+        }
+        let tags = field.tags();
+        quote! {
+            if ![#(#tags),*].iter().any(|tag| ignored_tags.contains(tag))
+                && self.#field_ident != other.#field_ident
+            {
+                return false;
+            }
+        }
+    });
+    let eq_ignoring_method = quote! {
+        /// Returns `true` if the message is equal to `other`, ignoring the fields with any of the
+        /// `ignored_tags`.
+        pub fn eq_ignoring(&self, other: &Self, ignored_tags: &[u32]) -> bool
+        where
+            #(#eq_bounds)*
+        {
+            #(#eqs)*
+            true
+        }
+    };
+
+    // The accessor under a former name delegates to the accessor of the field, if it has one, so
+    // that both return the same value; otherwise it returns a reference to the field.
+    let alias_methods = aliases.iter().map(|(field_ident, alias)| {
+        let field = fields
+            .iter()
+            .find(|&(ident, _)| ident == field_ident)
+            .map(|(_, field)| field)
+            .expect("aliased field");
+        let field_ty = field_tys
+            .iter()
+            .find(|&(ident, _)| ident == field_ident)
+            .map(|(_, ty)| ty)
+            .expect("field type");
+        let alias_mut = Ident::new(&format!("{}_mut", alias), Span::call_site());
+        let note = format!("renamed to `{}`", field_ident);
+        let getter = match field.getter_ty() {
+            Some(ty) => quote! {
+                pub fn #alias(&self) -> #ty {
+                    self.#field_ident()
+                }
+            },
+            None => quote! {
+                pub fn #alias(&self) -> &#field_ty {
+                    &self.#field_ident
+                }
+            },
+        };
+        quote! {
+            #[deprecated(note = #note)]
+            #getter
+
+            #[deprecated(note = #note)]
+            pub fn #alias_mut(&mut self) -> &mut #field_ty {
+                &mut self.#field_ident
+            }
+        }
     });
 
     let methods = fields
         .iter()
         .flat_map(|&(ref field_ident, ref field)| field.methods(field_ident))
+        .chain(Some(eq_ignoring_method))
+        .chain(clear_methods)
+        .chain(has_methods)
+        .chain(prefixed_len_method)
+        .chain(builder_method)
+        .chain(alias_methods)
         .collect::<Vec<_>>();
     let methods = if methods.is_empty() {
         quote!()
     } else {
         quote! {
             #[allow(dead_code)]
-            impl #ident {
+            impl #impl_generics #ident #ty_generics {
                 #(#methods)*
             }
         }
@@ -247,43 +1054,211 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
         quote!(f.debug_tuple(stringify!(#ident)))
     };
 
+    // Equal messages have equal normalized encodings, with maps in key order and `-0.0` written
+    // as `0.0`, so hashing the encoding is consistent with `Eq`.
+    let hash_impl = if hash_encoded {
+        quote! {
+            impl #impl_generics ::std::hash::Hash for #ident #ty_generics {
+                fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+                    ::std::hash::Hash::hash(&_prost::encoding::encode_normalized(self)[..], state)
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
+
+    // Floats, including the values of maps and the variants of oneofs, are compared by their
+    // bits, so that a message holding a `NaN` is equal to itself, and `0.0` is not equal to
+    // `-0.0`. Oneofs must have the `bit_eq` attribute too. Other fields, including nested
+    // messages, are compared with their own `PartialEq`, so a nested message only compares its
+    // floats by their bits if it has the `bit_eq` attribute itself.
+    let bit_eq_impl = if bit_eq {
+        let eqs = fields
+            .iter()
+            .map(|(field_ident, field)| {
+                if field.is_bit_eq() {
+                    quote!(_prost::encoding::BitEq::bit_eq(&self.#field_ident, &other.#field_ident))
+                } else {
+                    quote!(self.#field_ident == other.#field_ident)
+                }
+            })
+            .chain(
+                presence
+                    .iter()
+                    .map(|presence| quote!(self.#presence == other.#presence)),
+            );
+        quote! {
+            impl #impl_generics ::std::cmp::PartialEq for #ident #ty_generics {
+                fn eq(&self, other: &Self) -> bool {
+                    true #(&& #eqs)*
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
+
+    // Each field is written under its name, except that the fields of a `json_inline` field are
+    // written in its place. Their keys must not collide with those of the message, nor with those
+    // of any other inlined field.
+    let json_impl = if amino_json {
+        let json_fields = fields
+            .iter()
+            .filter(|(field_ident, _)| !deprecated_wire.contains(field_ident))
+            .collect::<Vec<_>>();
+        let mut keys = Vec::new();
+        let mut writes = Vec::new();
+        let mut inlined = Vec::new();
+        for &(field_ident, field) in &json_fields {
+            let context = format!("invalid message field {}.{}", ident, field_ident);
+            let json = field
+                .json(quote!(self.#field_ident))
+                .map_err(|err| err.context(context))?;
+            if field.is_json_inline() {
+                let field_ty = field_tys
+                    .iter()
+                    .find(|&(ident, _)| ident == field_ident)
+                    .map(|(_, ty)| ty)
+                    .expect("field type");
+                inlined.push((field_ident, option_inner_type(field_ty).unwrap_or(field_ty)));
+                writes.push(json);
+            } else {
+                let key = field_ident.to_string().trim_start_matches("r#").to_owned();
+                writes.push(quote! {
+                    object.insert(::std::string::String::from(#key), #json);
+                });
+                keys.push(key);
+            }
+        }
+        let inline_checks = inlined
+            .iter()
+            .enumerate()
+            .flat_map(|(i, &(field_ident, ty))| {
+                let ident = &ident;
+                let msg = format!(
+                    "message {} has a json_inline field {} whose keys collide with its own keys",
+                    ident, field_ident
+                );
+                let registered_msg = format!(
+                    "message {} has a json_inline field {} whose type is registered",
+                    ident, field_ident
+                );
+                let own = quote! {
+                    const _: () = assert!(
+                        !_prost::encoding::json::keys_overlap(
+                            &[#(#keys),*],
+                            <#ty as _prost::encoding::json::AminoJson>::JSON_KEYS,
+                        ),
+                        #msg
+                    );
+                    const _: () = assert!(
+                        !<#ty as _prost::encoding::FieldTags>::REGISTERED,
+                        #registered_msg
+                    );
+                };
+                let others = inlined[i + 1..]
+                    .iter()
+                    .map(move |&(other_ident, other_ty)| {
+                        let msg = format!(
+                            "message {} has json_inline fields {} and {} whose keys collide",
+                            ident, field_ident, other_ident
+                        );
+                        quote! {
+                            const _: () = assert!(
+                                !_prost::encoding::json::keys_overlap(
+                                    <#ty as _prost::encoding::json::AminoJson>::JSON_KEYS,
+                                    <#other_ty as _prost::encoding::json::AminoJson>::JSON_KEYS,
+                                ),
+                                #msg
+                            );
+                        }
+                    });
+                ::std::iter::once(own).chain(others)
+            })
+            .collect::<Vec<_>>();
+        let inlined_tys = inlined.iter().map(|&(_, ty)| ty).collect::<Vec<_>>();
+        let json_keys = if inlined.is_empty() {
+            quote!(&[#(#keys),*])
+        } else {
+            let own_len = keys.len();
+            quote!({
+                const LEN: usize = #own_len
+                    #(+ <#inlined_tys as _prost::encoding::json::AminoJson>::JSON_KEYS.len())*;
+                const KEYS: [&'static str; LEN] = _prost::encoding::json::inlined_keys(
+                    &[#(#keys),*],
+                    &[#(<#inlined_tys as _prost::encoding::json::AminoJson>::JSON_KEYS),*],
+                );
+                &KEYS
+            })
+        };
+        let to_json = json_name.as_ref().map(|name| {
+            quote! {
+                fn to_json(&self) -> _prost::serde_json::Value {
+                    let mut object = _prost::serde_json::Map::new();
+                    self.write_fields(&mut object);
+                    _prost::encoding::json::registered(#name, _prost::serde_json::Value::Object(object))
+                }
+            }
+        });
+        quote! {
+            impl #impl_generics _prost::encoding::json::AminoJson for #ident #ty_generics {
+                const JSON_KEYS: &'static [&'static str] = #json_keys;
+
+                #[allow(unused_variables)]
+                fn write_fields(&self, object: &mut _prost::serde_json::Map<::std::string::String, _prost::serde_json::Value>) {
+                    #(#writes)*
+                }
+
+                #to_json
+            }
+
+            #(#inline_checks)*
+        }
+    } else {
+        if let Some((field_ident, _)) = fields.iter().find(|(_, field)| field.is_json_inline()) {
+            bail!(
+                "message {} has a json_inline field {} but no amino_json attribute",
+                ident,
+                field_ident
+            );
+        }
+        quote!()
+    };
+
     let expanded = quote! {
+        #builder_struct
+
         #[allow(non_snake_case, unused_attributes)]
         mod #module {
             extern crate prost_amino as _prost;
 
             use super::*;
 
-            impl _prost::Message for #ident {
+            impl #impl_generics _prost::Message for #ident #ty_generics {
                 #[allow(unused_variables)]
                 fn encode_raw<B>(&self, buf: &mut B) where B: _prost::bytes::BufMut  {
                     if #is_registered {
                         // TODO: in go-amino this only get length-prefixed if MarhsalBinary is used
                         // opposed to MarshalBinaryBare
-                        let len = 4 #(+ #encoded_len2)*;
+                        let len = _prost::encoding::AMINO_PREFIX_LEN #(+ #encoded_len2)*;
                         _prost::encoding::encode_varint(len as u64, buf);
                     } else {
                         // not length prefixed!
                     }
                     #comp_prefix
-                    #(#encode)*
+                    #encode
                 }
 
                 #[allow(unused_variables)]
                 fn merge_field<B>(&mut self, buf: &mut B) -> ::std::result::Result<(), _prost::DecodeError>
                 where B: _prost::bytes::Buf {
                     #struct_name
-                    if #is_registered {
-                        // skip some bytes: varint(total_len) || prefix_bytes
-                        // prefix (4) + total_encoded_len:
-                        let _full_len = _prost::encoding::decode_varint(buf)?;
-                        buf.advance(4);
-                    }
                     if buf.remaining() > 0 {
                         let (tag, wire_type) = _prost::encoding::decode_key(buf)?;
                         match tag {
                             #(#merge)*
-                            _ => _prost::encoding::skip_field(wire_type, buf),
+                            #merge_unknown
                         }
                     } else {
                         Ok(())
@@ -291,30 +1266,77 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
 
                 }
 
+                #merge_field_shared
+
+                #finish_merge
+
                 #[inline]
                 fn encoded_len(&self) -> usize {
                     let len = 0 #(+ #encoded_len)*;
                     if #is_registered {
-                        4 + len
+                        _prost::encoding::AMINO_PREFIX_LEN + len
                     } else {
                         len
                     }
                 }
 
+                fn is_empty(&self) -> bool {
+                    true #(&& #is_default)*
+                }
+
                 fn clear(&mut self) {
                     #(#clear;)*
                 }
+
+                #[allow(unused_variables)]
+                fn merge_from(&mut self, other: Self) -> ::std::result::Result<(), _prost::DecodeError> {
+                    #(#merge_from)*
+                    ::std::result::Result::Ok(())
+                }
+
+                fn validate(&self) -> ::std::result::Result<(), _prost::ValidationError> {
+                    #(#validate)*
+                    ::std::result::Result::Ok(())
+                }
+
+                fn descriptor() -> &'static [_prost::FieldDescriptor] {
+                    const DESCRIPTOR: &'static [_prost::FieldDescriptor] = &[#(#descriptors),*];
+                    DESCRIPTOR
+                }
+
+                #[allow(unused_variables)]
+                fn visit_fields<V>(&self, visitor: &mut V) where V: _prost::FieldVisitor {
+                    #(#visit)*
+                }
+
+                fn size_breakdown(&self) -> ::std::vec::Vec<(u32, &'static str, usize)> {
+                    let mut breakdown = ::std::vec::Vec::new();
+                    if #is_registered {
+                        breakdown.push((0, "amino_prefix", _prost::encoding::AMINO_PREFIX_LEN));
+                    }
+                    #(#size_breakdown)*
+                    #sort_breakdown
+                    breakdown
+                }
+
+                #registered_methods
             }
 
-            impl Default for #ident {
-                fn default() -> #ident {
+            impl #impl_generics Default for #ident #ty_generics {
+                fn default() -> Self {
                     #ident {
                         #(#default)*
                     }
                 }
             }
 
-            impl ::std::fmt::Debug for #ident {
+            impl #impl_generics ::std::convert::From<#ident #ty_generics> for ::std::vec::Vec<u8> {
+                fn from(msg: #ident #ty_generics) -> ::std::vec::Vec<u8> {
+                    _prost::Message::encode_to_vec(&msg)
+                }
+            }
+
+            impl #impl_generics ::std::fmt::Debug for #ident #ty_generics {
                 fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
                     let mut builder = #debug_builder;
                     #(#debugs;)*
@@ -323,18 +1345,172 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
             }
 
             #methods
+
+            #decode_borrowed
+
+            #hash_impl
+
+            #bit_eq_impl
+
+            #json_impl
+
+            impl #impl_generics _prost::encoding::EqIgnoring for #ident #ty_generics
+            where
+                #(#eq_bounds)*
+            {
+                fn eq_ignoring(&self, other: &Self, ignored_tags: &[u32]) -> bool {
+                    #ident::eq_ignoring(self, other, ignored_tags)
+                }
+            }
+
+            impl #impl_generics _prost::encoding::FieldTags for #ident #ty_generics {
+                const FIELD_TAGS: &'static [u32] = #field_tags;
+
+                const REGISTERED: bool = #is_registered;
+
+                #[inline]
+                #[allow(unused_variables)]
+                fn encode_field<B>(&self, tag: u32, buf: &mut B) where B: _prost::bytes::BufMut {
+                    match tag {
+                        #(#encode_arms)*
+                        _ => {
+                            #(#encode_flattened)*
+                        }
+                    }
+                }
+            }
+
+            #(#registered_checks)*
+
+            #(#flattened_checks)*
         };
     };
-    Ok(expanded.into())
+    Ok(expanded)
+}
+
+/// Returns `T` if the type is `Option<T>`.
+fn option_inner_type(ty: &syn::Type) -> Option<&syn::Type> {
+    let segment = match *ty {
+        syn::Type::Path(ref path) if path.qself.is_none() => path.path.segments.last()?,
+        _ => return None,
+    };
+    if segment.ident != "Option" {
+        return None;
+    }
+    match segment.arguments {
+        syn::PathArguments::AngleBracketed(ref args) if args.args.len() == 1 => {
+            match args.args[0] {
+                syn::GenericArgument::Type(ref inner) => Some(inner),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Implements `Message` for a single field struct by delegating to the field, without framing.
+fn try_transparent(
+    ident: &Ident,
+    field_ident: &TokenStream,
+    field_ty: &syn::Type,
+    impl_generics: syn::ImplGenerics,
+    ty_generics: syn::TypeGenerics,
+) -> TokenStream {
+    let module = Ident::new(&format!("{}_MESSAGE", ident), Span::call_site());
+    quote! {
+        #[allow(non_snake_case, unused_attributes)]
+        mod #module {
+            extern crate prost_amino as _prost;
+
+            use super::*;
+
+            impl #impl_generics _prost::Message for #ident #ty_generics {
+                fn encode_raw<B>(&self, buf: &mut B) where B: _prost::bytes::BufMut {
+                    _prost::Message::encode_raw(&self.#field_ident, buf)
+                }
+
+                fn merge_field<B>(&mut self, buf: &mut B) -> ::std::result::Result<(), _prost::DecodeError>
+                where B: _prost::bytes::Buf {
+                    _prost::Message::merge_field(&mut self.#field_ident, buf)
+                }
+
+                fn merge_field_shared(&mut self, buf: &mut _prost::bytes::Bytes) -> ::std::result::Result<(), _prost::DecodeError> {
+                    _prost::Message::merge_field_shared(&mut self.#field_ident, buf)
+                }
+
+                fn finish_merge(&mut self) {
+                    _prost::Message::finish_merge(&mut self.#field_ident)
+                }
+
+                #[inline]
+                fn encoded_len(&self) -> usize {
+                    _prost::Message::encoded_len(&self.#field_ident)
+                }
+
+                fn is_empty(&self) -> bool {
+                    _prost::Message::is_empty(&self.#field_ident)
+                }
+
+                fn clear(&mut self) {
+                    _prost::Message::clear(&mut self.#field_ident)
+                }
+
+                fn descriptor() -> &'static [_prost::FieldDescriptor] {
+                    <#field_ty as _prost::Message>::descriptor()
+                }
+
+                fn visit_fields<V>(&self, visitor: &mut V) where V: _prost::FieldVisitor {
+                    _prost::Message::visit_fields(&self.#field_ident, visitor)
+                }
+
+                fn size_breakdown(&self) -> ::std::vec::Vec<(u32, &'static str, usize)> {
+                    _prost::Message::size_breakdown(&self.#field_ident)
+                }
+            }
+
+            impl #impl_generics Default for #ident #ty_generics {
+                fn default() -> Self {
+                    #ident { #field_ident: ::std::default::Default::default() }
+                }
+            }
+
+            impl #impl_generics ::std::convert::From<#ident #ty_generics> for ::std::vec::Vec<u8> {
+                fn from(msg: #ident #ty_generics) -> ::std::vec::Vec<u8> {
+                    _prost::Message::encode_to_vec(&msg)
+                }
+            }
+
+            impl #impl_generics ::std::fmt::Debug for #ident #ty_generics {
+                fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                    ::std::fmt::Debug::fmt(&self.#field_ident, f)
+                }
+            }
+        };
+    }
+}
+
+/// Returns a `match` arm which merges the field, adding the field and its tag to the location of
+/// any error.
+fn merge_arm(field_ident: &Ident, field: &Field, merge: TokenStream) -> TokenStream {
+    let tags = field
+        .tags()
+        .into_iter()
+        .map(|tag| quote!(#tag))
+        .intersperse(quote!(|));
+    quote!(#(#tags)* => #merge.map_err(|mut error| {
+        error.set_tag(tag);
+        error.push(STRUCT_NAME, stringify!(#field_ident));
+        error
+    }),)
 }
 
 #[proc_macro_derive(Message, attributes(prost_amino, amino_name, aminoDisamb))]
-pub fn message(input: TokenStream) -> TokenStream {
-    try_message(input).unwrap()
+pub fn message(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    try_message(input.into()).unwrap().into()
 }
 
 fn try_enumeration(input: TokenStream) -> Result<TokenStream, Error> {
-    let input: DeriveInput = syn::parse(input)?;
+    let input: DeriveInput = syn::parse2(input)?;
     let ident = input.ident;
 
     if !input.generics.params.is_empty() || input.generics.where_clause.is_some() {
@@ -383,10 +1559,24 @@ fn try_enumeration(input: TokenStream) -> Result<TokenStream, Error> {
     let from = variants.iter().map(
         |&(ref variant, ref value)| quote!(#value => ::std::option::Option::Some(#ident::#variant)),
     );
-
-    let is_valid_doc = format!("Returns `true` if `value` is a variant of `{}`.", ident);
-    let from_i32_doc = format!(
-        "Converts an `i32` to a `{}`, or `None` if `value` is not a valid variant.",
+    let discriminants = variants.iter().map(|(_, value)| value);
+    let all_variants = variants.iter().map(|(variant, _)| quote!(#ident::#variant));
+
+    let is_valid_doc = format!("Returns `true` if `value` is a variant of `{}`.", ident);
+    // Rust rejects duplicate discriminants, so an aliased value (`allow_alias`)
+    // must be declared as an associated constant of the canonical variant, and
+    // `from_i32` always resolves it to the first-declared variant.
+    let from_i32_doc = format!(
+        "Converts an `i32` to a `{}`, or `None` if `value` is not a valid variant. \
+         Aliased values resolve to the first-declared variant.",
+        ident
+    );
+    let variants_doc = format!(
+        "The values of every variant of `{}`, in declaration order.",
+        ident
+    );
+    let all_variants_doc = format!(
+        "Returns every variant of `{}`, in declaration order.",
         ident
     );
 
@@ -397,6 +1587,14 @@ fn try_enumeration(input: TokenStream) -> Result<TokenStream, Error> {
 
             impl #ident {
 
+                #[doc=#variants_doc]
+                pub const VARIANTS: &'static [i32] = &[#(#discriminants,)*];
+
+                #[doc=#all_variants_doc]
+                pub fn all_variants() -> &'static [#ident] {
+                    &[#(#all_variants,)*]
+                }
+
                 #[doc=#is_valid_doc]
                 pub fn is_valid(value: i32) -> bool {
                     match value {
@@ -428,19 +1626,28 @@ fn try_enumeration(input: TokenStream) -> Result<TokenStream, Error> {
         };
     };
 
-    Ok(expanded.into())
+    Ok(expanded)
 }
 
 #[proc_macro_derive(Enumeration, attributes(prost_amino))]
-pub fn enumeration(input: TokenStream) -> TokenStream {
-    try_enumeration(input).unwrap()
+pub fn enumeration(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    try_enumeration(input.into()).unwrap().into()
 }
 
 fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
-    let input: DeriveInput = syn::parse(input)?;
+    let input: DeriveInput = syn::parse2(input)?;
 
     let ident = input.ident;
 
+    let mut bit_eq = false;
+    for attr in field::prost_attrs(input.attrs)? {
+        if field::word_attr("bit_eq", &attr) {
+            field::set_bool(&mut bit_eq, "duplicate bit_eq attributes")?;
+        } else {
+            bail!("unknown oneof attribute: {:?}", attr);
+        }
+    }
+
     let variants = match input.data {
         Data::Enum(DataEnum { variants, .. }) => variants,
         Data::Struct(..) => bail!("Oneof can not be derived for a struct"),
@@ -451,8 +1658,9 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
         bail!("Message may not be derived for generic type");
     }
 
-    // Map the variants into 'fields'.
+    // Map the variants into 'fields', along with the types they hold.
     let mut fields: Vec<(Ident, Field)> = Vec::new();
+    let mut variant_tys = Vec::new();
     for Variant {
         attrs,
         ident: variant_ident,
@@ -474,11 +1682,12 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
             Some(field) => fields.push((variant_ident, field)),
             None => bail!("invalid oneof variant: oneof variants may not be ignored"),
         }
+        variant_tys.push(variant_fields[0].ty.clone());
     }
 
     let mut tags = fields
         .iter()
-        .flat_map(|&(ref variant_ident, ref field)| -> Result<u32, Error> {
+        .map(|(variant_ident, field)| -> Result<(u32, &Ident), Error> {
             if field.tags().len() > 1 {
                 bail!(
                     "invalid oneof variant {}::{}: oneof variants may only have a single tag",
@@ -486,15 +1695,51 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
                     variant_ident
                 );
             }
-            Ok(field.tags()[0])
+            Ok((field.tags()[0], variant_ident))
         })
-        .collect::<Vec<_>>();
-    tags.sort();
-    tags.dedup();
-    if tags.len() != fields.len() {
-        panic!("invalid oneof {}: variants have duplicate tags", ident);
+        .collect::<Result<Vec<_>, _>>()?;
+    tags.sort_by_key(|&(tag, _)| tag);
+    // Registered messages may share a tag, like the implementations of an amino interface, and
+    // are told apart by their prefixes when decoded. Whether the type of a message variant is
+    // registered is only known when it is compiled, so it is checked by a `const` assertion.
+    let is_registered_variant = |variant_ident: &Ident| {
+        fields
+            .iter()
+            .any(|(ident, field)| ident == variant_ident && field.is_registered_message())
+    };
+    if let Some(pair) = tags.windows(2).find(|pair| {
+        pair[0].0 == pair[1].0
+            && !(is_registered_variant(pair[0].1) && is_registered_variant(pair[1].1))
+    }) {
+        bail!(
+            "invalid oneof {}: variants {} and {} have the same tag {}",
+            ident,
+            pair[0].1,
+            pair[1].1,
+            pair[0].0
+        );
     }
 
+    let registered_checks = fields
+        .iter()
+        .zip(&variant_tys)
+        .filter(|((variant_ident, field), _)| {
+            tags.iter()
+                .any(|(tag, other)| *tag == field.tags()[0] && other != &variant_ident)
+        })
+        .map(|((variant_ident, field), variant_ty)| {
+            let msg = format!(
+                "invalid oneof {}: variant {} shares tag {} with another variant, but its type \
+                 is not registered",
+                ident,
+                variant_ident,
+                field.tags()[0]
+            );
+            quote! {
+                const _: () = assert!(<#variant_ty as _prost::encoding::FieldTags>::REGISTERED, #msg);
+            }
+        });
+
     // Put impls in a special module, so that 'extern crate' can be used.
     let module = Ident::new(&format!("{}_ONEOF", ident), Span::call_site());
 
@@ -503,22 +1748,66 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
         quote!(#ident::#variant_ident(ref value) => { #encode })
     });
 
-    let merge = fields.iter().map(|&(ref variant_ident, ref field)| {
-        let tag = field.tags()[0];
-        let merge = field.merge(quote!(value));
-        quote! {
-            #tag => {
-                let mut value = ::std::default::Default::default();
-                #merge.map(|_| *field = ::std::option::Option::Some(#ident::#variant_ident(value)))
+    let merge = tags
+        .iter()
+        .map(|&(tag, _)| tag)
+        .dedup()
+        .map(|tag| {
+            let variants = fields
+                .iter()
+                .zip(&variant_tys)
+                .filter(|((_, field), _)| field.tags()[0] == tag)
+                .map(|((variant_ident, field), variant_ty)| {
+                    let merge = field.merge(quote!(value));
+                    let merge = quote! {
+                        let mut value = ::std::default::Default::default();
+                        #merge.map(|_| *field = ::std::option::Option::Some(#ident::#variant_ident(value)))
+                    };
+                    (merge, variant_ty)
+                })
+                .collect::<Vec<_>>();
+            if variants.len() == 1 {
+                let merge = &variants[0].0;
+                return quote!(#tag => { #merge });
             }
-        }
-    });
+            let arms = variants.iter().map(|(merge, variant_ty)| {
+                quote! {
+                    if &prefix[..] == <#variant_ty as _prost::Message>::amino_prefix() {
+                        return { #merge };
+                    }
+                }
+            });
+            quote! {
+                #tag => {
+                    let prefix = _prost::encoding::message::peek_prefix(wire_type, buf)?;
+                    #(#arms)*
+                    ::std::result::Result::Err(_prost::DecodeError::new(format!(
+                        "unknown amino prefix {:02x?} for {}",
+                        prefix,
+                        stringify!(#ident)
+                    )))
+                }
+            }
+        })
+        .collect::<Vec<_>>();
 
     let encoded_len = fields.iter().map(|&(ref variant_ident, ref field)| {
         let encoded_len = field.encoded_len(quote!(*value));
         quote!(#ident::#variant_ident(ref value) => #encoded_len)
     });
 
+    let visit = fields.iter().map(|(variant_ident, field)| {
+        let visit = field.visit(quote!(*value), &variant_ident.to_string());
+        quote!(#ident::#variant_ident(ref value) => { #visit })
+    });
+
+    let size_breakdown = fields.iter().map(|(variant_ident, field)| {
+        let tag = field.tags()[0];
+        let name = variant_ident.to_string();
+        let encoded_len = field.encoded_len(quote!(*value));
+        quote!(#ident::#variant_ident(ref value) => (#tag, #name, #encoded_len))
+    });
+
     let debug = fields.iter().map(|&(ref variant_ident, ref field)| {
         let wrapper = field.debug(quote!(*value));
         quote!(#ident::#variant_ident(ref value) => {
@@ -529,6 +1818,32 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
         })
     });
 
+    // Lets messages with the `bit_eq` attribute compare the oneof, with `float` and `double`
+    // variants compared by their bits.
+    let bit_eq_impl = if bit_eq {
+        let eqs = fields.iter().map(|(variant_ident, field)| {
+            let eq = if field.is_bit_eq() {
+                quote!(_prost::encoding::BitEq::bit_eq(value, other))
+            } else {
+                quote!(value == other)
+            };
+            quote!((&#ident::#variant_ident(ref value), &#ident::#variant_ident(ref other)) => #eq)
+        });
+        quote! {
+            impl _prost::encoding::BitEq for #ident {
+                fn bit_eq(&self, other: &#ident) -> bool {
+                    #[allow(unreachable_patterns)]
+                    match (self, other) {
+                        #(#eqs,)*
+                        _ => false,
+                    }
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
+
     let expanded = quote! {
         #[allow(non_snake_case, unused_attributes)]
         mod #module {
@@ -547,10 +1862,14 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
                                 wire_type: _prost::encoding::WireType,
                                 buf: &mut B)
                                 -> ::std::result::Result<(), _prost::DecodeError>
-                where B: _prost::bytes::BufMut {
+                where B: _prost::bytes::Buf {
                     match tag {
                         #(#merge,)*
-                        _ => unreachable!(concat!("invalid ", stringify!(#ident), " tag: {}"), tag),
+                        _ => ::std::result::Result::Err(_prost::DecodeError::new(format!(
+                            "invalid {} tag: {}",
+                            stringify!(#ident),
+                            tag
+                        ))),
                     }
                 }
 
@@ -560,6 +1879,20 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
                         #(#encoded_len,)*
                     }
                 }
+
+                /// Passes the value of the variant to `visitor`, named by the variant.
+                pub fn visit_fields<V>(&self, visitor: &mut V) where V: _prost::FieldVisitor {
+                    match *self {
+                        #(#visit,)*
+                    }
+                }
+
+                /// Returns the tag, name and encoded length of the variant.
+                pub fn size_breakdown(&self) -> (u32, &'static str, usize) {
+                    match *self {
+                        #(#size_breakdown,)*
+                    }
+                }
             }
 
             impl ::std::fmt::Debug for #ident {
@@ -569,15 +1902,19 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
                     }
                 }
             }
+
+            #bit_eq_impl
+
+            #(#registered_checks)*
         };
     };
 
-    Ok(expanded.into())
+    Ok(expanded)
 }
 
 #[proc_macro_derive(Oneof, attributes(prost_amino))]
-pub fn oneof(input: TokenStream) -> TokenStream {
-    try_oneof(input).unwrap()
+pub fn oneof(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    try_oneof(input.into()).unwrap().into()
 }
 
 fn compute_disfix(identity: &str) -> (Vec<u8>, Vec<u8>) {
@@ -585,28 +1922,229 @@ fn compute_disfix(identity: &str) -> (Vec<u8>, Vec<u8>) {
     sh.update(identity.as_bytes());
     let output = sh.finalize();
 
-    let disamb_bytes = output
-        .iter()
-        .filter(|&x| *x != 0x00)
-        .cloned()
-        .take(3)
-        .collect();
-
-    let prefix_bytes: Vec<u8> = output
-        .iter()
-        .filter(|&x| *x != 0x00)
-        .skip(3)
-        .filter(|&x| *x != 0x00)
-        .cloned()
-        .take(4)
-        .collect();
-    return (disamb_bytes, prefix_bytes);
+    // As in go-amino, only leading zero bytes are dropped before taking the
+    // disambiguation bytes, and again before taking the prefix bytes.
+    let mut bytes = output.iter().skip_while(|&&x| x == 0x00).cloned();
+    let disamb_bytes: Vec<u8> = bytes.by_ref().take(3).collect();
+    let prefix_bytes: Vec<u8> = bytes.skip_while(|&x| x == 0x00).take(4).collect();
+    (disamb_bytes, prefix_bytes)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn disfix_matches_go_amino() {
+        let (disamb, prefix) = compute_disfix("tendermint/PubKeyEd25519");
+        assert_eq!(disamb, vec![0xac, 0x26, 0x79]);
+        assert_eq!(prefix, vec![0x16, 0x24, 0xde, 0x64]);
+
+        // The hash of this name has a zero byte inside the disambiguation bytes,
+        // which must be kept.
+        let (disamb, prefix) = compute_disfix("test/Name47");
+        assert_eq!(disamb, vec![0x7c, 0x00, 0x57]);
+        assert_eq!(prefix, vec![0x67, 0xbd, 0x2c, 0xf9]);
+    }
+
+    #[test]
+    fn amino_prefix_collision() {
+        let output = try_message(quote! {
+            struct Keys {
+                #[prost_amino(bytes, tag = "1", amino_name = "test/Name49870")]
+                a: Vec<u8>,
+                #[prost_amino(bytes, tag = "2", amino_name = "test/Name55629")]
+                b: Vec<u8>,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().to_string(),
+            "message Keys has fields a and b whose amino names \"test/Name49870\" and \
+             \"test/Name55629\" have the same prefix [e0, b1, a1, 83]"
+        );
+
+        // The same registered type may be used by several fields.
+        let output = try_message(quote! {
+            struct Keys {
+                #[prost_amino(bytes, tag = "1", amino_name = "test/Name49870")]
+                a: Vec<u8>,
+                #[prost_amino(bytes, tag = "2", amino_name = "test/Name49870")]
+                b: Vec<u8>,
+            }
+        });
+        assert!(output.is_ok());
+    }
+
+    #[test]
+    fn tag_prefix_out_of_bounds() {
+        let output = try_message(quote! {
+            #[prost_amino(tag_prefix = 536870911)]
+            struct Message {
+                #[prost_amino(int32)]
+                a: i32,
+            }
+        });
+        assert!(output.is_err());
+
+        let output = try_message(quote! {
+            #[prost_amino(tag_prefix = 536870910)]
+            struct Message {
+                #[prost_amino(int32)]
+                a: i32,
+            }
+        });
+        assert!(output.is_ok());
+    }
+
+    #[test]
+    fn reserved_tags() {
+        let output = try_message(quote! {
+            #[prost_amino(reserved_tags = "2, 3")]
+            struct Message {
+                #[prost_amino(int32)]
+                a: i32,
+                #[prost_amino(int32)]
+                b: i32,
+                #[prost_amino(int32, tag = "5")]
+                c: i32,
+                #[prost_amino(int32)]
+                d: i32,
+            }
+        })
+        .unwrap()
+        .to_string();
+        for tag in &[1u32, 4, 5, 6] {
+            assert!(output.contains(&format!("tag : {}u32", tag)));
+        }
+        for tag in &[2u32, 3] {
+            assert!(!output.contains(&format!("tag : {}u32", tag)));
+        }
+
+        let output = try_message(quote! {
+            #[prost_amino(reserved_tags = "2")]
+            struct Message {
+                #[prost_amino(int32, tag = "2")]
+                a: i32,
+            }
+        });
+        assert!(output.is_err());
+
+        let output = try_message(quote! {
+            #[prost_amino(reserved_tags = "2")]
+            struct Message {
+                #[prost_amino(oneof = "Choice", tags = "1, 2")]
+                choice: Option<Choice>,
+            }
+        });
+        assert!(output.is_err());
+    }
+
+    #[test]
+    fn default_fn_conflicts() {
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(int32, tag = "1", default = "1", default_fn = "one")]
+                a: i32,
+            }
+        });
+        assert!(output.is_err());
+
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(int32, optional, tag = "1", default_fn = "one")]
+                a: Option<i32>,
+            }
+        });
+        assert!(output.is_err());
+
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(enumeration = "Kind", tag = "1", default_fn = "kind")]
+                a: i32,
+            }
+        });
+        assert!(output.is_err());
+    }
+
+    #[test]
+    fn zigzag_requires_signed_integer() {
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(int64, zigzag, tag = "1")]
+                a: i64,
+            }
+        });
+        assert!(output.is_ok());
+
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(uint64, signed_varint, tag = "1")]
+                a: u64,
+            }
+        });
+        assert!(output.is_err());
+    }
+
+    #[test]
+    fn oneof_variants_with_duplicate_tags() {
+        let error = try_oneof(quote! {
+            enum Choice {
+                #[prost_amino(int32, tag = "1")]
+                A(i32),
+                #[prost_amino(string, tag = "2")]
+                B(String),
+                #[prost_amino(bytes, tag = "1")]
+                C(Vec<u8>),
+            }
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid oneof Choice: variants A and C have the same tag 1"
+        );
+    }
+
+    #[test]
+    fn oneof_tags_collide_with_field() {
+        let error = try_message(quote! {
+            struct Message {
+                #[prost_amino(int32, tag = "1")]
+                a: i32,
+                #[prost_amino(string, tag = "2")]
+                b: String,
+                #[prost_amino(oneof = "Choice", tags = "3, 2")]
+                choice: Option<Choice>,
+            }
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "message Message has fields b and choice with the same tag 2"
+        );
+    }
+
+    #[test]
+    fn codegen_is_deterministic() {
+        let input = quote! {
+            #[amino_name = "test/Deterministic"]
+            struct Message {
+                #[prost_amino(enumeration = "Kind", tag = "1", default = "B")]
+                kind: i32,
+                #[prost_amino(map = "string, int32", tag = "2")]
+                map: ::std::collections::HashMap<String, i32>,
+                #[prost_amino(int64, repeated, tag = "3")]
+                values: Vec<i64>,
+                #[prost_amino(string, tag = "4", default = "foo")]
+                name: String,
+                #[prost_amino(oneof = "Choice", tags = "5, 6")]
+                choice: Option<Choice>,
+            }
+        };
+        let first = try_message(input.clone()).unwrap().to_string();
+        let second = try_message(input).unwrap().to_string();
+        assert_eq!(first, second);
+    }
+
     #[test]
     fn compare_to_go_amino() {
         // test vectors generated via:
@@ -626,4 +2164,732 @@ mod tests {
             assert_eq!(prefix, want_prefix);
         }
     }
+
+    #[test]
+    fn alias_accessors_are_deprecated() {
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(string, tag = "1", alias = "name")]
+                moniker: String,
+            }
+        })
+        .unwrap()
+        .to_string();
+        assert!(output.contains(
+            "# [deprecated (note = \"renamed to `moniker`\")] pub fn name (& self) -> & String"
+        ));
+        assert!(output.contains("pub fn name_mut (& mut self) -> & mut String"));
+
+        // The alias of a field with an accessor delegates to it.
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(uint32, optional, tag = "1", alias = "rank")]
+                priority: Option<u32>,
+            }
+        })
+        .unwrap()
+        .to_string();
+        assert!(output.contains("pub fn rank (& self) -> u32 { self . priority () }"));
+
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(string, tag = "1", alias(name))]
+                moniker: String,
+            }
+        });
+        assert!(output.is_err());
+    }
+
+    #[test]
+    fn hazzers_require_presence_field() {
+        let output = try_message(quote! {
+            #[prost_amino(hazzers)]
+            struct Message {
+                #[prost_amino(int64, tag = "1")]
+                a: i64,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().to_string(),
+            "message Message has the hazzers attribute, but no `#[prost_amino(presence)]` u64 field"
+        );
+
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(int64, tag = "1")]
+                a: i64,
+                #[prost_amino(presence)]
+                presence: u64,
+            }
+        });
+        assert!(output.is_err());
+    }
+
+    #[test]
+    fn varint_width_must_fit_type() {
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(int32, varint_width = "5", tag = "1")]
+                a: i32,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "varint_width 5 is too narrow for int32 fields, which may take 10 bytes"
+        );
+
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(string, varint_width = "5", tag = "1")]
+                a: String,
+            }
+        });
+        assert!(output.is_err());
+
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(uint32, varint_width = "5", tag = "1")]
+                a: u32,
+            }
+        });
+        assert!(output.is_ok());
+    }
+
+    #[test]
+    fn endian_requires_fixed_width() {
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(int64, endian = "big", tag = "1")]
+                a: i64,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "endian attribute may only be applied to fixed width fields"
+        );
+
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(int64, wire = "fixed", endian = "big", tag = "1")]
+                a: i64,
+            }
+        });
+        assert!(output.is_ok());
+    }
+
+    #[test]
+    fn builder_requires_named_fields() {
+        let output = try_message(quote! {
+            #[prost_amino(builder)]
+            struct Message(#[prost_amino(int64, tag = "1")] i64);
+        });
+        assert_eq!(
+            output.unwrap_err().to_string(),
+            "builder may only be derived for message Message with named fields"
+        );
+
+        let output = try_message(quote! {
+            #[prost_amino(builder)]
+            struct Message {
+                #[prost_amino(int64, tag = "1")]
+                build: i64,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().to_string(),
+            "message Message has a field build, which conflicts with the build method of its \
+             builder"
+        );
+    }
+
+    #[test]
+    fn chunk_requires_packed_field() {
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(int64, repeated, packed = "false", chunk = "10", tag = "1")]
+                a: Vec<i64>,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "chunk attribute may only be applied to packed repeated fields"
+        );
+
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(int64, repeated, chunk = "0", tag = "1")]
+                a: Vec<i64>,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "chunk attribute must be at least 1"
+        );
+    }
+
+    #[test]
+    fn reserved_names() {
+        let output = try_message(quote! {
+            #[prost_amino(reserved_tags = "2", reserved_names = "old_foo, old_bar")]
+            struct Message {
+                #[prost_amino(int32, tag = "1")]
+                a: i32,
+                #[prost_amino(int32, tag = "2")]
+                b: i32,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().to_string(),
+            "message Message has a field b with reserved tag 2"
+        );
+
+        let output = try_message(quote! {
+            #[prost_amino(reserved_names = "old_foo, old_bar")]
+            struct Message {
+                #[prost_amino(int32, tag = "1")]
+                a: i32,
+                #[prost_amino(int32, tag = "2")]
+                old_bar: i32,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().to_string(),
+            "message Message has a field old_bar whose name is reserved"
+        );
+
+        let output = try_message(quote! {
+            #[prost_amino(reserved_names = "old_foo")]
+            struct Message {
+                #[prost_amino(int32, tag = "1")]
+                foo: i32,
+            }
+        });
+        assert!(output.is_ok());
+    }
+
+    #[test]
+    fn flatten_has_no_tag() {
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(message, flatten, tag = "1")]
+                a: Inner,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "flattened message fields may not have a tag attribute"
+        );
+
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(int32, flatten, tag = "1")]
+                a: i32,
+            }
+        });
+        assert!(output.is_err());
+
+        // The tags of the message are inferred around the flattened field.
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(message, flatten)]
+                a: Inner,
+                #[prost_amino(int32)]
+                b: i32,
+            }
+        });
+        assert!(output.unwrap().to_string().contains("FIELD_TAGS"));
+
+        // A registered type can not be flattened, which is checked when the message is compiled.
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(message, flatten)]
+                a: Inner,
+            }
+        });
+        let output = output.unwrap().to_string();
+        assert!(output.contains("REGISTERED"));
+        assert!(output.contains("message Message has a flattened field a whose type is registered"));
+    }
+
+    #[test]
+    fn default_expr_requires_numeric_field() {
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(string, default_expr = "\"a\"", tag = "1")]
+                a: String,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "default_expr attribute may only be applied to numeric or bool fields"
+        );
+
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(uint32, default = "1", default_expr = "1 << 20", tag = "1")]
+                a: u32,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "default_expr attribute may not be combined with default or default_fn"
+        );
+    }
+
+    #[test]
+    fn ascii_requires_string_field() {
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(bytes, ascii, tag = "1")]
+                a: Vec<u8>,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "ascii attribute may only be applied to string fields"
+        );
+
+        // The values of a stream are decoded without being checked.
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(string, repeated, ascii, stream, tag = "1")]
+                a: Vec<String>,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "ascii attribute may not be combined with max_len, cow, trim or stream"
+        );
+    }
+
+    #[test]
+    fn zstd_requires_bytes_field() {
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(string, zstd, tag = "1")]
+                a: String,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "zstd attribute may only be applied to bytes fields"
+        );
+
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(bytes, zstd, cow, tag = "1")]
+                a: Cow<'static, [u8]>,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "zstd attribute may not be combined with amino_name, wire, cow or shared"
+        );
+    }
+
+    #[test]
+    fn deprecated_wire_oneof_variant() {
+        let output = try_oneof(quote! {
+            enum Oneof {
+                #[prost_amino(int32, tag = "1", deprecated_wire)]
+                A(i32),
+            }
+        });
+        // Only message fields are encoded by the message, so only they may be retired.
+        let error = output.unwrap_err().find_root_cause().to_string();
+        assert!(error.starts_with("unknown attribute: "), "{}", error);
+    }
+
+    #[test]
+    fn clamp_may_not_be_checked() {
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(int32, range = "0..=9", checked, clamp = "0..=9", tag = "1")]
+                a: i32,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "clamp attribute may not be combined with epoch, checked or stream"
+        );
+
+        // The values of a stream are decoded without being clamped.
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(int32, repeated, clamp = "0..=10", stream, tag = "1")]
+                a: Vec<i32>,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "clamp attribute may not be combined with epoch, checked or stream"
+        );
+
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(uint32, clamp = "-1..=9", tag = "1")]
+                a: u32,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "invalid clamp attribute \"-1..=9\" for uint32 fields"
+        );
+    }
+
+    #[test]
+    fn as_type_must_match_wire_width() {
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(fixed64, as = "Ipv4Addr", tag = "1")]
+                a: ::std::net::Ipv4Addr,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "as type Ipv4Addr may only be applied to fixed32 or sfixed32 fields, not fixed64"
+        );
+
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(uint32, as = "SocketAddr", tag = "1")]
+                a: ::std::net::SocketAddr,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "unknown as type \"SocketAddr\", expected \"Ipv4Addr\", \"Ipv6Addr\" or \"u16\""
+        );
+    }
+
+    #[test]
+    fn dedup_requires_eq_repeated_field() {
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(double, repeated, dedup, tag = "1")]
+                a: Vec<f64>,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "dedup attribute may not be applied to float or double fields, which are not Eq"
+        );
+
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(uint32, dedup, tag = "1")]
+                a: u32,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "dedup attribute may only be applied to repeated fields"
+        );
+    }
+
+    #[test]
+    fn varint_codec_requires_varint_field() {
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(fixed64, varint_codec = "Codec", tag = "1")]
+                a: u64,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "varint_codec attribute may only be applied to varint fields"
+        );
+
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(uint64, repeated, varint_codec = "Codec", tag = "1")]
+                a: Vec<u64>,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "varint_codec attribute may not be applied to repeated fields"
+        );
+
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(uint64, varint_codec = "codecs::Codec", tag = "1")]
+                a: u64,
+            }
+        });
+        assert!(output.is_ok());
+    }
+
+    #[test]
+    fn closed_requires_enumeration() {
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(int32, closed, tag = "1")]
+                a: i32,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "closed attribute may only be applied to enumeration fields"
+        );
+
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(enumeration = "Status", repeated, closed, tag = "1")]
+                a: Vec<i32>,
+            }
+        });
+        assert!(output.is_ok());
+
+        // The values of a stream are decoded without the checks of `closed`.
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(enumeration = "Status", repeated, closed, stream, tag = "1")]
+                a: Vec<i32>,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "closed attribute may not be combined with stream"
+        );
+    }
+
+    #[test]
+    fn fixed_or_var_requires_bytes() {
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(string, fixed_or_var, tag = "1")]
+                a: String,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "fixed_or_var attribute may only be applied to bytes fields"
+        );
+
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(bytes, fixed_or_var, max_len = "32", tag = "1")]
+                a: Vec<u8>,
+            }
+        });
+        assert!(output.is_err());
+    }
+
+    #[test]
+    fn sorted_requires_ord_repeated_field() {
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(double, repeated, sorted, tag = "1")]
+                a: Vec<f64>,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "sorted attribute may not be applied to float or double fields, which are not Ord"
+        );
+
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(string, sorted, tag = "1")]
+                a: String,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "sorted attribute may only be applied to repeated fields"
+        );
+    }
+
+    #[test]
+    fn checked_may_not_stream() {
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(int32, checked, tag = "1")]
+                a: i32,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "checked attribute requires a range attribute"
+        );
+
+        // The values of a stream are decoded without being checked.
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(int32, repeated, range = "0..=10", checked, stream, tag = "1")]
+                a: Vec<i32>,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "checked attribute may not be combined with stream"
+        );
+
+        // Nor is the length of a streamed value limited.
+        let output = try_message(quote! {
+            struct Message {
+                #[prost_amino(bytes, repeated, max_len = "32", stream, tag = "1")]
+                a: Vec<Vec<u8>>,
+            }
+        });
+        assert_eq!(
+            output.unwrap_err().find_root_cause().to_string(),
+            "max_len attribute may not be combined with stream"
+        );
+    }
+
+    #[test]
+    fn hash_encoded_hashes_the_normalized_encoding() {
+        // Maps are written in key order when hashed, so a `HashMap` does not need the canonical
+        // feature.
+        let output = try_message(quote! {
+            #[prost_amino(hash_encoded)]
+            struct Message {
+                #[prost_amino(map = "string, int64", tag = "1")]
+                a: HashMap<String, i64>,
+            }
+        })
+        .unwrap()
+        .to_string();
+        assert!(output.contains("encode_normalized"));
+        assert!(!output.contains("CANONICAL"));
+    }
+
+    #[test]
+    fn oneof_variants_sharing_a_tag_must_be_registered() {
+        // A message variant only shares a tag with another message variant whose type is
+        // registered, which is asserted when the oneof is compiled.
+        let output = try_oneof(quote! {
+            enum Choice {
+                #[prost_amino(message, tag = "1")]
+                A(A),
+                #[prost_amino(message, tag = "1")]
+                B(B),
+            }
+        })
+        .unwrap()
+        .to_string();
+        assert!(output.contains(
+            "invalid oneof Choice: variant A shares tag 1 with another variant, but its type is not registered"
+        ));
+        assert!(output.contains(
+            "invalid oneof Choice: variant B shares tag 1 with another variant, but its type is not registered"
+        ));
+
+        let error = try_oneof(quote! {
+            enum Choice {
+                #[prost_amino(message, tag = "1")]
+                A(A),
+                #[prost_amino(string, tag = "1")]
+                B(String),
+            }
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid oneof Choice: variants A and B have the same tag 1"
+        );
+
+        // The prefix of a variant with an amino_name is not written by its type, so it may not
+        // share a tag.
+        let error = try_oneof(quote! {
+            enum Choice {
+                #[prost_amino(message, tag = "1", amino_name = "test/A")]
+                A(A),
+                #[prost_amino(message, tag = "1")]
+                B(B),
+            }
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid oneof Choice: variants A and B have the same tag 1"
+        );
+    }
+
+    #[test]
+    fn json_fields() {
+        let output = try_message(quote! {
+            struct Event {
+                #[prost_amino(json, optional, tag = "1")]
+                payload: Option<Value>,
+            }
+        })
+        .unwrap()
+        .to_string();
+        assert!(
+            output.contains("_prost :: encoding :: json :: merge"),
+            "{}",
+            output
+        );
+
+        let error = try_message(quote! {
+            struct Event {
+                #[prost_amino(json, repeated, sorted, tag = "1")]
+                payloads: Vec<Value>,
+            }
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.find_root_cause().to_string(),
+            "json attribute may not be combined with default, amino_name, dedup or sorted"
+        );
+    }
+
+    #[test]
+    fn json_inline_fields() {
+        let output = try_message(quote! {
+            #[prost_amino(amino_json)]
+            struct Send {
+                #[prost_amino(message, tag = "1", json_inline)]
+                fee: Option<Fee>,
+            }
+        })
+        .unwrap()
+        .to_string();
+        assert!(
+            output.contains("json_inline field fee whose keys collide"),
+            "{}",
+            output
+        );
+
+        let error = try_message(quote! {
+            struct Send {
+                #[prost_amino(message, tag = "1", json_inline)]
+                fee: Option<Fee>,
+            }
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "message Send has a json_inline field fee but no amino_json attribute"
+        );
+
+        let error = try_message(quote! {
+            #[prost_amino(amino_json)]
+            struct Send {
+                #[prost_amino(message, repeated, tag = "1", json_inline)]
+                fees: Vec<Fee>,
+            }
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.find_root_cause().to_string(),
+            "json_inline message fields may not be repeated or have an amino_name"
+        );
+
+        let error = try_message(quote! {
+            #[prost_amino(amino_json)]
+            struct Send {
+                #[prost_amino(oneof = "Choice", tags = "1, 2")]
+                choice: Option<Choice>,
+            }
+        })
+        .unwrap_err();
+        assert_eq!(
+            error.find_root_cause().to_string(),
+            "oneof fields have no Amino JSON form"
+        );
+    }
 }
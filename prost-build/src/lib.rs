@@ -126,6 +126,7 @@ mod ast;
 mod code_generator;
 mod ident;
 mod message_graph;
+mod schema;
 
 use std::collections::HashMap;
 use std::default;
@@ -139,6 +140,7 @@ use prost::Message;
 use prost_types::{FileDescriptorProto, FileDescriptorSet};
 
 pub use ast::{Comments, Method, Service};
+pub use schema::to_proto_schema;
 use code_generator::{module, CodeGenerator};
 use message_graph::MessageGraph;
 
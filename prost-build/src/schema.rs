@@ -0,0 +1,68 @@
+//! Renders `.proto` definitions of Rust-defined messages from their runtime descriptors.
+
+use std::any;
+use std::fmt::Write;
+
+use prost::{FieldDescriptor, Label, Message};
+
+/// Returns a `.proto` definition of the message `T`, rendered from `Message::descriptor`.
+///
+/// Singular fields are written without a label, as in `proto3`, and required fields are marked
+/// by a comment. The amino names of registered field types, and the amino prefix of a registered
+/// message, are also written as comments. Nested message and enumeration types are referred to
+/// by name, and are not defined.
+pub fn to_proto_schema<T>() -> String
+where
+    T: Message,
+{
+    let mut schema = String::new();
+    let prefix = T::amino_prefix();
+    if !prefix.is_empty() {
+        writeln!(schema, "// amino prefix: {:02x?}", prefix).unwrap();
+    }
+    writeln!(schema, "message {} {{", message_name::<T>()).unwrap();
+    for field in T::descriptor() {
+        writeln!(schema, "  {}", field_definition(field)).unwrap();
+    }
+    schema.push_str("}\n");
+    schema
+}
+
+/// Returns the name of the Rust type, without its module path or generic parameters.
+fn message_name<T>() -> &'static str {
+    let name = any::type_name::<T>();
+    let name = name.split('<').next().unwrap_or(name);
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+/// Returns the definition of a single field, without indentation.
+fn field_definition(field: &FieldDescriptor) -> String {
+    let ty = match field.ty {
+        "message" | "group" | "enum" | "map" => field.type_name,
+        "duration" => "google.protobuf.Duration",
+        // The variants of a oneof are not described, so it can not be defined.
+        "oneof" => {
+            return format!(
+                "// oneof {}: {} = {};",
+                field.name, field.type_name, field.tag
+            )
+        }
+        ty => ty,
+    };
+    let label = match field.label {
+        Label::Repeated if field.ty != "map" => "repeated ",
+        _ => "",
+    };
+    let mut definition = format!("{}{} {} = {};", label, ty, field.name, field.tag);
+    let mut comments = Vec::new();
+    if field.label == Label::Required {
+        comments.push("required".to_owned());
+    }
+    if !field.amino_name.is_empty() {
+        comments.push(format!("amino_name: {:?}", field.amino_name));
+    }
+    if !comments.is_empty() {
+        write!(definition, " // {}", comments.join(", ")).unwrap();
+    }
+    definition
+}
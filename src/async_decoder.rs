@@ -0,0 +1,132 @@
+//! Decoding of length-delimited messages from a Tokio `AsyncRead`.
+
+use std::future::Future;
+use std::io;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, ReadBuf};
+
+use decoder::{check_frame_len, push_length_byte, DEFAULT_MAX_FRAME_LEN};
+use encoding::encode_varint;
+use Message;
+
+/// Reads and decodes a single length-delimited message from an asynchronous reader.
+///
+/// The length delimiter is read one byte at a time, so that no bytes past the end of the frame
+/// are consumed, and the reader may be passed to `decode_async` again for the next message.
+/// Partial reads are resumed until the frame is complete. EOF anywhere in the frame, including
+/// before its first byte, results in an `UnexpectedEof` error, and a frame which exceeds 4 MiB or
+/// fails to decode results in an `InvalidData` error.
+pub fn decode_async<'a, M, R>(reader: &'a mut R) -> DecodeAsync<'a, M, R>
+where
+    M: Message + Default,
+    R: AsyncRead + Unpin,
+{
+    DecodeAsync {
+        reader,
+        state: State::Length { value: 0, count: 0 },
+        message: PhantomData,
+    }
+}
+
+/// The future returned by `decode_async`.
+#[derive(Debug)]
+pub struct DecodeAsync<'a, M, R: 'a> {
+    reader: &'a mut R,
+    state: State,
+    message: PhantomData<fn() -> M>,
+}
+
+#[derive(Debug)]
+enum State {
+    /// Reading the length delimiter, of which `count` bytes have been read.
+    Length { value: u64, count: u32 },
+    /// Reading the body of the frame, which follows its length delimiter in `buf`, up to
+    /// `filled` bytes.
+    Body { buf: Vec<u8>, filled: usize },
+    /// The message has been returned.
+    Done,
+}
+
+impl<'a, M, R> Future for DecodeAsync<'a, M, R>
+where
+    M: Message + Default,
+    R: AsyncRead + Unpin,
+{
+    type Output = io::Result<M>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<M>> {
+        let this = self.get_mut();
+        loop {
+            match this.state {
+                State::Length {
+                    ref mut value,
+                    ref mut count,
+                } => {
+                    let mut byte = [0u8];
+                    let mut buf = ReadBuf::new(&mut byte);
+                    match Pin::new(&mut *this.reader).poll_read(cx, &mut buf) {
+                        Poll::Ready(Ok(())) => (),
+                        Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+                        Poll::Pending => return Poll::Pending,
+                    }
+                    if buf.filled().is_empty() {
+                        let description = if *count == 0 {
+                            "no length-delimited message before EOF"
+                        } else {
+                            "truncated length delimiter"
+                        };
+                        return Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            description,
+                        )));
+                    }
+                    let len = match push_length_byte(value, *count, byte[0]) {
+                        Ok(Some(len)) => len,
+                        Ok(None) => {
+                            *count += 1;
+                            continue;
+                        }
+                        Err(error) => return Poll::Ready(Err(error)),
+                    };
+                    if let Err(error) = check_frame_len(len, DEFAULT_MAX_FRAME_LEN) {
+                        return Poll::Ready(Err(error));
+                    }
+                    // The length delimiter is kept in front of the frame, since a registered message
+                    // is decoded from its delimiter and prefix.
+                    let mut buf = Vec::new();
+                    encode_varint(len, &mut buf);
+                    let filled = buf.len();
+                    buf.resize(filled + len as usize, 0);
+                    this.state = State::Body { buf, filled };
+                }
+                State::Body {
+                    ref mut buf,
+                    ref mut filled,
+                } if *filled < buf.len() => {
+                    let mut read_buf = ReadBuf::new(&mut buf[*filled..]);
+                    match Pin::new(&mut *this.reader).poll_read(cx, &mut read_buf) {
+                        Poll::Ready(Ok(())) => (),
+                        Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+                        Poll::Pending => return Poll::Pending,
+                    }
+                    if read_buf.filled().is_empty() {
+                        return Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "truncated length-delimited message",
+                        )));
+                    }
+                    *filled += read_buf.filled().len();
+                }
+                State::Body { ref buf, .. } => {
+                    let result = M::decode_length_delimited(&buf[..]).map_err(io::Error::from);
+                    this.state = State::Done;
+                    return Poll::Ready(result);
+                }
+                State::Done => panic!("`DecodeAsync` polled after completion"),
+            }
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Decoding of length-delimited message streams from an `io::Read`.
+
+use std::io::{self, Read};
+
+use encoding::encode_varint;
+use Message;
+
+/// The default maximum length of a single frame, 4 MiB.
+pub(crate) const DEFAULT_MAX_FRAME_LEN: usize = 4 * 1024 * 1024;
+
+/// Decodes a stream of length-delimited messages from a reader.
+///
+/// The decoder owns a buffer which is reused for every frame, so decoding many messages does not
+/// allocate a fresh buffer for each of them. Frames longer than the maximum frame length are
+/// rejected before any of their contents are read, which bounds the size of the buffer.
+#[derive(Debug)]
+pub struct Decoder {
+    buf: Vec<u8>,
+    max_frame_len: usize,
+}
+
+impl Decoder {
+    /// Creates a new `Decoder` which rejects frames longer than `max_frame_len` bytes.
+    pub fn new(max_frame_len: usize) -> Decoder {
+        Decoder {
+            buf: Vec::new(),
+            max_frame_len,
+        }
+    }
+
+    /// Returns the maximum length of a single frame.
+    pub fn max_frame_len(&self) -> usize {
+        self.max_frame_len
+    }
+
+    /// Reads and decodes the next length-delimited message from the reader.
+    ///
+    /// Returns `None` if the reader is at EOF before the start of a frame. A frame which is
+    /// truncated by EOF, including inside its length delimiter, results in an
+    /// `UnexpectedEof` error, and a frame which exceeds the maximum frame length or fails to
+    /// decode results in an `InvalidData` error.
+    pub fn decode_next<M, R>(&mut self, reader: &mut R) -> io::Result<Option<M>>
+    where
+        M: Message + Default,
+        R: Read,
+    {
+        let len = match read_length_delimiter(reader)? {
+            Some(len) => len,
+            None => return Ok(None),
+        };
+        check_frame_len(len, self.max_frame_len)?;
+
+        // The length delimiter is kept in front of the frame, since a registered message is
+        // decoded from its delimiter and prefix.
+        self.buf.clear();
+        encode_varint(len, &mut self.buf);
+        let start = self.buf.len();
+        self.buf.resize(start + len as usize, 0);
+        reader.read_exact(&mut self.buf[start..])?;
+
+        let message = M::decode_length_delimited(&self.buf[..])?;
+        Ok(Some(message))
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Decoder {
+        Decoder::new(DEFAULT_MAX_FRAME_LEN)
+    }
+}
+
+/// Reads a varint length delimiter one byte at a time, so that no bytes of the frame are
+/// consumed. Returns `None` on EOF before the first byte.
+fn read_length_delimiter<R>(reader: &mut R) -> io::Result<Option<u64>>
+where
+    R: Read,
+{
+    let mut value = 0u64;
+    let mut count = 0;
+    loop {
+        let mut byte = [0u8];
+        loop {
+            match reader.read(&mut byte) {
+                Ok(0) if count == 0 => return Ok(None),
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "truncated length delimiter",
+                    ))
+                }
+                Ok(_) => break,
+                Err(ref error) if error.kind() == io::ErrorKind::Interrupted => (),
+                Err(error) => return Err(error),
+            }
+        }
+        if let Some(len) = push_length_byte(&mut value, count, byte[0])? {
+            return Ok(Some(len));
+        }
+        count += 1;
+    }
+}
+
+/// Adds the byte at index `count` of a varint length delimiter to `value`. Returns the length
+/// if the byte is the last of the delimiter.
+pub(crate) fn push_length_byte(value: &mut u64, count: u32, byte: u8) -> io::Result<Option<u64>> {
+    *value |= u64::from(byte & 0x7F) << (count * 7);
+    if byte < 0x80 && (count < 9 || byte < 0x02) {
+        return Ok(Some(*value));
+    }
+    if count == 9 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "invalid length delimiter",
+        ));
+    }
+    Ok(None)
+}
+
+/// Returns an error if a frame of `len` bytes exceeds the maximum frame length.
+pub(crate) fn check_frame_len(len: u64, max_frame_len: usize) -> io::Result<()> {
+    if len > max_frame_len as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "frame length {} exceeds the maximum frame length {}",
+                len, max_frame_len
+            ),
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,58 @@
+//! Runtime metadata describing the fields of a `Message`.
+
+/// The cardinality of a message field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Label {
+    /// A singular field, which may be omitted when it holds its default value.
+    Optional,
+    /// A singular field which is always encoded.
+    Required,
+    /// A repeated, packed, or map field.
+    Repeated,
+}
+
+/// The default value of a singular scalar field.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParsedDefault {
+    /// The default of an `int32`, `int64`, `sint*` or `sfixed*` field.
+    Int(i64),
+    /// The default of a `uint32`, `uint64` or `fixed*` field.
+    Uint(u64),
+    /// The default of a `float` or `double` field.
+    Float(f64),
+    /// The default of a `bool` field.
+    Bool(bool),
+    /// The default of a `string` field, or `null` for a `json` field.
+    Str(&'static str),
+    /// The default of a `bytes` field.
+    Bytes(&'static [u8]),
+    /// The name of the default variant of an enumeration field.
+    Enum(&'static str),
+    /// The path of the function which produces the default, from a `default_fn` attribute.
+    Fn(&'static str),
+}
+
+/// Describes a single field of a `Message`, as declared in its derive attributes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FieldDescriptor {
+    /// The field tag.
+    pub tag: u32,
+    /// The name of the Rust struct field.
+    pub name: &'static str,
+    /// The Protobuf type of the field, e.g. `int64`, `message` or `map`.
+    pub ty: &'static str,
+    /// The cardinality of the field.
+    pub label: Label,
+    /// The amino prefix of a registered field type, or an empty slice.
+    pub amino_prefix: &'static [u8],
+    /// The amino name of a registered field type, or an empty string.
+    pub amino_name: &'static str,
+    /// The name of the message, enumeration or oneof type of the field, e.g. `Header`, or the
+    /// key and value types of a map field, e.g. `map<string, Header>`. Empty for other scalar
+    /// fields.
+    pub type_name: &'static str,
+    /// The default value of a singular scalar field, or `None` for other fields. The default of
+    /// an enumeration field without a `default` attribute is its first variant, which is not
+    /// known to the message, so it is `None` as well.
+    pub default: Option<ParsedDefault>,
+}
@@ -0,0 +1,168 @@
+//! A signed span of time, encoded with amino's seconds and nanos layout.
+
+use std::convert::TryFrom;
+use std::time;
+
+use bytes::{Buf, BufMut};
+
+use encoding::*;
+use DecodeError;
+use FieldVisitor;
+use Message;
+use ScalarValue;
+
+const NANOS_PER_SECOND: i32 = 1_000_000_000;
+
+/// A signed span of time, encoded as `seconds` (field 1) followed by `nanos` (field 2).
+///
+/// A canonical duration has `nanos` in the range `(-1e9, 1e9)`, with the same sign as `seconds`
+/// when both are non-zero. Durations are canonicalized when encoded, and non-canonical durations
+/// are rejected when decoded as a `duration` field.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Duration {
+    pub seconds: i64,
+    pub nanos: i32,
+}
+
+impl Duration {
+    /// Returns the canonical form of the duration, saturating at the bounds of `seconds`.
+    pub fn normalized(&self) -> Duration {
+        let mut seconds = self.seconds;
+        let mut nanos = self.nanos;
+        if nanos <= -NANOS_PER_SECOND || nanos >= NANOS_PER_SECOND {
+            seconds = seconds.saturating_add(i64::from(nanos / NANOS_PER_SECOND));
+            nanos %= NANOS_PER_SECOND;
+        }
+        if seconds < 0 && nanos > 0 {
+            if let Some(s) = seconds.checked_add(1) {
+                seconds = s;
+                nanos -= NANOS_PER_SECOND;
+            } else {
+                nanos = 0;
+            }
+        } else if seconds > 0 && nanos < 0 {
+            if let Some(s) = seconds.checked_sub(1) {
+                seconds = s;
+                nanos += NANOS_PER_SECOND;
+            } else {
+                nanos = 0;
+            }
+        }
+        Duration { seconds, nanos }
+    }
+
+    /// Returns `true` if the duration is in canonical form.
+    pub fn is_canonical(&self) -> bool {
+        -NANOS_PER_SECOND < self.nanos
+            && self.nanos < NANOS_PER_SECOND
+            && !(self.seconds < 0 && self.nanos > 0)
+            && !(self.seconds > 0 && self.nanos < 0)
+    }
+}
+
+impl From<time::Duration> for Duration {
+    /// Converts a `std::time::Duration`, saturating at `i64::MAX` seconds.
+    fn from(duration: time::Duration) -> Duration {
+        Duration {
+            seconds: i64::try_from(duration.as_secs()).unwrap_or(i64::MAX),
+            nanos: duration.subsec_nanos() as i32,
+        }
+    }
+}
+
+impl TryFrom<Duration> for time::Duration {
+    type Error = Duration;
+
+    /// Converts a non-negative duration, returning the canonical duration if it is negative.
+    fn try_from(duration: Duration) -> Result<time::Duration, Duration> {
+        let duration = duration.normalized();
+        if duration.seconds >= 0 && duration.nanos >= 0 {
+            Ok(time::Duration::new(
+                duration.seconds as u64,
+                duration.nanos as u32,
+            ))
+        } else {
+            Err(duration)
+        }
+    }
+}
+
+impl Message for Duration {
+    fn encode_raw<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        let duration = self.normalized();
+        if duration.seconds != 0 {
+            int64::encode(1, &duration.seconds, buf);
+        }
+        if duration.nanos != 0 {
+            int32::encode(2, &duration.nanos, buf);
+        }
+    }
+    fn merge_field<B>(&mut self, buf: &mut B) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let (tag, wire_type) = decode_key(buf)?;
+        match tag {
+            1 => int64::merge(wire_type, &mut self.seconds, buf),
+            2 => int32::merge(wire_type, &mut self.nanos, buf),
+            _ => skip_field(wire_type, tag, buf, 0),
+        }
+    }
+    fn encoded_len(&self) -> usize {
+        let duration = self.normalized();
+        (if duration.seconds != 0 {
+            int64::encoded_len(1, &duration.seconds)
+        } else {
+            0
+        }) + if duration.nanos != 0 {
+            int32::encoded_len(2, &duration.nanos)
+        } else {
+            0
+        }
+    }
+    fn clear(&mut self) {
+        *self = Duration::default();
+    }
+    fn visit_fields<V>(&self, visitor: &mut V)
+    where
+        V: FieldVisitor,
+    {
+        visitor.visit_scalar(1, "seconds", ScalarValue::Int(self.seconds));
+        visitor.visit_scalar(2, "nanos", ScalarValue::Int(i64::from(self.nanos)));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn normalized() {
+        let cases = [
+            ((0, 0), (0, 0)),
+            ((1, -1), (0, 999_999_999)),
+            ((-1, 1), (0, -999_999_999)),
+            ((0, 1_500_000_000), (1, 500_000_000)),
+            ((1, 1_500_000_000), (2, 500_000_000)),
+            ((-1, -1_500_000_000), (-2, -500_000_000)),
+            ((2, -1_500_000_000), (0, 500_000_000)),
+            ((i64::MAX, 1_000_000_000), (i64::MAX, 0)),
+            ((i64::MAX, -1), (i64::MAX - 1, 999_999_999)),
+            ((i64::MIN, 1), (i64::MIN + 1, -999_999_999)),
+        ];
+        for &((seconds, nanos), (want_seconds, want_nanos)) in &cases[..] {
+            let duration = Duration { seconds, nanos }.normalized();
+            assert!(duration.is_canonical(), "{:?}", duration);
+            assert_eq!(
+                duration,
+                Duration {
+                    seconds: want_seconds,
+                    nanos: want_nanos,
+                }
+            );
+        }
+    }
+}
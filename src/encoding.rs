@@ -2,16 +2,25 @@
 //!
 //! Meant to be used only from `Message` implementations.
 
-use std::cmp::min;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::cmp::{max, min};
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::io::IoSlice;
 use std::mem;
 use std::str;
 use std::u32;
 use std::usize;
 
-use bytes::{Buf, BufMut};
+use bytes::buf::BufExt;
+use bytes::{Buf, BufMut, Bytes};
 
 use DecodeError;
 use Message;
+use ValidationError;
 
 /// Encodes an integer value into LEB128 variable length format, and writes it to the buffer.
 /// The buffer must have enough remaining space (maximum 10 bytes).
@@ -50,6 +59,45 @@ where
         buf.advance_mut(i);
     }
 }
+
+/// Encodes an integer value into LEB128 variable length format, padded with redundant
+/// continuation bits to `width` bytes, and writes it to the buffer. Values which need more than
+/// `width` bytes are written in full. Padded values are decoded by `decode_varint`.
+pub fn encode_varint_padded<B>(mut value: u64, width: usize, buf: &mut B)
+where
+    B: BufMut,
+{
+    for _ in 1..max(width, encoded_len_varint(value)) {
+        buf.put_u8(((value & 0x7F) | 0x80) as u8);
+        value >>= 7;
+    }
+    buf.put_u8(value as u8);
+}
+
+/// A custom encoding of the values of a varint field, given by the `varint_codec` attribute.
+///
+/// The codec is passed the `u64` which the standard encoding would write as a varint, e.g. the
+/// zigzag encoding of a `sint64`, and the wire type of the field remains `Varint`. Encoded values
+/// must delimit themselves, as they are followed directly by the key of the next field.
+///
+/// Encoded values must also keep the LEB128 continuation bits: every byte but the last has its
+/// high bit set, and the last has it clear. Unknown fields, and fields skipped because their tag is
+/// not in `allowed_tags`, are skipped by `skip_field` as standard varints, without the codec.
+pub trait VarintCodec {
+    /// Encodes a value and writes it to the buffer.
+    fn encode<B>(value: u64, buf: &mut B)
+    where
+        B: BufMut;
+
+    /// Decodes a value from the buffer.
+    fn decode<B>(buf: &mut B) -> Result<u64, DecodeError>
+    where
+        B: Buf;
+
+    /// Returns the encoded length of a value.
+    fn encoded_len(value: u64) -> usize;
+}
+
 /// Decodes a LEB128-encoded variable length integer from the buffer.
 pub fn decode_varint<B>(buf: &mut B) -> Result<u64, DecodeError>
 where
@@ -58,7 +106,7 @@ where
     let bytes = buf.bytes();
     let len = bytes.len();
     if len == 0 {
-        return Err(DecodeError::new("invalid varint"));
+        return Err(DecodeError::incomplete("invalid varint", None));
     }
 
     let byte = unsafe { *bytes.get_unchecked(0) };
@@ -163,7 +211,8 @@ where
     B: Buf,
 {
     let mut value = 0;
-    for count in 0..min(10, buf.remaining()) {
+    let len = min(10, buf.remaining());
+    for count in 0..len {
         let byte = buf.get_u8();
         value |= u64::from(byte & 0x7F) << (count * 7);
         if byte <= 0x7F {
@@ -171,7 +220,13 @@ where
         }
     }
 
-    Err(DecodeError::new("invalid varint"))
+    // A varint which ends with the buffer may be completed by more input, but one which is
+    // longer than 10 bytes is corrupt.
+    if len < 10 {
+        Err(DecodeError::incomplete("invalid varint", None))
+    } else {
+        Err(DecodeError::new("invalid varint"))
+    }
 }
 
 /// Returns the encoded length of the value in LEB128 variable length format.
@@ -183,18 +238,96 @@ pub fn encoded_len_varint(value: u64) -> usize {
     ((((value | 1).leading_zeros() ^ 63) * 9 + 73) / 64) as usize
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u8)]
 pub enum WireType {
     Varint = 0,
     SixtyFourBit = 1,
     LengthDelimited = 2,
+    StartGroup = 3,
+    EndGroup = 4,
     ThirtyTwoBit = 5,
 }
 
 pub const MIN_TAG: u32 = 1;
 pub const MAX_TAG: u32 = (1 << 29) - 1;
 
+/// The length of the prefix which precedes the fields of a registered message.
+pub const AMINO_PREFIX_LEN: usize = 4;
+
+/// The maximum number of groups a skipped field may be nested in, and of messages and groups a
+/// decoded field may be nested in.
+pub const RECURSION_LIMIT: u32 = 100;
+
+thread_local! {
+    /// The number of nested messages and groups being merged on this thread.
+    static DEPTH: Cell<u32> = const { Cell::new(0) };
+}
+
+/// Restores the depth of nested messages when dropped, even if merging the message panics.
+struct DepthGuard(u32);
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        DEPTH.with(|depth| depth.set(self.0));
+    }
+}
+
+thread_local! {
+    /// Whether messages are being encoded by `encode_normalized` on this thread, as they are for
+    /// `Message::checksum` and the `Hash` of `hash_encoded` messages.
+    static NORMALIZED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Restores whether messages are encoded normalized when dropped, even if encoding the message
+/// panics.
+struct NormalizedGuard(bool);
+
+impl Drop for NormalizedGuard {
+    fn drop(&mut self) {
+        NORMALIZED.with(|normalized| normalized.set(self.0));
+    }
+}
+
+/// Whether the message is being encoded by `encode_normalized`.
+#[inline]
+fn normalized() -> bool {
+    NORMALIZED.with(Cell::get)
+}
+
+/// Whether map entries are written in key order, either because the `canonical` feature is
+/// enabled or because the message is being encoded by `encode_normalized`.
+#[inline]
+fn in_key_order() -> bool {
+    CANONICAL || normalized()
+}
+
+/// Encodes a message to a `Vec` such that equal messages, and their nested messages, are encoded
+/// equally: the entries of maps are written in key order, as with the `canonical` feature, and
+/// `-0.0` is written as `0.0`, which it is equal to.
+pub fn encode_normalized<M>(msg: &M) -> Vec<u8>
+where
+    M: Message,
+{
+    let _guard = NormalizedGuard(NORMALIZED.with(|normalized| normalized.replace(true)));
+    msg.encode_to_vec()
+}
+
+/// Merges a nested message or group with `merge`, or fails if it is nested more than
+/// `RECURSION_LIMIT` levels deep, so that decoding untrusted input can not overflow the stack.
+fn merge_nested<F>(merge: F) -> Result<(), DecodeError>
+where
+    F: FnOnce() -> Result<(), DecodeError>,
+{
+    let depth = DEPTH.with(Cell::get);
+    if depth >= RECURSION_LIMIT {
+        return Err(DecodeError::new("recursion limit reached"));
+    }
+    let _guard = DepthGuard(depth);
+    DEPTH.with(|cell| cell.set(depth + 1));
+    merge()
+}
+
 impl WireType {
     // TODO: impl TryFrom<u8> when stable.
     #[inline]
@@ -203,6 +336,8 @@ impl WireType {
             0 => Ok(WireType::Varint),
             1 => Ok(WireType::SixtyFourBit),
             2 => Ok(WireType::LengthDelimited),
+            3 => Ok(WireType::StartGroup),
+            4 => Ok(WireType::EndGroup),
             5 => Ok(WireType::ThirtyTwoBit),
             _ => Err(DecodeError::new(format!(
                 "invalid wire type value: {}",
@@ -257,14 +392,293 @@ pub fn key_len(tag: u32) -> usize {
 #[inline]
 pub fn check_wire_type(expected: WireType, actual: WireType) -> Result<(), DecodeError> {
     if expected != actual {
-        return Err(DecodeError::new(format!(
-            "invalid wire type: {:?} (expected {:?})",
-            actual, expected
-        )));
+        return Err(DecodeError::wire_type_mismatch(expected, actual));
+    }
+    Ok(())
+}
+
+/// Checks that a decoded value of a `checked` field is within its `range`, or returns an error
+/// result.
+#[inline]
+pub fn check_range<T>(value: T, min: T, max: T) -> Result<(), DecodeError>
+where
+    T: PartialOrd + fmt::Display,
+{
+    if value < min || value > max {
+        return Err(DecodeError::out_of_range(out_of_range(value, min, max)));
+    }
+    Ok(())
+}
+
+/// Checks that a value of a field with a `range` is within it, or returns an error result.
+#[inline]
+pub fn validate_range<T>(value: T, min: T, max: T) -> Result<(), ValidationError>
+where
+    T: PartialOrd + fmt::Display,
+{
+    if value < min || value > max {
+        return Err(ValidationError::new(out_of_range(value, min, max)));
+    }
+    Ok(())
+}
+
+fn out_of_range<T>(value: T, min: T, max: T) -> String
+where
+    T: fmt::Display,
+{
+    format!("value {} is out of range {}..={}", value, min, max)
+}
+
+/// Merges values into a repeated `checked` field, and checks that each merged value is within
+/// the field's `range`.
+pub fn merge_repeated_checked<T, M>(
+    values: &mut Vec<T>,
+    min: T,
+    max: T,
+    merge: M,
+) -> Result<(), DecodeError>
+where
+    T: Copy + PartialOrd + fmt::Display,
+    M: FnOnce(&mut Vec<T>) -> Result<(), DecodeError>,
+{
+    let start = values.len();
+    merge(values)?;
+    for (index, &value) in values[start..].iter().enumerate() {
+        check_range(value, min, max).map_err(|mut error| {
+            error.push_index(start + index);
+            error
+        })?;
+    }
+    Ok(())
+}
+
+/// Checks that a decoded value of a `closed` enumeration field is a variant of the enumeration,
+/// as given by its `from_i32`, or returns an error result.
+#[inline]
+pub fn check_enum_value<E>(
+    tag: u32,
+    value: i32,
+    from_i32: fn(i32) -> Option<E>,
+) -> Result<(), DecodeError> {
+    match from_i32(value) {
+        Some(_) => Ok(()),
+        None => Err(DecodeError::unknown_enum(tag, value)),
+    }
+}
+
+/// Merges values into a repeated `closed` enumeration field, and checks that each merged value is
+/// a variant of the enumeration.
+pub fn merge_repeated_closed<E, M>(
+    values: &mut Vec<i32>,
+    tag: u32,
+    from_i32: fn(i32) -> Option<E>,
+    merge: M,
+) -> Result<(), DecodeError>
+where
+    M: FnOnce(&mut Vec<i32>) -> Result<(), DecodeError>,
+{
+    let start = values.len();
+    merge(values)?;
+    for (index, &value) in values[start..].iter().enumerate() {
+        check_enum_value(tag, value, from_i32).map_err(|mut error| {
+            error.push_index(start + index);
+            error
+        })?;
+    }
+    Ok(())
+}
+
+/// Returns a decoded value of a field with a `clamp` range, moved to the nearest bound of the
+/// range if it is outside it.
+#[inline]
+pub fn clamp<T>(value: T, min: T, max: T) -> T
+where
+    T: PartialOrd,
+{
+    if value < min {
+        min
+    } else if value > max {
+        max
+    } else {
+        value
+    }
+}
+
+/// Merges values into a repeated field with a `clamp` range, and clamps each merged value.
+pub fn merge_repeated_clamped<T, M>(
+    values: &mut Vec<T>,
+    min: T,
+    max: T,
+    merge: M,
+) -> Result<(), DecodeError>
+where
+    T: Copy + PartialOrd,
+    M: FnOnce(&mut Vec<T>) -> Result<(), DecodeError>,
+{
+    let start = values.len();
+    merge(values)?;
+    for value in &mut values[start..] {
+        *value = clamp(*value, min, max);
     }
     Ok(())
 }
 
+/// Drops each value of a repeated field with the `dedup` attribute which equals a value before
+/// it, keeping the first occurrence. Called once a message has been merged, rather than for each
+/// merged value, so that a field whose values are not packed is only deduplicated once.
+///
+/// Each value is hashed, so deduplicating `n` values takes `O(n)` expected time. The whole field
+/// is deduplicated each time the message is merged, so merging many buffers into one message
+/// hashes the values of the earlier buffers again each time.
+pub fn dedup_repeated<T>(values: &mut Vec<T>)
+where
+    T: Hash + Eq,
+{
+    // The kept values are indexed by their hashes, so that each value is only compared with the
+    // kept values which hash equally.
+    let state = RandomState::new();
+    let hash = |value: &T| state.hash_one(value);
+    let mut kept = HashMap::<u64, Vec<usize>>::with_capacity(values.len());
+    let mut end = 0;
+    for i in 0..values.len() {
+        let indices = kept.entry(hash(&values[i])).or_default();
+        if !indices.iter().any(|&j| values[j] == values[i]) {
+            values.swap(end, i);
+            indices.push(end);
+            end += 1;
+        }
+    }
+    values.truncate(end);
+}
+
+/// Returns the values of a repeated field with the `sorted` attribute in ascending order, so that
+/// they are encoded in order without reordering the field itself. Values which are already in
+/// order are borrowed rather than copied.
+pub fn sorted<'a, T>(values: &'a [T]) -> Cow<'a, [T]>
+where
+    T: Ord + Clone,
+{
+    if values.windows(2).all(|pair| pair[0] <= pair[1]) {
+        return Cow::Borrowed(values);
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort();
+    Cow::Owned(sorted)
+}
+
+/// Compares values by their bits, so that a float holding `NaN` is equal to itself, as by the
+/// `PartialEq` of messages with the `bit_eq` attribute. Unlike `==`, `0.0` and `-0.0` differ.
+pub trait BitEq {
+    fn bit_eq(&self, other: &Self) -> bool;
+}
+
+impl BitEq for f32 {
+    fn bit_eq(&self, other: &f32) -> bool {
+        self.to_bits() == other.to_bits()
+    }
+}
+
+impl BitEq for f64 {
+    fn bit_eq(&self, other: &f64) -> bool {
+        self.to_bits() == other.to_bits()
+    }
+}
+
+impl<T> BitEq for Option<T>
+where
+    T: BitEq,
+{
+    fn bit_eq(&self, other: &Option<T>) -> bool {
+        match (self, other) {
+            (Some(value), Some(other)) => value.bit_eq(other),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl<T> BitEq for Vec<T>
+where
+    T: BitEq,
+{
+    fn bit_eq(&self, other: &Vec<T>) -> bool {
+        self.len() == other.len() && self.iter().zip(other).all(|(a, b)| a.bit_eq(b))
+    }
+}
+
+impl<K, V, S> BitEq for HashMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: BitEq,
+    S: BuildHasher,
+{
+    fn bit_eq(&self, other: &HashMap<K, V, S>) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key).is_some_and(|other| value.bit_eq(other)))
+    }
+}
+
+impl<K, V> BitEq for BTreeMap<K, V>
+where
+    K: Ord,
+    V: BitEq,
+{
+    fn bit_eq(&self, other: &BTreeMap<K, V>) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .zip(other)
+                .all(|((key, value), (other_key, other))| key == other_key && value.bit_eq(other))
+    }
+}
+
+/// Returns an incomplete `DecodeError` for a value of `len` bytes, of which only `remaining` are
+/// in the buffer.
+pub(crate) fn underflow(len: u64, remaining: usize) -> DecodeError {
+    let needed = len - remaining as u64;
+    DecodeError::incomplete("buffer underflow", Some(needed as usize))
+}
+
+/// The lookup table of the CRC32 (IEEE) checksum, for the reflected polynomial `0xEDB88320`.
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Returns the CRC32 (IEEE) checksum of the bytes, as used by `Message::encode_framed`.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, &byte| {
+        CRC32_TABLE[((crc ^ u32::from(byte)) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+/// Returns the 64-bit FNV-1a hash of the bytes, as used by `Message::checksum`. Unlike the std
+/// hashers, the hash of given bytes never changes.
+pub fn fnv1a64(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
 /// Helper function which abstracts reading a length delimiter prefix followed
 /// by decoding values until the length of bytes is exhausted.
 pub fn merge_loop<T, M, B>(value: &mut T, buf: &mut B, mut merge: M) -> Result<(), DecodeError>
@@ -275,12 +689,14 @@ where
     let len = decode_varint(buf)?;
     let remaining = buf.remaining();
     if len > remaining as u64 {
-        return Err(DecodeError::new("buffer underflow"));
+        return Err(underflow(len, remaining));
     }
 
+    // The contents are all present, so a value which is truncated by the end of the contents is
+    // malformed, rather than incomplete.
     let limit = remaining - len as usize;
     while buf.remaining() > limit {
-        merge(value, buf)?;
+        merge(value, buf).map_err(DecodeError::into_malformed)?;
     }
 
     if buf.remaining() != limit {
@@ -289,101 +705,566 @@ where
     Ok(())
 }
 
-pub fn skip_field<B>(wire_type: WireType, buf: &mut B) -> Result<(), DecodeError>
+/// Skips the value of a field whose key, `tag` and `wire_type`, has already been read from `buf`.
+///
+/// A group is skipped up to and including its matching end group key. `depth` is the number of
+/// groups the field is nested in, which may not exceed `RECURSION_LIMIT`.
+pub fn skip_field<B>(
+    wire_type: WireType,
+    tag: u32,
+    buf: &mut B,
+    depth: u32,
+) -> Result<(), DecodeError>
 where
     B: Buf,
 {
+    if depth > RECURSION_LIMIT {
+        return Err(DecodeError::new("recursion limit reached"));
+    }
+
     let len = match wire_type {
         WireType::Varint => decode_varint(buf).map(|_| 0)?,
         WireType::ThirtyTwoBit => 4,
         WireType::SixtyFourBit => 8,
         WireType::LengthDelimited => decode_varint(buf)?,
+        WireType::StartGroup => loop {
+            let (inner_tag, inner_wire_type) = decode_key(buf)?;
+            if inner_wire_type == WireType::EndGroup {
+                if inner_tag != tag {
+                    return Err(DecodeError::new("unexpected end group tag"));
+                }
+                break 0;
+            }
+            skip_field(inner_wire_type, inner_tag, buf, depth + 1)?;
+        },
+        WireType::EndGroup => return Err(DecodeError::new("unexpected end group tag")),
     };
 
     if len > buf.remaining() as u64 {
-        return Err(DecodeError::new("buffer underflow"));
+        return Err(underflow(len, buf.remaining()));
     }
 
     buf.advance(len as usize);
     Ok(())
 }
 
-/// Helper macro which emits an `encode_repeated` function for the type.
-macro_rules! encode_repeated {
-    ($ty:ty) => {
-        pub fn encode_repeated<B>(tag: u32, values: &[$ty], buf: &mut B)
-        where
-            B: BufMut,
-        {
-            for value in values {
-                encode(tag, value, buf);
-            }
+/// Copies the value of a field whose key, `tag` and `wire_type`, has already been read from `buf`,
+/// like `skip_field`.
+pub(crate) fn copy_field<B>(
+    wire_type: WireType,
+    tag: u32,
+    buf: &mut B,
+    out: &mut Vec<u8>,
+    depth: u32,
+) -> Result<(), DecodeError>
+where
+    B: Buf,
+{
+    if depth > RECURSION_LIMIT {
+        return Err(DecodeError::new("recursion limit reached"));
+    }
+
+    let len = match wire_type {
+        WireType::Varint => {
+            encode_varint(decode_varint(buf)?, out);
+            0
+        }
+        WireType::ThirtyTwoBit => 4,
+        WireType::SixtyFourBit => 8,
+        WireType::LengthDelimited => {
+            let len = decode_varint(buf)?;
+            encode_varint(len, out);
+            len
         }
+        WireType::StartGroup => loop {
+            let (inner_tag, inner_wire_type) = decode_key(buf)?;
+            encode_key(inner_tag, inner_wire_type, out);
+            if inner_wire_type == WireType::EndGroup {
+                if inner_tag != tag {
+                    return Err(DecodeError::new("unexpected end group tag"));
+                }
+                break 0;
+            }
+            copy_field(inner_wire_type, inner_tag, buf, out, depth + 1)?;
+        },
+        WireType::EndGroup => return Err(DecodeError::new("unexpected end group tag")),
     };
+
+    if len > buf.remaining() as u64 {
+        return Err(underflow(len, buf.remaining()));
+    }
+
+    out.put((&mut *buf).take(len as usize));
+    Ok(())
 }
 
-/// Helper macro which emits a `merge_repeated_numeric` function for the numeric type.
-macro_rules! merge_repeated_numeric {
-    ($ty:ty,
-     $wire_type:expr,
-     $merge:ident,
-     $merge_repeated:ident) => {
-        pub fn $merge_repeated<B>(
-            wire_type: WireType,
-            values: &mut Vec<$ty>,
-            buf: &mut B,
-        ) -> Result<(), DecodeError>
-        where
-            B: Buf,
-        {
-            if wire_type == WireType::LengthDelimited {
-                // Packed.
-                merge_loop(values, buf, |values, buf| {
-                    let mut value = Default::default();
-                    $merge($wire_type, &mut value, buf)?;
-                    values.push(value);
-                    Ok(())
-                })
-            } else {
-                // Unpacked.
-                check_wire_type($wire_type, wire_type)?;
-                let mut value = Default::default();
-                $merge(wire_type, &mut value, buf)?;
-                values.push(value);
-                Ok(())
+/// Decodes the fields of a message without its type, returning the tag, wire type and raw value
+/// of each field in the order in which they appear.
+///
+/// The raw value of a varint field is its encoded varint, that of a fixed-width field its four or
+/// eight bytes, that of a length-delimited field its bytes without the length delimiter, and that
+/// of a group the encoded fields between its start and end group keys. Fields which appear more
+/// than once, like the elements of an unpacked repeated field, are returned once per appearance.
+///
+/// The amino prefix of a registered message is not a field, and must be stripped first.
+pub fn decode_raw<B>(mut buf: B) -> Result<Vec<(u32, WireType, Vec<u8>)>, DecodeError>
+where
+    B: Buf,
+{
+    let mut fields = Vec::new();
+    while buf.has_remaining() {
+        let (tag, wire_type) = decode_key(&mut buf)?;
+        let mut value = Vec::new();
+        match wire_type {
+            WireType::LengthDelimited => {
+                let len = decode_varint(&mut buf)?;
+                if len > buf.remaining() as u64 {
+                    return Err(underflow(len, buf.remaining()));
+                }
+                value.put((&mut buf).take(len as usize));
             }
+            WireType::StartGroup => {
+                copy_field(wire_type, tag, &mut buf, &mut value, 0)?;
+                let end = value.len() - key_len(tag);
+                value.truncate(end);
+            }
+            _ => copy_field(wire_type, tag, &mut buf, &mut value, 0)?,
         }
-    };
+        fields.push((tag, wire_type, value));
+    }
+    Ok(fields)
 }
 
-/// Macro which emits a module containing a set of encoding functions for a
-/// variable width numeric type.
-macro_rules! varint {
-    ($ty:ty,
-     $proto_ty:ident) => (
-        varint!($ty,
-                $proto_ty,
-                to_uint64(value) { *value as u64 },
-                from_uint64(value) { value as $ty });
-    );
+/// Merges a field whose key, `tag` and `wire_type`, has already been read from `buf` into a
+/// message, which decodes the key itself, e.g. a flattened message field.
+pub fn merge_field_with_key<M, B>(
+    tag: u32,
+    wire_type: WireType,
+    msg: &mut M,
+    buf: &mut B,
+) -> Result<(), DecodeError>
+where
+    M: Message,
+    B: Buf,
+{
+    // The key is put back in front of the value.
+    let mut key = Vec::with_capacity(5);
+    encode_key(tag, wire_type, &mut key);
+    msg.merge_field(&mut (&key[..]).chain(buf))
+}
 
-    ($ty:ty,
-     $proto_ty:ident,
-     to_uint64($to_uint64_value:ident) $to_uint64:expr,
-     from_uint64($from_uint64_value:ident) $from_uint64:expr) => (
+/// The tags of the fields of a message type, which are known when it is compiled.
+///
+/// Implemented by `#[derive(Message)]`, so that the tags of a flattened message field can be
+/// checked against those of the enclosing message, and its fields encoded among those of the
+/// enclosing message in tag order.
+pub trait FieldTags {
+    /// The tags of the fields of the message, including those of its flattened fields, in
+    /// ascending order.
+    const FIELD_TAGS: &'static [u32];
+
+    /// Whether the message is registered, i.e. has an amino prefix. A registered message can not
+    /// be flattened, since its fields follow its prefix.
+    const REGISTERED: bool;
+
+    /// Encodes the field with the given tag, including a field of a flattened message, if it is
+    /// set. A oneof is encoded for its lowest tag.
+    fn encode_field<B>(&self, tag: u32, buf: &mut B)
+    where
+        B: BufMut;
+}
 
-         pub mod $proto_ty {
-            use ::encoding::*;
+impl<M> FieldTags for Box<M>
+where
+    M: FieldTags,
+{
+    const FIELD_TAGS: &'static [u32] = M::FIELD_TAGS;
 
-            pub fn encode<B>(tag: u32, $to_uint64_value: &$ty, buf: &mut B) where B: BufMut {
-                encode_key(tag, WireType::Varint, buf);
-                encode_varint($to_uint64, buf);
-            }
+    const REGISTERED: bool = M::REGISTERED;
 
-            #[allow(unused_variables)]
-            pub fn encode_with_prefix<B>(_tag: u32, $to_uint64_value: &$ty, _amino_pre: &Vec<u8>, _buf: &mut B) where B: BufMut {
-                panic!("amino prefix not implemented for type");
-            }
+    fn encode_field<B>(&self, tag: u32, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        (**self).encode_field(tag, buf)
+    }
+}
+
+/// Compares messages while skipping the fields with the given tags.
+///
+/// Implemented by `#[derive(Message)]` for messages whose fields are `PartialEq`, by calling their
+/// `eq_ignoring` method, so that the `eq_ignoring` of an enclosing message can compare the fields
+/// of a flattened message field.
+pub trait EqIgnoring {
+    /// Returns `true` if the message is equal to `other`, ignoring the fields with any of the
+    /// `ignored_tags`.
+    fn eq_ignoring(&self, other: &Self, ignored_tags: &[u32]) -> bool;
+}
+
+impl<M> EqIgnoring for Box<M>
+where
+    M: EqIgnoring,
+{
+    fn eq_ignoring(&self, other: &Box<M>, ignored_tags: &[u32]) -> bool {
+        (**self).eq_ignoring(other, ignored_tags)
+    }
+}
+
+/// Returns the tags of a message followed by those of its flattened fields in ascending order,
+/// as the `FIELD_TAGS` of a message with flattened fields. `N` is the total number of tags.
+pub const fn flattened_tags<const N: usize>(own: &[u32], flattened: &[&[u32]]) -> [u32; N] {
+    let mut tags = [0; N];
+    let mut len = 0;
+    let mut i = 0;
+    while i < own.len() {
+        tags[len] = own[i];
+        len += 1;
+        i += 1;
+    }
+    let mut i = 0;
+    while i < flattened.len() {
+        let mut j = 0;
+        while j < flattened[i].len() {
+            tags[len] = flattened[i][j];
+            len += 1;
+            j += 1;
+        }
+        i += 1;
+    }
+    // An insertion sort, which is enough for the few tags of a message.
+    let mut i = 1;
+    while i < len {
+        let mut j = i;
+        while j > 0 && tags[j - 1] > tags[j] {
+            let tag = tags[j];
+            tags[j] = tags[j - 1];
+            tags[j - 1] = tag;
+            j -= 1;
+        }
+        i += 1;
+    }
+    tags
+}
+
+/// Returns `true` if a tag is in both `a` and `b`.
+pub const fn tags_overlap(a: &[u32], b: &[u32]) -> bool {
+    let mut i = 0;
+    while i < a.len() {
+        let mut j = 0;
+        while j < b.len() {
+            if a[i] == b[j] {
+                return true;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    false
+}
+
+/// An iterator over the values of a single repeated scalar field of an encoded message, which
+/// decodes each value as it is reached instead of collecting them all into a `Vec`.
+///
+/// Both packed and unpacked values are yielded, and the fields with other tags are skipped. After
+/// the first error, e.g. a value truncated by the end of the buffer, the iterator is exhausted.
+///
+/// Meant to be used only by generated `decode_<field>_stream` methods.
+pub struct RepeatedStream<T, B> {
+    tag: u32,
+    wire_type: WireType,
+    merge: fn(WireType, &mut T, &mut B) -> Result<(), DecodeError>,
+    buf: B,
+    /// The number of bytes left in the packed values currently being decoded.
+    packed_len: Option<u64>,
+    index: usize,
+    done: bool,
+}
+
+impl<T, B> RepeatedStream<T, B>
+where
+    T: Default,
+    B: Buf,
+{
+    /// Creates a stream over the values with the given tag, which are decoded with `merge` and
+    /// encoded with `wire_type` when unpacked.
+    pub fn new(
+        tag: u32,
+        wire_type: WireType,
+        merge: fn(WireType, &mut T, &mut B) -> Result<(), DecodeError>,
+        buf: B,
+    ) -> RepeatedStream<T, B> {
+        RepeatedStream {
+            tag,
+            wire_type,
+            merge,
+            buf,
+            packed_len: None,
+            index: 0,
+            done: false,
+        }
+    }
+
+    fn next_value(&mut self) -> Result<Option<T>, DecodeError> {
+        loop {
+            // The packed values are not checked against the length of the buffer up front, so
+            // that the values before a truncation are still yielded.
+            if let Some(len) = self.packed_len {
+                if len > 0 {
+                    let remaining = self.buf.remaining();
+                    if remaining == 0 {
+                        return Err(DecodeError::incomplete("buffer underflow", None));
+                    }
+                    let mut value = T::default();
+                    (self.merge)(self.wire_type, &mut value, &mut self.buf)?;
+                    let read = (remaining - self.buf.remaining()) as u64;
+                    if read > len {
+                        return Err(DecodeError::new("delimited length exceeded"));
+                    }
+                    self.packed_len = Some(len - read);
+                    return Ok(Some(value));
+                }
+                self.packed_len = None;
+            }
+
+            if !self.buf.has_remaining() {
+                return Ok(None);
+            }
+            let (tag, wire_type) = decode_key(&mut self.buf)?;
+            if tag != self.tag {
+                skip_field(wire_type, tag, &mut self.buf, 0)?;
+            } else if wire_type == WireType::LengthDelimited
+                && self.wire_type != WireType::LengthDelimited
+            {
+                self.packed_len = Some(decode_varint(&mut self.buf)?);
+            } else {
+                let mut value = T::default();
+                (self.merge)(wire_type, &mut value, &mut self.buf)?;
+                return Ok(Some(value));
+            }
+        }
+    }
+}
+
+impl<T, B> Iterator for RepeatedStream<T, B>
+where
+    T: Default,
+    B: Buf,
+{
+    type Item = Result<T, DecodeError>;
+
+    fn next(&mut self) -> Option<Result<T, DecodeError>> {
+        if self.done {
+            return None;
+        }
+        match self.next_value() {
+            Ok(Some(value)) => {
+                self.index += 1;
+                Some(Ok(value))
+            }
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(mut error) => {
+                self.done = true;
+                error.push_index(self.index);
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+/// Helper macro which emits an `encode_repeated` function for the type.
+macro_rules! encode_repeated {
+    ($ty:ty) => {
+        pub fn encode_repeated<B>(tag: u32, values: &[$ty], buf: &mut B)
+        where
+            B: BufMut,
+        {
+            for value in values {
+                encode(tag, value, buf);
+            }
+        }
+    };
+}
+
+/// Helper macro which emits a `merge_repeated_numeric` function for the numeric type.
+macro_rules! merge_repeated_numeric {
+    ($ty:ty,
+     $wire_type:expr,
+     $merge:ident,
+     $merge_repeated:ident) => {
+        pub fn $merge_repeated<B>(
+            wire_type: WireType,
+            values: &mut Vec<$ty>,
+            buf: &mut B,
+        ) -> Result<(), DecodeError>
+        where
+            B: Buf,
+        {
+            if wire_type == WireType::LengthDelimited {
+                // Packed.
+                merge_loop(values, buf, |values, buf| {
+                    let mut value = Default::default();
+                    $merge($wire_type, &mut value, buf).map_err(|mut error| {
+                        error.push_index(values.len());
+                        error
+                    })?;
+                    values.push(value);
+                    Ok(())
+                })
+            } else {
+                // Unpacked.
+                check_wire_type($wire_type, wire_type)?;
+                let mut value = Default::default();
+                $merge(wire_type, &mut value, buf).map_err(|mut error| {
+                    error.push_index(values.len());
+                    error
+                })?;
+                values.push(value);
+                Ok(())
+            }
+        }
+    };
+}
+
+/// Helper macro which emits delta encoding functions for a zigzag encoded signed type. Each
+/// value is stored as the difference from the previous one, which keeps sorted sequences small.
+macro_rules! delta {
+    ($ty:ty,
+     $to_uint64_value:ident $to_uint64:expr,
+     $from_uint64_value:ident $from_uint64:expr) => {
+        /// Encodes the values as a packed field of zigzag encoded deltas. The first value is
+        /// stored relative to zero.
+        pub fn encode_delta<B>(tag: u32, values: &[$ty], buf: &mut B)
+        where
+            B: BufMut,
+        {
+            if values.is_empty() {
+                return;
+            }
+
+            encode_key(tag, WireType::LengthDelimited, buf);
+            encode_varint(delta_len(values) as u64, buf);
+
+            let mut previous: $ty = 0;
+            for value in values {
+                let $to_uint64_value = &value.wrapping_sub(previous);
+                encode_varint($to_uint64, buf);
+                previous = *value;
+            }
+        }
+
+        /// Merges a packed field of deltas, appending the reconstructed values.
+        pub fn merge_delta<B>(
+            wire_type: WireType,
+            values: &mut Vec<$ty>,
+            buf: &mut B,
+        ) -> Result<(), DecodeError>
+        where
+            B: Buf,
+        {
+            check_wire_type(WireType::LengthDelimited, wire_type)?;
+            let mut previous: $ty = 0;
+            merge_loop(values, buf, |values, buf| {
+                let $from_uint64_value = decode_varint(buf).map_err(|mut error| {
+                    error.push_index(values.len());
+                    error
+                })?;
+                let delta: $ty = $from_uint64;
+                previous = previous.wrapping_add(delta);
+                values.push(previous);
+                Ok(())
+            })
+        }
+
+        #[inline]
+        pub fn encoded_len_delta(tag: u32, values: &[$ty]) -> usize {
+            if values.is_empty() {
+                0
+            } else {
+                let len = delta_len(values);
+                key_len(tag) + encoded_len_varint(len as u64) + len
+            }
+        }
+
+        fn delta_len(values: &[$ty]) -> usize {
+            let mut previous: $ty = 0;
+            values
+                .iter()
+                .map(|value| {
+                    let $to_uint64_value = &value.wrapping_sub(previous);
+                    previous = *value;
+                    encoded_len_varint($to_uint64)
+                })
+                .sum()
+        }
+
+        #[cfg(test)]
+        mod delta_test {
+            use quickcheck::TestResult;
+
+            use super::*;
+            use encoding::test::check_type;
+
+            quickcheck! {
+                fn check_delta(value: Vec<$ty>, tag: u32) -> TestResult {
+                    check_type(value, tag, WireType::LengthDelimited,
+                               encode_delta, merge_delta,
+                               encoded_len_delta)
+                }
+            }
+        }
+    };
+}
+
+/// Macro which emits a module containing a set of encoding functions for a
+/// variable width numeric type.
+macro_rules! varint {
+    ($ty:ty,
+     $proto_ty:ident) => (
+        varint!($ty,
+                $proto_ty,
+                to_uint64(value) { *value as u64 },
+                from_uint64(value) { value as $ty });
+    );
+
+    ($ty:ty,
+     $proto_ty:ident,
+     to_uint64($to_uint64_value:ident) $to_uint64:expr,
+     from_uint64($from_uint64_value:ident) $from_uint64:expr
+     $(, $delta:ident)*) => (
+
+         pub mod $proto_ty {
+            use ::encoding::*;
+
+            $(
+                $delta!($ty,
+                        $to_uint64_value $to_uint64,
+                        $from_uint64_value $from_uint64);
+            )*
+
+            pub fn encode<B>(tag: u32, $to_uint64_value: &$ty, buf: &mut B) where B: BufMut {
+                encode_key(tag, WireType::Varint, buf);
+                encode_varint($to_uint64, buf);
+            }
+
+            #[allow(unused_variables)]
+            pub fn encode_with_prefix<B>(_tag: u32, $to_uint64_value: &$ty, _amino_pre: &Vec<u8>, _buf: &mut B) where B: BufMut {
+                panic!("amino prefix not implemented for type");
+            }
+
+            /// Encodes the value padded to `width` bytes, see `encode_varint_padded`.
+            pub fn encode_padded<B>(tag: u32, $to_uint64_value: &$ty, width: usize, buf: &mut B) where B: BufMut {
+                encode_key(tag, WireType::Varint, buf);
+                encode_varint_padded($to_uint64, width, buf);
+            }
+
+            /// Encodes the value with the custom varint codec `C`.
+            pub fn encode_with_codec<C, B>(tag: u32, $to_uint64_value: &$ty, buf: &mut B) where C: VarintCodec, B: BufMut {
+                encode_key(tag, WireType::Varint, buf);
+                C::encode($to_uint64, buf);
+            }
 
             pub fn merge<B>(wire_type: WireType, value: &mut $ty, buf: &mut B) -> Result<(), DecodeError> where B: Buf {
                 check_wire_type(WireType::Varint, wire_type)?;
@@ -392,6 +1273,14 @@ macro_rules! varint {
                 Ok(())
             }
 
+            /// Merges a value encoded with the custom varint codec `C`.
+            pub fn merge_with_codec<C, B>(wire_type: WireType, value: &mut $ty, buf: &mut B) -> Result<(), DecodeError> where C: VarintCodec, B: Buf {
+                check_wire_type(WireType::Varint, wire_type)?;
+                let $from_uint64_value = C::decode(buf)?;
+                *value = $from_uint64;
+                Ok(())
+            }
+
             encode_repeated!($ty);
 
             pub fn encode_packed<B>(tag: u32, values: &[$ty], buf: &mut B) where B: BufMut {
@@ -415,6 +1304,16 @@ macro_rules! varint {
                 key_len(tag) + encoded_len_varint($to_uint64)
             }
 
+            #[inline]
+            pub fn encoded_len_with_codec<C>(tag: u32, $to_uint64_value: &$ty) -> usize where C: VarintCodec {
+                key_len(tag) + C::encoded_len($to_uint64)
+            }
+
+            #[inline]
+            pub fn encoded_len_padded(tag: u32, $to_uint64_value: &$ty, width: usize) -> usize {
+                key_len(tag) + max(width, encoded_len_varint($to_uint64))
+            }
+
             #[inline]
             pub fn encoded_len_repeated(tag: u32, values: &[$ty]) -> usize {
                 key_len(tag) * values.len() + values.iter().map(|$to_uint64_value| {
@@ -441,14 +1340,27 @@ macro_rules! varint {
                 use ::encoding::$proto_ty::*;
                 use ::encoding::test::{
                     check_collection_type,
+                    check_edges,
                     check_type,
+                    Full,
                 };
 
+                #[test]
+                fn check_edge_values() {
+                    check_edges(|value: $ty| {
+                        check_type(value, 1, WireType::Varint, encode, merge, encoded_len)
+                    });
+                }
+
                 quickcheck! {
                     fn check(value: $ty, tag: u32) -> TestResult {
                         check_type(value, tag, WireType::Varint,
                                    encode, merge, encoded_len)
                     }
+                    fn check_full(value: Full<$ty>, tag: u32) -> TestResult {
+                        check_type(value.0, tag, WireType::Varint,
+                                   encode, merge, encoded_len)
+                    }
                     fn check_repeated(value: Vec<$ty>, tag: u32) -> TestResult {
                         check_collection_type(value, tag, WireType::Varint,
                                               encode_repeated, merge_repeated,
@@ -479,14 +1391,65 @@ varint!(i32, sint32,
         from_uint64(value) {
             let value = value as u32;
             ((value >> 1) as i32) ^ (-((value & 1) as i32))
-        });
+        },
+        delta);
 varint!(i64, sint64,
         to_uint64(value) {
             ((value << 1) ^ (value >> 63)) as u64
         },
         from_uint64(value) {
             ((value >> 1) as i64) ^ (-((value & 1) as i64))
-        });
+        },
+        delta);
+
+/// Whether the `canonical` feature is enabled, in which case equal messages have equal
+/// encodings.
+pub const CANONICAL: bool = cfg!(feature = "canonical");
+
+/// The bits of the NaN written for every `float` value in canonical mode, matching
+/// `float32(math.NaN())` in Go.
+pub const CANONICAL_NAN_F32: u32 = 0x7FC0_0000;
+/// The bits of the NaN written for every `double` value in canonical mode, matching
+/// `math.NaN()` in Go.
+pub const CANONICAL_NAN_F64: u64 = 0x7FF8_0000_0000_0001;
+
+/// Normalizes a fixed width value before it is encoded. With the `canonical` feature enabled,
+/// every NaN is encoded with the same bits, so that equal messages have equal encodings, and
+/// `encode_normalized` writes `-0.0` as `0.0`.
+trait Canonicalize: Copy {
+    fn canonicalize(&self) -> Self {
+        *self
+    }
+}
+
+impl Canonicalize for f32 {
+    fn canonicalize(&self) -> f32 {
+        if CANONICAL && self.is_nan() {
+            f32::from_bits(CANONICAL_NAN_F32)
+        } else if *self == 0.0 && normalized() {
+            0.0
+        } else {
+            *self
+        }
+    }
+}
+
+impl Canonicalize for f64 {
+    fn canonicalize(&self) -> f64 {
+        if CANONICAL && self.is_nan() {
+            f64::from_bits(CANONICAL_NAN_F64)
+        } else if *self == 0.0 && normalized() {
+            0.0
+        } else {
+            *self
+        }
+    }
+}
+
+impl Canonicalize for u32 {}
+impl Canonicalize for u64 {}
+impl Canonicalize for i32 {}
+impl Canonicalize for i64 {}
 
 /// Macro which emits a module containing a set of encoding functions for a
 /// fixed width numeric type.
@@ -505,7 +1468,7 @@ macro_rules! fixed_width {
                 B: BufMut,
             {
                 encode_key(tag, $wire_type, buf);
-                buf.$put(*value);
+                buf.$put(value.canonicalize());
             }
 
             pub fn encode_with_prefix<B>(
@@ -529,7 +1492,7 @@ macro_rules! fixed_width {
             {
                 check_wire_type($wire_type, wire_type)?;
                 if buf.remaining() < $width {
-                    return Err(DecodeError::new("buffer underflow"));
+                    return Err(underflow($width, buf.remaining()));
                 }
                 *value = buf.$get();
                 Ok(())
@@ -550,11 +1513,48 @@ macro_rules! fixed_width {
                 encode_varint(len as u64, buf);
 
                 for value in values {
-                    buf.$put(*value);
+                    buf.$put(value.canonicalize());
                 }
             }
 
-            merge_repeated_numeric!($ty, $wire_type, merge, merge_repeated);
+            pub fn merge_repeated<B>(
+                wire_type: WireType,
+                values: &mut Vec<$ty>,
+                buf: &mut B,
+            ) -> Result<(), DecodeError>
+            where
+                B: Buf,
+            {
+                if wire_type != WireType::LengthDelimited {
+                    // Unpacked.
+                    check_wire_type($wire_type, wire_type)?;
+                    let mut value = Default::default();
+                    merge(wire_type, &mut value, buf).map_err(|mut error| {
+                        error.push_index(values.len());
+                        error
+                    })?;
+                    values.push(value);
+                    return Ok(());
+                }
+
+                // Packed, so the length must be an exact multiple of the width.
+                let len = decode_varint(buf)?;
+                if len % $width != 0 {
+                    return Err(DecodeError::new(format!(
+                        "packed length {} is not a multiple of {}",
+                        len, $width
+                    )));
+                }
+                if len > buf.remaining() as u64 {
+                    return Err(underflow(len, buf.remaining()));
+                }
+                let count = (len / $width) as usize;
+                values.reserve(count);
+                for _ in 0..count {
+                    values.push(buf.$get());
+                }
+                Ok(())
+            }
 
             #[inline]
             pub fn encoded_len(tag: u32, _: &$ty) -> usize {
@@ -580,14 +1580,25 @@ macro_rules! fixed_width {
             mod test {
                 use quickcheck::TestResult;
 
-                use super::super::test::{check_collection_type, check_type};
+                use super::super::test::{check_collection_type, check_edges, check_type, Full};
                 use super::*;
 
+                #[test]
+                fn check_edge_values() {
+                    check_edges(|value: $ty| {
+                        check_type(value, 1, $wire_type, encode, merge, encoded_len)
+                    });
+                }
+
                 quickcheck! {
                     fn check(value: $ty, tag: u32) -> TestResult {
                         check_type(value, tag, $wire_type,
                                    encode, merge, encoded_len)
                     }
+                    fn check_full(value: Full<$ty>, tag: u32) -> TestResult {
+                        check_type(value.0, tag, $wire_type,
+                                   encode, merge, encoded_len)
+                    }
                     fn check_repeated(value: Vec<$ty>, tag: u32) -> TestResult {
                         check_collection_type(value, tag, $wire_type,
                                               encode_repeated, merge_repeated,
@@ -652,6 +1663,28 @@ fixed_width!(
     get_i64_le
 );
 
+// Big-endian variants of the fixed width types, for fields with the `endian = "big"` attribute.
+fixed_width!(f32, 4, WireType::ThirtyTwoBit, float_be, put_f32, get_f32);
+fixed_width!(f64, 8, WireType::SixtyFourBit, double_be, put_f64, get_f64);
+fixed_width!(u32, 4, WireType::ThirtyTwoBit, fixed32_be, put_u32, get_u32);
+fixed_width!(u64, 8, WireType::SixtyFourBit, fixed64_be, put_u64, get_u64);
+fixed_width!(
+    i32,
+    4,
+    WireType::ThirtyTwoBit,
+    sfixed32_be,
+    put_i32,
+    get_i32
+);
+fixed_width!(
+    i64,
+    8,
+    WireType::SixtyFourBit,
+    sfixed64_be,
+    put_i64,
+    get_i64
+);
+
 /// Macro which emits encoding functions for a length-delimited type.
 macro_rules! length_delimited {
     ($ty:ty) => {
@@ -667,7 +1700,10 @@ macro_rules! length_delimited {
         {
             check_wire_type(WireType::LengthDelimited, wire_type)?;
             let mut value = Default::default();
-            merge(wire_type, &mut value, buf)?;
+            merge(wire_type, &mut value, buf).map_err(|mut error| {
+                error.push_index(values.len());
+                error
+            })?;
             values.push(value);
             Ok(())
         }
@@ -690,14 +1726,32 @@ macro_rules! length_delimited {
         mod test {
             use quickcheck::TestResult;
 
-            use super::super::test::{check_collection_type, check_type};
+            use super::super::test::{check_collection_type, check_edges, check_type, Full};
             use super::*;
 
+            #[test]
+            fn check_edge_values() {
+                check_edges(|value: $ty| {
+                    check_type(
+                        value,
+                        1,
+                        WireType::LengthDelimited,
+                        encode,
+                        merge,
+                        encoded_len,
+                    )
+                });
+            }
+
             quickcheck! {
                 fn check(value: $ty, tag: u32) -> TestResult {
                     super::test::check_type(value, tag, WireType::LengthDelimited,
                                             encode, merge, encoded_len)
                 }
+                fn check_full(value: Full<$ty>, tag: u32) -> TestResult {
+                    check_type(value.0, tag, WireType::LengthDelimited,
+                               encode, merge, encoded_len)
+                }
                 fn check_repeated(value: Vec<$ty>, tag: u32) -> TestResult {
                     super::test::check_collection_type(value, tag, WireType::LengthDelimited,
                                                        encode_repeated, merge_repeated,
@@ -711,190 +1765,1359 @@ macro_rules! length_delimited {
 pub mod string {
     use super::*;
 
-    pub fn encode<B>(tag: u32, value: &String, buf: &mut B)
+    pub fn encode<B>(tag: u32, value: &String, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        encode_key(tag, WireType::LengthDelimited, buf);
+        encode_varint(value.len() as u64, buf);
+        buf.put_slice(value.as_bytes());
+    }
+
+    pub fn encode_with_prefix<B>(_tag: u32, _value: &String, _amino_prefix: Vec<u8>, _buf: &mut B)
+    where
+        B: BufMut,
+    {
+        panic!("amino prefix not implemented for type");
+    }
+
+    pub fn merge<B>(wire_type: WireType, value: &mut String, buf: &mut B) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        unsafe {
+            // String::as_mut_vec is unsafe because it doesn't check that the bytes
+            // inserted into it the resulting vec are valid UTF-8. We check
+            // explicitly in order to ensure this is safe.
+            super::bytes::merge(wire_type, value.as_mut_vec(), buf)?;
+            if let Err(error) = str::from_utf8(value.as_bytes()) {
+                value.as_mut_vec().clear();
+                return Err(invalid_utf8(error));
+            }
+        }
+        Ok(())
+    }
+
+    /// Like `merge`, but fails before copying the value if its length exceeds `max_len` bytes.
+    pub fn merge_max_len<B>(
+        wire_type: WireType,
+        value: &mut String,
+        max_len: usize,
+        buf: &mut B,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        unsafe {
+            // See `merge` for why this is safe.
+            super::bytes::merge_max_len(wire_type, value.as_mut_vec(), max_len, buf)?;
+            if let Err(error) = str::from_utf8(value.as_bytes()) {
+                value.as_mut_vec().clear();
+                return Err(invalid_utf8(error));
+            }
+        }
+        Ok(())
+    }
+
+    /// Like `merge`, but fails unless every byte of the value is ASCII, which is cheaper to check
+    /// than UTF-8.
+    pub fn merge_ascii<B>(
+        wire_type: WireType,
+        value: &mut String,
+        buf: &mut B,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        unsafe {
+            // An ASCII value is valid UTF-8, so this is safe for the same reason as `merge`.
+            super::bytes::merge(wire_type, value.as_mut_vec(), buf)?;
+            if let Some(offset) = value.bytes().position(|byte| !byte.is_ascii()) {
+                value.as_mut_vec().clear();
+                return Err(DecodeError::not_ascii(offset));
+            }
+        }
+        Ok(())
+    }
+
+    /// Like `merge_repeated`, but fails unless every byte of the value is ASCII.
+    pub fn merge_repeated_ascii<B>(
+        wire_type: WireType,
+        values: &mut Vec<String>,
+        buf: &mut B,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let mut value = String::new();
+        merge_ascii(wire_type, &mut value, buf).map_err(|mut error| {
+            error.push_index(values.len());
+            error
+        })?;
+        values.push(value);
+        Ok(())
+    }
+
+    /// Like `merge`, but strips leading and trailing whitespace from the decoded value, which then
+    /// differs from the encoded value.
+    pub fn merge_trimmed<B>(
+        wire_type: WireType,
+        value: &mut String,
+        buf: &mut B,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        merge(wire_type, value, buf)?;
+        let end = value.trim_end().len();
+        value.truncate(end);
+        let start = value.len() - value.trim_start().len();
+        value.drain(..start);
+        Ok(())
+    }
+
+    /// Returns an error reporting the offset of the first invalid byte sequence in a string.
+    pub(super) fn invalid_utf8(error: str::Utf8Error) -> DecodeError {
+        DecodeError::not_utf8(error.valid_up_to())
+    }
+
+    length_delimited!(String);
+}
+
+pub mod bytes {
+    use super::*;
+
+    pub fn encode<B>(tag: u32, value: &Vec<u8>, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        encode_key(tag, WireType::LengthDelimited, buf);
+        encode_varint(value.len() as u64, buf);
+        buf.put_slice(value);
+    }
+
+    pub fn encode_with_prefix<B>(tag: u32, value: &Vec<u8>, amino_prefix: &Vec<u8>, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        encode_key(tag, WireType::LengthDelimited, buf);
+        // length delim: the prefix bytes + the length delimited actual bytes.
+        encode_varint(prefixed_len(value, amino_prefix) as u64, buf);
+        buf.put_slice(amino_prefix);
+        encode_varint((value.len()) as u64, buf);
+        buf.put_slice(value);
+    }
+
+    /// Returns the encoded length of a field written by `encode_with_prefix`.
+    #[inline]
+    pub fn encoded_len_with_prefix(tag: u32, value: &Vec<u8>, amino_prefix: &Vec<u8>) -> usize {
+        let len = prefixed_len(value, amino_prefix);
+        key_len(tag) + encoded_len_varint(len as u64) + len
+    }
+
+    /// Returns the length of the prefix followed by the length delimited value.
+    #[inline]
+    fn prefixed_len(value: &Vec<u8>, amino_prefix: &Vec<u8>) -> usize {
+        amino_prefix.len() + encoded_len_varint(value.len() as u64) + value.len()
+    }
+
+    pub fn merge<B>(
+        wire_type: WireType,
+        value: &mut Vec<u8>,
+        buf: &mut B,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        check_wire_type(WireType::LengthDelimited, wire_type)?;
+        let len = decode_varint(buf)?;
+        merge_len(len, value, buf)
+    }
+
+    /// Like `merge`, but fails before copying the value if its length exceeds `max_len` bytes.
+    pub fn merge_max_len<B>(
+        wire_type: WireType,
+        value: &mut Vec<u8>,
+        max_len: usize,
+        buf: &mut B,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        check_wire_type(WireType::LengthDelimited, wire_type)?;
+        let len = decode_varint(buf)?;
+        if len > max_len as u64 {
+            return Err(DecodeError::too_long(len, max_len));
+        }
+        merge_len(len, value, buf)
+    }
+
+    /// Like `merge`, but also accepts a `fixed32` or `fixed64` encoding of the value, which is
+    /// decoded as its four or eight bytes in wire order.
+    pub fn merge_fixed_or_var<B>(
+        wire_type: WireType,
+        value: &mut Vec<u8>,
+        buf: &mut B,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        match wire_type {
+            WireType::ThirtyTwoBit => merge_len(4, value, buf),
+            WireType::SixtyFourBit => merge_len(8, value, buf),
+            _ => merge(wire_type, value, buf),
+        }
+    }
+
+    /// Like `merge_repeated`, but also accepts values with a `fixed32` or `fixed64` encoding, as
+    /// by `merge_fixed_or_var`.
+    pub fn merge_repeated_fixed_or_var<B>(
+        wire_type: WireType,
+        values: &mut Vec<Vec<u8>>,
+        buf: &mut B,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let mut value = Vec::new();
+        merge_fixed_or_var(wire_type, &mut value, buf).map_err(|mut error| {
+            error.push_index(values.len());
+            error
+        })?;
+        values.push(value);
+        Ok(())
+    }
+
+    /// Replaces the value with the next `len` bytes of the buffer, so that a decoded value
+    /// never extends a non-empty default.
+    fn merge_len<B>(len: u64, value: &mut Vec<u8>, buf: &mut B) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        if len > buf.remaining() as u64 {
+            return Err(underflow(len, buf.remaining()));
+        }
+
+        value.clear();
+
+        let mut remaining = len as usize;
+        while remaining > 0 {
+            let len = {
+                let bytes = buf.bytes();
+                debug_assert!(!bytes.is_empty(), "Buf::bytes returned empty slice");
+                let len = min(remaining, bytes.len());
+                let bytes = &bytes[..len];
+                value.extend_from_slice(bytes);
+                len
+            };
+            remaining -= len;
+            buf.advance(len);
+        }
+        Ok(())
+    }
+
+    pub fn merge_with_prefix<B>(
+        wire_type: WireType,
+        value: &mut Vec<u8>,
+        amino_prefix: &Vec<u8>,
+        buf: &mut B,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        check_wire_type(WireType::LengthDelimited, wire_type)?;
+        let len = decode_varint(buf)?;
+        let remaining = buf.remaining();
+        if len > remaining as u64 {
+            return Err(underflow(len, remaining));
+        }
+        if (len as usize) < amino_prefix.len() {
+            return Err(DecodeError::new("invalid amino prefix"));
+        }
+        for &expected in amino_prefix {
+            if buf.get_u8() != expected {
+                return Err(DecodeError::new("invalid amino prefix"));
+            }
+        }
+        merge(wire_type, value, buf)?;
+        if remaining - buf.remaining() != len as usize {
+            return Err(DecodeError::new("delimited length exceeded"));
+        }
+        Ok(())
+    }
+
+    length_delimited!(Vec<u8>);
+}
+
+/// `bytes` fields which are compressed with zstd on the wire.
+///
+/// Nothing is kept between calls, so a value is compressed each time the encoded length of the
+/// field is computed, as well as when it is encoded. The length of a message is computed once
+/// for each message it is nested in, so a large value in a deeply nested message is better
+/// compressed up front, with `compress`, and held in a plain `bytes` field.
+#[cfg(feature = "zstd")]
+pub mod zstd_bytes {
+    use super::*;
+    use std::io::{self, Read};
+
+    use zstd;
+
+    /// The maximum length of a decompressed value, unless the field has a `max_len` attribute.
+    pub const MAX_DECOMPRESSED_LEN: usize = 64 << 20;
+
+    /// Returns the value compressed at zstd's default level.
+    pub fn compress(value: &[u8]) -> Vec<u8> {
+        // The value is compressed into a buffer of zstd's bound on its compressed length, which
+        // can only fail if zstd fails to allocate.
+        zstd::bulk::compress(value, 0).expect("zstd compression failed")
+    }
+
+    /// Decompresses a value, or fails if the decompressed value is longer than `max_len` bytes.
+    /// Decompression stops as soon as the limit is exceeded, so a small value can not expand
+    /// into an unbounded allocation.
+    pub fn decompress(compressed: &[u8], max_len: usize) -> Result<Vec<u8>, DecodeError> {
+        let invalid = |error: io::Error| DecodeError::new(format!("invalid zstd value: {}", error));
+        let decoder = zstd::stream::read::Decoder::with_buffer(compressed).map_err(invalid)?;
+        let mut value = Vec::new();
+        decoder
+            .take(max_len as u64 + 1)
+            .read_to_end(&mut value)
+            .map_err(invalid)?;
+        if value.len() > max_len {
+            return Err(DecodeError::too_large(max_len));
+        }
+        Ok(value)
+    }
+
+    pub fn encode<B>(tag: u32, value: &[u8], buf: &mut B)
+    where
+        B: BufMut,
+    {
+        super::bytes::encode(tag, &compress(value), buf);
+    }
+
+    pub fn merge<B>(
+        wire_type: WireType,
+        value: &mut Vec<u8>,
+        buf: &mut B,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        merge_max_len(wire_type, value, MAX_DECOMPRESSED_LEN, buf)
+    }
+
+    /// Like `merge`, but fails if the length of the decompressed value exceeds `max_len` bytes.
+    pub fn merge_max_len<B>(
+        wire_type: WireType,
+        value: &mut Vec<u8>,
+        max_len: usize,
+        buf: &mut B,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let mut compressed = Vec::new();
+        super::bytes::merge(wire_type, &mut compressed, buf)?;
+        *value = decompress(&compressed, max_len)?;
+        Ok(())
+    }
+
+    encode_repeated!(Vec<u8>);
+
+    pub fn merge_repeated<B>(
+        wire_type: WireType,
+        values: &mut Vec<Vec<u8>>,
+        buf: &mut B,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let mut value = Vec::new();
+        merge(wire_type, &mut value, buf).map_err(|mut error| {
+            error.push_index(values.len());
+            error
+        })?;
+        values.push(value);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn encoded_len(tag: u32, value: &[u8]) -> usize {
+        super::bytes::encoded_len(tag, &compress(value))
+    }
+
+    #[inline]
+    pub fn encoded_len_repeated(tag: u32, values: &[Vec<u8>]) -> usize {
+        values.iter().map(|value| encoded_len(tag, value)).sum()
+    }
+}
+
+/// `json` fields, which hold a `serde_json::Value` encoded as a JSON string. The value is
+/// written without whitespace, and a value which is not valid JSON fails to decode.
+#[cfg(feature = "json")]
+pub mod json {
+    use super::*;
+    use std::io;
+
+    use serde_json::{self, Map, Value};
+
+    /// An `io::Write` which counts the bytes written to it.
+    struct Counter(usize);
+
+    impl io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Returns the length of the value written as JSON.
+    fn json_len(value: &Value) -> usize {
+        let mut counter = Counter(0);
+        // Writing a `Value` only fails if the writer does, and the counter never fails.
+        serde_json::to_writer(&mut counter, value).expect("JSON value is not serializable");
+        counter.0
+    }
+
+    pub fn encode<B>(tag: u32, value: &Value, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        super::string::encode(tag, &value.to_string(), buf);
+    }
+
+    pub fn merge<B>(wire_type: WireType, value: &mut Value, buf: &mut B) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let mut json = Vec::new();
+        super::bytes::merge(wire_type, &mut json, buf)?;
+        *value = serde_json::from_slice(&json)
+            .map_err(|error| DecodeError::new(format!("invalid JSON value: {}", error)))?;
+        Ok(())
+    }
+
+    encode_repeated!(Value);
+
+    pub fn merge_repeated<B>(
+        wire_type: WireType,
+        values: &mut Vec<Value>,
+        buf: &mut B,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let mut value = Value::Null;
+        merge(wire_type, &mut value, buf).map_err(|mut error| {
+            error.push_index(values.len());
+            error
+        })?;
+        values.push(value);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn encoded_len(tag: u32, value: &Value) -> usize {
+        let len = json_len(value);
+        key_len(tag) + encoded_len_varint(len as u64) + len
+    }
+
+    #[inline]
+    pub fn encoded_len_repeated(tag: u32, values: &[Value]) -> usize {
+        values.iter().map(|value| encoded_len(tag, value)).sum()
+    }
+
+    /// The Amino JSON form of a message, as generated for messages with the `amino_json`
+    /// attribute.
+    ///
+    /// Each field is written under the name of the Rust struct field. 64-bit integers are written
+    /// as strings, `bytes` fields as base64, and unset optional fields as `null`.
+    pub trait AminoJson {
+        /// The keys written by `write_fields`, including those of `json_inline` fields.
+        const JSON_KEYS: &'static [&'static str];
+
+        /// Writes the fields of the message into a JSON object. The fields of a `json_inline`
+        /// message field are written as fields of the enclosing message.
+        fn write_fields(&self, object: &mut Map<String, Value>);
+
+        /// Returns the message as a JSON object. A registered message is wrapped as
+        /// `{"type": <amino name>, "value": <object>}`.
+        fn to_json(&self) -> Value {
+            let mut object = Map::new();
+            self.write_fields(&mut object);
+            Value::Object(object)
+        }
+    }
+
+    impl<M> AminoJson for Box<M>
+    where
+        M: AminoJson,
+    {
+        const JSON_KEYS: &'static [&'static str] = M::JSON_KEYS;
+
+        fn write_fields(&self, object: &mut Map<String, Value>) {
+            (**self).write_fields(object)
+        }
+
+        fn to_json(&self) -> Value {
+            (**self).to_json()
+        }
+    }
+
+    /// Wraps the JSON object of a registered message with its amino name.
+    pub fn registered(name: &str, value: Value) -> Value {
+        let mut object = Map::new();
+        object.insert("type".to_owned(), Value::String(name.to_owned()));
+        object.insert("value".to_owned(), value);
+        Value::Object(object)
+    }
+
+    /// Returns the value in standard base64 with padding, as `bytes` fields are written.
+    pub fn base64(value: &[u8]) -> String {
+        const ALPHABET: &[u8; 64] =
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut encoded = String::with_capacity(value.len().div_ceil(3) * 4);
+        for chunk in value.chunks(3) {
+            let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| {
+                bits | u32::from(byte) << (16 - 8 * i)
+            });
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3f) as usize] as char);
+                } else {
+                    encoded.push('=');
+                }
+            }
+        }
+        encoded
+    }
+
+    /// Returns the keys of a message followed by those of its `json_inline` fields, as the
+    /// `JSON_KEYS` of a message with `json_inline` fields. `N` is the total number of keys.
+    pub const fn inlined_keys<const N: usize>(
+        own: &[&'static str],
+        inlined: &[&[&'static str]],
+    ) -> [&'static str; N] {
+        let mut keys = [""; N];
+        let mut len = 0;
+        let mut i = 0;
+        while i < own.len() {
+            keys[len] = own[i];
+            len += 1;
+            i += 1;
+        }
+        let mut i = 0;
+        while i < inlined.len() {
+            let mut j = 0;
+            while j < inlined[i].len() {
+                keys[len] = inlined[i][j];
+                len += 1;
+                j += 1;
+            }
+            i += 1;
+        }
+        keys
+    }
+
+    /// Returns `true` if a key is in both `a` and `b`.
+    pub const fn keys_overlap(a: &[&str], b: &[&str]) -> bool {
+        let mut i = 0;
+        while i < a.len() {
+            let mut j = 0;
+            while j < b.len() {
+                if str_eq(a[i], b[j]) {
+                    return true;
+                }
+                j += 1;
+            }
+            i += 1;
+        }
+        false
+    }
+
+    const fn str_eq(a: &str, b: &str) -> bool {
+        let (a, b) = (a.as_bytes(), b.as_bytes());
+        if a.len() != b.len() {
+            return false;
+        }
+        let mut i = 0;
+        while i < a.len() {
+            if a[i] != b[i] {
+                return false;
+            }
+            i += 1;
+        }
+        true
+    }
+}
+
+/// Splits a length delimited value off the front of a slice, without copying it.
+fn split_length_delimited<'a>(
+    wire_type: WireType,
+    buf: &mut &'a [u8],
+) -> Result<&'a [u8], DecodeError> {
+    check_wire_type(WireType::LengthDelimited, wire_type)?;
+    let len = decode_varint(buf)?;
+    if len > buf.len() as u64 {
+        return Err(underflow(len, buf.len()));
+    }
+    let (value, rest) = buf.split_at(len as usize);
+    *buf = rest;
+    Ok(value)
+}
+
+/// `string` fields held in a `Cow<str>`, which may borrow from the decoded buffer.
+pub mod cow_string {
+    use super::*;
+    use std::borrow::Cow;
+
+    pub fn encode<B>(tag: u32, value: &str, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        encode_key(tag, WireType::LengthDelimited, buf);
+        encode_varint(value.len() as u64, buf);
+        buf.put_slice(value.as_bytes());
+    }
+
+    /// Replaces the value with an owned copy of the decoded string.
+    pub fn merge<B>(
+        wire_type: WireType,
+        value: &mut Cow<str>,
+        buf: &mut B,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let mut owned = String::new();
+        super::string::merge(wire_type, &mut owned, buf)?;
+        *value = Cow::Owned(owned);
+        Ok(())
+    }
+
+    /// Replaces the value with a string borrowed from the slice.
+    pub fn merge_borrowed<'a>(
+        wire_type: WireType,
+        value: &mut Cow<'a, str>,
+        buf: &mut &'a [u8],
+    ) -> Result<(), DecodeError> {
+        let bytes = split_length_delimited(wire_type, buf)?;
+        let string = str::from_utf8(bytes).map_err(super::string::invalid_utf8)?;
+        *value = Cow::Borrowed(string);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn encoded_len(tag: u32, value: &str) -> usize {
+        key_len(tag) + encoded_len_varint(value.len() as u64) + value.len()
+    }
+}
+
+/// `bytes` fields held in a `Cow<[u8]>`, which may borrow from the decoded buffer.
+pub mod cow_bytes {
+    use super::*;
+    use std::borrow::Cow;
+
+    pub fn encode<B>(tag: u32, value: &[u8], buf: &mut B)
+    where
+        B: BufMut,
+    {
+        encode_key(tag, WireType::LengthDelimited, buf);
+        encode_varint(value.len() as u64, buf);
+        buf.put_slice(value);
+    }
+
+    /// Replaces the value with an owned copy of the decoded bytes.
+    pub fn merge<B>(
+        wire_type: WireType,
+        value: &mut Cow<[u8]>,
+        buf: &mut B,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let mut owned = Vec::new();
+        super::bytes::merge(wire_type, &mut owned, buf)?;
+        *value = Cow::Owned(owned);
+        Ok(())
+    }
+
+    /// Replaces the value with bytes borrowed from the slice.
+    pub fn merge_borrowed<'a>(
+        wire_type: WireType,
+        value: &mut Cow<'a, [u8]>,
+        buf: &mut &'a [u8],
+    ) -> Result<(), DecodeError> {
+        *value = Cow::Borrowed(split_length_delimited(wire_type, buf)?);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn encoded_len(tag: u32, value: &[u8]) -> usize {
+        key_len(tag) + encoded_len_varint(value.len() as u64) + value.len()
+    }
+}
+
+/// `bytes` fields held in a `Bytes`, which shares the decoded buffer when decoded from a `Bytes`.
+pub mod shared_bytes {
+    use super::*;
+
+    pub fn encode<B>(tag: u32, value: &[u8], buf: &mut B)
+    where
+        B: BufMut,
+    {
+        encode_key(tag, WireType::LengthDelimited, buf);
+        encode_varint(value.len() as u64, buf);
+        buf.put_slice(value);
+    }
+
+    /// Replaces the value with a copy of the decoded bytes.
+    pub fn merge<B>(wire_type: WireType, value: &mut Bytes, buf: &mut B) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let mut owned = Vec::new();
+        super::bytes::merge(wire_type, &mut owned, buf)?;
+        *value = Bytes::from(owned);
+        Ok(())
+    }
+
+    /// Replaces the value with a view of the buffer, without copying it.
+    pub fn merge_shared(
+        wire_type: WireType,
+        value: &mut Bytes,
+        buf: &mut Bytes,
+    ) -> Result<(), DecodeError> {
+        check_wire_type(WireType::LengthDelimited, wire_type)?;
+        let len = decode_varint(buf)?;
+        if len > buf.len() as u64 {
+            return Err(underflow(len, buf.len()));
+        }
+        *value = buf.split_to(len as usize);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn encoded_len(tag: u32, value: &[u8]) -> usize {
+        key_len(tag) + encoded_len_varint(value.len() as u64) + value.len()
+    }
+}
+
+pub mod message {
+    use super::*;
+
+    pub fn encode<M, B>(tag: u32, msg: &M, buf: &mut B)
+    where
+        M: Message,
+        B: BufMut,
+    {
+        encode_key(tag, WireType::LengthDelimited, buf);
+        // Registered messages write their own length delimiter, followed by their prefix.
+        if M::amino_prefix().is_empty() {
+            encode_varint(msg.encoded_len() as u64, buf);
+        }
+        msg.encode_raw(buf);
+    }
+
+    /// Returns `true` if a required message field holding `msg` is encoded. An empty message is
+    /// skipped, unless it is registered, since Amino writes the prefix of a registered message
+    /// even when it has no fields.
+    pub fn is_encoded<M>(msg: &M) -> bool
+    where
+        M: Message,
+    {
+        !M::amino_prefix().is_empty() || !msg.is_empty()
+    }
+
+    /// Returns the prefix of the registered message at the start of a length-delimited field,
+    /// without consuming it. Used to decode a oneof whose registered variants share a tag.
+    pub fn peek_prefix<B>(
+        wire_type: WireType,
+        buf: &B,
+    ) -> Result<[u8; AMINO_PREFIX_LEN], DecodeError>
+    where
+        B: Buf,
+    {
+        check_wire_type(WireType::LengthDelimited, wire_type)?;
+        // The length delimiter and the prefix may be split across the chunks of the buffer, so
+        // they are copied into a stack buffer without advancing it.
+        let mut head = [0; 10 + AMINO_PREFIX_LEN];
+        let mut head_len = 0;
+        let mut chunks = [IoSlice::new(&[]); 10 + AMINO_PREFIX_LEN];
+        let count = buf.bytes_vectored(&mut chunks);
+        for chunk in &chunks[..count] {
+            let n = min(chunk.len(), head.len() - head_len);
+            head[head_len..head_len + n].copy_from_slice(&chunk[..n]);
+            head_len += n;
+        }
+        let mut bytes = &head[..head_len];
+        let len = decode_varint(&mut bytes)?;
+        if len < AMINO_PREFIX_LEN as u64 {
+            return Err(DecodeError::new("buffer underflow"));
+        }
+        if bytes.len() < AMINO_PREFIX_LEN {
+            return Err(DecodeError::incomplete("buffer underflow", None));
+        }
+        let mut prefix = [0; AMINO_PREFIX_LEN];
+        prefix.copy_from_slice(&bytes[..AMINO_PREFIX_LEN]);
+        Ok(prefix)
+    }
+
+    pub fn encode_with_prefix<M, B>(tag: u32, msg: &M, amino_prefix: &Vec<u8>, buf: &mut B)
+    where
+        M: Message,
+        B: BufMut,
+    {
+        encode_key(tag, WireType::LengthDelimited, buf);
+        encode_varint((msg.encoded_len() + amino_prefix.len() - 1) as u64, buf);
+        buf.put(amino_prefix.as_ref());
+        encode_varint((msg.encoded_len() - 2) as u64, buf);
+    }
+
+    pub fn merge<M, B>(wire_type: WireType, msg: &mut M, buf: &mut B) -> Result<(), DecodeError>
+    where
+        M: Message,
+        B: Buf,
+    {
+        check_wire_type(WireType::LengthDelimited, wire_type)?;
+        let amino_prefix = M::amino_prefix();
+        if amino_prefix.is_empty() {
+            merge_nested(|| merge_loop(msg, buf, M::merge_field))?;
+            msg.finish_merge();
+            return Ok(());
+        }
+
+        // The contents of a registered message start with its prefix.
+        let mut prefixed = false;
+        merge_nested(|| {
+            merge_loop(msg, buf, |msg, buf| {
+                if prefixed {
+                    return msg.merge_field(buf);
+                }
+                prefixed = true;
+                if buf.remaining() < amino_prefix.len() {
+                    return Err(underflow(amino_prefix.len() as u64, buf.remaining()));
+                }
+                for &byte in amino_prefix {
+                    if buf.get_u8() != byte {
+                        return Err(DecodeError::new(format!(
+                            "invalid amino prefix, expected {:02x?}",
+                            amino_prefix
+                        )));
+                    }
+                }
+                Ok(())
+            })
+        })?;
+        msg.finish_merge();
+        Ok(())
+    }
+
+    /// Like `merge`, but decodes the fields with `merge_field_shared`, so that `shared` fields
+    /// share the buffer. Registered messages are decoded with `merge`.
+    pub fn merge_shared<M>(
+        wire_type: WireType,
+        msg: &mut M,
+        buf: &mut Bytes,
+    ) -> Result<(), DecodeError>
+    where
+        M: Message,
+    {
+        if !M::amino_prefix().is_empty() {
+            return merge(wire_type, msg, buf);
+        }
+        check_wire_type(WireType::LengthDelimited, wire_type)?;
+        merge_nested(|| merge_loop(msg, buf, M::merge_field_shared))?;
+        msg.finish_merge();
+        Ok(())
+    }
+
+    pub fn merge_repeated_shared<M>(
+        wire_type: WireType,
+        messages: &mut Vec<M>,
+        buf: &mut Bytes,
+    ) -> Result<(), DecodeError>
+    where
+        M: Message + Default,
+    {
+        let mut msg = M::default();
+        merge_shared(wire_type, &mut msg, buf).map_err(|mut error| {
+            error.push_index(messages.len());
+            error
+        })?;
+        messages.push(msg);
+        Ok(())
+    }
+
+    pub fn encode_repeated<M, B>(tag: u32, messages: &[M], buf: &mut B)
+    where
+        M: Message,
+        B: BufMut,
+    {
+        for msg in messages {
+            encode(tag, msg, buf);
+        }
+    }
+
+    pub fn merge_repeated<M, B>(
+        wire_type: WireType,
+        messages: &mut Vec<M>,
+        buf: &mut B,
+    ) -> Result<(), DecodeError>
+    where
+        M: Message + Default,
+        B: Buf,
+    {
+        check_wire_type(WireType::LengthDelimited, wire_type)?;
+        let mut msg = M::default();
+        merge(WireType::LengthDelimited, &mut msg, buf).map_err(|mut error| {
+            error.push_index(messages.len());
+            error
+        })?;
+        messages.push(msg);
+        Ok(())
+    }
+
+    /// Returns the encoded length of a message field. The length of a registered message counts
+    /// its prefix, so this is the key length plus its `encoded_len_with_prefix`.
+    #[inline]
+    pub fn encoded_len<M>(tag: u32, msg: &M) -> usize
+    where
+        M: Message,
+    {
+        let len = msg.encoded_len();
+        key_len(tag) + encoded_len_varint(len as u64) + len
+    }
+
+    #[inline]
+    pub fn encoded_len_repeated<M>(tag: u32, messages: &[M]) -> usize
+    where
+        M: Message,
+    {
+        key_len(tag) * messages.len()
+            + messages
+                .iter()
+                .map(Message::encoded_len)
+                .map(|len| len + encoded_len_varint(len as u64))
+                .sum::<usize>()
+    }
+}
+
+/// Encodes a `Lazy` message field. The encoded message is copied when it is decoded, without
+/// decoding its fields.
+pub mod lazy {
+    pub use super::message::{encode, encode_repeated, encoded_len, encoded_len_repeated};
+
+    use super::*;
+    use Lazy;
+
+    pub fn merge<M, B>(
+        wire_type: WireType,
+        lazy: &mut Lazy<M>,
+        buf: &mut B,
+    ) -> Result<(), DecodeError>
+    where
+        M: Message + Default,
+        B: Buf,
+    {
+        check_wire_type(WireType::LengthDelimited, wire_type)?;
+        let len = decode_varint(buf)?;
+        if len > buf.remaining() as u64 {
+            return Err(underflow(len, buf.remaining()));
+        }
+        lazy.merge_raw(&mut buf.take(len as usize));
+        Ok(())
+    }
+
+    pub fn merge_repeated<M, B>(
+        wire_type: WireType,
+        messages: &mut Vec<Lazy<M>>,
+        buf: &mut B,
+    ) -> Result<(), DecodeError>
+    where
+        M: Message + Default,
+        B: Buf,
+    {
+        let mut lazy = Lazy::default();
+        merge(wire_type, &mut lazy, buf)?;
+        messages.push(lazy);
+        Ok(())
+    }
+}
+
+/// `group` fields, which hold a message framed by start group and end group keys rather than a
+/// length delimiter.
+///
+/// Registered messages write their own length delimiter, so they may not be held in a group.
+pub mod group {
+    use super::*;
+
+    pub fn encode<M, B>(tag: u32, msg: &M, buf: &mut B)
+    where
+        M: Message,
+        B: BufMut,
+    {
+        debug_assert!(M::amino_prefix().is_empty());
+        encode_key(tag, WireType::StartGroup, buf);
+        msg.encode_raw(buf);
+        encode_key(tag, WireType::EndGroup, buf);
+    }
+
+    pub fn merge<M, B>(
+        tag: u32,
+        wire_type: WireType,
+        msg: &mut M,
+        buf: &mut B,
+    ) -> Result<(), DecodeError>
+    where
+        M: Message,
+        B: Buf,
+    {
+        check_wire_type(WireType::StartGroup, wire_type)?;
+        // The key of each field is put back in front of the buffer, which is type erased so that
+        // a group nested in the message doesn't chain onto the buffer type without end.
+        let mut buf: &mut dyn Buf = buf;
+        merge_nested(|| loop {
+            let (field_tag, field_wire_type) = decode_key(&mut buf)?;
+            if field_wire_type == WireType::EndGroup {
+                if field_tag != tag {
+                    return Err(DecodeError::new("unexpected end group tag"));
+                }
+                msg.finish_merge();
+                return Ok(());
+            }
+            merge_field_with_key(field_tag, field_wire_type, msg, &mut buf)?;
+        })
+    }
+
+    pub fn encode_repeated<M, B>(tag: u32, messages: &[M], buf: &mut B)
+    where
+        M: Message,
+        B: BufMut,
+    {
+        for msg in messages {
+            encode(tag, msg, buf);
+        }
+    }
+
+    pub fn merge_repeated<M, B>(
+        tag: u32,
+        wire_type: WireType,
+        messages: &mut Vec<M>,
+        buf: &mut B,
+    ) -> Result<(), DecodeError>
+    where
+        M: Message + Default,
+        B: Buf,
+    {
+        let mut msg = M::default();
+        merge(tag, wire_type, &mut msg, buf).map_err(|mut error| {
+            error.push_index(messages.len());
+            error
+        })?;
+        messages.push(msg);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn encoded_len<M>(tag: u32, msg: &M) -> usize
+    where
+        M: Message,
+    {
+        2 * key_len(tag) + msg.encoded_len()
+    }
+
+    #[inline]
+    pub fn encoded_len_repeated<M>(tag: u32, messages: &[M]) -> usize
+    where
+        M: Message,
+    {
+        2 * key_len(tag) * messages.len() + messages.iter().map(Message::encoded_len).sum::<usize>()
+    }
+}
+
+/// `duration` fields, which hold a `Duration` encoded as a nested message.
+///
+/// Durations are canonicalized when encoded, and rejected when decoded unless canonical.
+pub mod duration {
+    use super::*;
+    use Duration;
+
+    pub fn encode<B>(tag: u32, duration: &Duration, buf: &mut B)
     where
         B: BufMut,
     {
-        encode_key(tag, WireType::LengthDelimited, buf);
-        encode_varint(value.len() as u64, buf);
-        buf.put_slice(value.as_bytes());
+        message::encode(tag, duration, buf)
     }
 
-    pub fn encode_with_prefix<B>(_tag: u32, _value: &String, _amino_prefix: Vec<u8>, _buf: &mut B)
+    pub fn merge<B>(wire_type: WireType, duration: &mut Duration, buf: &mut B) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        message::merge(wire_type, duration, buf)?;
+        check(duration)
+    }
+
+    pub fn encode_repeated<B>(tag: u32, durations: &[Duration], buf: &mut B)
     where
         B: BufMut,
     {
-        panic!("amino prefix not implemented for type");
+        message::encode_repeated(tag, durations, buf)
     }
 
-    pub fn merge<B>(wire_type: WireType, value: &mut String, buf: &mut B) -> Result<(), DecodeError>
+    pub fn merge_repeated<B>(
+        wire_type: WireType,
+        durations: &mut Vec<Duration>,
+        buf: &mut B,
+    ) -> Result<(), DecodeError>
     where
         B: Buf,
     {
-        unsafe {
-            // String::as_mut_vec is unsafe because it doesn't check that the bytes
-            // inserted into it the resulting vec are valid UTF-8. We check
-            // explicitly in order to ensure this is safe.
-            super::bytes::merge(wire_type, value.as_mut_vec(), buf)?;
-            str::from_utf8(value.as_bytes())
-                .map_err(|_| DecodeError::new("invalid string value: data is not UTF-8 encoded"))?;
-        }
+        check_wire_type(WireType::LengthDelimited, wire_type)?;
+        let mut duration = Duration::default();
+        merge(WireType::LengthDelimited, &mut duration, buf).map_err(|mut error| {
+            error.push_index(durations.len());
+            error
+        })?;
+        durations.push(duration);
         Ok(())
     }
 
-    length_delimited!(String);
+    #[inline]
+    pub fn encoded_len(tag: u32, duration: &Duration) -> usize {
+        message::encoded_len(tag, duration)
+    }
+
+    #[inline]
+    pub fn encoded_len_repeated(tag: u32, durations: &[Duration]) -> usize {
+        message::encoded_len_repeated(tag, durations)
+    }
+
+    fn check(duration: &Duration) -> Result<(), DecodeError> {
+        if duration.is_canonical() {
+            Ok(())
+        } else {
+            Err(DecodeError::new(format!(
+                "invalid duration: {} seconds and {} nanos are not canonical",
+                duration.seconds, duration.nanos
+            )))
+        }
+    }
 }
 
-pub mod bytes {
+/// `SystemTime` fields encoded as a `fixed64` count of nanoseconds since the unix epoch, which is
+/// negative for earlier times. Only times within the range of an `i64` count, i.e. between the
+/// years 1677 and 2262, can be encoded faithfully. Encoding does not fail for other times: they
+/// are saturated to `i64::MIN` or `i64::MAX` nanoseconds, and rejected by `check`, which is run by
+/// `Message::validate`.
+pub mod unix_nanos {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
     use super::*;
+    use ValidationError;
+
+    /// Returns the number of nanoseconds between the unix epoch and the time, or `None` if it is
+    /// out of range of an `i64`.
+    pub fn to_nanos(time: &SystemTime) -> Option<i64> {
+        let nanos = match time.duration_since(UNIX_EPOCH) {
+            Ok(after) => after.as_nanos() as i128,
+            Err(before) => -(before.duration().as_nanos() as i128),
+        };
+        if nanos < i128::from(i64::MIN) || nanos > i128::from(i64::MAX) {
+            None
+        } else {
+            Some(nanos as i64)
+        }
+    }
 
-    pub fn encode<B>(tag: u32, value: &Vec<u8>, buf: &mut B)
-    where
-        B: BufMut,
-    {
-        encode_key(tag, WireType::LengthDelimited, buf);
-        encode_varint(value.len() as u64, buf);
-        buf.put_slice(value);
+    /// Returns the time the number of nanoseconds after the unix epoch, or `None` if the platform
+    /// cannot represent it.
+    pub fn from_nanos(nanos: i64) -> Option<SystemTime> {
+        if nanos >= 0 {
+            UNIX_EPOCH.checked_add(Duration::from_nanos(nanos as u64))
+        } else {
+            UNIX_EPOCH.checked_sub(Duration::from_nanos(nanos.unsigned_abs()))
+        }
     }
 
-    pub fn encode_with_prefix<B>(tag: u32, value: &Vec<u8>, amino_prefix: &Vec<u8>, buf: &mut B)
+    /// Returns the number of nanoseconds between the unix epoch and the time, saturated to the
+    /// range of an `i64`.
+    pub fn saturating_nanos(time: &SystemTime) -> i64 {
+        to_nanos(time).unwrap_or(if *time < UNIX_EPOCH {
+            i64::MIN
+        } else {
+            i64::MAX
+        })
+    }
+
+    /// Encodes the time. Times out of range are saturated to the earliest or latest time which
+    /// can be encoded, so messages holding them should be checked with `Message::validate`.
+    pub fn encode<B>(tag: u32, time: &SystemTime, buf: &mut B)
     where
         B: BufMut,
     {
-        encode_key(tag, WireType::LengthDelimited, buf);
-        // length delim: the actual bytes + 4 prefix bytes + 1 additional length delim.
-        encode_varint((value.len() + amino_prefix.len() + 1) as u64, buf);
-        buf.put_slice(amino_prefix);
-        encode_varint((value.len()) as u64, buf);
-        buf.put_slice(value);
+        encode_key(tag, WireType::SixtyFourBit, buf);
+        buf.put_i64_le(saturating_nanos(time));
     }
 
     pub fn merge<B>(
         wire_type: WireType,
-        value: &mut Vec<u8>,
+        time: &mut SystemTime,
         buf: &mut B,
     ) -> Result<(), DecodeError>
     where
         B: Buf,
     {
-        check_wire_type(WireType::LengthDelimited, wire_type)?;
-        let len = decode_varint(buf)?;
-        if len > buf.remaining() as u64 {
-            return Err(DecodeError::new("buffer underflow"));
-        }
-
-        let mut remaining = len as usize;
-        while remaining > 0 {
-            let len = {
-                let bytes = buf.bytes();
-                debug_assert!(!bytes.is_empty(), "Buf::bytes returned empty slice");
-                let len = min(remaining, bytes.len());
-                let bytes = &bytes[..len];
-                value.extend_from_slice(bytes);
-                len
-            };
-            remaining -= len;
-            buf.advance(len);
+        check_wire_type(WireType::SixtyFourBit, wire_type)?;
+        if buf.remaining() < 8 {
+            return Err(underflow(8, buf.remaining()));
         }
+        let nanos = buf.get_i64_le();
+        *time = from_nanos(nanos).ok_or_else(|| {
+            DecodeError::new(format!(
+                "invalid time: {} nanoseconds since the unix epoch is out of range",
+                nanos
+            ))
+        })?;
         Ok(())
     }
 
-    pub fn merge_with_prefix<B>(
-        wire_type: WireType,
-        value: &mut Vec<u8>,
-        amino_prefix: &Vec<u8>,
-        buf: &mut B,
-    ) -> Result<(), DecodeError>
-    where
-        B: Buf,
-    {
-        // skip 4+1 bytes (or amino_prefix + length delimiter)
-        buf.advance(amino_prefix.len() + 1);
-        merge(wire_type, value, buf)
+    #[inline]
+    pub fn encoded_len(tag: u32, _time: &SystemTime) -> usize {
+        key_len(tag) + 8
     }
 
-    length_delimited!(Vec<u8>);
+    /// Checks that the time can be encoded without saturating.
+    pub fn check(time: &SystemTime) -> Result<(), ValidationError> {
+        match to_nanos(time) {
+            Some(_) => Ok(()),
+            None => Err(ValidationError::new(
+                "time is out of range of i64 nanoseconds since the unix epoch",
+            )),
+        }
+    }
 }
 
-pub mod message {
+/// Encodes an `Ipv4Addr` as a `fixed32` or `sfixed32`, whose value is the address in network
+/// byte order, as given by `u32::from`.
+pub mod ipv4_addr {
+    use std::net::Ipv4Addr;
+
     use super::*;
 
-    pub fn encode<M, B>(tag: u32, msg: &M, buf: &mut B)
+    pub fn encode<B>(tag: u32, addr: &Ipv4Addr, buf: &mut B)
     where
-        M: Message,
         B: BufMut,
     {
-        encode_key(tag, WireType::LengthDelimited, buf);
-        encode_varint(msg.encoded_len() as u64, buf);
-        msg.encode_raw(buf);
+        encode_key(tag, WireType::ThirtyTwoBit, buf);
+        buf.put_u32_le(u32::from(*addr));
     }
 
-    pub fn encode_with_prefix<M, B>(tag: u32, msg: &M, amino_prefix: &Vec<u8>, buf: &mut B)
+    pub fn merge<B>(
+        wire_type: WireType,
+        addr: &mut Ipv4Addr,
+        buf: &mut B,
+    ) -> Result<(), DecodeError>
     where
-        M: Message,
-        B: BufMut,
+        B: Buf,
     {
-        encode_key(tag, WireType::LengthDelimited, buf);
-        encode_varint((msg.encoded_len() + amino_prefix.len() - 1) as u64, buf);
-        buf.put(amino_prefix.as_ref());
-        encode_varint((msg.encoded_len() - 2) as u64, buf);
+        check_wire_type(WireType::ThirtyTwoBit, wire_type)?;
+        if buf.remaining() < 4 {
+            return Err(underflow(4, buf.remaining()));
+        }
+        *addr = Ipv4Addr::from(buf.get_u32_le());
+        Ok(())
     }
 
-    pub fn merge<M, B>(wire_type: WireType, msg: &mut M, buf: &mut B) -> Result<(), DecodeError>
-    where
-        M: Message,
-        B: Buf,
-    {
-        check_wire_type(WireType::LengthDelimited, wire_type)?;
-        merge_loop(msg, buf, M::merge_field)
+    #[inline]
+    pub fn encoded_len(tag: u32, _addr: &Ipv4Addr) -> usize {
+        key_len(tag) + 4
     }
+}
 
-    pub fn encode_repeated<M, B>(tag: u32, messages: &[M], buf: &mut B)
+/// Encodes an `Ipv6Addr` as `bytes` holding its 16 octets.
+pub mod ipv6_addr {
+    use std::net::Ipv6Addr;
+
+    use super::*;
+
+    pub fn encode<B>(tag: u32, addr: &Ipv6Addr, buf: &mut B)
     where
-        M: Message,
         B: BufMut,
     {
-        for msg in messages {
-            encode(tag, msg, buf);
-        }
+        encode_key(tag, WireType::LengthDelimited, buf);
+        encode_varint(16, buf);
+        buf.put_slice(&addr.octets());
     }
 
-    pub fn merge_repeated<M, B>(
+    pub fn merge<B>(
         wire_type: WireType,
-        messages: &mut Vec<M>,
+        addr: &mut Ipv6Addr,
         buf: &mut B,
     ) -> Result<(), DecodeError>
     where
-        M: Message + Default,
         B: Buf,
     {
         check_wire_type(WireType::LengthDelimited, wire_type)?;
-        let mut msg = M::default();
-        merge(WireType::LengthDelimited, &mut msg, buf)?;
-        messages.push(msg);
+        let len = decode_varint(buf)?;
+        if len != 16 {
+            return Err(DecodeError::new(format!(
+                "invalid IPv6 address: expected 16 bytes, got {}",
+                len
+            )));
+        }
+        if buf.remaining() < 16 {
+            return Err(underflow(16, buf.remaining()));
+        }
+        let mut octets = [0u8; 16];
+        buf.copy_to_slice(&mut octets);
+        *addr = Ipv6Addr::from(octets);
         Ok(())
     }
 
     #[inline]
-    pub fn encoded_len<M>(tag: u32, msg: &M) -> usize
+    pub fn encoded_len(tag: u32, _addr: &Ipv6Addr) -> usize {
+        key_len(tag) + 1 + 16
+    }
+}
+
+/// Encodes a `u16`, such as a port, as a `uint32`. Decoded values which do not fit in a `u16` are
+/// rejected.
+pub mod port {
+    use super::*;
+
+    pub fn encode<B>(tag: u32, port: &u16, buf: &mut B)
     where
-        M: Message,
+        B: BufMut,
     {
-        let len = msg.encoded_len();
-        key_len(tag) + encoded_len_varint(len as u64) + msg.encoded_len()
+        encode_key(tag, WireType::Varint, buf);
+        encode_varint(u64::from(*port), buf);
     }
 
-    #[inline]
-    pub fn encoded_len_repeated<M>(tag: u32, messages: &[M]) -> usize
+    pub fn merge<B>(wire_type: WireType, port: &mut u16, buf: &mut B) -> Result<(), DecodeError>
     where
-        M: Message,
+        B: Buf,
     {
-        key_len(tag) * messages.len()
-            + messages
-                .iter()
-                .map(Message::encoded_len)
-                .map(|len| len + encoded_len_varint(len as u64))
-                .sum::<usize>()
+        check_wire_type(WireType::Varint, wire_type)?;
+        let value = decode_varint(buf)?;
+        if value > u64::from(u16::MAX) {
+            return Err(DecodeError::new(format!(
+                "invalid u16: {} is out of range",
+                value
+            )));
+        }
+        *port = value as u16;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn encoded_len(tag: u32, port: &u16) -> usize {
+        key_len(tag) + encoded_len_varint(u64::from(*port))
     }
 }
 
@@ -939,6 +3162,7 @@ macro_rules! map {
 
         /// Generic protobuf map merge function.
         pub fn merge<K, V, B, KM, VM>(
+            wire_type: WireType,
             key_merge: KM,
             val_merge: VM,
             values: &mut $map_ty<K, V>,
@@ -951,7 +3175,7 @@ macro_rules! map {
             KM: Fn(WireType, &mut K, &mut B) -> Result<(), DecodeError>,
             VM: Fn(WireType, &mut V, &mut B) -> Result<(), DecodeError>,
         {
-            merge_with_default(key_merge, val_merge, V::default(), values, buf)
+            merge_with_default(wire_type, key_merge, val_merge, V::default(), values, buf)
         }
 
         /// Generic protobuf map encode function.
@@ -992,7 +3216,7 @@ macro_rules! map {
             VE: Fn(u32, &V, &mut B),
             VL: Fn(u32, &V) -> usize,
         {
-            for (key, val) in values.iter() {
+            let mut encode_entry = |key: &K, val: &V| {
                 let skip_key = key == &K::default();
                 let skip_val = val == val_default;
 
@@ -1007,6 +3231,19 @@ macro_rules! map {
                 if !skip_val {
                     val_encode(2, val, buf);
                 }
+            };
+            // In canonical mode, and for checksums, the entries are written in key order, so that
+            // equal maps are encoded equally.
+            if in_key_order() {
+                let mut entries = values.iter().collect::<Vec<_>>();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                for (key, val) in entries {
+                    encode_entry(key, val);
+                }
+            } else {
+                for (key, val) in values {
+                    encode_entry(key, val);
+                }
             }
         }
 
@@ -1015,6 +3252,7 @@ macro_rules! map {
         /// This is necessary because enumeration values can have a default value other
         /// than 0 in proto2.
         pub fn merge_with_default<K, V, B, KM, VM>(
+            wire_type: WireType,
             key_merge: KM,
             val_merge: VM,
             val_default: V,
@@ -1027,6 +3265,7 @@ macro_rules! map {
             KM: Fn(WireType, &mut K, &mut B) -> Result<(), DecodeError>,
             VM: Fn(WireType, &mut V, &mut B) -> Result<(), DecodeError>,
         {
+            check_wire_type(WireType::LengthDelimited, wire_type)?;
             let mut key = Default::default();
             let mut val = val_default;
             merge_loop(
@@ -1037,7 +3276,7 @@ macro_rules! map {
                     match tag {
                         1 => key_merge(wire_type, key, buf),
                         2 => val_merge(wire_type, val, buf),
-                        _ => skip_field(wire_type, buf),
+                        _ => skip_field(wire_type, tag, buf, 0),
                     }
                 },
             )?;
@@ -1098,7 +3337,7 @@ mod test {
     use std::u64;
 
     use bytes::{Bytes, BytesMut};
-    use quickcheck::TestResult;
+    use quickcheck::{Arbitrary, Gen, TestResult};
 
     use encoding::*;
 
@@ -1261,6 +3500,232 @@ mod test {
         }
     }
 
+    /// A scalar type with values at the edges of its range, e.g. `i64::MIN` or subnormal floats,
+    /// which `Arbitrary` rarely or never generates.
+    pub trait Edges: Arbitrary {
+        /// Returns the edge values of the type.
+        fn edges() -> Vec<Self>;
+
+        /// Returns a value drawn from the full range of the type.
+        fn full<G: Gen>(g: &mut G) -> Self;
+    }
+
+    /// A value drawn from the full range of its type, or one of its edge values, rather than
+    /// from the small values `Arbitrary` generates for integers. Shrinks like the wrapped value.
+    #[derive(Clone, Debug)]
+    pub struct Full<T>(pub T);
+
+    impl<T> Arbitrary for Full<T>
+    where
+        T: Edges,
+    {
+        fn arbitrary<G: Gen>(g: &mut G) -> Full<T> {
+            // One in four values is an edge value.
+            if g.next_u32() & 3 == 0 {
+                let edges = T::edges();
+                let index = g.next_u32() as usize % edges.len();
+                Full(edges[index].clone())
+            } else {
+                Full(T::full(g))
+            }
+        }
+
+        fn shrink(&self) -> Box<dyn Iterator<Item = Full<T>>> {
+            Box::new(self.0.shrink().map(Full))
+        }
+    }
+
+    /// Checks every edge value of a type with `check`, e.g. a call to `check_type`.
+    pub fn check_edges<T, F>(check: F)
+    where
+        T: Edges + Debug,
+        F: Fn(T) -> TestResult,
+    {
+        for value in T::edges() {
+            let result = check(value.clone());
+            assert!(
+                !result.is_failure() && !result.is_error(),
+                "edge value {:?} failed to round trip",
+                value
+            );
+        }
+    }
+
+    macro_rules! int_edges {
+        ($($ty:ident),*) => {
+            $(
+                impl Edges for $ty {
+                    fn edges() -> Vec<$ty> {
+                        // Zero, the limits, and the values around varint width boundaries.
+                        let values: [i128; 9] =
+                            [0, 1, -1, 127, 128, 16_383, 16_384, 1 << 31, 1 << 63];
+                        values
+                            .iter()
+                            .map(|&value| value as $ty)
+                            .chain(vec![$ty::MIN, $ty::MIN + 1, $ty::MAX - 1, $ty::MAX])
+                            .collect()
+                    }
+
+                    fn full<G: Gen>(g: &mut G) -> $ty {
+                        g.next_u64() as $ty
+                    }
+                }
+            )*
+        };
+    }
+
+    int_edges!(i32, i64, u32, u64);
+
+    macro_rules! float_edges {
+        ($ty:ident, $bits:ident, $next:ident) => {
+            impl Edges for $ty {
+                fn edges() -> Vec<$ty> {
+                    let min_positive = $ty::MIN_POSITIVE;
+                    vec![
+                        0.0,
+                        -0.0,
+                        1.0,
+                        -1.0,
+                        $ty::MIN,
+                        $ty::MAX,
+                        min_positive,
+                        $ty::EPSILON,
+                        $ty::INFINITY,
+                        $ty::NEG_INFINITY,
+                        // The smallest and largest subnormal values.
+                        $ty::from_bits(1),
+                        $ty::from_bits(min_positive.to_bits() - 1),
+                    ]
+                }
+
+                fn full<G: Gen>(g: &mut G) -> $ty {
+                    // NaN is not equal to itself, so it can not be compared after a round trip.
+                    loop {
+                        let value = $ty::from_bits(g.$next() as $bits);
+                        if !value.is_nan() {
+                            return value;
+                        }
+                    }
+                }
+            }
+        };
+    }
+
+    float_edges!(f32, u32, next_u32);
+    float_edges!(f64, u64, next_u64);
+
+    impl Edges for bool {
+        fn edges() -> Vec<bool> {
+            vec![false, true]
+        }
+
+        fn full<G: Gen>(g: &mut G) -> bool {
+            g.next_u32() & 1 == 0
+        }
+    }
+
+    impl Edges for String {
+        fn edges() -> Vec<String> {
+            vec![
+                String::new(),
+                "\0".to_owned(),
+                "\u{7f}".to_owned(),
+                "\u{80}".to_owned(),
+                "\u{10ffff}".to_owned(),
+                // Long enough for a three byte length delimiter.
+                "\u{e9}".repeat(50_000),
+            ]
+        }
+
+        fn full<G: Gen>(g: &mut G) -> String {
+            String::arbitrary(g)
+        }
+    }
+
+    impl Edges for Vec<u8> {
+        fn edges() -> Vec<Vec<u8>> {
+            vec![Vec::new(), vec![0], vec![0xff], vec![0xab; 100_000]]
+        }
+
+        fn full<G: Gen>(g: &mut G) -> Vec<u8> {
+            Vec::arbitrary(g)
+        }
+    }
+
+    fn encode_nan_f32(bits: u32) -> Vec<u8> {
+        let mut buf = Vec::new();
+        float::encode(1, &f32::from_bits(bits), &mut buf);
+        float::encode_packed(2, &[f32::from_bits(bits)], &mut buf);
+        buf
+    }
+
+    fn encode_nan_f64(bits: u64) -> Vec<u8> {
+        let mut buf = Vec::new();
+        double::encode(1, &f64::from_bits(bits), &mut buf);
+        double::encode_packed(2, &[f64::from_bits(bits)], &mut buf);
+        buf
+    }
+
+    #[test]
+    #[cfg(feature = "canonical")]
+    fn nan_is_canonical() {
+        let want = encode_nan_f32(CANONICAL_NAN_F32);
+        for &bits in &[0x7FC0_0000, 0x7F80_0001, 0xFFC0_0000, 0x7FFF_FFFF] {
+            assert_eq!(encode_nan_f32(bits), want);
+        }
+
+        let want = encode_nan_f64(CANONICAL_NAN_F64);
+        for &bits in &[
+            0x7FF8_0000_0000_0000,
+            0x7FF0_0000_0000_0001,
+            0xFFF8_0000_0000_0000,
+            0x7FFF_FFFF_FFFF_FFFF,
+        ] {
+            assert_eq!(encode_nan_f64(bits), want);
+        }
+
+        // Other values are unchanged.
+        assert_eq!(encode_nan_f32(1.5f32.to_bits())[1..5], 1.5f32.to_le_bytes());
+        assert_eq!(encode_nan_f64(1.5f64.to_bits())[1..9], 1.5f64.to_le_bytes());
+    }
+
+    #[test]
+    #[cfg(feature = "canonical")]
+    fn map_entries_are_ordered() {
+        use std::collections::HashMap;
+
+        let encode = |keys: &[u32]| {
+            let values = keys
+                .iter()
+                .map(|&key| (key, key.to_string()))
+                .collect::<HashMap<_, _>>();
+            let mut buf = Vec::new();
+            super::hash_map::encode(
+                uint32::encode,
+                uint32::encoded_len,
+                string::encode,
+                string::encoded_len,
+                1,
+                &values,
+                &mut buf,
+            );
+            buf
+        };
+        let want = encode(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(encode(&[8, 7, 6, 5, 4, 3, 2, 1]), want);
+        assert_eq!(encode(&[5, 1, 7, 3, 8, 2, 6, 4]), want);
+    }
+
+    #[test]
+    #[cfg(not(feature = "canonical"))]
+    fn nan_payload_is_preserved() {
+        assert_ne!(encode_nan_f32(0x7FC0_0000), encode_nan_f32(0x7F80_0001));
+        assert_ne!(
+            encode_nan_f64(0x7FF8_0000_0000_0000),
+            encode_nan_f64(0x7FF0_0000_0000_0001)
+        );
+    }
+
     #[test]
     fn varint() {
         fn check(value: u64, encoded: &[u8]) {
@@ -1338,6 +3803,95 @@ mod test {
         );
     }
 
+    #[test]
+    fn skip_each_wire_type() {
+        let mut buf = Vec::new();
+        int64::encode(1, &-1, &mut buf);
+        fixed64::encode(2, &7, &mut buf);
+        string::encode(3, &"skipped".to_owned(), &mut buf);
+        fixed32::encode(5, &9, &mut buf);
+        // A group holding a varint and a nested group.
+        encode_key(6, WireType::StartGroup, &mut buf);
+        uint32::encode(1, &300, &mut buf);
+        encode_key(7, WireType::StartGroup, &mut buf);
+        bytes::encode(1, &vec![1, 2, 3], &mut buf);
+        encode_key(7, WireType::EndGroup, &mut buf);
+        encode_key(6, WireType::EndGroup, &mut buf);
+        uint32::encode(8, &42, &mut buf);
+
+        let mut buf = &buf[..];
+        for &(expected_tag, expected_wire_type) in &[
+            (1, WireType::Varint),
+            (2, WireType::SixtyFourBit),
+            (3, WireType::LengthDelimited),
+            (5, WireType::ThirtyTwoBit),
+            (6, WireType::StartGroup),
+        ] {
+            let (tag, wire_type) = decode_key(&mut buf).unwrap();
+            assert_eq!((tag, wire_type), (expected_tag, expected_wire_type));
+            skip_field(wire_type, tag, &mut buf, 0).unwrap();
+        }
+
+        let (tag, wire_type) = decode_key(&mut buf).unwrap();
+        assert_eq!(tag, 8);
+        let mut value = 0;
+        uint32::merge(wire_type, &mut value, &mut buf).unwrap();
+        assert_eq!(value, 42);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn skip_invalid_groups() {
+        // Mismatched end group tag.
+        let mut buf = Vec::new();
+        encode_key(1, WireType::StartGroup, &mut buf);
+        encode_key(2, WireType::EndGroup, &mut buf);
+        let mut buf = &buf[1..];
+        assert!(skip_field(WireType::StartGroup, 1, &mut buf, 0).is_err());
+
+        // Unterminated group.
+        let mut buf = Vec::new();
+        uint32::encode(1, &1, &mut buf);
+        let mut buf = &buf[..];
+        assert!(skip_field(WireType::StartGroup, 1, &mut buf, 0).is_err());
+
+        // Stray end group.
+        let mut buf = &[][..];
+        assert!(skip_field(WireType::EndGroup, 1, &mut buf, 0).is_err());
+
+        // Groups nested past the recursion limit.
+        let mut buf = Vec::new();
+        for _ in 0..=RECURSION_LIMIT + 1 {
+            encode_key(1, WireType::StartGroup, &mut buf);
+        }
+        for _ in 0..=RECURSION_LIMIT + 1 {
+            encode_key(1, WireType::EndGroup, &mut buf);
+        }
+        let mut buf = &buf[1..];
+        let error = skip_field(WireType::StartGroup, 1, &mut buf, 0).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "failed to decode Protobuf message: recursion limit reached"
+        );
+    }
+
+    #[test]
+    fn crc32_check_values() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414F_A339
+        );
+    }
+
+    #[test]
+    fn fnv1a64_check_values() {
+        assert_eq!(fnv1a64(b""), 0xCBF2_9CE4_8422_2325);
+        assert_eq!(fnv1a64(b"a"), 0xAF63_DC4C_8601_EC8C);
+        assert_eq!(fnv1a64(b"foobar"), 0x8594_4171_F739_67E8);
+    }
+
     /// This big bowl o' macro soup generates a quickcheck encoding test for each
     /// combination of map type, scalar map key, and value type.
     /// TODO: these tests take a long time to compile, can this be improved?
@@ -1387,8 +3941,8 @@ mod test {
                                                                     buf)
                                               },
                                               |wire_type, values, buf| {
-                                                  check_wire_type(WireType::LengthDelimited, wire_type)?;
-                                                  $mod_name::merge($key_proto::merge,
+                                                  $mod_name::merge(wire_type,
+                                                                   $key_proto::merge,
                                                                    $val_proto::merge,
                                                                    values,
                                                                    buf)
@@ -1,10 +1,12 @@
-//! Protobuf encoding and decoding errors.
+//! Protobuf encoding, decoding and validation errors.
 
 use std::borrow::Cow;
 use std::error;
 use std::fmt;
 use std::io;
 
+use encoding::WireType;
+
 /// A Protobuf message decoding error.
 ///
 /// `DecodeError` indicates that the input buffer does not contain a valid
@@ -14,10 +16,52 @@ use std::io;
 pub struct DecodeError {
     /// A 'best effort' root cause description.
     description: Cow<'static, str>,
-    /// A stack of (message, field) name pairs, which identify the specific
-    /// message type and field where decoding failed. The stack contains an
-    /// entry per level of nesting.
-    stack: Vec<(&'static str, &'static str)>,
+    /// A stack of locations which identify the specific message type, field
+    /// and repeated element where decoding failed. The stack is built as the
+    /// error propagates, so the innermost location comes first.
+    stack: Vec<Location>,
+    /// Whether the buffer ended in the middle of a field, rather than holding malformed data.
+    incomplete: bool,
+    /// The number of additional bytes needed to decode the field, if known.
+    needed: Option<usize>,
+    /// The tag of the innermost field which failed to decode, if it is a field of a message.
+    tag: Option<u32>,
+    /// The kind of error, for errors which callers may match on.
+    kind: Option<Kind>,
+}
+
+/// The kinds of decoding error which are exposed through accessors, rather than only through the
+/// description.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    /// A singular field which was repeated in the input, as rejected by
+    /// `DecodeOptions::reject_duplicate_singular`, with its tag.
+    DuplicateField(u32),
+    /// The CRC32 of a frame read by `Message::decode_framed` did not match its body.
+    ChecksumMismatch,
+    /// The tag and value of a `closed` enumeration field whose value is not a variant of the
+    /// enumeration.
+    UnknownEnumValue(u32, i32),
+    /// The value of a field with a `max_len` attribute, or the decompressed value of a `zstd`
+    /// field, was longer than the maximum.
+    TooLong(usize),
+    /// A string value was not UTF-8, with the offset of its first invalid byte.
+    NotUtf8(usize),
+    /// The value of a `checked` field was outside its `range`.
+    OutOfRange,
+    /// The value of an `ascii` field was not ASCII, with the offset of its first non-ASCII byte.
+    NotAscii(usize),
+    /// A field was encoded with the wrong wire type, with the expected and actual wire types.
+    UnexpectedWireType(WireType, WireType),
+}
+
+/// A single step in the path to the value which failed to decode.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Location {
+    /// A (message, field) name pair.
+    Field(&'static str, &'static str),
+    /// The index of an element in a repeated field.
+    Index(usize),
 }
 
 impl DecodeError {
@@ -32,6 +76,34 @@ impl DecodeError {
         DecodeError {
             description: description.into(),
             stack: Vec::new(),
+            incomplete: false,
+            needed: None,
+            tag: None,
+            kind: None,
+        }
+    }
+
+    /// Creates a new `DecodeError` for a buffer which ends in the middle of a field, with the
+    /// number of additional bytes needed to decode the field, if known.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    pub fn incomplete<S>(description: S, needed: Option<usize>) -> DecodeError
+    where
+        S: Into<Cow<'static, str>>,
+    {
+        DecodeError {
+            incomplete: true,
+            needed,
+            ..DecodeError::new(description)
+        }
+    }
+
+    /// Creates a new `DecodeError` for a singular field whose tag appears more than once.
+    pub(crate) fn duplicate_field(tag: u32) -> DecodeError {
+        DecodeError {
+            kind: Some(Kind::DuplicateField(tag)),
+            ..DecodeError::new(format!("duplicate singular field: tag {}", tag))
         }
     }
 
@@ -40,20 +112,238 @@ impl DecodeError {
     /// Meant to be used only by `Message` implementations.
     #[doc(hidden)]
     pub fn push(&mut self, message: &'static str, field: &'static str) {
-        self.stack.push((message, field));
+        self.stack.push(Location::Field(message, field));
+    }
+
+    /// Pushes the index of the repeated field element being decoded on to the
+    /// location stack.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    pub fn push_index(&mut self, index: usize) {
+        self.stack.push(Location::Index(index));
+    }
+
+    /// Records the tag of the field being decoded, unless the error was already recorded by a
+    /// field nested within it.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    pub fn set_tag(&mut self, tag: u32) {
+        self.tag.get_or_insert(tag);
+    }
+
+    /// Creates a new `DecodeError` for a frame whose body does not match its CRC32.
+    pub(crate) fn checksum_mismatch(expected: u32, actual: u32) -> DecodeError {
+        DecodeError {
+            kind: Some(Kind::ChecksumMismatch),
+            ..DecodeError::new(format!(
+                "checksum mismatch: expected CRC32 {:08x}, got {:08x}",
+                expected, actual
+            ))
+        }
+    }
+
+    /// Creates a new `DecodeError` for a value of a `closed` enumeration field which is not a
+    /// variant of the enumeration.
+    pub(crate) fn unknown_enum(tag: u32, value: i32) -> DecodeError {
+        DecodeError {
+            kind: Some(Kind::UnknownEnumValue(tag, value)),
+            ..DecodeError::new(format!(
+                "unknown enumeration value {} for tag {}",
+                value, tag
+            ))
+        }
+    }
+
+    /// Creates a new `DecodeError` for a value of a field with a `max_len` attribute which is
+    /// longer than `max_len` bytes.
+    pub(crate) fn too_long(len: u64, max_len: usize) -> DecodeError {
+        DecodeError {
+            kind: Some(Kind::TooLong(max_len)),
+            ..DecodeError::new(format!(
+                "field too long: length {} exceeds maximum of {}",
+                len, max_len
+            ))
+        }
+    }
+
+    /// Creates a new `DecodeError` for a string value which is not UTF-8, with the offset of its
+    /// first invalid byte.
+    pub(crate) fn not_utf8(offset: usize) -> DecodeError {
+        DecodeError {
+            kind: Some(Kind::NotUtf8(offset)),
+            ..DecodeError::new(format!(
+                "invalid string value: data is not UTF-8 encoded (invalid byte at offset {})",
+                offset
+            ))
+        }
+    }
+
+    /// Creates a new `DecodeError` for a value of a `checked` field which is outside its `range`.
+    pub(crate) fn out_of_range(description: String) -> DecodeError {
+        DecodeError {
+            kind: Some(Kind::OutOfRange),
+            ..DecodeError::new(description)
+        }
+    }
+
+    /// Creates a new `DecodeError` for a value of an `ascii` field which is not ASCII, with the
+    /// offset of its first non-ASCII byte.
+    pub(crate) fn not_ascii(offset: usize) -> DecodeError {
+        DecodeError {
+            kind: Some(Kind::NotAscii(offset)),
+            ..DecodeError::new(format!(
+                "invalid string value: non-ASCII byte at offset {}",
+                offset
+            ))
+        }
+    }
+
+    /// Creates a new `DecodeError` for a compressed value whose decompressed value is longer than
+    /// `max_len` bytes.
+    #[cfg(feature = "zstd")]
+    pub(crate) fn too_large(max_len: usize) -> DecodeError {
+        DecodeError {
+            kind: Some(Kind::TooLong(max_len)),
+            ..DecodeError::new(format!(
+                "decompressed value too large: exceeds maximum of {} bytes",
+                max_len
+            ))
+        }
+    }
+
+    /// Creates a new `DecodeError` for a field encoded with the wire type `actual`, rather than
+    /// the `expected` wire type of the field.
+    pub(crate) fn wire_type_mismatch(expected: WireType, actual: WireType) -> DecodeError {
+        DecodeError {
+            kind: Some(Kind::UnexpectedWireType(expected, actual)),
+            ..DecodeError::new(format!(
+                "invalid wire type: {:?} (expected {:?})",
+                actual, expected
+            ))
+        }
+    }
+
+    /// Returns `true` if the buffer ended in the middle of a field, so that decoding may succeed
+    /// once more input is available. Other errors indicate that the input is malformed.
+    pub fn is_incomplete(&self) -> bool {
+        self.incomplete
+    }
+
+    /// Returns the number of additional bytes needed to decode the truncated field, if the error
+    /// is incomplete and the number is known.
+    pub fn needed(&self) -> Option<usize> {
+        self.needed
+    }
+
+    /// Returns the tag of the singular field which appeared more than once, if decoding with
+    /// `DecodeOptions::reject_duplicate_singular` failed because of it.
+    pub fn duplicate_tag(&self) -> Option<u32> {
+        match self.kind {
+            Some(Kind::DuplicateField(tag)) => Some(tag),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if a frame read by `Message::decode_framed` was corrupted, i.e. the CRC32
+    /// of its body did not match the checksum which follows it.
+    pub fn is_checksum_mismatch(&self) -> bool {
+        self.kind == Some(Kind::ChecksumMismatch)
+    }
+
+    /// Returns the tag and value of the field if decoding failed because the value of a `closed`
+    /// enumeration field was not a variant of the enumeration.
+    pub fn unknown_enum_value(&self) -> Option<(u32, i32)> {
+        match self.kind {
+            Some(Kind::UnknownEnumValue(tag, value)) => Some((tag, value)),
+            _ => None,
+        }
+    }
+
+    /// Returns the tag and maximum length of the field if decoding failed because the value of a
+    /// message field with a `max_len` attribute was longer than the maximum. For a `zstd` field,
+    /// the maximum applies to the decompressed value, and defaults to
+    /// `encoding::zstd_bytes::MAX_DECOMPRESSED_LEN`.
+    pub fn max_len_exceeded(&self) -> Option<(u32, usize)> {
+        match (self.tag, self.kind) {
+            (Some(tag), Some(Kind::TooLong(max_len))) => Some((tag, max_len)),
+            _ => None,
+        }
+    }
+
+    /// Returns the tag of the field and the offset of the first invalid byte in its value if
+    /// decoding failed because the value of a message's string field was not UTF-8.
+    pub fn invalid_utf8(&self) -> Option<(u32, usize)> {
+        match (self.tag, self.kind) {
+            (Some(tag), Some(Kind::NotUtf8(offset))) => Some((tag, offset)),
+            _ => None,
+        }
+    }
+
+    /// Returns the tag of the field if decoding failed because the value of a message's `checked`
+    /// field was outside its `range`.
+    pub fn out_of_range_tag(&self) -> Option<u32> {
+        match self.kind {
+            Some(Kind::OutOfRange) => self.tag,
+            _ => None,
+        }
+    }
+
+    /// Returns the tag of the field and the offset of the first non-ASCII byte in its value if
+    /// decoding failed because the value of a message's `ascii` field was not ASCII.
+    pub fn non_ascii_byte(&self) -> Option<(u32, usize)> {
+        match (self.tag, self.kind) {
+            (Some(tag), Some(Kind::NotAscii(offset))) => Some((tag, offset)),
+            _ => None,
+        }
+    }
+
+    /// Returns the tag of the field, the wire type it expects and the wire type it was encoded
+    /// with, if decoding failed because a field of a message was encoded with the wrong wire type.
+    pub fn unexpected_wire_type(&self) -> Option<(u32, WireType, WireType)> {
+        match (self.tag, self.kind) {
+            (Some(tag), Some(Kind::UnexpectedWireType(expected, actual))) => {
+                Some((tag, expected, actual))
+            }
+            _ => None,
+        }
+    }
+
+    /// Marks an incomplete error as malformed, for a truncated value within a length-delimited
+    /// field whose contents are all present, which more input could not complete.
+    pub(crate) fn into_malformed(mut self) -> DecodeError {
+        self.incomplete = false;
+        self.needed = None;
+        self
     }
 }
 
 impl fmt::Display for DecodeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.write_str("failed to decode Protobuf message: ")?;
-        for &(message, field) in &self.stack {
-            write!(f, "{}.{}: ", message, field)?;
-        }
+        fmt_stack(&self.stack, f)?;
         f.write_str(&self.description)
     }
 }
 
+/// Renders the path from the outermost message inwards, e.g. `Outer.field[2].subfield: `.
+fn fmt_stack(stack: &[Location], f: &mut fmt::Formatter) -> fmt::Result {
+    let mut root = true;
+    for location in stack.iter().rev() {
+        match *location {
+            Location::Field(message, field) if root => write!(f, "{}.{}", message, field)?,
+            Location::Field(_, field) => write!(f, ".{}", field)?,
+            Location::Index(index) => write!(f, "[{}]", index)?,
+        }
+        root = false;
+    }
+    if !stack.is_empty() {
+        f.write_str(": ")?;
+    }
+    Ok(())
+}
+
 impl error::Error for DecodeError {
     fn description(&self) -> &str {
         &self.description
@@ -66,6 +356,97 @@ impl From<DecodeError> for io::Error {
     }
 }
 
+/// A message validation error.
+///
+/// `ValidationError` indicates that a message is well formed, but holds a value which its
+/// definition does not allow, e.g. an empty required string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidationError {
+    /// A description of the invalid value.
+    description: Cow<'static, str>,
+    /// A stack of locations which identify the invalid field, innermost first.
+    stack: Vec<Location>,
+}
+
+impl ValidationError {
+    /// Creates a new `ValidationError` with a description of the invalid value.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    pub fn new<S>(description: S) -> ValidationError
+    where
+        S: Into<Cow<'static, str>>,
+    {
+        ValidationError {
+            description: description.into(),
+            stack: Vec::new(),
+        }
+    }
+
+    /// Pushes a (message, field) name location pair on to the location stack.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    pub fn push(&mut self, message: &'static str, field: &'static str) {
+        self.stack.push(Location::Field(message, field));
+    }
+
+    /// Pushes the index of the invalid repeated field element on to the location stack.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    pub fn push_index(&mut self, index: usize) {
+        self.stack.push(Location::Index(index));
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid Protobuf message: ")?;
+        fmt_stack(&self.stack, f)?;
+        f.write_str(&self.description)
+    }
+}
+
+impl error::Error for ValidationError {
+    fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+/// An error registering a message type with a `Registry`.
+///
+/// `RegistryError` indicates that the amino name or prefix of a message type is already
+/// registered, or that the type is not a registered message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegistryError {
+    description: Cow<'static, str>,
+}
+
+impl RegistryError {
+    pub(crate) fn new<S>(description: S) -> RegistryError
+    where
+        S: Into<Cow<'static, str>>,
+    {
+        RegistryError {
+            description: description.into(),
+        }
+    }
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("failed to register amino type: ")?;
+        f.write_str(&self.description)
+    }
+}
+
+impl error::Error for RegistryError {
+    fn description(&self) -> &str {
+        &self.description
+    }
+}
+
 /// A Protobuf message encoding error.
 ///
 /// `EncodeError` always indicates that a message failed to encode because the
@@ -99,7 +480,7 @@ impl EncodeError {
 
 impl fmt::Display for EncodeError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.write_str(self.to_string().as_ref())?;
+        f.write_str("failed to encode Protobuf message: insufficient buffer capacity")?;
         write!(
             f,
             " (required: {}, remaining: {})",
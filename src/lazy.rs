@@ -0,0 +1,208 @@
+//! Nested messages which are decoded on first access.
+
+use std::fmt;
+use std::sync::OnceLock;
+
+use bytes::{Buf, BufMut};
+
+use encoding::*;
+use DecodeError;
+use FieldDescriptor;
+use FieldVisitor;
+use Message;
+use ValidationError;
+
+/// A nested message which is held in its encoded form, and decoded when it is first accessed.
+///
+/// Fields with the `lazy` attribute hold a `Lazy<M>`. Decoding the enclosing message copies the
+/// encoded fields of the nested message without decoding them, and encoding it writes them back
+/// unchanged, so a message which is only passed along is never decoded. `get` decodes the
+/// message and caches it, and errors in the nested message are reported by `get` rather than by
+/// the decoding of the enclosing message.
+///
+/// Registered messages, whose encoding starts with their amino prefix, may not be held lazily.
+#[derive(Clone)]
+pub struct Lazy<M> {
+    raw: Vec<u8>,
+    decoded: OnceLock<M>,
+}
+
+impl<M> Lazy<M>
+where
+    M: Message + Default,
+{
+    /// Creates a `Lazy` holding the message, which is encoded up front.
+    pub fn new(msg: M) -> Lazy<M> {
+        let mut raw = Vec::with_capacity(msg.encoded_len());
+        msg.encode_raw(&mut raw);
+        Lazy {
+            raw,
+            decoded: OnceLock::from(msg),
+        }
+    }
+
+    /// Creates a `Lazy` holding the encoded fields of a message, which are decoded by `get`.
+    pub fn from_raw(raw: Vec<u8>) -> Lazy<M> {
+        Lazy {
+            raw,
+            decoded: OnceLock::new(),
+        }
+    }
+
+    /// Returns the encoded fields of the message.
+    pub fn raw(&self) -> &[u8] {
+        &self.raw
+    }
+
+    /// Returns `true` if the message has been decoded, by `get` or because it was created with
+    /// `new`.
+    pub fn is_decoded(&self) -> bool {
+        self.decoded.get().is_some()
+    }
+
+    /// Returns the message, decoding it if it has not been decoded yet.
+    pub fn get(&self) -> Result<&M, DecodeError> {
+        if let Some(msg) = self.decoded.get() {
+            return Ok(msg);
+        }
+        let msg = decode(&self.raw)?;
+        Ok(self.decoded.get_or_init(|| msg))
+    }
+
+    /// Replaces the message, which is encoded up front.
+    pub fn set(&mut self, msg: M) {
+        *self = Lazy::new(msg);
+    }
+
+    /// Returns the message, decoding it if it has not been decoded yet.
+    pub fn into_inner(self) -> Result<M, DecodeError> {
+        match self.decoded.into_inner() {
+            Some(msg) => Ok(msg),
+            None => decode(&self.raw),
+        }
+    }
+
+    /// Appends the encoded fields in `buf` to the message, discarding any decoded message.
+    pub(crate) fn merge_raw<B>(&mut self, buf: &mut B)
+    where
+        B: Buf,
+    {
+        self.decoded = OnceLock::new();
+        self.raw.reserve(buf.remaining());
+        while buf.has_remaining() {
+            let len = {
+                let chunk = buf.bytes();
+                self.raw.extend_from_slice(chunk);
+                chunk.len()
+            };
+            buf.advance(len);
+        }
+    }
+}
+
+impl<M> Default for Lazy<M> {
+    fn default() -> Lazy<M> {
+        Lazy {
+            raw: Vec::new(),
+            decoded: OnceLock::new(),
+        }
+    }
+}
+
+/// Lazy messages are equal if their encodings are equal.
+impl<M> PartialEq for Lazy<M> {
+    fn eq(&self, other: &Lazy<M>) -> bool {
+        self.raw == other.raw
+    }
+}
+
+impl<M> Eq for Lazy<M> {}
+
+impl<M> fmt::Debug for Lazy<M>
+where
+    M: fmt::Debug,
+{
+    /// Formats the decoded message, or the length of the encoding if it has not been decoded, so
+    /// that formatting does not decode the message.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.decoded.get() {
+            Some(msg) => f.debug_tuple("Lazy").field(msg).finish(),
+            None => f
+                .debug_struct("Lazy")
+                .field("len", &self.raw.len())
+                .finish(),
+        }
+    }
+}
+
+impl<M> Message for Lazy<M>
+where
+    M: Message + Default,
+{
+    fn encode_raw<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        buf.put_slice(&self.raw);
+    }
+    fn merge_field<B>(&mut self, buf: &mut B) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let (tag, wire_type) = decode_key(buf)?;
+        let mut field = Vec::new();
+        encode_key(tag, wire_type, &mut field);
+        copy_field(wire_type, tag, buf, &mut field, 0)?;
+        self.merge_raw(&mut &field[..]);
+        Ok(())
+    }
+    fn encoded_len(&self) -> usize {
+        self.raw.len()
+    }
+    fn is_empty(&self) -> bool {
+        self.raw.is_empty()
+    }
+    fn clear(&mut self) {
+        self.raw.clear();
+        self.decoded = OnceLock::new();
+    }
+    /// Merging encoded messages is the same as concatenating their encodings.
+    fn merge_from(&mut self, other: Lazy<M>) -> Result<(), DecodeError> {
+        self.merge_raw(&mut &other.raw[..]);
+        Ok(())
+    }
+    /// Decodes the message to validate it.
+    fn validate(&self) -> Result<(), ValidationError> {
+        match self.get() {
+            Ok(msg) => msg.validate(),
+            Err(error) => Err(ValidationError::new(format!(
+                "lazy message does not decode: {}",
+                error
+            ))),
+        }
+    }
+    fn descriptor() -> &'static [FieldDescriptor] {
+        M::descriptor()
+    }
+    /// Decodes the message to visit its fields. A message which does not decode has no fields.
+    fn visit_fields<V>(&self, visitor: &mut V)
+    where
+        V: FieldVisitor,
+    {
+        if let Ok(msg) = self.get() {
+            msg.visit_fields(visitor);
+        }
+    }
+}
+
+fn decode<M>(raw: &[u8]) -> Result<M, DecodeError>
+where
+    M: Message + Default,
+{
+    if !M::amino_prefix().is_empty() {
+        return Err(DecodeError::new(
+            "registered messages may not be decoded lazily",
+        ));
+    }
+    M::decode(raw)
+}
@@ -9,16 +9,41 @@ pub use bytes;
 #[cfg(test)]
 #[macro_use]
 extern crate quickcheck;
+#[cfg(feature = "json")]
+pub extern crate serde_json;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(feature = "zstd")]
+extern crate zstd;
 
+#[cfg(feature = "tokio")]
+mod async_decoder;
+mod decoder;
+mod descriptor;
+mod duration;
 pub mod error;
+mod lazy;
 mod message;
+mod options;
+mod registry;
 mod types;
+mod visit;
 
 #[doc(hidden)]
 pub mod encoding;
 
-pub use error::{DecodeError, EncodeError};
-pub use message::Message;
+#[cfg(feature = "tokio")]
+pub use async_decoder::{decode_async, DecodeAsync};
+pub use decoder::Decoder;
+pub use descriptor::{FieldDescriptor, Label, ParsedDefault};
+pub use duration::Duration;
+pub use encoding::{decode_raw, VarintCodec, WireType};
+pub use error::{DecodeError, EncodeError, RegistryError, ValidationError};
+pub use lazy::Lazy;
+pub use message::{AminoMessage, Message};
+pub use options::DecodeOptions;
+pub use registry::{Constructor, Registry};
+pub use visit::{FieldVisitor, ScalarValue};
 
 use bytes::{Buf, BufMut};
 
@@ -60,7 +85,8 @@ pub fn length_delimiter_len(length: usize) -> usize {
 /// An error may be returned in two cases:
 ///
 ///  * If the supplied buffer contains fewer than 10 bytes, then an error indicates that more
-///    input is required to decode the full delimiter.
+///    input is required to decode the full delimiter, and `DecodeError::is_incomplete` returns
+///    `true`.
 ///  * If the supplied buffer contains more than 10 bytes, then the buffer contains an invalid
 ///    delimiter, and typically the buffer should be considered corrupt.
 pub fn decode_length_delimiter<B>(mut buf: B) -> Result<usize, DecodeError>
@@ -1,46 +1,85 @@
+use std::any::Any;
 use std::fmt::Debug;
 use std::usize;
 
-use bytes::{Buf, BufMut};
+use bytes::{Buf, BufMut, Bytes};
 
+use options::Restricted;
 use DecodeError;
+use DecodeOptions;
 use EncodeError;
+use FieldDescriptor;
+use FieldVisitor;
+use ValidationError;
 
-use crate::encoding::{encode_varint, encoded_len_varint, message, WireType};
+use crate::encoding::{
+    crc32, decode_varint, encode_normalized, encode_varint, encoded_len_varint, fnv1a64, message,
+    underflow, WireType,
+};
 /// A Protocol Buffers message.
 pub trait Message: Debug + Send + Sync {
-    /// Encodes the message to a buffer.
+    /// Encodes the fields of the message to a buffer, without checking its capacity. Unlike
+    /// `encode_length_delimited`, no length delimiter is written, except by registered messages,
+    /// whose encoding is always a length delimiter, the amino prefix and then the fields.
     ///
-    /// This method will panic if the buffer has insufficient capacity.
+    /// This is the building block of the `encode` methods, for use by libraries which frame
+    /// messages themselves. `encoded_len` returns the number of bytes written, not counting the
+    /// length delimiter of a registered message.
     ///
-    /// Meant to be used only by `Message` implementations.
-    #[doc(hidden)]
+    /// This method will panic if the buffer has insufficient capacity.
     fn encode_raw<B>(&self, buf: &mut B)
     where
         B: BufMut,
         Self: Sized;
 
-    /// Decodes a field from a buffer, and merges it into `self`.
+    /// Decodes a single field, i.e. a key and its value, from a buffer, and merges it into `self`.
+    /// Fields with unknown tags are skipped.
     ///
-    /// Meant to be used only by `Message` implementations.
-    #[doc(hidden)]
+    /// Calling this method until the buffer is empty, and then `finish_merge`, is equivalent to
+    /// `merge`, except for registered messages, whose `merge` first reads the length delimiter
+    /// and amino prefix written by `encode_raw`.
     fn merge_field<B>(&mut self, buf: &mut B) -> Result<(), DecodeError>
     where
         B: Buf,
         Self: Sized;
 
+    /// Decodes a field from a `Bytes`, and merges it into `self`. The values of `shared` fields
+    /// are views of the buffer rather than copies.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    fn merge_field_shared(&mut self, buf: &mut Bytes) -> Result<(), DecodeError>
+    where
+        Self: Sized,
+    {
+        self.merge_field(buf)
+    }
+
+    /// Completes a merge once each field has been merged by `merge_field`, e.g. by dropping the
+    /// duplicate values of `dedup` fields, including those of flattened messages.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    fn finish_merge(&mut self) {}
+
     /// Returns the encoded length of the message without a length delimiter.
     fn encoded_len(&self) -> usize;
 
+    /// Returns `true` if every field of the message holds its default value.
+    fn is_empty(&self) -> bool {
+        self.encoded_len() == 0
+    }
+
     /// Encodes the message to a buffer.
     ///
-    /// An error will be returned if the buffer does not have sufficient capacity.
+    /// An error will be returned if the buffer does not have sufficient capacity, including room
+    /// for the length delimiter of a registered message.
     fn encode<B>(&self, buf: &mut B) -> Result<(), EncodeError>
     where
         B: BufMut,
         Self: Sized,
     {
-        let required = self.encoded_len();
+        let required = written_len(self);
         let remaining = buf.remaining_mut();
         if required > buf.remaining_mut() {
             return Err(EncodeError::new(required, remaining));
@@ -50,9 +89,69 @@ pub trait Message: Debug + Send + Sync {
         Ok(())
     }
 
+    /// Encodes the message to a newly allocated buffer.
+    fn encode_to_vec(&self) -> Vec<u8>
+    where
+        Self: Sized,
+    {
+        let len = written_len(self);
+        let mut buf = Vec::with_capacity(len);
+        self.encode_raw(&mut buf);
+        debug_assert_eq!(buf.len(), len, "encoded length of {:?}", self);
+        buf
+    }
+
+    /// Returns a hash of the encoding of the message, e.g. to detect that a cached message has
+    /// changed. The hash is stable across builds and platforms, but is not cryptographic.
+    ///
+    /// The message is encoded by `encode_normalized`, so that equal messages have equal
+    /// checksums even if their `HashMap` fields iterate in different orders, or a float holds
+    /// `-0.0` in one and `0.0` in the other.
+    fn checksum(&self) -> u64
+    where
+        Self: Sized,
+    {
+        fnv1a64(&encode_normalized(self))
+    }
+
+    /// Encodes the message to the end of a `Vec`, reserving its encoded length up front.
+    ///
+    /// `encode` can not reserve capacity through `BufMut`, so a `Vec` grows as the fields are
+    /// written, and is reallocated many times for a large message. This method reallocates it at
+    /// most once.
+    fn encode_append(&self, buf: &mut Vec<u8>)
+    where
+        Self: Sized,
+    {
+        let len = written_len(self);
+        buf.reserve(len);
+        let start = buf.len();
+        self.encode_raw(buf);
+        debug_assert_eq!(buf.len() - start, len, "encoded length of {:?}", self);
+    }
+
+    /// Encodes the message to the start of a slice, returning the number of bytes written.
+    ///
+    /// An error will be returned if the slice is too short to hold the encoded message.
+    fn encode_to_slice(&self, out: &mut [u8]) -> Result<usize, EncodeError>
+    where
+        Self: Sized,
+    {
+        let required = written_len(self);
+        if required > out.len() {
+            return Err(EncodeError::new(required, out.len()));
+        }
+
+        let mut buf = &mut out[..required];
+        self.encode_raw(&mut buf);
+        debug_assert!(buf.is_empty(), "encoded length of {:?}", self);
+        Ok(required)
+    }
+
     /// Encodes the message with a length-delimiter to a buffer.
     ///
-    /// An error will be returned if the buffer does not have sufficient capacity.
+    /// A registered message is written as by `encode`, which already precedes it with a length
+    /// delimiter. An error will be returned if the buffer does not have sufficient capacity.
     fn encode_length_delimited<B>(&self, buf: &mut B) -> Result<(), EncodeError>
     where
         B: BufMut,
@@ -64,11 +163,60 @@ pub trait Message: Debug + Send + Sync {
         if required > remaining {
             return Err(EncodeError::new(required, remaining));
         }
-        encode_varint(len as u64, buf);
+        if Self::amino_prefix().is_empty() {
+            encode_varint(len as u64, buf);
+        }
         self.encode_raw(buf);
         Ok(())
     }
 
+    /// Encodes the message as a frame of a self-delimiting stream: a length delimiter, the
+    /// message as written by `encode`, and the CRC32 of the message in little-endian byte order.
+    ///
+    /// An error will be returned if the buffer does not have sufficient capacity.
+    fn encode_framed<B>(&self, buf: &mut B) -> Result<(), EncodeError>
+    where
+        B: BufMut,
+        Self: Sized,
+    {
+        let len = written_len(self);
+        let required = encoded_len_varint(len as u64) + len + 4;
+        let remaining = buf.remaining_mut();
+        if required > remaining {
+            return Err(EncodeError::new(required, remaining));
+        }
+        let body = self.encode_to_vec();
+        encode_varint(len as u64, buf);
+        buf.put_slice(&body);
+        buf.put_u32_le(crc32(&body));
+        Ok(())
+    }
+
+    /// Decodes a frame written by `encode_framed`, checking the CRC32 of the message before it is
+    /// decoded. A corrupted frame results in an error for which
+    /// `DecodeError::is_checksum_mismatch` returns `true`.
+    ///
+    /// Exactly the bytes of the frame are consumed, so a buffer passed by `&mut` is left at the
+    /// start of the next frame.
+    fn decode_framed<B>(mut buf: B) -> Result<Self, DecodeError>
+    where
+        B: Buf,
+        Self: Default,
+    {
+        let len = decode_varint(&mut buf)?;
+        if len.saturating_add(4) > buf.remaining() as u64 {
+            return Err(underflow(len.saturating_add(4), buf.remaining()));
+        }
+        let mut body = vec![0; len as usize];
+        buf.copy_to_slice(&mut body);
+        let expected = buf.get_u32_le();
+        let actual = crc32(&body);
+        if actual != expected {
+            return Err(DecodeError::checksum_mismatch(expected, actual));
+        }
+        Self::decode(&body[..])
+    }
+
     /// Decodes an instance of the message from a buffer.
     ///
     /// The entire buffer will be consumed.
@@ -81,6 +229,64 @@ pub trait Message: Debug + Send + Sync {
         Self::merge(&mut message, &mut buf).map(|_| message)
     }
 
+    /// Decodes an instance of the message from a buffer, as restricted by `options`.
+    ///
+    /// Top-level fields whose tags are not in `options.allowed_tags` are skipped, as if they were
+    /// unknown, so a message type may be reused to accept only a subset of its fields. Nested
+    /// messages of allowed fields are decoded in full. With `options.reject_duplicate_singular`,
+    /// a top-level singular field which appears more than once is an error.
+    fn decode_with_options<B>(mut buf: B, options: &DecodeOptions) -> Result<Self, DecodeError>
+    where
+        B: Buf,
+        Self: Default,
+    {
+        let mut message = Self::default();
+        if options.allowed_tags.is_none() && !options.reject_duplicate_singular {
+            return message.merge(buf).map(|()| message);
+        }
+        let mut restricted = Restricted {
+            message: &mut message,
+            allowed_tags: options.allowed_tags,
+            singular_tags: if options.reject_duplicate_singular {
+                Some(Vec::new())
+            } else {
+                None
+            },
+        };
+        // Registered messages are framed by a length delimiter and their prefix.
+        let registered = !Self::amino_prefix().is_empty();
+        while buf.has_remaining() {
+            if registered {
+                message::merge(WireType::LengthDelimited, &mut restricted, &mut buf)?;
+            } else {
+                restricted.merge_field(&mut buf)?;
+            }
+        }
+        if !registered {
+            restricted.finish_merge();
+        }
+        Ok(message)
+    }
+
+    /// Decodes an instance of the message from a `Bytes`, e.g. a view of a memory mapped file.
+    ///
+    /// The values of `shared` bytes fields, including those of nested messages, share the
+    /// buffer instead of copying it. Registered messages are decoded with `decode`.
+    fn decode_from_bytes(mut buf: Bytes) -> Result<Self, DecodeError>
+    where
+        Self: Default,
+    {
+        if !Self::amino_prefix().is_empty() {
+            return Self::decode(buf);
+        }
+        let mut message = Self::default();
+        while buf.has_remaining() {
+            message.merge_field_shared(&mut buf)?;
+        }
+        message.finish_merge();
+        Ok(message)
+    }
+
     /// Decodes a length-delimited instance of the message from the buffer.
     fn decode_length_delimited<B>(buf: B) -> Result<Self, DecodeError>
     where
@@ -104,11 +310,16 @@ pub trait Message: Debug + Send + Sync {
         while buf.has_remaining() {
             self.merge_field(&mut buf)?;
         }
+        self.finish_merge();
         Ok(())
     }
 
     /// Decodes a length-delimited instance of the message from buffer, and
     /// merges it into `self`.
+    ///
+    /// Exactly the number of bytes given by the length delimiter are consumed, so a buffer passed
+    /// by `&mut` is left at the start of whatever follows the message, e.g. the next message of a
+    /// stream written by `encode_length_delimited`.
     fn merge_length_delimited<B>(&mut self, mut buf: B) -> Result<(), DecodeError>
     where
         B: Buf,
@@ -118,7 +329,134 @@ pub trait Message: Debug + Send + Sync {
     }
 
     /// Clears the message, resetting all fields to their default.
+    ///
+    /// `string`, `bytes`, repeated and map fields are emptied in place, keeping their capacity.
     fn clear(&mut self);
+
+    /// Clears the message, and then decodes an instance of the message from a buffer into it.
+    ///
+    /// Like `decode`, but reuses the capacity of the fields of `self`, e.g. when decoding many
+    /// messages in a loop. If an error is returned, the message holds the fields decoded so far.
+    fn clear_and_decode<B>(&mut self, buf: B) -> Result<(), DecodeError>
+    where
+        B: Buf,
+        Self: Sized,
+    {
+        self.clear();
+        self.merge(buf)
+    }
+
+    /// Merges `other` into `self`, following protobuf merge semantics: set scalar fields
+    /// overwrite, repeated fields and maps are concatenated, and nested messages are merged.
+    ///
+    /// The default implementation encodes `other` and merges the encoded bytes, which fails if
+    /// `other` holds a value which does not decode, e.g. a value outside the range of a `checked`
+    /// field. The derived implementation merges the fields directly, and only fails if such a
+    /// nested message fails to merge.
+    fn merge_from(&mut self, other: Self) -> Result<(), DecodeError>
+    where
+        Self: Sized,
+    {
+        let mut buf = Vec::with_capacity(other.encoded_len());
+        other.encode_raw(&mut buf);
+        self.merge(&buf[..])
+    }
+
+    /// Checks that the fields of the message hold values which its definition allows, e.g. that
+    /// required `string` and `bytes` fields are not empty. Nested messages are validated too.
+    ///
+    /// Validation is independent of encoding: an invalid message still encodes and decodes.
+    fn validate(&self) -> Result<(), ValidationError> {
+        Ok(())
+    }
+
+    /// Returns the amino prefix of a registered message, or an empty slice if the message is not
+    /// registered.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    fn amino_prefix() -> &'static [u8]
+    where
+        Self: Sized,
+    {
+        &[]
+    }
+
+    /// Returns metadata describing each field of the message, in tag order.
+    fn descriptor() -> &'static [FieldDescriptor]
+    where
+        Self: Sized,
+    {
+        &[]
+    }
+
+    /// Passes the value of each field of the message to `visitor`, in tag order, without encoding
+    /// the message.
+    fn visit_fields<V>(&self, visitor: &mut V)
+    where
+        V: FieldVisitor,
+        Self: Sized,
+    {
+        let _ = visitor;
+    }
+
+    /// Returns the tag, name and encoded length of each field of the message, in the order in
+    /// which the fields are encoded, to find which fields make the encoding large.
+    ///
+    /// The lengths include keys, length delimiters and amino prefixes, and sum to `encoded_len`.
+    /// The amino prefix of a registered message is returned with tag 0 and the name
+    /// `amino_prefix`, a flattened message returns each of its fields, and a oneof returns its set
+    /// variant, named by the variant. Messages which are not derived return no fields.
+    fn size_breakdown(&self) -> Vec<(u32, &'static str, usize)> {
+        Vec::new()
+    }
+}
+
+/// Returns the number of bytes written by `encode_raw`, which includes the length delimiter of a
+/// registered message.
+fn written_len<M>(msg: &M) -> usize
+where
+    M: Message,
+{
+    let len = msg.encoded_len();
+    if M::amino_prefix().is_empty() {
+        len
+    } else {
+        len + encoded_len_varint(len as u64)
+    }
+}
+
+/// A message which can be downcast to its concrete type, e.g. when held as a
+/// `Box<dyn AminoMessage>` after decoding a registered message.
+///
+/// Implemented for every `'static` message.
+pub trait AminoMessage: Message + Any {
+    /// Returns the message as `Any`, to be downcast with `downcast_ref`.
+    fn as_any(&self) -> &dyn Any;
+
+    /// Returns the amino prefix of the message's type, or an empty slice if it is not registered.
+    fn registered_prefix(&self) -> &'static [u8];
+
+    /// Decodes an instance of the message from a buffer, and merges it into `self`, like
+    /// `Message::merge`.
+    fn merge_dyn(&mut self, buf: &mut dyn Buf) -> Result<(), DecodeError>;
+}
+
+impl<M> AminoMessage for M
+where
+    M: Message + Any,
+{
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn registered_prefix(&self) -> &'static [u8] {
+        M::amino_prefix()
+    }
+
+    fn merge_dyn(&mut self, buf: &mut dyn Buf) -> Result<(), DecodeError> {
+        self.merge(buf)
+    }
 }
 
 impl<M> Message for Box<M>
@@ -137,10 +475,40 @@ where
     {
         (**self).merge_field(buf)
     }
+    fn merge_field_shared(&mut self, buf: &mut Bytes) -> Result<(), DecodeError> {
+        (**self).merge_field_shared(buf)
+    }
+    fn finish_merge(&mut self) {
+        (**self).finish_merge()
+    }
     fn encoded_len(&self) -> usize {
         (**self).encoded_len()
     }
+    fn is_empty(&self) -> bool {
+        (**self).is_empty()
+    }
     fn clear(&mut self) {
         (**self).clear()
     }
+    fn merge_from(&mut self, other: Self) -> Result<(), DecodeError> {
+        (**self).merge_from(*other)
+    }
+    fn validate(&self) -> Result<(), ValidationError> {
+        (**self).validate()
+    }
+    fn amino_prefix() -> &'static [u8] {
+        M::amino_prefix()
+    }
+    fn descriptor() -> &'static [FieldDescriptor] {
+        M::descriptor()
+    }
+    fn visit_fields<V>(&self, visitor: &mut V)
+    where
+        V: FieldVisitor,
+    {
+        (**self).visit_fields(visitor)
+    }
+    fn size_breakdown(&self) -> Vec<(u32, &'static str, usize)> {
+        (**self).size_breakdown()
+    }
 }
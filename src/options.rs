@@ -0,0 +1,87 @@
+//! Options which restrict how a message is decoded.
+
+use bytes::{Buf, BufMut};
+
+use encoding::{decode_key, merge_field_with_key, skip_field};
+use DecodeError;
+use Label;
+use Message;
+
+/// Options for `Message::decode_with_options`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DecodeOptions<'a> {
+    /// The tags of the top-level fields which may be decoded, or `None` to decode every field.
+    ///
+    /// Fields with other tags are skipped as if they were unknown, even if the message defines
+    /// them, so they keep their default values.
+    pub allowed_tags: Option<&'a [u32]>,
+    /// Whether to fail if a top-level singular field appears more than once, instead of keeping
+    /// its last value.
+    ///
+    /// Singular fields are those which are not repeated, packed or maps in `Message::descriptor`,
+    /// including each variant of a oneof. The error's `duplicate_tag` is the tag of the repeated
+    /// field. Fields of nested and flattened messages are not checked.
+    pub reject_duplicate_singular: bool,
+}
+
+/// A message whose fields are merged as restricted by `DecodeOptions`. Used to decode a message
+/// with `decode_with_options`, including the framing of a registered message.
+#[derive(Debug)]
+pub(crate) struct Restricted<'a, M> {
+    pub message: &'a mut M,
+    pub allowed_tags: Option<&'a [u32]>,
+    /// The tags of the singular fields merged so far, or `None` if duplicates are allowed.
+    pub singular_tags: Option<Vec<u32>>,
+}
+
+impl<'a, M> Message for Restricted<'a, M>
+where
+    M: Message,
+{
+    fn encode_raw<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        self.message.encode_raw(buf)
+    }
+
+    fn merge_field<B>(&mut self, buf: &mut B) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let (tag, wire_type) = decode_key(buf)?;
+        if let Some(allowed_tags) = self.allowed_tags {
+            if !allowed_tags.contains(&tag) {
+                return skip_field(wire_type, tag, buf, 0);
+            }
+        }
+        if let Some(ref mut singular_tags) = self.singular_tags {
+            let singular = M::descriptor()
+                .iter()
+                .any(|field| field.tag == tag && field.label != Label::Repeated);
+            if singular {
+                if singular_tags.contains(&tag) {
+                    return Err(DecodeError::duplicate_field(tag));
+                }
+                singular_tags.push(tag);
+            }
+        }
+        merge_field_with_key(tag, wire_type, self.message, buf)
+    }
+
+    fn finish_merge(&mut self) {
+        self.message.finish_merge()
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.message.encoded_len()
+    }
+
+    fn clear(&mut self) {
+        self.message.clear()
+    }
+
+    fn amino_prefix() -> &'static [u8] {
+        M::amino_prefix()
+    }
+}
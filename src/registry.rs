@@ -0,0 +1,146 @@
+//! A registry of message types, which decodes registered messages by their amino prefix.
+
+use std::collections::HashMap;
+
+use bytes::Buf;
+
+use encoding::{message, WireType, AMINO_PREFIX_LEN};
+use AminoMessage;
+use DecodeError;
+use RegistryError;
+
+/// Creates an empty instance of a registered message type.
+pub type Constructor = fn() -> Box<dyn AminoMessage>;
+
+/// Registered message types, by amino prefix.
+///
+/// Used to decode a registered message whose type is known only by the prefix on the wire, e.g.
+/// a transaction holding one of many message types. Types are usually registered all at once
+/// with the `register_amino_types!` macro.
+#[derive(Debug, Default)]
+pub struct Registry {
+    types: HashMap<[u8; AMINO_PREFIX_LEN], (&'static str, Constructor)>,
+}
+
+impl Registry {
+    /// Creates an empty registry.
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    /// Creates a registry of the message types created by the constructors, by amino name.
+    ///
+    /// An error is returned if two types have the same name or prefix, or if a type is not a
+    /// registered message.
+    pub fn from_entries(
+        entries: &[(&'static str, Constructor)],
+    ) -> Result<Registry, RegistryError> {
+        let mut registry = Registry::new();
+        for &(name, constructor) in entries {
+            registry.register(name, constructor)?;
+        }
+        Ok(registry)
+    }
+
+    /// Registers the message type created by the constructor with its amino name.
+    ///
+    /// An error is returned if the name or the prefix of the type is already registered, or if
+    /// the type is not a registered message.
+    pub fn register(
+        &mut self,
+        name: &'static str,
+        constructor: Constructor,
+    ) -> Result<(), RegistryError> {
+        let registered_prefix = constructor().registered_prefix();
+        if registered_prefix.len() != AMINO_PREFIX_LEN {
+            return Err(RegistryError::new(format!(
+                "{} is not a registered message",
+                name
+            )));
+        }
+        if self.types.values().any(|&(other, _)| other == name) {
+            return Err(RegistryError::new(format!(
+                "{} is already registered",
+                name
+            )));
+        }
+        let mut prefix = [0; AMINO_PREFIX_LEN];
+        prefix.copy_from_slice(registered_prefix);
+        if let Some(&(other, _)) = self.types.get(&prefix) {
+            return Err(RegistryError::new(format!(
+                "{} has the same prefix {:02x?} as {}",
+                name, prefix, other
+            )));
+        }
+        self.types.insert(prefix, (name, constructor));
+        Ok(())
+    }
+
+    /// Returns the amino name of the message type registered with the prefix, if any.
+    pub fn name(&self, prefix: &[u8]) -> Option<&'static str> {
+        if prefix.len() != AMINO_PREFIX_LEN {
+            return None;
+        }
+        let mut key = [0; AMINO_PREFIX_LEN];
+        key.copy_from_slice(prefix);
+        self.types.get(&key).map(|&(name, _)| name)
+    }
+
+    /// Returns the number of registered message types.
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+
+    /// Returns `true` if no message types are registered.
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+
+    /// Decodes a registered message, as written by `Message::encode`, whose type is given by the
+    /// prefix which follows its length delimiter.
+    ///
+    /// The entire buffer will be consumed. The decoded message may be downcast to its type with
+    /// `AminoMessage::as_any`.
+    pub fn decode<B>(&self, mut buf: B) -> Result<Box<dyn AminoMessage>, DecodeError>
+    where
+        B: Buf,
+    {
+        let prefix = message::peek_prefix(WireType::LengthDelimited, &buf)?;
+        let constructor = match self.types.get(&prefix) {
+            Some(&(_, constructor)) => constructor,
+            None => {
+                return Err(DecodeError::new(format!(
+                    "unknown amino prefix {:02x?}",
+                    prefix
+                )))
+            }
+        };
+        let mut msg = constructor();
+        msg.merge_dyn(&mut buf)?;
+        Ok(msg)
+    }
+}
+
+/// Creates a `Registry` of message types by amino name, returning a
+/// `Result<Registry, RegistryError>`.
+///
+/// ```ignore
+/// let registry = register_amino_types! {
+///     "cosmos-sdk/MsgSend" => MsgSend,
+///     "cosmos-sdk/MsgDelegate" => MsgDelegate,
+/// }?;
+/// ```
+#[macro_export]
+macro_rules! register_amino_types {
+    ($($name:expr => $ty:ty),* $(,)*) => {
+        $crate::Registry::from_entries(&[
+            $((
+                $name,
+                (|| {
+                    ::std::boxed::Box::new(<$ty as ::std::default::Default>::default())
+                        as ::std::boxed::Box<dyn $crate::AminoMessage>
+                }) as $crate::Constructor,
+            )),*
+        ])
+    };
+}
@@ -0,0 +1,50 @@
+//! Structured access to the fields of a `Message`, without encoding it.
+
+use Message;
+
+/// The value of a scalar field, as passed to `FieldVisitor::visit_scalar`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScalarValue<'a> {
+    /// The value of an `int32`, `int64`, `sint*` or `sfixed*` field, or of an `epoch` field in
+    /// nanoseconds since the unix epoch.
+    Int(i64),
+    /// The value of a `uint32`, `uint64` or `fixed*` field.
+    Uint(u64),
+    /// The value of a `float` or `double` field.
+    Float(f64),
+    /// The value of a `bool` field.
+    Bool(bool),
+    /// The value of a `string` field, or of a `json` field written as JSON.
+    Str(&'a str),
+    /// The value of a `bytes` field.
+    Bytes(&'a [u8]),
+    /// The value of an enumeration field, which may not be a known variant.
+    Enum(i32),
+}
+
+/// Receives the fields of a message from `Message::visit_fields`.
+///
+/// Fields are visited in tag order, by their tag and the name of the Rust struct field, whether
+/// or not they hold their default values. Each value of a repeated field is visited in turn, and
+/// unset optional fields are not visited. The fields of a flattened message are visited as fields
+/// of the enclosing message, and a set oneof is visited by its tag and the name of its variant.
+pub trait FieldVisitor {
+    /// Visits a value of a scalar field.
+    fn visit_scalar(&mut self, tag: u32, name: &'static str, value: ScalarValue);
+
+    /// Visits a nested message. By default, the fields of the message are visited in turn.
+    fn visit_message<M>(&mut self, tag: u32, name: &'static str, value: &M)
+    where
+        M: Message,
+        Self: Sized,
+    {
+        let _ = (tag, name);
+        value.visit_fields(self)
+    }
+
+    /// Visits the key of a map entry, which is followed by a visit of its value with the same
+    /// tag and name. Entries are visited in the iteration order of the map.
+    fn visit_map_key(&mut self, tag: u32, name: &'static str, key: ScalarValue) {
+        let _ = (tag, name, key);
+    }
+}
@@ -0,0 +1,50 @@
+//! Tests for the deprecated accessors generated for the former names of renamed fields.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Validator {
+    #[prost_amino(string, tag = "1", alias = "name")]
+    pub moniker: String,
+    #[prost_amino(int64, tag = "2", alias = "power", alias = "votes")]
+    pub voting_power: i64,
+    #[prost_amino(enumeration = "Status", tag = "3", alias = "state")]
+    pub status: i32,
+    #[prost_amino(uint32, optional, tag = "4", alias = "rank")]
+    pub priority: Option<u32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Enumeration)]
+pub enum Status {
+    Active = 0,
+    Jailed = 1,
+}
+
+#[test]
+#[allow(deprecated)]
+fn aliased_accessors() {
+    let mut validator = Validator {
+        moniker: "node0".to_owned(),
+        voting_power: 10,
+        ..Validator::default()
+    };
+    assert_eq!(validator.name(), "node0");
+    assert_eq!(*validator.power(), 10);
+    *validator.votes_mut() += 1;
+    validator.name_mut().push('!');
+    assert_eq!(validator.moniker, "node0!");
+    assert_eq!(validator.voting_power, 11);
+
+    // The alias of a field with an accessor returns the value of the accessor.
+    validator.status = 1;
+    assert_eq!(validator.state(), Status::Jailed);
+    assert_eq!(validator.state(), validator.status());
+    assert_eq!(validator.rank(), 0);
+    *validator.rank_mut() = Some(3);
+    assert_eq!(validator.rank(), 3);
+
+    // Aliases do not change the encoding.
+    let mut buf = Vec::new();
+    validator.encode(&mut buf).unwrap();
+    assert_eq!(Validator::decode(&buf[..]).unwrap(), validator);
+}
@@ -0,0 +1,107 @@
+//! Tests for decoding with `DecodeOptions::allowed_tags`, which skips the fields of other tags.
+
+use prost::{DecodeOptions, Message};
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Inner {
+    #[prost_amino(int64, tag = "1")]
+    pub value: i64,
+    #[prost_amino(string, tag = "2")]
+    pub note: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Request {
+    #[prost_amino(string, tag = "1")]
+    pub name: String,
+    #[prost_amino(bytes, tag = "2")]
+    pub payload: Vec<u8>,
+    #[prost_amino(message, optional, tag = "3")]
+    pub inner: Option<Inner>,
+    #[prost_amino(int64, repeated, tag = "4")]
+    pub values: Vec<i64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[amino_name = "test/Registered"]
+pub struct Registered {
+    #[prost_amino(string, tag = "1")]
+    pub name: String,
+    #[prost_amino(uint64, tag = "2")]
+    pub admin_key: u64,
+}
+
+fn request() -> Request {
+    Request {
+        name: "alice".to_owned(),
+        payload: vec![1, 2, 3],
+        inner: Some(Inner {
+            value: 5,
+            note: "nested".to_owned(),
+        }),
+        values: vec![1, 2, 3],
+    }
+}
+
+#[test]
+fn disallowed_field_keeps_default() {
+    let msg = request();
+    let buf = msg.encode_to_vec();
+    let options = DecodeOptions {
+        allowed_tags: Some(&[1, 3]),
+        ..DecodeOptions::default()
+    };
+    let decoded = Request::decode_with_options(&buf[..], &options).unwrap();
+    assert_eq!(
+        decoded,
+        Request {
+            payload: Vec::new(),
+            values: Vec::new(),
+            ..msg
+        }
+    );
+}
+
+#[test]
+fn no_allowed_tags_decodes_every_field() {
+    let msg = request();
+    let buf = msg.encode_to_vec();
+    let decoded = Request::decode_with_options(&buf[..], &DecodeOptions::default()).unwrap();
+    assert_eq!(decoded, msg);
+
+    let options = DecodeOptions {
+        allowed_tags: Some(&[]),
+        ..DecodeOptions::default()
+    };
+    let decoded = Request::decode_with_options(&buf[..], &options).unwrap();
+    assert_eq!(decoded, Request::default());
+}
+
+#[test]
+fn disallowed_field_of_registered_message() {
+    let msg = Registered {
+        name: "bob".to_owned(),
+        admin_key: 7,
+    };
+    let buf = msg.encode_to_vec();
+    let options = DecodeOptions {
+        allowed_tags: Some(&[1]),
+        ..DecodeOptions::default()
+    };
+    let decoded = Registered::decode_with_options(&buf[..], &options).unwrap();
+    assert_eq!(decoded.name, "bob");
+    assert_eq!(decoded.admin_key, 0);
+}
+
+#[test]
+fn disallowed_field_is_still_checked() {
+    // A truncated disallowed field is an error, as it would be for an unknown field.
+    let mut buf = request().encode_to_vec();
+    buf.pop();
+    let options = DecodeOptions {
+        allowed_tags: Some(&[1]),
+        ..DecodeOptions::default()
+    };
+    let error = Request::decode_with_options(&buf[..], &options).unwrap_err();
+    assert!(error.is_incomplete());
+}
@@ -0,0 +1,54 @@
+//! Tests for `string` fields whose decoded values must be ASCII.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Coin {
+    #[prost_amino(string, ascii, tag = "1")]
+    pub denom: String,
+    #[prost_amino(string, optional, ascii, tag = "2")]
+    pub issuer: Option<String>,
+    #[prost_amino(string, repeated, ascii, tag = "3")]
+    pub aliases: Vec<String>,
+    #[prost_amino(string, tag = "4")]
+    pub description: String,
+}
+
+#[test]
+fn ascii_strings_are_decoded() {
+    let msg = Coin {
+        denom: "uatom".to_owned(),
+        issuer: Some("cosmos".to_owned()),
+        aliases: vec!["atom".to_owned(), "ATOM".to_owned()],
+        description: "Ατομ".to_owned(),
+    };
+    let decoded = Coin::decode(&msg.encode_to_vec()[..]).unwrap();
+    assert_eq!(decoded, msg);
+}
+
+#[test]
+fn multibyte_string_is_rejected() {
+    let msg = Coin {
+        denom: "µatom".to_owned(),
+        ..Coin::default()
+    };
+    let error = Coin::decode(&msg.encode_to_vec()[..]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "failed to decode Protobuf message: Coin.denom: \
+         invalid string value: non-ASCII byte at offset 0"
+    );
+    assert_eq!(error.non_ascii_byte(), Some((1, 0)));
+
+    let msg = Coin {
+        aliases: vec!["atom".to_owned(), "at°m".to_owned()],
+        ..Coin::default()
+    };
+    let error = Coin::decode(&msg.encode_to_vec()[..]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "failed to decode Protobuf message: Coin.aliases[1]: \
+         invalid string value: non-ASCII byte at offset 2"
+    );
+    assert_eq!(error.non_ascii_byte(), Some((3, 2)));
+}
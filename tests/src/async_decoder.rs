@@ -0,0 +1,117 @@
+//! Tests for `decode_async`, which decodes length-delimited messages from a Tokio `AsyncRead`.
+
+use std::io::ErrorKind;
+use std::thread;
+
+use tokio::io::{duplex, AsyncWriteExt};
+use tokio::runtime::{Builder, Runtime};
+
+use prost::{decode_async, Message};
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Block {
+    #[prost_amino(int64, tag = "1")]
+    pub height: i64,
+    #[prost_amino(string, tag = "2")]
+    pub hash: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[amino_name = "test/Vote"]
+pub struct Vote {
+    #[prost_amino(int64, tag = "1")]
+    pub height: i64,
+}
+
+fn runtime() -> Runtime {
+    Builder::new_current_thread().build().unwrap()
+}
+
+fn blocks() -> Vec<Block> {
+    (0..5)
+        .map(|height| Block {
+            height,
+            hash: "x".repeat(height as usize * 100),
+        })
+        .collect()
+}
+
+#[test]
+fn decodes_messages_streamed_over_a_pipe() {
+    let blocks = blocks();
+    let mut buf = Vec::new();
+    for block in &blocks {
+        block.encode_length_delimited(&mut buf).unwrap();
+    }
+
+    // The pipe holds only a few bytes, so the frames arrive in many partial reads.
+    let (mut reader, mut writer) = duplex(7);
+    let sender = thread::spawn(move || {
+        runtime().block_on(writer.write_all(&buf)).unwrap();
+    });
+
+    let runtime = runtime();
+    for block in &blocks {
+        let decoded: Block = runtime.block_on(decode_async(&mut reader)).unwrap();
+        assert_eq!(&decoded, block);
+    }
+    sender.join().unwrap();
+
+    let err = runtime
+        .block_on(decode_async::<Block, _>(&mut reader))
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn truncated_frame() {
+    let mut buf = Vec::new();
+    blocks()[3].encode_length_delimited(&mut buf).unwrap();
+    buf.truncate(buf.len() - 1);
+
+    let (mut reader, mut writer) = duplex(buf.len());
+    let runtime = runtime();
+    runtime.block_on(writer.write_all(&buf)).unwrap();
+    drop(writer);
+
+    let err = runtime
+        .block_on(decode_async::<Block, _>(&mut reader))
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn oversized_frame() {
+    let (mut reader, mut writer) = duplex(16);
+    let runtime = runtime();
+    // A length delimiter of 1 GiB, without any of the frame.
+    runtime
+        .block_on(writer.write_all(&[0x80, 0x80, 0x80, 0x80, 0x04]))
+        .unwrap();
+
+    let err = runtime
+        .block_on(decode_async::<Block, _>(&mut reader))
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn decodes_registered_messages() {
+    let votes = (1..4).map(|height| Vote { height }).collect::<Vec<_>>();
+    let mut buf = Vec::new();
+    for vote in &votes {
+        vote.encode_length_delimited(&mut buf).unwrap();
+    }
+
+    let (mut reader, mut writer) = duplex(7);
+    let sender = thread::spawn(move || {
+        runtime().block_on(writer.write_all(&buf)).unwrap();
+    });
+
+    let runtime = runtime();
+    for vote in &votes {
+        let decoded: Vote = runtime.block_on(decode_async(&mut reader)).unwrap();
+        assert_eq!(&decoded, vote);
+    }
+    sender.join().unwrap();
+}
@@ -0,0 +1,121 @@
+//! Tests for messages with the `bit_eq` attribute, whose `PartialEq` compares floats by their
+//! bits.
+
+use std::collections::{BTreeMap, HashMap};
+use std::f64;
+
+use prost::Message;
+
+#[derive(Clone, Message)]
+#[prost_amino(bit_eq)]
+pub struct Sample {
+    #[prost_amino(double, tag = "1")]
+    pub value: f64,
+    #[prost_amino(float, optional, tag = "2")]
+    pub weight: Option<f32>,
+    #[prost_amino(double, repeated, tag = "3")]
+    pub history: Vec<f64>,
+    #[prost_amino(string, tag = "4")]
+    pub name: String,
+}
+
+fn sample() -> Sample {
+    Sample {
+        value: f64::NAN,
+        weight: Some(::std::f32::NAN),
+        history: vec![1.5, f64::NAN],
+        name: "sensor".to_owned(),
+    }
+}
+
+#[test]
+fn nan_messages_are_equal() {
+    assert_eq!(sample(), sample());
+    let decoded = Sample::decode(&sample().encode_to_vec()[..]).unwrap();
+    #[cfg(not(feature = "canonical"))]
+    assert_eq!(decoded, sample());
+    // The `canonical` feature changes the bits of a NaN when it is encoded, so a decoded
+    // message is only equal to itself.
+    #[cfg(feature = "canonical")]
+    assert_eq!(decoded, decoded.clone());
+}
+
+#[test]
+fn other_fields_are_compared() {
+    let mut other = sample();
+    other.name = "other".to_owned();
+    assert_ne!(sample(), other);
+
+    let mut other = sample();
+    other.history.push(2.0);
+    assert_ne!(sample(), other);
+
+    let mut other = sample();
+    other.weight = None;
+    assert_ne!(sample(), other);
+}
+
+#[test]
+fn signed_zeros_differ() {
+    let zero = Sample {
+        value: 0.0,
+        ..Sample::default()
+    };
+    let negative_zero = Sample {
+        value: -0.0,
+        ..Sample::default()
+    };
+    assert_ne!(zero, negative_zero);
+}
+
+#[derive(Clone, Oneof)]
+#[prost_amino(bit_eq)]
+pub enum Reading {
+    #[prost_amino(double, tag = "3")]
+    Value(f64),
+    #[prost_amino(string, tag = "4")]
+    Label(String),
+}
+
+#[derive(Clone, Message)]
+#[prost_amino(bit_eq)]
+pub struct Report {
+    #[prost_amino(map = "string, double", tag = "1")]
+    pub totals: HashMap<String, f64>,
+    #[prost_amino(btree_map = "uint32, float", tag = "2")]
+    pub scores: BTreeMap<u32, f32>,
+    #[prost_amino(oneof = "Reading", tags = "3, 4")]
+    pub reading: Option<Reading>,
+    #[prost_amino(message, tag = "5")]
+    pub sample: Option<Sample>,
+}
+
+fn report() -> Report {
+    Report {
+        totals: vec![("a".to_owned(), f64::NAN)].into_iter().collect(),
+        scores: vec![(1, ::std::f32::NAN)].into_iter().collect(),
+        reading: Some(Reading::Value(f64::NAN)),
+        sample: Some(sample()),
+    }
+}
+
+#[test]
+fn nested_nan_fields_are_equal() {
+    assert_eq!(report(), report());
+
+    let mut other = report();
+    other.totals.insert("b".to_owned(), 1.0);
+    assert_ne!(report(), other);
+
+    let mut other = report();
+    other.scores.insert(1, 0.5);
+    assert_ne!(report(), other);
+
+    let mut other = report();
+    other.reading = Some(Reading::Label("a".to_owned()));
+    assert_ne!(report(), other);
+
+    let mut other = report();
+    other.sample.as_mut().unwrap().name = "other".to_owned();
+    assert_ne!(report(), other);
+}
@@ -0,0 +1,66 @@
+//! Tests for the builders generated for messages with the `builder` attribute.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Header {
+    #[prost_amino(string, tag = "1")]
+    pub chain_id: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[prost_amino(builder)]
+pub struct Block {
+    #[prost_amino(int64, tag = "1")]
+    pub height: i64,
+    #[prost_amino(uint32, required, tag = "2")]
+    pub round: u32,
+    #[prost_amino(int32, optional, tag = "3")]
+    pub index: Option<i32>,
+    #[prost_amino(string, tag = "4", default = "amino")]
+    pub proposer: String,
+    #[prost_amino(bytes, repeated, tag = "5")]
+    pub txs: Vec<Vec<u8>>,
+    #[prost_amino(message, tag = "6")]
+    pub header: Option<Header>,
+}
+
+#[test]
+fn builder_matches_struct_literal() {
+    let header = Header {
+        chain_id: "test-chain".to_owned(),
+    };
+    let built = Block::builder()
+        .height(7)
+        .round(2)
+        .index(3)
+        .proposer("validator".to_owned())
+        .txs(vec![vec![1, 2], vec![3]])
+        .header(header.clone())
+        .build();
+    let literal = Block {
+        height: 7,
+        round: 2,
+        index: Some(3),
+        proposer: "validator".to_owned(),
+        txs: vec![vec![1, 2], vec![3]],
+        header: Some(header),
+    };
+    assert_eq!(built, literal);
+    assert_eq!(built.encode_to_vec(), literal.encode_to_vec());
+}
+
+#[test]
+fn builder_fills_defaults() {
+    let built = Block::builder().height(1).build();
+    assert_eq!(built.proposer, "amino");
+    assert_eq!(built.index, None);
+    assert_eq!(
+        built,
+        Block {
+            height: 1,
+            ..Block::default()
+        }
+    );
+    assert_eq!(Block::builder().build(), Block::default());
+}
@@ -0,0 +1,79 @@
+//! Tests for `Message::checksum`, a stable hash of the encoding of a message.
+
+use std::collections::{BTreeMap, HashMap};
+
+use prost::encoding::fnv1a64;
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Inventory {
+    #[prost_amino(string, tag = "1")]
+    pub owner: String,
+    #[prost_amino(btree_map = "string, uint32", tag = "2")]
+    pub sorted: BTreeMap<String, u32>,
+    #[prost_amino(map = "string, uint32", tag = "3")]
+    pub items: HashMap<String, u32>,
+}
+
+const ITEMS: &[(&str, u32)] = &[
+    ("apple", 3),
+    ("banana", 12),
+    ("cherry", 7),
+    ("date", 1),
+    ("elderberry", 40),
+    ("fig", 2),
+    ("grape", 99),
+    ("honeydew", 5),
+];
+
+fn inventory<'a, I>(items: I) -> Inventory
+where
+    I: Iterator<Item = &'a (&'static str, u32)> + Clone,
+{
+    Inventory {
+        owner: "stall".to_owned(),
+        sorted: items
+            .clone()
+            .map(|&(name, count)| (name.to_owned(), count))
+            .collect(),
+        items: items
+            .map(|&(name, count)| (name.to_owned(), count))
+            .collect(),
+    }
+}
+
+#[test]
+fn checksum_hashes_the_encoding() {
+    let inventory = inventory(ITEMS.iter().take(1));
+    assert_eq!(inventory.checksum(), fnv1a64(&inventory.encode_to_vec()));
+}
+
+#[test]
+fn different_messages_have_different_checksums() {
+    let a = inventory(ITEMS.iter());
+    let mut b = a.clone();
+    b.owner = "market".to_owned();
+    assert_ne!(a.checksum(), b.checksum());
+}
+
+#[test]
+fn empty_message_checksum_is_stable() {
+    assert_eq!(Inventory::default().checksum(), 0xCBF2_9CE4_8422_2325);
+}
+
+#[test]
+fn maps_built_in_different_orders_have_equal_checksums() {
+    let forward = inventory(ITEMS.iter());
+    let reversed = inventory(ITEMS.iter().rev());
+    assert_eq!(forward, reversed);
+    assert_eq!(forward.checksum(), reversed.checksum());
+
+    // Maps which are filled in a different order, and whose hashers have different keys,
+    // iterate in different orders.
+    let mut grown = inventory(ITEMS.iter().take(2));
+    for &(name, count) in &ITEMS[2..] {
+        grown.items.insert(name.to_owned(), count);
+        grown.sorted.insert(name.to_owned(), count);
+    }
+    assert_eq!(grown.checksum(), forward.checksum());
+}
@@ -0,0 +1,79 @@
+//! Tests for packed repeated fields with the `chunk` attribute, which are encoded as several
+//! length-delimited blocks of the same tag.
+
+use prost::encoding::{decode_key, decode_varint, WireType};
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Chunked {
+    #[prost_amino(int64, repeated, chunk = 100, tag = "1")]
+    pub values: Vec<i64>,
+    #[prost_amino(fixed32, repeated, chunk = 100, tag = "2")]
+    pub ids: Vec<u32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Unchunked {
+    #[prost_amino(int64, repeated, tag = "1")]
+    pub values: Vec<i64>,
+    #[prost_amino(fixed32, repeated, tag = "2")]
+    pub ids: Vec<u32>,
+}
+
+/// Returns the number of values in each packed block of the encoded message.
+fn block_lengths(mut buf: &[u8], width: Option<usize>) -> Vec<(u32, usize)> {
+    let mut blocks = Vec::new();
+    while !buf.is_empty() {
+        let (tag, wire_type) = decode_key(&mut buf).unwrap();
+        assert_eq!(wire_type, WireType::LengthDelimited);
+        let len = decode_varint(&mut buf).unwrap() as usize;
+        let (block, rest) = buf.split_at(len);
+        let count = match (tag, width) {
+            (2, Some(width)) => len / width,
+            _ => block.iter().filter(|&&byte| byte < 0x80).count(),
+        };
+        blocks.push((tag, count));
+        buf = rest;
+    }
+    blocks
+}
+
+#[test]
+fn chunked_packed_roundtrip() {
+    let msg = Chunked {
+        values: (-500..500).collect(),
+        ids: (0..1000).collect(),
+    };
+    let buf = msg.encode_to_vec();
+    assert_eq!(buf.len(), msg.encoded_len());
+
+    let blocks = block_lengths(&buf, Some(4));
+    assert_eq!(blocks.len(), 20);
+    assert!(blocks[..10].iter().all(|&block| block == (1, 100)));
+    assert!(blocks[10..].iter().all(|&block| block == (2, 100)));
+
+    assert_eq!(Chunked::decode(&buf[..]).unwrap(), msg);
+
+    // The chunks are concatenated, so the encoding is compatible with an unchunked field.
+    let unchunked = Unchunked::decode(&buf[..]).unwrap();
+    assert_eq!(unchunked.values, msg.values);
+    assert_eq!(unchunked.ids, msg.ids);
+    assert_eq!(
+        Chunked::decode(&unchunked.encode_to_vec()[..]).unwrap(),
+        msg
+    );
+}
+
+#[test]
+fn chunked_partial_block() {
+    let msg = Chunked {
+        values: (0..250).collect(),
+        ids: Vec::new(),
+    };
+    let buf = msg.encode_to_vec();
+    assert_eq!(buf.len(), msg.encoded_len());
+    assert_eq!(block_lengths(&buf, None), [(1, 100), (1, 100), (1, 50)]);
+    assert_eq!(Chunked::decode(&buf[..]).unwrap(), msg);
+
+    assert!(Chunked::default().encode_to_vec().is_empty());
+}
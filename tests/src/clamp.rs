@@ -0,0 +1,83 @@
+//! Tests for integer fields with a `clamp` range, whose decoded values are moved to the nearest
+//! bound of the range instead of being rejected.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Volume {
+    #[prost_amino(int64, clamp = "0..=100", tag = "1")]
+    pub level: i64,
+    #[prost_amino(sint32, optional, clamp = "-10..=10", tag = "2")]
+    pub balance: Option<i32>,
+    #[prost_amino(uint32, repeated, clamp = "1..=5", tag = "3")]
+    pub presets: Vec<u32>,
+}
+
+/// The same message without clamping, to encode out of range values.
+#[derive(Clone, PartialEq, Message)]
+pub struct RawVolume {
+    #[prost_amino(int64, tag = "1")]
+    pub level: i64,
+    #[prost_amino(sint32, optional, tag = "2")]
+    pub balance: Option<i32>,
+    #[prost_amino(uint32, repeated, tag = "3")]
+    pub presets: Vec<u32>,
+}
+
+fn decode(level: i64, balance: i32, presets: Vec<u32>) -> Volume {
+    let raw = RawVolume {
+        level,
+        balance: Some(balance),
+        presets,
+    };
+    Volume::decode(&raw.encode_to_vec()[..]).unwrap()
+}
+
+#[test]
+fn values_within_range_are_unchanged() {
+    let volume = decode(50, -3, vec![1, 3, 5]);
+    assert_eq!(volume.level, 50);
+    assert_eq!(volume.balance, Some(-3));
+    assert_eq!(volume.presets, [1, 3, 5]);
+
+    let volume = decode(0, 10, vec![]);
+    assert_eq!(volume.level, 0);
+    assert_eq!(volume.balance, Some(10));
+}
+
+#[test]
+fn values_below_range_are_raised_to_min() {
+    let volume = decode(-7, -11, vec![0, 2]);
+    assert_eq!(volume.level, 0);
+    assert_eq!(volume.balance, Some(-10));
+    assert_eq!(volume.presets, [1, 2]);
+
+    assert_eq!(decode(i64::MIN, i32::MIN, vec![]).level, 0);
+}
+
+#[test]
+fn values_above_range_are_lowered_to_max() {
+    let volume = decode(101, 1000, vec![6, u32::MAX]);
+    assert_eq!(volume.level, 100);
+    assert_eq!(volume.balance, Some(10));
+    assert_eq!(volume.presets, [5, 5]);
+}
+
+#[test]
+fn clamped_value_differs_from_wire() {
+    let raw = RawVolume {
+        level: 250,
+        ..RawVolume::default()
+    };
+    let volume = Volume::decode(&raw.encode_to_vec()[..]).unwrap();
+    assert_eq!(volume.level, 100);
+    // The clamped value is encoded, not the value which was decoded.
+    assert_ne!(volume.encode_to_vec(), raw.encode_to_vec());
+
+    // Values are only clamped when decoded.
+    let volume = Volume {
+        level: 250,
+        ..Volume::default()
+    };
+    assert_eq!(volume.encode_to_vec(), raw.encode_to_vec());
+}
@@ -0,0 +1,75 @@
+//! Tests for enumeration fields with the `closed` attribute, which fail to decode values which
+//! are not variants of the enumeration.
+
+use prost::encoding::{encode_key, encode_varint, WireType};
+use prost::Message;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Enumeration)]
+pub enum Status {
+    Pending = 0,
+    Active = 1,
+    Closed = 2,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Account {
+    #[prost_amino(enumeration = "Status", closed, tag = "1")]
+    pub status: i32,
+    #[prost_amino(enumeration = "Status", optional, closed, tag = "2")]
+    pub previous: Option<i32>,
+    #[prost_amino(enumeration = "Status", repeated, closed, tag = "3")]
+    pub history: Vec<i32>,
+    #[prost_amino(enumeration = "Status", tag = "4")]
+    pub open: i32,
+}
+
+fn varint_field(tag: u32, value: i32) -> Vec<u8> {
+    let mut buf = Vec::new();
+    encode_key(tag, WireType::Varint, &mut buf);
+    encode_varint(value as u64, &mut buf);
+    buf
+}
+
+#[test]
+fn known_values_decode() {
+    let account = Account {
+        status: Status::Active as i32,
+        previous: Some(Status::Pending as i32),
+        history: vec![Status::Pending as i32, Status::Closed as i32],
+        open: Status::Closed as i32,
+    };
+    let decoded = Account::decode(&account.encode_to_vec()[..]).unwrap();
+    assert_eq!(decoded, account);
+    assert_eq!(decoded.status(), Status::Active);
+}
+
+#[test]
+fn unknown_value_is_rejected() {
+    let error = Account::decode(&varint_field(1, 7)[..]).unwrap_err();
+    assert_eq!(error.unknown_enum_value(), Some((1, 7)));
+
+    let error = Account::decode(&varint_field(2, -1)[..]).unwrap_err();
+    assert_eq!(error.unknown_enum_value(), Some((2, -1)));
+}
+
+#[test]
+fn unknown_repeated_value_is_rejected() {
+    let account = Account {
+        history: vec![Status::Active as i32, 5],
+        ..Account::default()
+    };
+    let error = Account::decode(&account.encode_to_vec()[..]).unwrap_err();
+    assert_eq!(error.unknown_enum_value(), Some((3, 5)));
+}
+
+#[test]
+fn open_field_keeps_unknown_value() {
+    let account = Account::decode(&varint_field(4, 7)[..]).unwrap();
+    assert_eq!(account.open, 7);
+}
+
+#[test]
+fn other_errors_have_no_unknown_enum_value() {
+    let error = Account::decode(&[0x08][..]).unwrap_err();
+    assert_eq!(error.unknown_enum_value(), None);
+}
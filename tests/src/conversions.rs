@@ -0,0 +1,92 @@
+//! Tests for fields with an `as` attribute, which hold a std type in place of their Protobuf
+//! type.
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Peer {
+    #[prost_amino(fixed32, as = "Ipv4Addr", tag = "1")]
+    pub ip: Ipv4Addr,
+    #[prost_amino(uint32, as = "u16", tag = "2")]
+    pub port: u16,
+    #[prost_amino(sfixed32, optional, as = "Ipv4Addr", tag = "3")]
+    pub gateway: Option<Ipv4Addr>,
+    #[prost_amino(bytes, as = "Ipv6Addr", tag = "4")]
+    pub ipv6: Ipv6Addr,
+}
+
+/// The same message with the Protobuf types of its fields.
+#[derive(Clone, PartialEq, Message)]
+pub struct RawPeer {
+    #[prost_amino(fixed32, tag = "1")]
+    pub ip: u32,
+    #[prost_amino(uint32, tag = "2")]
+    pub port: u32,
+    #[prost_amino(sfixed32, optional, tag = "3")]
+    pub gateway: Option<i32>,
+    #[prost_amino(bytes, tag = "4")]
+    pub ipv6: Vec<u8>,
+}
+
+fn peer() -> Peer {
+    Peer {
+        ip: Ipv4Addr::new(192, 168, 1, 20),
+        port: 26656,
+        gateway: Some(Ipv4Addr::new(192, 168, 1, 1)),
+        ipv6: Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1),
+    }
+}
+
+#[test]
+fn ipv4_addr_round_trip() {
+    let peer = peer();
+    let decoded = Peer::decode(&peer.encode_to_vec()[..]).unwrap();
+    assert_eq!(decoded, peer);
+    assert_eq!(decoded.gateway(), Ipv4Addr::new(192, 168, 1, 1));
+}
+
+#[test]
+fn encoded_as_protobuf_type() {
+    let raw = RawPeer::decode(&peer().encode_to_vec()[..]).unwrap();
+    assert_eq!(raw.ip, 0xC0A8_0114);
+    assert_eq!(raw.port, 26656);
+    assert_eq!(raw.gateway, Some(0xC0A8_0101u32 as i32));
+    assert_eq!(
+        raw.ipv6,
+        vec![0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]
+    );
+}
+
+#[test]
+fn unspecified_addresses_are_not_encoded() {
+    let peer = Peer::default();
+    assert_eq!(peer.ip, Ipv4Addr::UNSPECIFIED);
+    assert_eq!(peer.ipv6, Ipv6Addr::UNSPECIFIED);
+    assert_eq!(peer.gateway(), Ipv4Addr::UNSPECIFIED);
+    assert_eq!(peer.encoded_len(), 0);
+    assert_eq!(Peer::decode(&[][..]).unwrap(), peer);
+}
+
+#[test]
+fn port_out_of_range() {
+    let raw = RawPeer {
+        port: 65536,
+        ..RawPeer::default()
+    };
+    let err = Peer::decode(&raw.encode_to_vec()[..]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "failed to decode Protobuf message: Peer.port: invalid u16: 65536 is out of range"
+    );
+}
+
+#[test]
+fn ipv6_addr_of_wrong_length() {
+    let raw = RawPeer {
+        ipv6: vec![0; 4],
+        ..RawPeer::default()
+    };
+    assert!(Peer::decode(&raw.encode_to_vec()[..]).is_err());
+}
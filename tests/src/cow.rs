@@ -0,0 +1,76 @@
+//! Tests for `cow` fields, which borrow from the decoded slice when possible.
+
+use std::borrow::Cow;
+
+use prost::bytes::buf::BufExt;
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Borrowing<'a> {
+    #[prost_amino(string, cow, tag = "1")]
+    pub name: Cow<'a, str>,
+    #[prost_amino(bytes, cow, tag = "2")]
+    pub data: Cow<'a, [u8]>,
+    #[prost_amino(uint32, tag = "3")]
+    pub count: u32,
+    #[prost_amino(string, cow, tag = "4", default = "none")]
+    pub label: Cow<'a, str>,
+}
+
+fn encoded() -> Vec<u8> {
+    let msg = Borrowing {
+        name: Cow::Borrowed("amino"),
+        data: Cow::Owned(vec![1, 2, 3]),
+        count: 7,
+        ..Borrowing::default()
+    };
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+    assert_eq!(msg.encoded_len(), buf.len());
+    buf
+}
+
+#[test]
+fn decode_borrowed_borrows_from_slice() {
+    let buf = encoded();
+    let msg = Borrowing::decode_borrowed(&buf).unwrap();
+    match msg.name {
+        Cow::Borrowed(name) => assert_eq!(name, "amino"),
+        Cow::Owned(_) => panic!("expected a borrowed string"),
+    }
+    match msg.data {
+        Cow::Borrowed(data) => assert_eq!(data, &[1, 2, 3]),
+        Cow::Owned(_) => panic!("expected borrowed bytes"),
+    }
+    assert_eq!(msg.count, 7);
+    assert_eq!(msg.label, "none");
+}
+
+#[test]
+fn decode_from_buf_owns() {
+    let buf = encoded();
+    // A chained buffer is not contiguous, so the decoded values must be copied.
+    let (head, tail) = buf.split_at(4);
+    let msg = Borrowing::decode(head.chain(tail)).unwrap();
+    match msg.name {
+        Cow::Owned(ref name) => assert_eq!(name, "amino"),
+        Cow::Borrowed(_) => panic!("expected an owned string"),
+    }
+    match msg.data {
+        Cow::Owned(ref data) => assert_eq!(data, &[1, 2, 3]),
+        Cow::Borrowed(_) => panic!("expected owned bytes"),
+    }
+    assert_eq!(msg, Borrowing::decode_borrowed(&buf).unwrap());
+}
+
+#[test]
+fn decode_borrowed_rejects_invalid_utf8() {
+    let buf = [0x0a, 0x01, 0xff];
+    let error = Borrowing::decode_borrowed(&buf).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "failed to decode Protobuf message: Borrowing.name: \
+         invalid string value: data is not UTF-8 encoded (invalid byte at offset 0)"
+    );
+    assert_eq!(error.invalid_utf8(), Some((1, 0)));
+}
@@ -0,0 +1,107 @@
+//! Tests for `decode_raw`, which decodes the fields of a message without its type.
+
+use prost::encoding::{encode_key, encode_varint};
+use prost::Message;
+use prost::{decode_raw, WireType};
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Inner {
+    #[prost_amino(string, tag = "1")]
+    pub name: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Sample {
+    #[prost_amino(uint64, tag = "1")]
+    pub id: u64,
+    #[prost_amino(fixed32, tag = "2")]
+    pub checksum: u32,
+    #[prost_amino(sfixed64, tag = "3")]
+    pub offset: i64,
+    #[prost_amino(message, optional, tag = "4")]
+    pub inner: Option<Inner>,
+    #[prost_amino(string, repeated, tag = "5")]
+    pub labels: Vec<String>,
+}
+
+#[test]
+fn fields_are_decoded_in_order() {
+    let sample = Sample {
+        id: 300,
+        checksum: 0x0102_0304,
+        offset: -1,
+        inner: Some(Inner {
+            name: "a".to_owned(),
+        }),
+        labels: vec![],
+    };
+    let fields = decode_raw(&sample.encode_to_vec()[..]).unwrap();
+    assert_eq!(
+        fields,
+        vec![
+            (1, WireType::Varint, vec![0xac, 0x02]),
+            (2, WireType::ThirtyTwoBit, vec![0x04, 0x03, 0x02, 0x01]),
+            (3, WireType::SixtyFourBit, vec![0xff; 8]),
+            (
+                4,
+                WireType::LengthDelimited,
+                Inner::encode_to_vec(&Inner {
+                    name: "a".to_owned(),
+                })
+            ),
+        ]
+    );
+}
+
+#[test]
+fn repeated_tags_appear_once_per_value() {
+    let sample = Sample {
+        labels: vec!["x".to_owned(), "".to_owned(), "yz".to_owned()],
+        ..Sample::default()
+    };
+    let fields = decode_raw(&sample.encode_to_vec()[..]).unwrap();
+    assert_eq!(
+        fields,
+        vec![
+            (5, WireType::LengthDelimited, b"x".to_vec()),
+            (5, WireType::LengthDelimited, vec![]),
+            (5, WireType::LengthDelimited, b"yz".to_vec()),
+        ]
+    );
+}
+
+#[test]
+fn groups_hold_their_inner_fields() {
+    let mut buf = Vec::new();
+    encode_key(7, WireType::StartGroup, &mut buf);
+    encode_key(1, WireType::Varint, &mut buf);
+    encode_varint(5, &mut buf);
+    encode_key(7, WireType::EndGroup, &mut buf);
+    encode_key(8, WireType::Varint, &mut buf);
+    encode_varint(1, &mut buf);
+
+    let fields = decode_raw(&buf[..]).unwrap();
+    assert_eq!(
+        fields,
+        vec![
+            (7, WireType::StartGroup, vec![0x08, 0x05]),
+            (8, WireType::Varint, vec![0x01]),
+        ]
+    );
+}
+
+#[test]
+fn empty_message_has_no_fields() {
+    assert_eq!(decode_raw(&[][..]).unwrap(), vec![]);
+}
+
+#[test]
+fn truncated_field_is_an_error() {
+    let mut buf = Sample {
+        labels: vec!["label".to_owned()],
+        ..Sample::default()
+    }
+    .encode_to_vec();
+    buf.pop();
+    assert!(decode_raw(&buf[..]).is_err());
+}
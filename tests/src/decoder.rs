@@ -0,0 +1,130 @@
+//! Tests for `Decoder`, which decodes a stream of length-delimited messages from a reader.
+
+use std::io::{Cursor, ErrorKind};
+
+use prost::{Decoder, Message};
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Block {
+    #[prost_amino(int64, tag = "1")]
+    pub height: i64,
+    #[prost_amino(string, tag = "2")]
+    pub hash: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[amino_name = "test/Vote"]
+pub struct Vote {
+    #[prost_amino(int64, tag = "1")]
+    pub height: i64,
+}
+
+fn stream(blocks: &[Block]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for block in blocks {
+        block.encode_length_delimited(&mut buf).unwrap();
+    }
+    buf
+}
+
+#[test]
+fn decodes_each_message() {
+    let blocks = (0..5)
+        .map(|height| Block {
+            height,
+            hash: "x".repeat(height as usize * 100),
+        })
+        .collect::<Vec<_>>();
+    let mut reader = Cursor::new(stream(&blocks));
+
+    let mut decoder = Decoder::default();
+    for block in &blocks {
+        assert_eq!(
+            decoder
+                .decode_next::<Block, _>(&mut reader)
+                .unwrap()
+                .as_ref(),
+            Some(block)
+        );
+    }
+    assert_eq!(decoder.decode_next::<Block, _>(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn empty_messages() {
+    let mut reader = Cursor::new(stream(&[Block::default(), Block::default()]));
+    let mut decoder = Decoder::default();
+    assert_eq!(
+        decoder.decode_next::<Block, _>(&mut reader).unwrap(),
+        Some(Block::default())
+    );
+    assert_eq!(
+        decoder.decode_next::<Block, _>(&mut reader).unwrap(),
+        Some(Block::default())
+    );
+    assert_eq!(decoder.decode_next::<Block, _>(&mut reader).unwrap(), None);
+}
+
+#[test]
+fn truncated_frame() {
+    let mut buf = stream(&[Block {
+        height: 1,
+        hash: "abc".to_owned(),
+    }]);
+    buf.pop();
+    let error = Decoder::default()
+        .decode_next::<Block, _>(&mut Cursor::new(buf))
+        .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn truncated_length_delimiter() {
+    let error = Decoder::default()
+        .decode_next::<Block, _>(&mut Cursor::new(vec![0x80]))
+        .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn frame_too_long() {
+    let buf = stream(&[Block {
+        height: 1,
+        hash: "x".repeat(100),
+    }]);
+    let mut decoder = Decoder::new(16);
+    let error = decoder
+        .decode_next::<Block, _>(&mut Cursor::new(buf))
+        .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn invalid_message() {
+    let error = Decoder::default()
+        .decode_next::<Block, _>(&mut Cursor::new(vec![0x01, 0x08]))
+        .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn decodes_registered_messages() {
+    let votes = (1..4).map(|height| Vote { height }).collect::<Vec<_>>();
+    let mut buf = Vec::new();
+    for vote in &votes {
+        vote.encode_length_delimited(&mut buf).unwrap();
+    }
+    let mut reader = Cursor::new(buf);
+
+    let mut decoder = Decoder::default();
+    for vote in &votes {
+        assert_eq!(
+            decoder
+                .decode_next::<Vote, _>(&mut reader)
+                .unwrap()
+                .as_ref(),
+            Some(vote)
+        );
+    }
+    assert_eq!(decoder.decode_next::<Vote, _>(&mut reader).unwrap(), None);
+}
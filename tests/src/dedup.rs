@@ -0,0 +1,130 @@
+//! Tests for repeated fields with the `dedup` attribute, whose duplicate values are dropped when
+//! decoding.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Peers {
+    #[prost_amino(string, repeated, dedup, tag = "1")]
+    pub ids: Vec<String>,
+    #[prost_amino(uint32, repeated, dedup, tag = "2")]
+    pub ports: Vec<u32>,
+    #[prost_amino(int64, repeated, dedup, clamp = "0..=10", tag = "3")]
+    pub scores: Vec<i64>,
+}
+
+/// The same message without deduplication, to encode duplicate values.
+#[derive(Clone, PartialEq, Message)]
+pub struct RawPeers {
+    #[prost_amino(string, repeated, tag = "1")]
+    pub ids: Vec<String>,
+    #[prost_amino(uint32, repeated, tag = "2")]
+    pub ports: Vec<u32>,
+    #[prost_amino(int64, repeated, tag = "3")]
+    pub scores: Vec<i64>,
+}
+
+fn ids(ids: &[&str]) -> Vec<String> {
+    ids.iter().map(|id| id.to_string()).collect()
+}
+
+#[test]
+fn duplicates_are_dropped() {
+    let raw = RawPeers {
+        ids: ids(&["b", "a", "b", "c", "a"]),
+        ports: vec![80, 443, 80, 80, 8080],
+        scores: vec![],
+    };
+    let peers = Peers::decode(&raw.encode_to_vec()[..]).unwrap();
+    assert_eq!(peers.ids, ids(&["b", "a", "c"]));
+    assert_eq!(peers.ports, vec![80, 443, 8080]);
+}
+
+#[test]
+fn duplicates_across_packed_blocks_are_dropped() {
+    let mut buf = RawPeers {
+        ports: vec![1, 2, 3],
+        ..RawPeers::default()
+    }
+    .encode_to_vec();
+    buf.extend(
+        RawPeers {
+            ports: vec![3, 4, 1],
+            ..RawPeers::default()
+        }
+        .encode_to_vec(),
+    );
+    let peers = Peers::decode(&buf[..]).unwrap();
+    assert_eq!(peers.ports, vec![1, 2, 3, 4]);
+}
+
+#[test]
+fn values_are_deduplicated_after_clamping() {
+    let raw = RawPeers {
+        scores: vec![-5, 0, 20, 10, 5],
+        ..RawPeers::default()
+    };
+    let peers = Peers::decode(&raw.encode_to_vec()[..]).unwrap();
+    assert_eq!(peers.scores, vec![0, 10, 5]);
+}
+
+#[test]
+fn duplicates_are_encoded() {
+    let peers = Peers {
+        ports: vec![80, 80],
+        ..Peers::default()
+    };
+    let raw = RawPeers::decode(&peers.encode_to_vec()[..]).unwrap();
+    assert_eq!(raw.ports, vec![80, 80]);
+}
+
+#[test]
+fn many_duplicates_are_dropped() {
+    let raw = RawPeers {
+        ports: (0..100_000).map(|i| i % 1000).rev().collect(),
+        ..RawPeers::default()
+    };
+    let peers = Peers::decode(&raw.encode_to_vec()[..]).unwrap();
+    assert_eq!(peers.ports, (0..1000).rev().collect::<Vec<_>>());
+}
+
+#[test]
+fn many_unpacked_duplicates_are_dropped() {
+    // Each string is merged on its own, so the field must not be deduplicated per value.
+    let raw = RawPeers {
+        ids: (0..40_000).map(|i| (i % 1000).to_string()).collect(),
+        ..RawPeers::default()
+    };
+    let peers = Peers::decode(&raw.encode_to_vec()[..]).unwrap();
+    assert_eq!(
+        peers.ids,
+        (0..1000).map(|i| i.to_string()).collect::<Vec<_>>()
+    );
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Swarm {
+    #[prost_amino(message, optional, tag = "1")]
+    pub peers: Option<Peers>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct RawSwarm {
+    #[prost_amino(message, optional, tag = "1")]
+    pub peers: Option<RawPeers>,
+}
+
+#[test]
+fn duplicates_of_nested_messages_are_dropped() {
+    let raw = RawSwarm {
+        peers: Some(RawPeers {
+            ids: ids(&["a", "b", "a"]),
+            ports: vec![80, 80],
+            scores: vec![],
+        }),
+    };
+    let swarm = Swarm::decode(&raw.encode_to_vec()[..]).unwrap();
+    let peers = swarm.peers.unwrap();
+    assert_eq!(peers.ids, ids(&["a", "b"]));
+    assert_eq!(peers.ports, vec![80]);
+}
@@ -0,0 +1,314 @@
+//! Tests for messages and fields holding their default values.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Inner {
+    #[prost_amino(int32, tag = "1")]
+    pub int32: i32,
+    #[prost_amino(string, tag = "2")]
+    pub string: String,
+    #[prost_amino(int64, repeated, tag = "3")]
+    pub int64s: Vec<i64>,
+    #[prost_amino(bool, optional, tag = "4")]
+    pub optional_bool: Option<bool>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Outer {
+    #[prost_amino(message, required, tag = "1")]
+    pub inner: Inner,
+    #[prost_amino(uint32, tag = "2")]
+    pub uint32: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct BytesDefault {
+    #[prost_amino(bytes, tag = "1", default = b"amino")]
+    pub magic: Vec<u8>,
+}
+
+#[test]
+fn all_default_is_empty() {
+    assert!(Inner::default().is_empty());
+    assert!(Outer::default().is_empty());
+
+    let mut buf = Vec::new();
+    Outer::default().encode(&mut buf).unwrap();
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn partially_populated_is_not_empty() {
+    let inner = Inner {
+        optional_bool: Some(false),
+        ..Inner::default()
+    };
+    assert!(!inner.is_empty());
+
+    let outer = Outer {
+        inner: Inner::default(),
+        uint32: 7,
+    };
+    assert!(!outer.is_empty());
+    assert!(outer.inner.is_empty());
+}
+
+#[test]
+fn default_nested_message_is_skipped() {
+    let outer = Outer {
+        inner: Inner::default(),
+        uint32: 7,
+    };
+    let mut buf = Vec::new();
+    outer.encode(&mut buf).unwrap();
+    assert_eq!(buf, vec![0x10, 0x07]);
+    assert_eq!(outer.encoded_len(), buf.len());
+    assert_eq!(Outer::decode(&buf[..]).unwrap(), outer);
+
+    let outer = Outer {
+        inner: Inner {
+            int32: 1,
+            ..Inner::default()
+        },
+        uint32: 0,
+    };
+    let mut buf = Vec::new();
+    outer.encode(&mut buf).unwrap();
+    assert_eq!(buf, vec![0x0a, 0x02, 0x08, 0x01]);
+    assert_eq!(Outer::decode(&buf[..]).unwrap(), outer);
+}
+
+#[test]
+fn bytes_equal_to_default_is_skipped() {
+    let msg = BytesDefault::default();
+    assert_eq!(msg.magic, b"amino");
+    assert!(msg.is_empty());
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+    assert!(buf.is_empty());
+
+    for magic in &[&b"amin0"[..], &b"ami"[..], &b""[..]] {
+        let msg = BytesDefault {
+            magic: magic.to_vec(),
+        };
+        assert!(!msg.is_empty());
+        let mut buf = Vec::new();
+        msg.encode(&mut buf).unwrap();
+        assert_eq!(buf[..2], [0x0a, magic.len() as u8]);
+        assert_eq!(buf[2..], **magic);
+        assert_eq!(msg.encoded_len(), buf.len());
+    }
+}
+
+mod generated {
+    pub fn chain_id() -> String {
+        format!("{}-{}", "test-chain", 42)
+    }
+
+    pub fn height() -> i64 {
+        -1
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct FnDefault {
+    #[prost_amino(string, tag = "1", default_fn = "generated::chain_id")]
+    pub chain_id: String,
+    #[prost_amino(int64, tag = "2", default_fn = "self::generated::height")]
+    pub height: i64,
+}
+
+#[test]
+fn default_fn_is_called() {
+    let msg = FnDefault::default();
+    assert_eq!(msg.chain_id, "test-chain-42");
+    assert_eq!(msg.height, -1);
+
+    let mut cleared = FnDefault {
+        chain_id: "other".to_owned(),
+        height: 7,
+    };
+    cleared.clear();
+    assert_eq!(cleared, msg);
+}
+
+#[test]
+fn default_fn_fields_are_always_encoded() {
+    let msg = FnDefault {
+        chain_id: String::new(),
+        height: 0,
+    };
+    assert!(!msg.is_empty());
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+    assert_eq!(buf, vec![0x0a, 0x00, 0x10, 0x00]);
+    assert_eq!(FnDefault::decode(&buf[..]).unwrap(), msg);
+
+    // Fields missing from the input hold the value of the default function.
+    assert_eq!(FnDefault::decode(&[][..]).unwrap(), FnDefault::default());
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct StringDefault {
+    #[prost_amino(string, tag = "1", default = "amino")]
+    pub name: String,
+}
+
+#[test]
+fn decoded_string_replaces_default() {
+    let msg = StringDefault::decode(&[0x0a, 0x02, b'h', b'i'][..]).unwrap();
+    assert_eq!(msg.name, "hi");
+}
+
+#[test]
+fn clear_single_field() {
+    let mut inner = Inner {
+        int32: 1,
+        string: "a".to_owned(),
+        int64s: vec![1, 2],
+        optional_bool: Some(true),
+    };
+
+    inner.clear_int32();
+    assert_eq!(inner.int32, 0);
+    inner.clear_int64s();
+    assert!(inner.int64s.is_empty());
+    assert_eq!(inner.string, "a");
+    assert_eq!(inner.optional_bool, Some(true));
+
+    inner.clear_optional_bool();
+    assert_eq!(inner.optional_bool, None);
+    inner.clear_string();
+    assert!(inner.is_empty());
+
+    let mut outer = Outer {
+        inner: Inner {
+            int32: 1,
+            ..Inner::default()
+        },
+        uint32: 2,
+    };
+    outer.clear_inner();
+    assert_eq!(outer.inner, Inner::default());
+    assert_eq!(outer.uint32, 2);
+
+    let mut msg = StringDefault {
+        name: "other".to_owned(),
+    };
+    msg.clear_name();
+    assert_eq!(msg, StringDefault::default());
+}
+
+const PAGE_SIZE: u32 = 4096;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ExprDefault {
+    #[prost_amino(uint32, tag = "1", default_expr = "1 << 20")]
+    pub max_len: u32,
+    #[prost_amino(uint32, tag = "2", default_expr = "PAGE_SIZE * 2")]
+    pub buf_len: u32,
+    #[prost_amino(sint64, optional, tag = "3", default_expr = "-(1 << 40)")]
+    pub offset: Option<i64>,
+}
+
+#[test]
+fn default_expr() {
+    let msg = ExprDefault::default();
+    assert_eq!(msg.max_len, 1 << 20);
+    assert_eq!(msg.buf_len, 8192);
+    assert_eq!(msg.offset, None);
+    assert_eq!(msg.offset(), -(1 << 40));
+
+    // Fields holding their default are not encoded.
+    assert!(msg.is_empty());
+    assert!(msg.encode_to_vec().is_empty());
+
+    let msg = ExprDefault {
+        max_len: 0,
+        ..ExprDefault::default()
+    };
+    let decoded = ExprDefault::decode(&msg.encode_to_vec()[..]).unwrap();
+    assert_eq!(decoded, msg);
+
+    let descriptor = &ExprDefault::descriptor()[0];
+    assert_eq!(
+        descriptor.default,
+        Some(prost::ParsedDefault::Uint(1 << 20))
+    );
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Enumeration)]
+pub enum Level {
+    Low = 0,
+    High = 1,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct LiteralDefault {
+    #[prost_amino(int32, tag = "1", default = "5")]
+    pub retries: i32,
+    #[prost_amino(bool, tag = "2", default = "true")]
+    pub enabled: bool,
+    #[prost_amino(string, tag = "3", default = "amino")]
+    pub name: String,
+    #[prost_amino(enumeration = "Level", tag = "4", default = "High")]
+    pub level: i32,
+    #[prost_amino(double, required, tag = "5", default = "0.5")]
+    pub ratio: f64,
+    #[prost_amino(uint64, optional, tag = "6", default = "88")]
+    pub limit: Option<u64>,
+}
+
+#[test]
+fn derived_default_uses_field_defaults() {
+    let msg = LiteralDefault::default();
+    assert_eq!(msg.retries, 5);
+    assert!(msg.enabled);
+    assert_eq!(msg.name, "amino");
+    assert_eq!(msg.level(), Level::High);
+    assert_eq!(msg.ratio, 0.5);
+    // An optional field starts unset, and its accessor returns the default.
+    assert_eq!(msg.limit, None);
+    assert_eq!(msg.limit(), 88);
+
+    // Decoding starts from the same defaults, so missing fields keep them.
+    assert_eq!(LiteralDefault::decode(&[][..]).unwrap(), msg);
+
+    let mut cleared = LiteralDefault {
+        retries: 0,
+        enabled: false,
+        name: String::new(),
+        level: Level::Low as i32,
+        ratio: 0.0,
+        limit: Some(0),
+    };
+    cleared.clear();
+    assert_eq!(cleared, msg);
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[amino_name = "test/Registered"]
+pub struct Registered {
+    #[prost_amino(int32, tag = "1")]
+    pub int32: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct WithRegistered {
+    #[prost_amino(message, required, tag = "1")]
+    pub registered: Registered,
+}
+
+#[test]
+fn default_registered_message_is_encoded() {
+    // The prefix of a registered message is written even when it has no fields.
+    let outer = WithRegistered::default();
+    let mut buf = Vec::new();
+    outer.encode(&mut buf).unwrap();
+    let mut expected = vec![0x0a, 0x04];
+    expected.extend_from_slice(Registered::amino_prefix());
+    assert_eq!(buf, expected);
+    assert_eq!(outer.encoded_len(), buf.len());
+    assert_eq!(WithRegistered::decode(&buf[..]).unwrap(), outer);
+}
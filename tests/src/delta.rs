@@ -0,0 +1,68 @@
+//! Tests for `delta` fields, which store packed signed integers as differences between
+//! consecutive values.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Heights {
+    #[prost_amino(sint64, repeated, delta, tag = "1")]
+    pub heights: Vec<i64>,
+    #[prost_amino(sint32, repeated, delta, tag = "2")]
+    pub rounds: Vec<i32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct PlainHeights {
+    #[prost_amino(sint64, repeated, tag = "1")]
+    pub heights: Vec<i64>,
+}
+
+fn round_trip(heights: Vec<i64>, rounds: Vec<i32>) -> Vec<u8> {
+    let msg = Heights { heights, rounds };
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+    assert_eq!(msg.encoded_len(), buf.len());
+    assert_eq!(msg, Heights::decode(&buf[..]).unwrap());
+    buf
+}
+
+#[test]
+fn empty() {
+    assert_eq!(round_trip(vec![], vec![]), Vec::<u8>::new());
+}
+
+#[test]
+fn single_element() {
+    assert_eq!(round_trip(vec![5], vec![-1]), vec![0x0a, 0x01, 0x0a, 0x12, 0x01, 0x01]);
+}
+
+#[test]
+fn increasing() {
+    let heights = vec![1_000_000, 1_000_001, 1_000_002, 1_000_010];
+    let buf = round_trip(heights.clone(), vec![]);
+    assert_eq!(buf, vec![0x0a, 0x06, 0x80, 0x89, 0x7a, 0x02, 0x02, 0x10]);
+
+    let mut plain = Vec::new();
+    PlainHeights { heights }.encode(&mut plain).unwrap();
+    assert!(buf.len() < plain.len());
+
+    round_trip(vec![], vec![0, 1, 2, 3]);
+}
+
+#[test]
+fn decreasing() {
+    round_trip(vec![10, 5, 0, -5], vec![3, 2, 1, 0]);
+}
+
+#[test]
+fn extremes() {
+    round_trip(
+        vec![::std::i64::MIN, ::std::i64::MAX, 0],
+        vec![::std::i32::MAX, ::std::i32::MIN],
+    );
+}
+
+#[test]
+fn unpacked_values_are_rejected() {
+    assert!(Heights::decode(&[0x08, 0x02][..]).is_err());
+}
@@ -0,0 +1,76 @@
+//! Tests for `deprecated_wire` fields, which are decoded but never encoded.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Inner {
+    #[prost_amino(int64, tag = "1")]
+    pub value: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Account {
+    #[prost_amino(string, tag = "1")]
+    pub name: String,
+    #[prost_amino(uint64, tag = "2", deprecated_wire)]
+    pub legacy_id: u64,
+    #[prost_amino(message, required, tag = "3", deprecated_wire)]
+    pub legacy_inner: Inner,
+    #[prost_amino(int32, repeated, tag = "4", deprecated_wire)]
+    pub legacy_flags: Vec<i32>,
+}
+
+/// The schema before the fields were retired.
+#[derive(Clone, PartialEq, Message)]
+pub struct LegacyAccount {
+    #[prost_amino(string, tag = "1")]
+    pub name: String,
+    #[prost_amino(uint64, tag = "2")]
+    pub legacy_id: u64,
+    #[prost_amino(message, required, tag = "3")]
+    pub legacy_inner: Inner,
+    #[prost_amino(int32, repeated, tag = "4")]
+    pub legacy_flags: Vec<i32>,
+}
+
+#[test]
+fn decoded_but_not_encoded() {
+    let legacy = LegacyAccount {
+        name: "alice".to_owned(),
+        legacy_id: 7,
+        legacy_inner: Inner { value: 5 },
+        legacy_flags: vec![1, 2],
+    };
+    let account = Account::decode(&legacy.encode_to_vec()[..]).unwrap();
+    assert_eq!(account.name, "alice");
+    assert_eq!(account.legacy_id, 7);
+    assert_eq!(account.legacy_inner, Inner { value: 5 });
+    assert_eq!(account.legacy_flags, [1, 2]);
+
+    // Only the live field is written, even though the retired fields hold values.
+    let buf = account.encode_to_vec();
+    assert_eq!(buf.len(), account.encoded_len());
+    assert_eq!(
+        buf,
+        LegacyAccount {
+            name: "alice".to_owned(),
+            ..LegacyAccount::default()
+        }
+        .encode_to_vec()
+    );
+    let decoded = Account::decode(&buf[..]).unwrap();
+    assert_eq!(decoded.name, "alice");
+    assert_eq!(decoded.legacy_id, 0);
+}
+
+#[test]
+fn message_with_only_retired_values_is_empty() {
+    let account = Account {
+        legacy_id: 7,
+        legacy_inner: Inner { value: 5 },
+        legacy_flags: vec![1],
+        ..Account::default()
+    };
+    assert_eq!(account.encoded_len(), 0);
+    assert!(account.encode_to_vec().is_empty());
+}
@@ -0,0 +1,133 @@
+//! Tests for the runtime field metadata returned by `Message::descriptor`.
+
+use prost::{FieldDescriptor, Label, Message, ParsedDefault};
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Described {
+    #[prost_amino(string, tag = "2")]
+    pub name: String,
+    #[prost_amino(int64, repeated, tag = "1")]
+    pub values: Vec<i64>,
+    #[prost_amino(bytes, tag = "3", amino_name = "tendermint/PubKeyEd25519")]
+    pub pub_key: Vec<u8>,
+    #[prost_amino(message, optional, tag = "4")]
+    pub child: Option<Box<Described>>,
+    #[prost_amino(uint32, required, tag = "5")]
+    pub count: u32,
+}
+
+#[test]
+fn descriptor_lists_fields_in_tag_order() {
+    assert_eq!(
+        Described::descriptor(),
+        &[
+            FieldDescriptor {
+                tag: 1,
+                name: "values",
+                ty: "int64",
+                label: Label::Repeated,
+                amino_prefix: &[],
+                amino_name: "",
+                type_name: "",
+                default: None,
+            },
+            FieldDescriptor {
+                tag: 2,
+                name: "name",
+                ty: "string",
+                label: Label::Optional,
+                amino_prefix: &[],
+                amino_name: "",
+                type_name: "",
+                default: Some(ParsedDefault::Str("")),
+            },
+            FieldDescriptor {
+                tag: 3,
+                name: "pub_key",
+                ty: "bytes",
+                label: Label::Optional,
+                amino_prefix: &[0x16, 0x24, 0xde, 0x64],
+                amino_name: "tendermint/PubKeyEd25519",
+                type_name: "",
+                default: Some(ParsedDefault::Bytes(b"")),
+            },
+            FieldDescriptor {
+                tag: 4,
+                name: "child",
+                ty: "message",
+                label: Label::Optional,
+                amino_prefix: &[],
+                amino_name: "",
+                type_name: "Described",
+                default: None,
+            },
+            FieldDescriptor {
+                tag: 5,
+                name: "count",
+                ty: "uint32",
+                label: Label::Required,
+                amino_prefix: &[],
+                amino_name: "",
+                type_name: "",
+                default: Some(ParsedDefault::Uint(0)),
+            },
+        ][..]
+    );
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Enumeration)]
+pub enum Level {
+    Low = 0,
+    High = 1,
+}
+
+fn answer() -> i64 {
+    42
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Defaulted {
+    #[prost_amino(sint32, tag = "1", default = "-7")]
+    pub int: i32,
+    #[prost_amino(fixed64, tag = "2", default = "7")]
+    pub uint: u64,
+    #[prost_amino(float, tag = "3", default = "1.5")]
+    pub float: f32,
+    #[prost_amino(double, tag = "4", default = "-inf")]
+    pub double: f64,
+    #[prost_amino(bool, tag = "5", default = "true")]
+    pub flag: bool,
+    #[prost_amino(string, tag = "6", default = "amino")]
+    pub string: String,
+    #[prost_amino(bytes, tag = "7", default = b"\x01\x02")]
+    pub bytes: Vec<u8>,
+    #[prost_amino(enumeration = "Level", tag = "8", default = "High")]
+    pub level: i32,
+    #[prost_amino(enumeration = "Level", tag = "9")]
+    pub implicit_level: i32,
+    #[prost_amino(int64, tag = "10", default_fn = "answer")]
+    pub answer: i64,
+}
+
+#[test]
+fn descriptor_lists_parsed_defaults() {
+    let defaults = Defaulted::descriptor()
+        .iter()
+        .map(|field| field.default)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        defaults,
+        vec![
+            Some(ParsedDefault::Int(-7)),
+            Some(ParsedDefault::Uint(7)),
+            Some(ParsedDefault::Float(1.5)),
+            Some(ParsedDefault::Float(::std::f64::NEG_INFINITY)),
+            Some(ParsedDefault::Bool(true)),
+            Some(ParsedDefault::Str("amino")),
+            Some(ParsedDefault::Bytes(&[1, 2])),
+            Some(ParsedDefault::Enum("High")),
+            None,
+            Some(ParsedDefault::Fn("answer")),
+        ]
+    );
+}
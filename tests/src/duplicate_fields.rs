@@ -0,0 +1,116 @@
+//! Tests for decoding with `DecodeOptions::reject_duplicate_singular`, which rejects a singular
+//! field that appears more than once instead of keeping its last value.
+
+use prost::{DecodeOptions, Message};
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Inner {
+    #[prost_amino(int64, tag = "1")]
+    pub value: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Record {
+    #[prost_amino(string, tag = "1")]
+    pub name: String,
+    #[prost_amino(int64, repeated, tag = "2")]
+    pub values: Vec<i64>,
+    #[prost_amino(message, optional, tag = "3")]
+    pub inner: Option<Inner>,
+    #[prost_amino(uint64, tag = "4")]
+    pub id: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[amino_name = "test/DuplicateRegistered"]
+pub struct Registered {
+    #[prost_amino(uint64, tag = "1")]
+    pub id: u64,
+}
+
+const STRICT: DecodeOptions<'static> = DecodeOptions {
+    allowed_tags: None,
+    reject_duplicate_singular: true,
+};
+
+/// `Record` with `id` set to 1 and then to 2.
+const DUPLICATE_ID: &[u8] = &[0x20, 0x01, 0x20, 0x02];
+
+#[test]
+fn lenient_keeps_last_value() {
+    let record = Record::decode(DUPLICATE_ID).unwrap();
+    assert_eq!(record.id, 2);
+    let record = Record::decode_with_options(DUPLICATE_ID, &DecodeOptions::default()).unwrap();
+    assert_eq!(record.id, 2);
+}
+
+#[test]
+fn strict_rejects_duplicate_singular_field() {
+    let error = Record::decode_with_options(DUPLICATE_ID, &STRICT).unwrap_err();
+    assert_eq!(error.duplicate_tag(), Some(4));
+    assert!(!error.is_incomplete());
+    assert_eq!(
+        error.to_string(),
+        "failed to decode Protobuf message: duplicate singular field: tag 4"
+    );
+
+    // A duplicated nested message would be merged, and is rejected as well.
+    let buf = [0x1a, 0x02, 0x08, 0x01, 0x1a, 0x02, 0x08, 0x02];
+    assert!(Record::decode(&buf[..]).is_ok());
+    let error = Record::decode_with_options(&buf[..], &STRICT).unwrap_err();
+    assert_eq!(error.duplicate_tag(), Some(3));
+}
+
+#[test]
+fn strict_accepts_repeated_and_canonical_fields() {
+    let record = Record {
+        name: "alice".to_owned(),
+        values: vec![1, 2, 3],
+        inner: Some(Inner { value: 5 }),
+        id: 7,
+    };
+    let buf = record.encode_to_vec();
+    assert_eq!(
+        Record::decode_with_options(&buf[..], &STRICT).unwrap(),
+        record
+    );
+
+    // Unpacked repeated elements share a tag.
+    let buf = [0x10, 0x01, 0x10, 0x02];
+    let decoded = Record::decode_with_options(&buf[..], &STRICT).unwrap();
+    assert_eq!(decoded.values, [1, 2]);
+}
+
+#[test]
+fn strict_with_allowed_tags() {
+    // A skipped field is not merged, so it is not a duplicate.
+    let options = DecodeOptions {
+        allowed_tags: Some(&[1]),
+        ..STRICT
+    };
+    let decoded = Record::decode_with_options(DUPLICATE_ID, &options).unwrap();
+    assert_eq!(decoded, Record::default());
+
+    let options = DecodeOptions {
+        allowed_tags: Some(&[4]),
+        ..STRICT
+    };
+    let error = Record::decode_with_options(DUPLICATE_ID, &options).unwrap_err();
+    assert_eq!(error.duplicate_tag(), Some(4));
+}
+
+#[test]
+fn strict_registered_message() {
+    let mut buf = Registered { id: 1 }.encode_to_vec();
+    assert_eq!(
+        Registered::decode_with_options(&buf[..], &STRICT).unwrap(),
+        Registered { id: 1 }
+    );
+
+    // Append a second `id` field within the length delimiter.
+    buf[0] += 2;
+    buf.extend_from_slice(&[0x08, 0x02]);
+    assert_eq!(Registered::decode(&buf[..]).unwrap().id, 2);
+    let error = Registered::decode_with_options(&buf[..], &STRICT).unwrap_err();
+    assert_eq!(error.duplicate_tag(), Some(1));
+}
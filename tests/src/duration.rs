@@ -0,0 +1,74 @@
+//! Tests for `duration` fields.
+
+use prost::{Duration, Message};
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Timeout {
+    #[prost_amino(duration, tag = "1")]
+    pub after: Option<Duration>,
+    #[prost_amino(duration, repeated, tag = "2")]
+    pub retries: Vec<Duration>,
+}
+
+#[test]
+fn negative_duration() {
+    let msg = Timeout {
+        after: Some(Duration {
+            seconds: -2,
+            nanos: -500_000_000,
+        }),
+        retries: vec![Duration {
+            seconds: 1,
+            nanos: 0,
+        }],
+    };
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+    assert_eq!(msg.encoded_len(), buf.len());
+    assert_eq!(&buf[..4], &[0x0a, 0x16, 0x08, 0xfe]);
+    assert_eq!(msg, Timeout::decode(&buf[..]).unwrap());
+}
+
+#[test]
+fn non_canonical_duration_is_canonicalized_on_encode() {
+    let msg = Timeout {
+        after: Some(Duration {
+            seconds: -1,
+            nanos: 500_000_000,
+        }),
+        retries: Vec::new(),
+    };
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+    assert_eq!(msg.encoded_len(), buf.len());
+    let decoded = Timeout::decode(&buf[..]).unwrap();
+    assert_eq!(
+        decoded.after,
+        Some(Duration {
+            seconds: 0,
+            nanos: -500_000_000,
+        })
+    );
+}
+
+#[test]
+fn mismatched_sign_nanos_is_rejected() {
+    // seconds = 1, nanos = -1
+    let buf = [0x0a, 0x0d, 0x08, 0x01, 0x10, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+    assert_eq!(
+        Timeout::decode(&buf[..]).unwrap_err().to_string(),
+        "failed to decode Protobuf message: Timeout.after: \
+         invalid duration: 1 seconds and -1 nanos are not canonical"
+    );
+}
+
+#[test]
+fn out_of_range_nanos_is_rejected() {
+    // seconds = 0, nanos = 1_000_000_000
+    let buf = [0x12, 0x06, 0x10, 0x80, 0x94, 0xeb, 0xdc, 0x03];
+    assert_eq!(
+        Timeout::decode(&buf[..]).unwrap_err().to_string(),
+        "failed to decode Protobuf message: Timeout.retries[0]: \
+         invalid duration: 0 seconds and 1000000000 nanos are not canonical"
+    );
+}
@@ -0,0 +1,139 @@
+//! Tests for enumeration fields.
+
+use prost::Message;
+
+/// An enum declared with `allow_alias`: `STARTED` is an alias of `RUNNING`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Enumeration)]
+pub enum Aliased {
+    UNKNOWN = 0,
+    RUNNING = 1,
+}
+
+impl Aliased {
+    #[allow(non_upper_case_globals)]
+    pub const STARTED: Aliased = Aliased::RUNNING;
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Status {
+    #[prost_amino(enumeration = "Aliased", tag = "1")]
+    pub state: i32,
+}
+
+#[test]
+fn aliased_value_resolves_to_first_declared_variant() {
+    let status = Status::decode(&[0x08, 0x01][..]).unwrap();
+    assert_eq!(status.state(), Aliased::RUNNING);
+    assert_eq!(status.state(), Aliased::STARTED);
+    assert_eq!(Aliased::from_i32(1), Some(Aliased::RUNNING));
+
+    let mut buf = Vec::new();
+    Status {
+        state: Aliased::STARTED as i32,
+    }
+    .encode(&mut buf)
+    .unwrap();
+    assert_eq!(buf, [0x08, 0x01]);
+}
+
+pub mod kinds {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Enumeration)]
+    pub enum Kind {
+        Unknown = 0,
+        Block = 1,
+        Vote = 2,
+    }
+}
+
+/// Enumerations referred to by qualified paths, which are not resolved from the parent module.
+#[derive(Clone, PartialEq, Message)]
+pub struct Qualified {
+    #[prost_amino(
+        enumeration = "crate::enumerations::kinds::Kind",
+        tag = "1",
+        default = "Vote"
+    )]
+    pub absolute: i32,
+    #[prost_amino(enumeration = "self::kinds::Kind", tag = "2")]
+    pub relative: i32,
+    #[prost_amino(enumeration = "kinds::Kind", repeated, tag = "3")]
+    pub kinds: Vec<i32>,
+}
+
+#[test]
+fn qualified_enumeration_paths() {
+    let mut msg = Qualified::default();
+    assert_eq!(msg.absolute(), kinds::Kind::Vote);
+    msg.set_relative(kinds::Kind::Block);
+    msg.push_kinds(kinds::Kind::Vote);
+    msg.kinds.push(7);
+    assert_eq!(
+        format!("{:?}", msg),
+        "Qualified { absolute: Vote, relative: Block, kinds: [Vote, 7] }"
+    );
+}
+
+/// Enumeration fields whose accessors return `None` for values unknown to this version of `Kind`.
+#[derive(Clone, PartialEq, Message)]
+pub struct Forward {
+    #[prost_amino(enumeration = "kinds::Kind", default_unknown, tag = "1")]
+    pub kind: i32,
+    #[prost_amino(
+        enumeration = "kinds::Kind",
+        optional,
+        default = "Block",
+        default_unknown,
+        tag = "2"
+    )]
+    pub optional_kind: Option<i32>,
+    #[prost_amino(enumeration = "kinds::Kind", repeated, default_unknown, tag = "3")]
+    pub kinds: Vec<i32>,
+}
+
+#[test]
+fn default_unknown_keeps_unknown_values() {
+    let msg = Forward {
+        kind: 9,
+        optional_kind: Some(10),
+        kinds: vec![2, 11, 1],
+    };
+    assert_eq!(msg.kind(), None);
+    assert_eq!(msg.optional_kind(), None);
+    assert_eq!(
+        msg.kinds().collect::<Vec<_>>(),
+        [Some(kinds::Kind::Vote), None, Some(kinds::Kind::Block)]
+    );
+
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+    let decoded = Forward::decode(&buf[..]).unwrap();
+    assert_eq!(decoded, msg);
+    let mut reencoded = Vec::new();
+    decoded.encode(&mut reencoded).unwrap();
+    assert_eq!(reencoded, buf);
+
+    let mut msg = Forward::default();
+    assert_eq!(msg.kind(), Some(kinds::Kind::Unknown));
+    assert_eq!(msg.optional_kind(), Some(kinds::Kind::Block));
+    msg.set_kind(kinds::Kind::Vote);
+    assert_eq!(msg.kind(), Some(kinds::Kind::Vote));
+}
+
+#[test]
+fn variants_are_listed_in_declaration_order() {
+    use self::kinds::Kind;
+
+    assert_eq!(Kind::VARIANTS, &[0, 1, 2]);
+    assert_eq!(
+        Kind::all_variants(),
+        &[Kind::Unknown, Kind::Block, Kind::Vote]
+    );
+    assert!(Kind::VARIANTS.iter().all(|&value| Kind::is_valid(value)));
+
+    // Aliases are not variants, so they are not listed twice.
+    assert_eq!(Aliased::VARIANTS, &[0, 1]);
+    assert_eq!(
+        Aliased::all_variants(),
+        &[Aliased::UNKNOWN, Aliased::RUNNING]
+    );
+}
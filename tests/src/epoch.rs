@@ -0,0 +1,93 @@
+//! Tests for `SystemTime` fields encoded as a `fixed64` count of nanoseconds since the unix
+//! epoch, with the `epoch = "unix_nanos"` attribute.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Event {
+    #[prost_amino(fixed64, epoch = "unix_nanos", tag = "1")]
+    pub time: SystemTime,
+    #[prost_amino(sfixed64, optional, epoch = "unix_nanos", tag = "2")]
+    pub expires: Option<SystemTime>,
+}
+
+#[test]
+fn epoch_is_the_default() {
+    let msg = Event::default();
+    assert_eq!(msg.time, UNIX_EPOCH);
+    assert!(msg.encode_to_vec().is_empty());
+
+    let msg = Event {
+        time: UNIX_EPOCH,
+        expires: Some(UNIX_EPOCH),
+    };
+    let buf = msg.encode_to_vec();
+    assert_eq!(buf, vec![0x11, 0, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(Event::decode(&buf[..]).unwrap(), msg);
+}
+
+#[test]
+fn far_future_and_past_round_trip() {
+    // 2262-04-11, shortly before the latest time which fits in i64 nanoseconds.
+    let future = UNIX_EPOCH + Duration::new(9_223_372_036, 854_775_807);
+    // 1900-01-01.
+    let past = UNIX_EPOCH - Duration::from_secs(2_208_988_800);
+    let msg = Event {
+        time: future,
+        expires: Some(past),
+    };
+    msg.validate().unwrap();
+
+    let buf = msg.encode_to_vec();
+    assert_eq!(buf.len(), 18);
+    assert_eq!(msg.encoded_len(), buf.len());
+    assert_eq!(&buf[1..9], &i64::max_value().to_le_bytes()[..]);
+    assert_eq!(
+        &buf[10..],
+        &(-2_208_988_800_000_000_000i64).to_le_bytes()[..]
+    );
+    assert_eq!(Event::decode(&buf[..]).unwrap(), msg);
+}
+
+#[test]
+fn out_of_range_time_is_rejected() {
+    let msg = Event {
+        time: UNIX_EPOCH + Duration::new(9_223_372_036, 854_775_808),
+        expires: None,
+    };
+    let error = msg.validate().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "invalid Protobuf message: Event.time: time is out of range of i64 \
+         nanoseconds since the unix epoch"
+    );
+
+    let msg = Event {
+        time: UNIX_EPOCH,
+        expires: Some(UNIX_EPOCH - Duration::from_secs(10_000_000_000)),
+    };
+    assert!(msg.validate().is_err());
+}
+
+#[test]
+fn out_of_range_time_is_saturated() {
+    // Encoding does not fail for times out of range, which are written as the latest or earliest
+    // time which can be encoded.
+    let msg = Event {
+        time: UNIX_EPOCH + Duration::from_secs(10_000_000_000),
+        expires: Some(UNIX_EPOCH - Duration::from_secs(10_000_000_000)),
+    };
+    let buf = msg.encode_to_vec();
+    assert_eq!(msg.encoded_len(), buf.len());
+    assert_eq!(&buf[1..9], &i64::max_value().to_le_bytes()[..]);
+    assert_eq!(&buf[10..], &i64::min_value().to_le_bytes()[..]);
+
+    let decoded = Event::decode(&buf[..]).unwrap();
+    assert_eq!(
+        decoded.time,
+        UNIX_EPOCH + Duration::from_nanos(i64::max_value() as u64)
+    );
+    assert!(msg.validate().is_err());
+}
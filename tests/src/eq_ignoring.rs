@@ -0,0 +1,127 @@
+//! Tests for the `eq_ignoring` method of derived messages, which compares two messages while
+//! skipping the fields with the given tags.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Header {
+    #[prost_amino(uint64, tag = "1")]
+    pub id: u64,
+    #[prost_amino(int64, tag = "2")]
+    pub timestamp: i64,
+}
+
+#[derive(Clone, PartialEq, Oneof)]
+pub enum Payload {
+    #[prost_amino(string, tag = "5")]
+    Text(String),
+    #[prost_amino(bytes, tag = "6")]
+    Data(Vec<u8>),
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Event {
+    #[prost_amino(message, flatten)]
+    pub header: Header,
+    #[prost_amino(string, tag = "3")]
+    pub source: String,
+    #[prost_amino(uint32, repeated, tag = "4")]
+    pub codes: Vec<u32>,
+    #[prost_amino(oneof = "Payload", tags = "5, 6")]
+    pub payload: Option<Payload>,
+}
+
+/// A message without `PartialEq`, which still derives `Message`.
+#[derive(Clone, Message)]
+pub struct Opaque {
+    #[prost_amino(uint32, tag = "1")]
+    pub value: u32,
+}
+
+#[derive(Clone, Message)]
+pub struct Wrapper {
+    #[prost_amino(message, tag = "1")]
+    pub opaque: Option<Opaque>,
+}
+
+fn event() -> Event {
+    Event {
+        header: Header {
+            id: 7,
+            timestamp: 1_000,
+        },
+        source: "sensor".to_owned(),
+        codes: vec![1, 2, 3],
+        payload: Some(Payload::Text("hello".to_owned())),
+    }
+}
+
+#[test]
+fn messages_differing_only_in_ignored_field_are_equal() {
+    let a = event();
+    let mut b = event();
+    b.source = "other".to_owned();
+    assert_ne!(a, b);
+    assert!(a.eq_ignoring(&b, &[3]));
+    assert!(!a.eq_ignoring(&b, &[4]));
+    assert!(!a.eq_ignoring(&b, &[]));
+}
+
+#[test]
+fn flattened_fields_are_ignored_by_their_own_tags() {
+    let a = event();
+    let mut b = event();
+    b.header.timestamp = 2_000;
+    assert!(a.eq_ignoring(&b, &[2]));
+    assert!(!a.eq_ignoring(&b, &[1]));
+}
+
+#[test]
+fn oneof_is_ignored_by_any_of_its_tags() {
+    let a = event();
+    let mut b = event();
+    b.payload = Some(Payload::Data(vec![1]));
+    assert!(a.eq_ignoring(&b, &[5]));
+    assert!(a.eq_ignoring(&b, &[6]));
+    assert!(!a.eq_ignoring(&b, &[3]));
+}
+
+#[test]
+fn equal_messages_are_equal() {
+    assert!(event().eq_ignoring(&event(), &[]));
+}
+
+#[test]
+fn message_without_partial_eq_fields_still_derives() {
+    let wrapper = Wrapper {
+        opaque: Some(Opaque { value: 1 }),
+    };
+    assert_eq!(wrapper.opaque.map(|opaque| opaque.value), Some(1));
+}
+
+/// A flattened message with a field which is not `PartialEq`.
+#[derive(Clone, Message)]
+pub struct OpaqueHeader {
+    #[prost_amino(message, tag = "1")]
+    pub opaque: Option<Opaque>,
+}
+
+#[derive(Clone, Message)]
+pub struct OpaqueEvent {
+    #[prost_amino(message, flatten)]
+    pub header: OpaqueHeader,
+    #[prost_amino(string, tag = "2")]
+    pub source: String,
+}
+
+#[test]
+fn message_with_flattened_field_without_partial_eq_still_derives() {
+    let event = OpaqueEvent {
+        header: OpaqueHeader {
+            opaque: Some(Opaque { value: 1 }),
+        },
+        source: "sensor".to_owned(),
+    };
+    let decoded = OpaqueEvent::decode(&event.encode_to_vec()[..]).unwrap();
+    assert_eq!(decoded.source, "sensor");
+}
@@ -0,0 +1,123 @@
+//! Tests for the location reported by decode errors, and for the message of encode errors.
+
+use prost::{Message, WireType};
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Leaf {
+    #[prost_amino(int64, tag = "1")]
+    pub value: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Inner {
+    #[prost_amino(message, optional, tag = "1")]
+    pub leaf: Option<Leaf>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Outer {
+    #[prost_amino(message, repeated, tag = "1")]
+    pub inners: Vec<Inner>,
+}
+
+#[test]
+fn nested_field_path() {
+    // The third `inners` element contains a leaf whose varint is truncated.
+    let buf = [0x0a, 0x00, 0x0a, 0x00, 0x0a, 0x04, 0x0a, 0x02, 0x08, 0xff];
+    let error = Outer::decode(&buf[..]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "failed to decode Protobuf message: Outer.inners[2].leaf.value: invalid varint"
+    );
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct LengthDelimited {
+    #[prost_amino(string, tag = "1")]
+    pub name: String,
+    #[prost_amino(bytes, tag = "2")]
+    pub data: Vec<u8>,
+    #[prost_amino(message, optional, tag = "3")]
+    pub leaf: Option<Leaf>,
+    #[prost_amino(map = "string, int64", tag = "4")]
+    pub counts: ::std::collections::HashMap<String, i64>,
+    #[prost_amino(duration, tag = "5")]
+    pub timeout: Option<::prost::Duration>,
+}
+
+#[test]
+fn varint_for_length_delimited_field() {
+    let fields = [(1, "name"), (2, "data"), (3, "leaf"), (4, "counts"), (5, "timeout")];
+    for &(tag, field) in fields.iter() {
+        // A varint key for the field, followed by the varint 1.
+        let buf = [tag << 3, 0x01];
+        let error = LengthDelimited::decode(&buf[..]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "failed to decode Protobuf message: LengthDelimited.{}: \
+                 invalid wire type: Varint (expected LengthDelimited)",
+                field
+            )
+        );
+        assert_eq!(
+            error.unexpected_wire_type(),
+            Some((u32::from(tag), WireType::LengthDelimited, WireType::Varint))
+        );
+    }
+}
+
+#[test]
+fn length_delimited_for_varint_field() {
+    let buf = [0x0a, 0x01, 0x01];
+    let error = Leaf::decode(&buf[..]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "failed to decode Protobuf message: Leaf.value: \
+         invalid wire type: LengthDelimited (expected Varint)"
+    );
+    assert_eq!(
+        error.unexpected_wire_type(),
+        Some((1, WireType::Varint, WireType::LengthDelimited))
+    );
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Named {
+    #[prost_amino(string, tag = "1")]
+    pub name: String,
+}
+
+#[test]
+fn invalid_utf8_offset() {
+    // `0xc3` starts a two byte sequence, but `0x28` is not a continuation byte.
+    let buf = [0x0a, 0x04, b'a', b'b', 0xc3, 0x28];
+    let error = Named::decode(&buf[..]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "failed to decode Protobuf message: Named.name: \
+         invalid string value: data is not UTF-8 encoded (invalid byte at offset 2)"
+    );
+    assert_eq!(error.invalid_utf8(), Some((1, 2)));
+
+    // A failed merge does not leave invalid UTF-8 in the string.
+    let mut msg = Named {
+        name: "valid".to_owned(),
+    };
+    assert!(msg.merge(&buf[..]).is_err());
+    assert_eq!(msg.name, "");
+}
+
+#[test]
+fn encode_error_message() {
+    let msg = Named {
+        name: "name".to_owned(),
+    };
+    let mut out = [0; 4];
+    let error = msg.encode(&mut &mut out[..]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "failed to encode Protobuf message: insufficient buffer capacity \
+         (required: 6, remaining: 4)"
+    );
+}
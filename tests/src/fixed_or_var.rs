@@ -0,0 +1,84 @@
+//! Tests for `bytes` fields with the `fixed_or_var` attribute, which also accept values with a
+//! `fixed32` or `fixed64` encoding.
+
+use prost::encoding::{encode_key, WireType};
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Block {
+    #[prost_amino(bytes, fixed_or_var, tag = "1")]
+    pub hash: Vec<u8>,
+    #[prost_amino(bytes, optional, fixed_or_var, tag = "2")]
+    pub parent: Option<Vec<u8>>,
+    #[prost_amino(bytes, repeated, fixed_or_var, tag = "3")]
+    pub proofs: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct FixedBlock {
+    #[prost_amino(fixed64, tag = "1")]
+    pub hash: u64,
+    #[prost_amino(fixed32, optional, tag = "2")]
+    pub parent: Option<u32>,
+    #[prost_amino(fixed32, repeated, packed = "false", tag = "3")]
+    pub proofs: Vec<u32>,
+}
+
+#[test]
+fn length_delimited_values_decode() {
+    let block = Block {
+        hash: vec![1, 2, 3],
+        parent: Some(vec![4; 32]),
+        proofs: vec![vec![5], vec![]],
+    };
+    let buf = block.encode_to_vec();
+    assert_eq!(Block::decode(&buf[..]).unwrap(), block);
+}
+
+#[test]
+fn fixed_values_decode_as_their_bytes() {
+    let fixed = FixedBlock {
+        hash: 0x0807_0605_0403_0201,
+        parent: Some(0x0d0c_0b0a),
+        proofs: vec![0x1413_1211, 0x1817_1615],
+    };
+    let block = Block::decode(&fixed.encode_to_vec()[..]).unwrap();
+    assert_eq!(
+        block,
+        Block {
+            hash: vec![1, 2, 3, 4, 5, 6, 7, 8],
+            parent: Some(vec![0x0a, 0x0b, 0x0c, 0x0d]),
+            proofs: vec![vec![0x11, 0x12, 0x13, 0x14], vec![0x15, 0x16, 0x17, 0x18]],
+        }
+    );
+}
+
+#[test]
+fn values_are_encoded_length_delimited() {
+    let fixed = FixedBlock {
+        hash: 1,
+        ..FixedBlock::default()
+    };
+    let block = Block::decode(&fixed.encode_to_vec()[..]).unwrap();
+    let mut expected = Vec::new();
+    encode_key(1, WireType::LengthDelimited, &mut expected);
+    expected.push(8);
+    expected.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(block.encode_to_vec(), expected);
+}
+
+#[test]
+fn truncated_fixed_value_is_an_error() {
+    let mut buf = Vec::new();
+    encode_key(1, WireType::SixtyFourBit, &mut buf);
+    buf.extend_from_slice(&[1, 2, 3]);
+    assert!(Block::decode(&buf[..]).is_err());
+}
+
+#[test]
+fn varint_value_is_rejected() {
+    let mut buf = Vec::new();
+    encode_key(1, WireType::Varint, &mut buf);
+    buf.push(1);
+    assert!(Block::decode(&buf[..]).is_err());
+}
@@ -0,0 +1,162 @@
+//! Tests for flattened message fields, whose fields are encoded with their own tags directly in
+//! the enclosing message.
+
+use prost::encoding::FieldTags;
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Header {
+    #[prost_amino(uint64, tag = "1")]
+    pub id: u64,
+    #[prost_amino(string, tag = "2")]
+    pub name: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Envelope {
+    #[prost_amino(message, flatten)]
+    pub header: Header,
+    #[prost_amino(uint32, tag = "3")]
+    pub seq: u32,
+    #[prost_amino(string, tag = "4")]
+    pub body: String,
+}
+
+/// The same fields as `Envelope`, declared in a single message.
+#[derive(Clone, PartialEq, Message)]
+pub struct Flat {
+    #[prost_amino(uint64, tag = "1")]
+    pub id: u64,
+    #[prost_amino(string, tag = "2")]
+    pub name: String,
+    #[prost_amino(uint32, tag = "3")]
+    pub seq: u32,
+    #[prost_amino(string, tag = "4")]
+    pub body: String,
+}
+
+fn envelope() -> Envelope {
+    Envelope {
+        header: Header {
+            id: 42,
+            name: "greeting".to_owned(),
+        },
+        seq: 7,
+        body: "hello".to_owned(),
+    }
+}
+
+#[test]
+fn round_trip() {
+    let envelope = envelope();
+    let encoded = envelope.encode_to_vec();
+    assert_eq!(encoded.len(), envelope.encoded_len());
+    assert_eq!(Envelope::decode(&encoded[..]).unwrap(), envelope);
+}
+
+#[test]
+fn fields_are_encoded_in_the_enclosing_message() {
+    let flat = Flat::decode(&envelope().encode_to_vec()[..]).unwrap();
+    assert_eq!(
+        flat,
+        Flat {
+            id: 42,
+            name: "greeting".to_owned(),
+            seq: 7,
+            body: "hello".to_owned(),
+        }
+    );
+
+    let decoded = Envelope::decode(&flat.encode_to_vec()[..]).unwrap();
+    assert_eq!(decoded, envelope());
+}
+
+#[test]
+fn unknown_tags_are_skipped() {
+    let mut encoded = envelope().encode_to_vec();
+    // An unknown varint field with tag 9.
+    encoded.extend_from_slice(&[9 << 3, 1]);
+    assert_eq!(Envelope::decode(&encoded[..]).unwrap(), envelope());
+}
+
+#[test]
+fn field_tags() {
+    assert_eq!(Header::FIELD_TAGS, &[1, 2]);
+    // The tags of the flattened field are included.
+    assert_eq!(Envelope::FIELD_TAGS, &[1, 2, 3, 4]);
+    assert_eq!(Outer::FIELD_TAGS, &[1, 2, 3, 4, 5]);
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Inner {
+    #[prost_amino(uint64, tag = "2")]
+    pub c: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Middle {
+    #[prost_amino(message, flatten)]
+    pub inner: Inner,
+    #[prost_amino(string, tag = "4")]
+    pub b: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Outer {
+    #[prost_amino(uint32, tag = "1")]
+    pub a: u32,
+    #[prost_amino(message, flatten)]
+    pub middle: Middle,
+    #[prost_amino(bool, tag = "3")]
+    pub d: bool,
+    #[prost_amino(string, tag = "5")]
+    pub e: String,
+}
+
+/// The same fields as `Outer`, declared in a single message.
+#[derive(Clone, PartialEq, Message)]
+pub struct FlatOuter {
+    #[prost_amino(uint32, tag = "1")]
+    pub a: u32,
+    #[prost_amino(uint64, tag = "2")]
+    pub c: u64,
+    #[prost_amino(bool, tag = "3")]
+    pub d: bool,
+    #[prost_amino(string, tag = "4")]
+    pub b: String,
+    #[prost_amino(string, tag = "5")]
+    pub e: String,
+}
+
+fn outer() -> Outer {
+    Outer {
+        a: 1,
+        middle: Middle {
+            inner: Inner { c: 2 },
+            b: "four".to_owned(),
+        },
+        d: true,
+        e: "five".to_owned(),
+    }
+}
+
+#[test]
+fn nested_flattened_fields_round_trip() {
+    let outer = outer();
+    let encoded = outer.encode_to_vec();
+    assert_eq!(encoded.len(), outer.encoded_len());
+    assert_eq!(Outer::decode(&encoded[..]).unwrap(), outer);
+}
+
+#[test]
+fn flattened_fields_are_encoded_in_tag_order() {
+    let flat = FlatOuter {
+        a: 1,
+        c: 2,
+        d: true,
+        b: "four".to_owned(),
+        e: "five".to_owned(),
+    };
+    assert_eq!(outer().encode_to_vec(), flat.encode_to_vec());
+    assert_eq!(Outer::decode(&flat.encode_to_vec()[..]).unwrap(), outer());
+}
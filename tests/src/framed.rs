@@ -0,0 +1,71 @@
+//! Tests for `Message::encode_framed` and `Message::decode_framed`, which frame a message with a
+//! length delimiter and a trailing CRC32.
+
+use prost::encoding::crc32;
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Record {
+    #[prost_amino(uint64, tag = "1")]
+    pub offset: u64,
+    #[prost_amino(bytes, tag = "2")]
+    pub payload: Vec<u8>,
+}
+
+fn record(offset: u64) -> Record {
+    Record {
+        offset,
+        payload: b"hello, world".to_vec(),
+    }
+}
+
+#[test]
+fn valid_frame() {
+    let mut buf = Vec::new();
+    record(7).encode_framed(&mut buf).unwrap();
+
+    let body = record(7).encode_to_vec();
+    assert_eq!(buf[0] as usize, body.len());
+    assert_eq!(&buf[1..buf.len() - 4], &body[..]);
+    assert_eq!(&buf[buf.len() - 4..], &crc32(&body).to_le_bytes()[..]);
+
+    assert_eq!(Record::decode_framed(&buf[..]).unwrap(), record(7));
+}
+
+#[test]
+fn stream_of_frames() {
+    let mut buf = Vec::new();
+    for offset in 0..3 {
+        record(offset).encode_framed(&mut buf).unwrap();
+    }
+    let mut buf = &buf[..];
+    for offset in 0..3 {
+        assert_eq!(Record::decode_framed(&mut buf).unwrap(), record(offset));
+    }
+    assert!(buf.is_empty());
+}
+
+#[test]
+fn single_bit_flip_is_detected() {
+    let mut frame = Vec::new();
+    record(7).encode_framed(&mut frame).unwrap();
+
+    // Every bit of the body and of the checksum is covered.
+    for byte in 1..frame.len() {
+        for bit in 0..8 {
+            let mut corrupted = frame.clone();
+            corrupted[byte] ^= 1 << bit;
+            let error = Record::decode_framed(&corrupted[..]).unwrap_err();
+            assert!(error.is_checksum_mismatch(), "byte {} bit {}", byte, bit);
+        }
+    }
+}
+
+#[test]
+fn truncated_frame() {
+    let mut frame = Vec::new();
+    record(7).encode_framed(&mut frame).unwrap();
+    let error = Record::decode_framed(&frame[..frame.len() - 1]).unwrap_err();
+    assert!(error.is_incomplete());
+    assert!(!error.is_checksum_mismatch());
+}
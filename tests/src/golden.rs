@@ -0,0 +1,207 @@
+//! Compatibility tests against go-amino golden encodings.
+//!
+//! Each file in `golden/` holds the hex encoded bytes which go-amino produces
+//! for the Go value described above the corresponding Rust type. Whitespace in
+//! the files is ignored.
+
+use std::fmt::Debug;
+
+use prost::Message;
+
+fn golden(hex: &str) -> Vec<u8> {
+    let digits = hex
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_digit(16).expect("invalid hex digit") as u8)
+        .collect::<Vec<_>>();
+    assert_eq!(digits.len() % 2, 0, "odd number of hex digits");
+    digits
+        .chunks(2)
+        .map(|pair| pair[0] << 4 | pair[1])
+        .collect()
+}
+
+/// Checks that `msg` encodes to the golden bytes, and that they decode back to `msg`.
+fn check<M>(msg: &M, hex: &str)
+where
+    M: Message + Default + Debug + PartialEq,
+{
+    let want = golden(hex);
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+    assert_eq!(want, buf);
+    assert_eq!(want.len(), msg.encoded_len());
+    assert_eq!(*msg, M::decode(&want[..]).unwrap());
+}
+
+// type PubKeyEd25519 [32]byte // registered as "tendermint/PubKeyEd25519"
+// type Validator struct { PubKey crypto.PubKey }
+#[derive(Clone, PartialEq, Message)]
+pub struct Validator {
+    #[prost_amino(bytes, tag = "1", amino_name = "tendermint/PubKeyEd25519")]
+    pub pub_key: Vec<u8>,
+}
+
+// type Coin struct { Denom string; Amount sdk.Int }
+#[derive(Clone, PartialEq, Message)]
+pub struct Coin {
+    #[prost_amino(string, tag = "1")]
+    pub denom: String,
+    #[prost_amino(string, tag = "2")]
+    pub amount: String,
+}
+
+// type PartSetHeader struct { Total int; Hash []byte }
+#[derive(Clone, PartialEq, Message)]
+pub struct PartSetHeader {
+    #[prost_amino(sint64, tag = "1")]
+    pub total: i64,
+    #[prost_amino(bytes, tag = "2")]
+    pub hash: Vec<u8>,
+}
+
+// type BlockID struct { Hash []byte; PartsHeader PartSetHeader }
+#[derive(Clone, PartialEq, Message)]
+pub struct BlockID {
+    #[prost_amino(bytes, tag = "1")]
+    pub hash: Vec<u8>,
+    #[prost_amino(message, tag = "2")]
+    pub parts_header: Option<PartSetHeader>,
+}
+
+// type Vote struct {
+//     Type             byte
+//     Height           int64
+//     Round            int
+//     BlockID          BlockID
+//     ValidatorAddress []byte
+//     ValidatorIndex   int
+//     Signature        []byte
+// }
+#[derive(Clone, PartialEq, Message)]
+pub struct Vote {
+    #[prost_amino(uint32, tag = "1")]
+    pub vote_type: u32,
+    #[prost_amino(sint64)]
+    pub height: i64,
+    #[prost_amino(sint64)]
+    pub round: i64,
+    #[prost_amino(message)]
+    pub block_id: Option<BlockID>,
+    #[prost_amino(bytes)]
+    pub validator_address: Vec<u8>,
+    #[prost_amino(sint64)]
+    pub validator_index: i64,
+    #[prost_amino(bytes)]
+    pub signature: Vec<u8>,
+}
+
+// type Heartbeat struct {
+//     ValidatorAddress []byte
+//     ValidatorIndex   int
+//     Height           int64
+//     Round            int
+//     Sequence         int
+//     Signature        []byte
+// }
+#[derive(Clone, PartialEq, Message)]
+pub struct Heartbeat {
+    #[prost_amino(bytes, tag = "1")]
+    pub validator_address: Vec<u8>,
+    #[prost_amino(sint64)]
+    pub validator_index: i64,
+    #[prost_amino(sint64)]
+    pub height: i64,
+    #[prost_amino(sint64)]
+    pub round: i64,
+    #[prost_amino(sint64)]
+    pub sequence: i64,
+    #[prost_amino(bytes)]
+    pub signature: Vec<u8>,
+}
+
+// type SignHeartbeatMsg struct { Heartbeat *Heartbeat }
+// // registered as "tendermint/socketpv/SignHeartbeatMsg", encoded length prefixed.
+#[derive(Clone, PartialEq, Message)]
+#[amino_name = "tendermint/socketpv/SignHeartbeatMsg"]
+pub struct SignHeartbeatMsg {
+    #[prost_amino(message, tag = "1")]
+    pub heartbeat: Option<Heartbeat>,
+}
+
+// type Blob []byte // registered as "test/LargeBlob"
+// type Container struct { Blob Blob }
+#[derive(Clone, PartialEq, Message)]
+pub struct Container {
+    #[prost_amino(bytes, tag = "1", amino_name = "test/LargeBlob")]
+    pub blob: Vec<u8>,
+}
+
+#[test]
+fn pubkey_ed25519() {
+    let validator = Validator {
+        pub_key: (1..33).collect(),
+    };
+    check(&validator, include_str!("golden/pubkey_ed25519.hex"));
+}
+
+#[test]
+fn coin() {
+    let coin = Coin {
+        denom: "stake".to_owned(),
+        amount: "100".to_owned(),
+    };
+    check(&coin, include_str!("golden/coin.hex"));
+}
+
+#[test]
+fn vote() {
+    let vote = Vote {
+        vote_type: 2,
+        height: 12345,
+        round: 2,
+        block_id: Some(BlockID {
+            hash: vec![0xde, 0xad, 0xbe, 0xef],
+            parts_header: Some(PartSetHeader {
+                total: 1,
+                hash: vec![0xca, 0xfe],
+            }),
+        }),
+        validator_address: (0xa0..0xb4).collect(),
+        validator_index: 0,
+        signature: vec![1, 2, 3],
+    };
+    check(&vote, include_str!("golden/vote.hex"));
+}
+
+#[test]
+fn sign_heartbeat_msg() {
+    let msg = SignHeartbeatMsg {
+        heartbeat: Some(Heartbeat {
+            validator_address: vec![
+                0xa3, 0xb2, 0xcc, 0xdd, 0x71, 0x86, 0xf1, 0x68, 0x5f, 0x21, 0xf2, 0x48, 0x2a, 0xf4,
+                0xfb, 0x34, 0x46, 0xa8, 0x4b, 0x35,
+            ],
+            validator_index: 1,
+            height: 15,
+            round: 10,
+            sequence: 30,
+            signature: Vec::new(),
+        }),
+    };
+    let want = golden(include_str!("golden/heartbeat.hex"));
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+    assert_eq!(want, buf);
+    assert_eq!(msg, SignHeartbeatMsg::decode(&want[..]).unwrap());
+}
+
+#[test]
+fn large_prefixed_bytes() {
+    // The prefixed value is longer than 127 bytes, so both its length and the
+    // length of the enclosing field take two bytes.
+    let container = Container {
+        blob: (0..200).collect(),
+    };
+    check(&container, include_str!("golden/large_blob.hex"));
+}
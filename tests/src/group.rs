@@ -0,0 +1,113 @@
+//! Tests for message fields framed by start group and end group keys.
+
+use prost::bytes::buf::BufExt;
+use prost::encoding::{decode_key, WireType};
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Part {
+    #[prost_amino(uint32, tag = "1")]
+    pub index: u32,
+    #[prost_amino(bytes, tag = "2")]
+    pub hash: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct PartSet {
+    #[prost_amino(group, tag = "1")]
+    pub header: Option<Part>,
+    #[prost_amino(group, repeated, tag = "2")]
+    pub parts: Vec<Part>,
+    #[prost_amino(string, tag = "3")]
+    pub memo: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Outer {
+    #[prost_amino(group, required, tag = "5")]
+    pub set: PartSet,
+}
+
+/// `PartSet` without its group fields.
+#[derive(Clone, PartialEq, Message)]
+pub struct Memo {
+    #[prost_amino(string, tag = "3")]
+    pub memo: String,
+}
+
+fn part(index: u32) -> Part {
+    Part {
+        index,
+        hash: vec![index as u8; 3],
+    }
+}
+
+#[test]
+fn group_round_trip() {
+    let outer = Outer {
+        set: PartSet {
+            header: Some(part(0)),
+            parts: vec![part(1), Part::default(), part(2)],
+            memo: "parts".to_owned(),
+        },
+    };
+    let buf = outer.encode_to_vec();
+    assert_eq!(outer.encoded_len(), buf.len());
+    assert_eq!(Outer::decode(&buf[..]).unwrap(), outer);
+
+    let mut start = &buf[..];
+    assert_eq!(decode_key(&mut start).unwrap(), (5, WireType::StartGroup));
+    let mut end = &buf[buf.len() - 1..];
+    assert_eq!(decode_key(&mut end).unwrap(), (5, WireType::EndGroup));
+}
+
+#[test]
+fn group_wire_format() {
+    let set = PartSet {
+        header: Some(Part {
+            index: 1,
+            hash: Vec::new(),
+        }),
+        ..PartSet::default()
+    };
+    assert_eq!(set.encode_to_vec(), [1 << 3 | 3, 1 << 3, 1, 1 << 3 | 4]);
+}
+
+#[test]
+fn unknown_groups_are_skipped() {
+    let set = PartSet {
+        header: Some(part(0)),
+        parts: vec![part(1)],
+        memo: "parts".to_owned(),
+    };
+    let memo = Memo::decode(&set.encode_to_vec()[..]).unwrap();
+    assert_eq!(memo.memo, "parts");
+}
+
+#[test]
+fn mismatched_end_group() {
+    // The header group is ended by a key with the tag of the parts field.
+    let buf = [1 << 3 | 3, 1 << 3, 1, 2 << 3 | 4];
+    assert!(PartSet::decode(&buf[..]).is_err());
+
+    // The header group is never ended.
+    let buf = [1 << 3 | 3, 1 << 3, 1];
+    assert!(PartSet::decode(&buf[..]).is_err());
+}
+
+#[test]
+fn group_decode_from_chunks() {
+    let outer = Outer {
+        set: PartSet {
+            header: Some(part(300)),
+            parts: vec![part(1), part(2)],
+            memo: "parts".to_owned(),
+        },
+    };
+    let buf = outer.encode_to_vec();
+    // Every split, including one leaving an empty first chunk, falls within or between keys.
+    for i in 0..buf.len() {
+        let (head, tail) = buf.split_at(i);
+        assert_eq!(Outer::decode(head.chain(tail)).unwrap(), outer);
+    }
+}
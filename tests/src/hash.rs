@@ -0,0 +1,96 @@
+//! Tests for messages with the `hash_encoded` attribute, which are hashed by their encoding.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+#[prost_amino(hash_encoded)]
+pub struct Price {
+    #[prost_amino(string, tag = "1")]
+    pub denom: String,
+    #[prost_amino(double, tag = "2")]
+    pub amount: f64,
+    #[prost_amino(btree_map = "string, int64", tag = "3")]
+    pub fees: BTreeMap<String, i64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[prost_amino(hash_encoded)]
+pub struct Quote {
+    #[prost_amino(double, optional, tag = "1")]
+    pub bid: Option<f64>,
+    #[prost_amino(float, repeated, tag = "2")]
+    pub asks: Vec<f32>,
+    #[prost_amino(message, optional, tag = "3")]
+    pub price: Option<Price>,
+    #[prost_amino(map = "string, double", tag = "4")]
+    pub rates: HashMap<String, f64>,
+}
+
+fn hash<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn price(amount: f64) -> Price {
+    let mut fees = BTreeMap::new();
+    fees.insert("gas".to_owned(), 2);
+    fees.insert("tip".to_owned(), 1);
+    Price {
+        denom: "uatom".to_owned(),
+        amount,
+        fees,
+    }
+}
+
+#[test]
+fn equal_messages_hash_equally() {
+    assert_eq!(hash(&price(1.5)), hash(&price(1.5)));
+    assert_ne!(hash(&price(1.5)), hash(&price(2.5)));
+    assert_eq!(hash(&Price::default()), hash(&[][..] as &[u8]));
+}
+
+#[test]
+fn negative_zero_hashes_as_zero() {
+    // `-0.0 == 0.0`, so the two must hash equally, wherever the float is.
+    let quote = |zero: f64| {
+        let mut rates = HashMap::new();
+        rates.insert("usd".to_owned(), zero);
+        Quote {
+            bid: Some(zero),
+            asks: vec![1.0, zero as f32],
+            price: Some(Price {
+                amount: zero,
+                ..price(0.0)
+            }),
+            rates,
+        }
+    };
+    assert_eq!(quote(-0.0), quote(0.0));
+    assert_eq!(hash(&quote(-0.0)), hash(&quote(0.0)));
+    assert_ne!(hash(&quote(0.0)), hash(&quote(1.0)));
+
+    // The sign of zero is still written on the wire.
+    assert_ne!(quote(-0.0).encode_to_vec(), quote(0.0).encode_to_vec());
+}
+
+#[test]
+fn hash_maps_hash_in_key_order() {
+    let a = Quote {
+        rates: (0..32)
+            .map(|i| (format!("rate{}", i), f64::from(i)))
+            .collect(),
+        ..Quote::default()
+    };
+    // A map with the same entries, which is likely to iterate in a different order.
+    let b = Quote {
+        rates: a.rates.iter().map(|(k, v)| (k.clone(), *v)).collect(),
+        ..Quote::default()
+    };
+    assert_eq!(a, b);
+    assert_eq!(hash(&a), hash(&b));
+}
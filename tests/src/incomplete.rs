@@ -0,0 +1,86 @@
+//! Tests for the incomplete decode errors returned for truncated input.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Leaf {
+    #[prost_amino(int64, tag = "1")]
+    pub value: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Record {
+    #[prost_amino(uint64, tag = "1")]
+    pub id: u64,
+    #[prost_amino(string, tag = "2")]
+    pub name: String,
+    #[prost_amino(fixed64, tag = "3")]
+    pub checksum: u64,
+    #[prost_amino(message, optional, tag = "4")]
+    pub leaf: Option<Leaf>,
+}
+
+#[test]
+fn truncated_length_delimited_field() {
+    let msg = Record {
+        name: "validator".to_owned(),
+        ..Record::default()
+    };
+    let buf = msg.encode_to_vec();
+    assert_eq!(buf.len(), 11);
+
+    for cut in 3..buf.len() {
+        let error = Record::decode(&buf[..cut]).unwrap_err();
+        assert!(error.is_incomplete(), "{}", error);
+        assert_eq!(error.needed(), Some(buf.len() - cut));
+    }
+    assert_eq!(Record::decode(&buf[..]).unwrap(), msg);
+}
+
+#[test]
+fn truncated_varint() {
+    let msg = Record {
+        id: u64::max_value(),
+        ..Record::default()
+    };
+    let buf = msg.encode_to_vec();
+
+    for cut in 1..buf.len() {
+        let error = Record::decode(&buf[..cut]).unwrap_err();
+        assert!(error.is_incomplete(), "{}", error);
+        assert_eq!(error.needed(), None);
+    }
+}
+
+#[test]
+fn truncated_fixed_width() {
+    let buf = Record {
+        checksum: 7,
+        ..Record::default()
+    }
+    .encode_to_vec();
+    let error = Record::decode(&buf[..4]).unwrap_err();
+    assert!(error.is_incomplete());
+    assert_eq!(error.needed(), Some(5));
+}
+
+#[test]
+fn malformed_is_not_incomplete() {
+    // An 11 byte varint.
+    let mut buf = vec![1 << 3];
+    buf.extend_from_slice(&[0xff; 10]);
+    buf.push(0x01);
+    let error = Record::decode(&buf[..]).unwrap_err();
+    assert!(!error.is_incomplete());
+
+    // The leaf is complete, but its varint is truncated by the end of its contents.
+    let buf = [4 << 3 | 2, 0x02, 0x08, 0xff];
+    let error = Record::decode(&buf[..]).unwrap_err();
+    assert!(!error.is_incomplete());
+    assert_eq!(error.needed(), None);
+
+    // The contents of the leaf are truncated, so more input may complete it.
+    let error = Record::decode(&[4 << 3 | 2, 0x02, 0x08][..]).unwrap_err();
+    assert!(error.is_incomplete());
+    assert_eq!(error.needed(), Some(1));
+}
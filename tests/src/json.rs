@@ -0,0 +1,169 @@
+//! Tests for `json` fields, which hold a `serde_json::Value` encoded as a JSON string, and for the
+//! Amino JSON form of messages with the `amino_json` attribute.
+
+use prost::serde_json::{self, Value};
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Event {
+    #[prost_amino(string, tag = "1")]
+    pub kind: String,
+    #[prost_amino(json, tag = "2")]
+    pub payload: Value,
+    #[prost_amino(json, optional, tag = "3")]
+    pub meta: Option<Value>,
+    #[prost_amino(json, repeated, tag = "4")]
+    pub attributes: Vec<Value>,
+}
+
+/// The same fields as `Event`, with the JSON values as strings.
+#[derive(Clone, PartialEq, Message)]
+pub struct RawEvent {
+    #[prost_amino(string, tag = "1")]
+    pub kind: String,
+    #[prost_amino(string, tag = "2")]
+    pub payload: String,
+}
+
+fn json(s: &str) -> Value {
+    serde_json::from_str(s).unwrap()
+}
+
+#[test]
+fn round_trip_object() {
+    let event = Event {
+        kind: "transfer".to_owned(),
+        payload: json(r#"{"amount": [1, 2.5, null], "memo": "hi", "nested": {"ok": true}}"#),
+        meta: Some(Value::Null),
+        attributes: vec![json(r#""a""#), json("{}")],
+    };
+    let buf = event.encode_to_vec();
+    assert_eq!(buf.len(), event.encoded_len());
+    assert_eq!(Event::decode(&buf[..]).unwrap(), event);
+
+    // The value is written as compact JSON.
+    let raw = RawEvent::decode(&buf[..]).unwrap();
+    assert_eq!(
+        raw.payload,
+        r#"{"amount":[1,2.5,null],"memo":"hi","nested":{"ok":true}}"#
+    );
+}
+
+#[test]
+fn null_is_the_default() {
+    let event = Event::default();
+    assert_eq!(event.payload, Value::Null);
+    assert_eq!(event.meta(), &Value::Null);
+    assert_eq!(event.encoded_len(), 0);
+    assert_eq!(Event::decode(&[][..]).unwrap(), event);
+}
+
+#[test]
+fn malformed_json_is_rejected() {
+    let buf = RawEvent {
+        kind: "transfer".to_owned(),
+        payload: r#"{"amount": 1"#.to_owned(),
+    }
+    .encode_to_vec();
+    let error = Event::decode(&buf[..]).unwrap_err().to_string();
+    assert!(
+        error.starts_with("failed to decode Protobuf message: Event.payload: invalid JSON value: "),
+        "{}",
+        error
+    );
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[prost_amino(amino_json)]
+pub struct Coin {
+    #[prost_amino(string, tag = "1")]
+    pub denom: String,
+    #[prost_amino(uint64, tag = "2")]
+    pub amount: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[prost_amino(amino_json)]
+pub struct Fee {
+    #[prost_amino(message, tag = "1")]
+    pub coin: Option<Coin>,
+    #[prost_amino(uint32, tag = "2")]
+    pub gas: u32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[amino_name = "test/Send"]
+#[prost_amino(amino_json)]
+pub struct Send {
+    #[prost_amino(bytes, tag = "1")]
+    pub from: Vec<u8>,
+    #[prost_amino(message, tag = "2", json_inline)]
+    pub fee: Option<Fee>,
+    #[prost_amino(message, repeated, tag = "3")]
+    pub coins: Vec<Coin>,
+    #[prost_amino(bool, optional, tag = "4")]
+    pub memo: Option<bool>,
+}
+
+#[test]
+fn amino_json_fields() {
+    use prost::encoding::json::AminoJson;
+
+    let coin = Coin {
+        denom: "uatom".to_owned(),
+        amount: u64::MAX,
+    };
+    // 64-bit integers are written as strings.
+    assert_eq!(
+        coin.to_json(),
+        json(r#"{"denom": "uatom", "amount": "18446744073709551615"}"#)
+    );
+    assert_eq!(Coin::JSON_KEYS, &["denom", "amount"]);
+
+    let send = Send {
+        from: b"addr".to_vec(),
+        fee: Some(Fee {
+            coin: Some(coin.clone()),
+            gas: 200,
+        }),
+        coins: vec![coin],
+        memo: None,
+    };
+    // The fields of the inlined fee are written at the level of the message, which is wrapped
+    // with its amino name.
+    assert_eq!(
+        send.to_json(),
+        json(
+            r#"{
+                "type": "test/Send",
+                "value": {
+                    "from": "YWRkcg==",
+                    "coin": {"denom": "uatom", "amount": "18446744073709551615"},
+                    "gas": 200,
+                    "coins": [{"denom": "uatom", "amount": "18446744073709551615"}],
+                    "memo": null
+                }
+            }"#
+        )
+    );
+    assert_eq!(Send::JSON_KEYS, &["from", "coins", "memo", "coin", "gas"]);
+
+    // An unset inlined field writes no keys.
+    let send = Send::default();
+    assert_eq!(
+        send.to_json(),
+        json(r#"{"type": "test/Send", "value": {"from": "", "coins": [], "memo": null}}"#)
+    );
+}
+
+#[test]
+fn base64() {
+    use prost::encoding::json::base64;
+
+    assert_eq!(base64(b""), "");
+    assert_eq!(base64(b"f"), "Zg==");
+    assert_eq!(base64(b"fo"), "Zm8=");
+    assert_eq!(base64(b"foo"), "Zm9v");
+    assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    assert_eq!(base64(&[0xfb, 0xff]), "+/8=");
+}
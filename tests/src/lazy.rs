@@ -0,0 +1,130 @@
+//! Tests for `lazy` message fields, which hold a `Lazy` message that is decoded when it is first
+//! accessed.
+
+use prost::{Lazy, Message};
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Header {
+    #[prost_amino(int64, tag = "1")]
+    pub height: i64,
+    #[prost_amino(string, tag = "2")]
+    pub chain_id: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Block {
+    #[prost_amino(uint32, tag = "1")]
+    pub version: u32,
+    #[prost_amino(message, lazy, tag = "2")]
+    pub header: Option<Lazy<Header>>,
+    #[prost_amino(message, lazy, repeated, tag = "3")]
+    pub evidence: Vec<Lazy<Header>>,
+}
+
+/// The same message with eagerly decoded fields.
+#[derive(Clone, PartialEq, Message)]
+pub struct EagerBlock {
+    #[prost_amino(uint32, tag = "1")]
+    pub version: u32,
+    #[prost_amino(message, tag = "2")]
+    pub header: Option<Header>,
+    #[prost_amino(message, repeated, tag = "3")]
+    pub evidence: Vec<Header>,
+}
+
+fn header(height: i64) -> Header {
+    Header {
+        height,
+        chain_id: "test-chain".to_owned(),
+    }
+}
+
+fn eager_block() -> EagerBlock {
+    EagerBlock {
+        version: 11,
+        header: Some(header(100)),
+        evidence: vec![header(98), header(99)],
+    }
+}
+
+#[test]
+fn nested_message_is_decoded_on_access() {
+    let block = Block::decode(&eager_block().encode_to_vec()[..]).unwrap();
+    assert_eq!(block.version, 11);
+
+    let lazy = block.header.as_ref().unwrap();
+    assert!(!lazy.is_decoded());
+    assert!(block.evidence.iter().all(|lazy| !lazy.is_decoded()));
+
+    assert_eq!(lazy.get().unwrap(), &header(100));
+    assert!(lazy.is_decoded());
+    assert!(block.evidence.iter().all(|lazy| !lazy.is_decoded()));
+    assert_eq!(block.evidence[1].get().unwrap(), &header(99));
+}
+
+#[test]
+fn encodes_like_eager_message() {
+    let eager = eager_block();
+    let block = Block::decode(&eager.encode_to_vec()[..]).unwrap();
+    assert_eq!(block.encode_to_vec(), eager.encode_to_vec());
+
+    let built = Block {
+        version: 11,
+        header: Some(Lazy::new(header(100))),
+        evidence: vec![Lazy::new(header(98)), Lazy::new(header(99))],
+    };
+    assert_eq!(built, block);
+    assert_eq!(built.encoded_len(), eager.encoded_len());
+}
+
+#[test]
+fn invalid_nested_message_fails_on_access() {
+    let raw = EagerBlock {
+        version: 11,
+        ..EagerBlock::default()
+    };
+    let mut buf = raw.encode_to_vec();
+    // A header whose only field is a truncated varint.
+    buf.extend_from_slice(&[0x12, 0x02, 0x08, 0x80]);
+
+    let block = Block::decode(&buf[..]).unwrap();
+    assert!(block.header.as_ref().unwrap().get().is_err());
+    assert!(block.validate().is_err());
+    assert!(EagerBlock::decode(&buf[..]).is_err());
+}
+
+#[test]
+fn repeated_fields_merge_into_one_message() {
+    let mut buf = EagerBlock {
+        header: Some(Header {
+            height: 1,
+            ..Header::default()
+        }),
+        ..EagerBlock::default()
+    }
+    .encode_to_vec();
+    buf.extend(
+        EagerBlock {
+            header: Some(Header {
+                chain_id: "merged".to_owned(),
+                ..Header::default()
+            }),
+            ..EagerBlock::default()
+        }
+        .encode_to_vec(),
+    );
+
+    let block = Block::decode(&buf[..]).unwrap();
+    let header = block.header.unwrap().into_inner().unwrap();
+    assert_eq!(header.height, 1);
+    assert_eq!(header.chain_id, "merged");
+}
+
+#[test]
+fn decode_copies_fields() {
+    let encoded = header(5).encode_to_vec();
+    let lazy = Lazy::<Header>::decode(&encoded[..]).unwrap();
+    assert!(!lazy.is_decoded());
+    assert_eq!(lazy.raw(), &encoded[..]);
+    assert_eq!(lazy.get().unwrap(), &header(5));
+}
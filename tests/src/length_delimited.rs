@@ -0,0 +1,75 @@
+//! Tests for `Message::merge_length_delimited`, which consumes exactly one length-delimited
+//! message from a buffer.
+
+use bytes::Buf;
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Point {
+    #[prost_amino(sint32, tag = "1")]
+    pub x: i32,
+    #[prost_amino(sint32, tag = "2")]
+    pub y: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[amino_name = "test/Label"]
+pub struct Label {
+    #[prost_amino(string, tag = "1")]
+    pub text: String,
+}
+
+#[test]
+fn merge_stops_at_the_next_message() {
+    let first = Point { x: 1, y: -2 };
+    let second = Point { x: 300, y: 0 };
+    let mut buf = Vec::new();
+    first.encode_length_delimited(&mut buf).unwrap();
+    second.encode_length_delimited(&mut buf).unwrap();
+
+    let mut cursor = &buf[..];
+    let mut point = Point::default();
+    point.merge_length_delimited(&mut cursor).unwrap();
+    assert_eq!(point, first);
+    // The cursor is at the length delimiter of the second message.
+    assert_eq!(cursor.remaining(), buf.len() - first.encoded_len() - 1);
+    assert_eq!(cursor[0] as usize, second.encoded_len());
+
+    assert_eq!(Point::decode_length_delimited(&mut cursor).unwrap(), second);
+    assert!(!cursor.has_remaining());
+}
+
+#[test]
+fn merge_leaves_trailing_bytes() {
+    let point = Point { x: 5, y: 6 };
+    let mut buf = Vec::new();
+    point.encode_length_delimited(&mut buf).unwrap();
+    buf.extend_from_slice(&[0xff, 0xff]);
+
+    let mut cursor = &buf[..];
+    assert_eq!(Point::decode_length_delimited(&mut cursor).unwrap(), point);
+    assert_eq!(cursor, &[0xff, 0xff][..]);
+}
+
+#[test]
+fn registered_messages_round_trip() {
+    let first = Label {
+        text: "first".to_owned(),
+    };
+    let second = Label {
+        text: "second".to_owned(),
+    };
+    let mut buf = Vec::new();
+    first.encode_length_delimited(&mut buf).unwrap();
+    second.encode_length_delimited(&mut buf).unwrap();
+    // A registered message is framed by its own length delimiter only.
+    assert_eq!(
+        buf.len(),
+        first.encoded_len_with_prefix() + second.encoded_len_with_prefix()
+    );
+
+    let mut cursor = &buf[..];
+    assert_eq!(Label::decode_length_delimited(&mut cursor).unwrap(), first);
+    assert_eq!(Label::decode_length_delimited(&mut cursor).unwrap(), second);
+    assert!(!cursor.has_remaining());
+}
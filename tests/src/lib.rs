@@ -1,5 +1,5 @@
 extern crate bytes;
-extern crate prost_amino;
+extern crate prost_amino as prost;
 extern crate prost_types;
 
 #[macro_use]
@@ -9,19 +9,151 @@ extern crate prost_amino_derive;
 extern crate prost_build;
 #[cfg(test)]
 extern crate tempdir;
+#[cfg(feature = "tokio")]
+extern crate tokio;
 
 pub mod amino;
 pub mod packages;
 pub mod unittest;
 
+#[cfg(test)]
+mod alias;
+#[cfg(test)]
+mod allowed_tags;
+#[cfg(test)]
+mod ascii;
+#[cfg(all(test, feature = "tokio"))]
+mod async_decoder;
+#[cfg(test)]
+mod bit_eq;
 #[cfg(test)]
 mod bootstrap;
 #[cfg(test)]
+mod builder;
+#[cfg(test)]
+mod checksum;
+#[cfg(test)]
+mod chunk;
+#[cfg(test)]
+mod clamp;
+#[cfg(test)]
+mod closed_enum;
+#[cfg(test)]
+mod conversions;
+#[cfg(test)]
+mod cow;
+#[cfg(test)]
 mod debug;
 #[cfg(test)]
+mod decoder;
+#[cfg(test)]
+mod decode_raw;
+#[cfg(test)]
+mod dedup;
+#[cfg(test)]
+mod defaults;
+#[cfg(test)]
+mod delta;
+#[cfg(test)]
+mod deprecated_wire;
+#[cfg(test)]
+mod descriptor;
+#[cfg(test)]
+mod duplicate_fields;
+#[cfg(test)]
+mod duration;
+#[cfg(test)]
+mod enumerations;
+#[cfg(test)]
+mod epoch;
+#[cfg(test)]
+mod eq_ignoring;
+#[cfg(test)]
+mod errors;
+#[cfg(test)]
+mod fixed_or_var;
+#[cfg(test)]
+mod flatten;
+#[cfg(test)]
+mod framed;
+#[cfg(test)]
+mod golden;
+#[cfg(test)]
+mod group;
+#[cfg(test)]
+mod hash;
+#[cfg(test)]
+mod incomplete;
+#[cfg(all(test, feature = "json"))]
+mod json;
+#[cfg(test)]
+mod lazy;
+#[cfg(test)]
+mod length_delimited;
+#[cfg(test)]
+mod limits;
+#[cfg(test)]
+mod merge_from;
+#[cfg(test)]
 mod message_encoding;
 #[cfg(test)]
+mod nesting;
+#[cfg(test)]
+mod no_panic;
+#[cfg(test)]
 mod no_unused_results;
+#[cfg(test)]
+mod oneof;
+#[cfg(test)]
+mod optional_message;
+#[cfg(test)]
+mod optional_repeated;
+#[cfg(test)]
+mod packed;
+#[cfg(test)]
+mod presence;
+#[cfg(test)]
+mod range;
+#[cfg(test)]
+mod raw;
+#[cfg(test)]
+mod recursive;
+#[cfg(test)]
+mod registry;
+#[cfg(test)]
+mod repeated;
+#[cfg(test)]
+mod reserve;
+#[cfg(test)]
+mod reuse;
+#[cfg(test)]
+mod schema;
+#[cfg(test)]
+mod shared;
+#[cfg(test)]
+mod size_breakdown;
+#[cfg(test)]
+mod sorted;
+#[cfg(test)]
+mod stream;
+#[cfg(test)]
+mod tags;
+#[cfg(test)]
+mod transparent;
+#[cfg(test)]
+mod trim;
+#[cfg(test)]
+mod validate;
+#[cfg(test)]
+mod varint_codec;
+#[cfg(test)]
+mod varint_width;
+#[cfg(test)]
+mod visit;
+#[cfg(test)]
+mod wire;
+#[cfg(all(test, feature = "zstd"))]
+mod zstd;
 
 pub mod protobuf_test_messages {
     pub mod proto2 {
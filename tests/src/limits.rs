@@ -0,0 +1,60 @@
+//! Tests for decode-time length limits on `string` and `bytes` fields.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Limited {
+    #[prost_amino(string, max_len = 4, tag = "1")]
+    pub name: String,
+    #[prost_amino(bytes, optional, max_len = "2", tag = "2")]
+    pub data: Option<Vec<u8>>,
+}
+
+#[test]
+fn at_limit() {
+    let msg = Limited {
+        name: "abcd".to_owned(),
+        data: Some(vec![1, 2]),
+    };
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+    assert_eq!(msg, Limited::decode(&buf[..]).unwrap());
+}
+
+#[test]
+fn over_limit() {
+    let msg = Limited {
+        name: "abcde".to_owned(),
+        data: None,
+    };
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+    let error = Limited::decode(&buf[..]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "failed to decode Protobuf message: Limited.name: \
+         field too long: length 5 exceeds maximum of 4"
+    );
+    assert_eq!(error.max_len_exceeded(), Some((1, 4)));
+}
+
+#[test]
+fn over_limit_fails_before_reading_value() {
+    // The field claims a 1GiB value which is not present in the buffer.
+    let buf = [0x12, 0x80, 0x80, 0x80, 0x80, 0x04];
+    let error = Limited::decode(&buf[..]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "failed to decode Protobuf message: Limited.data: \
+         field too long: length 1073741824 exceeds maximum of 2"
+    );
+    assert_eq!(error.max_len_exceeded(), Some((2, 2)));
+    assert!(!error.is_incomplete());
+}
+
+#[test]
+fn other_errors_do_not_exceed_max_len() {
+    let buf = [0x0a, 0x02, 0xff, 0xff];
+    let error = Limited::decode(&buf[..]).unwrap_err();
+    assert_eq!(error.max_len_exceeded(), None);
+}
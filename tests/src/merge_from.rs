@@ -0,0 +1,175 @@
+//! Tests for `Message::merge_from`, which merges one message value into another.
+
+use std::collections::HashMap;
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Inner {
+    #[prost_amino(int32, tag = "1")]
+    pub a: i32,
+    #[prost_amino(string, tag = "2")]
+    pub b: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Outer {
+    #[prost_amino(int64, tag = "1")]
+    pub height: i64,
+    #[prost_amino(string, tag = "2")]
+    pub name: String,
+    #[prost_amino(uint32, optional, tag = "3")]
+    pub round: Option<u32>,
+    #[prost_amino(int32, repeated, tag = "4")]
+    pub values: Vec<i32>,
+    #[prost_amino(message, repeated, tag = "5")]
+    pub inners: Vec<Inner>,
+    #[prost_amino(message, optional, tag = "6")]
+    pub inner: Option<Inner>,
+    #[prost_amino(map = "string, int32", tag = "7")]
+    pub counts: HashMap<String, i32>,
+}
+
+#[test]
+fn scalars_overwrite_when_set() {
+    let mut msg = Outer {
+        height: 1,
+        name: "a".to_owned(),
+        ..Outer::default()
+    };
+    msg.merge_from(Outer {
+        height: 2,
+        ..Outer::default()
+    })
+    .unwrap();
+    assert_eq!(msg.height, 2);
+    assert_eq!(msg.name, "a");
+}
+
+#[test]
+fn repeated_fields_concatenate() {
+    let mut msg = Outer {
+        values: vec![1, 2],
+        inners: vec![Inner {
+            a: 1,
+            b: String::new(),
+        }],
+        ..Outer::default()
+    };
+    msg.merge_from(Outer {
+        values: vec![3],
+        inners: vec![Inner {
+            a: 2,
+            b: String::new(),
+        }],
+        ..Outer::default()
+    })
+    .unwrap();
+    assert_eq!(msg.values, vec![1, 2, 3]);
+    assert_eq!(msg.inners.iter().map(|inner| inner.a).collect::<Vec<_>>(), vec![1, 2]);
+}
+
+#[test]
+fn optional_presence() {
+    let mut msg = Outer {
+        round: Some(3),
+        ..Outer::default()
+    };
+    msg.merge_from(Outer::default()).unwrap();
+    assert_eq!(msg.round, Some(3));
+
+    msg.merge_from(Outer {
+        round: Some(0),
+        ..Outer::default()
+    })
+    .unwrap();
+    assert_eq!(msg.round, Some(0));
+}
+
+#[test]
+fn nested_messages_merge() {
+    let mut msg = Outer::default();
+    msg.merge_from(Outer {
+        inner: Some(Inner {
+            a: 1,
+            b: String::new(),
+        }),
+        ..Outer::default()
+    })
+    .unwrap();
+    msg.merge_from(Outer {
+        inner: Some(Inner {
+            a: 0,
+            b: "b".to_owned(),
+        }),
+        ..Outer::default()
+    })
+    .unwrap();
+    assert_eq!(
+        msg.inner,
+        Some(Inner {
+            a: 1,
+            b: "b".to_owned(),
+        })
+    );
+}
+
+#[test]
+fn maps_overwrite_keys() {
+    let mut msg = Outer::default();
+    msg.counts.insert("a".to_owned(), 1);
+    msg.counts.insert("b".to_owned(), 2);
+    let mut other = Outer::default();
+    other.counts.insert("b".to_owned(), 3);
+    msg.merge_from(other).unwrap();
+    assert_eq!(msg.counts["a"], 1);
+    assert_eq!(msg.counts["b"], 3);
+}
+
+#[test]
+fn matches_wire_merge() {
+    let first = Outer {
+        height: 1,
+        values: vec![1],
+        inner: Some(Inner {
+            a: 1,
+            b: String::new(),
+        }),
+        ..Outer::default()
+    };
+    let second = Outer {
+        name: "b".to_owned(),
+        values: vec![2],
+        inner: Some(Inner {
+            a: 0,
+            b: "b".to_owned(),
+        }),
+        ..Outer::default()
+    };
+
+    let mut buf = Vec::new();
+    second.encode(&mut buf).unwrap();
+    let mut decoded = first.clone();
+    decoded.merge(&buf[..]).unwrap();
+
+    let mut merged = first;
+    merged.merge_from(second).unwrap();
+    assert_eq!(merged, decoded);
+}
+
+#[test]
+fn default_merges_encoding() {
+    // `Duration` has no derived implementation, so it is merged through its encoding.
+    let mut duration = prost::Duration {
+        seconds: 1,
+        nanos: 2,
+    };
+    duration
+        .merge_from(prost::Duration {
+            seconds: 0,
+            nanos: 3,
+        })
+        .unwrap();
+    assert_eq!(duration.seconds, 1);
+    assert_eq!(duration.nanos, 3);
+}
@@ -0,0 +1,241 @@
+//! Tests for registered messages nested inside length-delimited fields.
+
+use prost::{AminoMessage, Message};
+
+#[derive(Clone, PartialEq, Message)]
+#[amino_name = "cosmos-sdk/MsgSend"]
+pub struct MsgSend {
+    #[prost_amino(string, tag = "1")]
+    pub from: String,
+    #[prost_amino(int64, tag = "2")]
+    pub amount: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct StdTx {
+    #[prost_amino(message, tag = "1")]
+    pub msg: Option<MsgSend>,
+    #[prost_amino(message, repeated, tag = "2")]
+    pub msgs: Vec<MsgSend>,
+    #[prost_amino(string, tag = "3")]
+    pub memo: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Envelope {
+    #[prost_amino(message, tag = "1")]
+    pub tx: Option<StdTx>,
+}
+
+fn msg_send(amount: i64) -> MsgSend {
+    MsgSend {
+        from: "cosmos1".to_owned(),
+        amount,
+    }
+}
+
+#[test]
+fn registered_message_in_field() {
+    let tx = StdTx {
+        msg: Some(msg_send(5)),
+        msgs: Vec::new(),
+        memo: "m".to_owned(),
+    };
+    let mut buf = Vec::new();
+    tx.encode(&mut buf).unwrap();
+    assert_eq!(tx.encoded_len(), buf.len());
+
+    // The field length is followed by the prefix and the fields of the registered message.
+    let mut want = vec![0x0a, 0x0f];
+    want.extend_from_slice(&MsgSend::amino_prefix());
+    want.extend_from_slice(&[0x0a, 0x07]);
+    want.extend_from_slice(b"cosmos1");
+    want.extend_from_slice(&[0x10, 0x05, 0x1a, 0x01, b'm']);
+    assert_eq!(buf, want);
+
+    assert_eq!(StdTx::decode(&buf[..]).unwrap(), tx);
+}
+
+#[test]
+fn two_level_nesting() {
+    let envelope = Envelope {
+        tx: Some(StdTx {
+            msg: Some(msg_send(1)),
+            msgs: vec![msg_send(2), msg_send(3)],
+            memo: String::new(),
+        }),
+    };
+    let mut buf = Vec::new();
+    envelope.encode(&mut buf).unwrap();
+    assert_eq!(envelope.encoded_len(), buf.len());
+    assert_eq!(Envelope::decode(&buf[..]).unwrap(), envelope);
+}
+
+#[test]
+fn registered_message_on_its_own() {
+    let msg = msg_send(7);
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+    assert_eq!(buf[0] as usize, buf.len() - 1);
+    assert_eq!(&buf[1..5], MsgSend::amino_prefix());
+    assert_eq!(MsgSend::decode(&buf[..]).unwrap(), msg);
+}
+
+#[test]
+fn wrong_prefix() {
+    let tx = StdTx {
+        msg: Some(msg_send(5)),
+        ..StdTx::default()
+    };
+    let mut buf = Vec::new();
+    tx.encode(&mut buf).unwrap();
+    buf[2] ^= 0xff;
+    assert!(StdTx::decode(&buf[..]).is_err());
+}
+
+#[test]
+fn encoded_len_with_prefix() {
+    for from in &["", "cosmos1", &"x".repeat(200)] {
+        let msg = MsgSend {
+            from: from.to_string(),
+            amount: 7,
+        };
+        let mut buf = Vec::new();
+        msg.encode(&mut buf).unwrap();
+        assert_eq!(msg.encoded_len_with_prefix(), buf.len());
+    }
+}
+
+#[test]
+fn into_vec() {
+    let tx = StdTx {
+        msg: Some(msg_send(5)),
+        msgs: vec![msg_send(6)],
+        memo: "m".to_owned(),
+    };
+    let mut buf = Vec::new();
+    tx.encode(&mut buf).unwrap();
+    assert_eq!(tx.encode_to_vec(), buf);
+    let bytes: Vec<u8> = tx.into();
+    assert_eq!(bytes, buf);
+
+    let msg = msg_send(7);
+    let encoded = msg.encode_to_vec();
+    assert_eq!(encoded.len(), msg.encoded_len_with_prefix());
+    assert_eq!(Vec::from(msg), encoded);
+}
+
+#[test]
+fn encode_to_slice() {
+    fn check<M: Message>(msg: &M) {
+        let encoded = msg.encode_to_vec();
+
+        // Exact fit.
+        let mut out = vec![0; encoded.len()];
+        assert_eq!(msg.encode_to_slice(&mut out).unwrap(), encoded.len());
+        assert_eq!(out, encoded);
+
+        // A longer slice is written from the start, and the rest is left untouched.
+        let mut out = vec![0xff; encoded.len() + 3];
+        assert_eq!(msg.encode_to_slice(&mut out).unwrap(), encoded.len());
+        assert_eq!(out[..encoded.len()], encoded[..]);
+        assert_eq!(out[encoded.len()..], [0xff; 3]);
+
+        // Too short.
+        let mut out = vec![0; encoded.len() - 1];
+        let error = msg.encode_to_slice(&mut out).unwrap_err();
+        assert_eq!(error.required_capacity(), encoded.len());
+        assert_eq!(error.remaining(), encoded.len() - 1);
+    }
+
+    check(&StdTx {
+        msg: Some(msg_send(5)),
+        msgs: vec![msg_send(6)],
+        memo: "m".to_owned(),
+    });
+    // Registered messages are written with their length delimiter.
+    check(&msg_send(7));
+}
+
+#[test]
+fn encode_checks_the_length_delimiter() {
+    // A registered message is written with its length delimiter, which must fit too.
+    let msg = msg_send(8);
+    let encoded = msg.encode_to_vec();
+    assert!(encoded.len() > msg.encoded_len());
+
+    let mut out = vec![0; encoded.len()];
+    msg.encode(&mut &mut out[..]).unwrap();
+    assert_eq!(out, encoded);
+
+    let mut out = vec![0; encoded.len() - 1];
+    let error = msg.encode(&mut &mut out[..]).unwrap_err();
+    assert_eq!(error.required_capacity(), encoded.len());
+    assert_eq!(error.remaining(), encoded.len() - 1);
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Batch {
+    #[prost_amino(message, required, tag = "1")]
+    pub first: MsgSend,
+    #[prost_amino(message, repeated, tag = "2")]
+    pub rest: Vec<MsgSend>,
+    #[prost_amino(message, tag = "3")]
+    pub tx: Option<StdTx>,
+}
+
+#[test]
+fn encoded_len_counts_nested_prefixes() {
+    let long = MsgSend {
+        from: "x".repeat(200),
+        amount: 1,
+    };
+    let batch = Batch {
+        first: long.clone(),
+        rest: vec![msg_send(2), long.clone(), MsgSend::default()],
+        tx: Some(StdTx {
+            msg: Some(long),
+            msgs: vec![msg_send(3)],
+            memo: String::new(),
+        }),
+    };
+    let buf = batch.encode_to_vec();
+    assert_eq!(batch.encoded_len(), buf.len());
+    assert_eq!(Batch::decode(&buf[..]).unwrap(), batch);
+
+    let empty = Batch::default();
+    assert_eq!(empty.encoded_len(), empty.encode_to_vec().len());
+}
+
+#[test]
+fn downcast_decoded_message() {
+    let buf = msg_send(7).encode_to_vec();
+    let decoded: Box<dyn AminoMessage> = Box::new(MsgSend::decode(&buf[..]).unwrap());
+    assert_eq!(decoded.encoded_len(), msg_send(7).encoded_len());
+    assert_eq!(
+        decoded.as_any().downcast_ref::<MsgSend>(),
+        Some(&msg_send(7))
+    );
+    assert!(decoded.as_any().downcast_ref::<StdTx>().is_none());
+}
+
+/// A message field with the `amino_name` of its registered type, whose prefix is written ahead
+/// of each value.
+#[derive(Clone, PartialEq, Message)]
+pub struct Aliased {
+    #[prost_amino(message, required, tag = "1", amino_name = "cosmos-sdk/MsgSend")]
+    pub msg: MsgSend,
+    #[prost_amino(message, repeated, tag = "2", amino_name = "cosmos-sdk/MsgSend")]
+    pub msgs: Vec<MsgSend>,
+}
+
+#[test]
+fn encoded_len_counts_field_prefixes() {
+    let msg = Aliased {
+        msg: msg_send(1),
+        msgs: vec![msg_send(2), msg_send(3)],
+    };
+    // `encode_to_vec` checks the encoded length in debug builds.
+    let buf = msg.encode_to_vec();
+    assert_eq!(msg.encoded_len(), buf.len());
+}
@@ -0,0 +1,139 @@
+//! Tests that decoding malformed input returns an error instead of panicking or overflowing the
+//! stack.
+
+use std::collections::HashMap;
+
+use prost::encoding::{encode_varint, RECURSION_LIMIT};
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Nested {
+    #[prost_amino(message, optional, boxed, tag = "1")]
+    pub child: Option<Box<Nested>>,
+    #[prost_amino(int32, tag = "2")]
+    pub value: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct NestedGroup {
+    #[prost_amino(group, optional, boxed, tag = "1")]
+    pub child: Option<Box<NestedGroup>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Everything {
+    #[prost_amino(int32, tag = "1")]
+    pub int32: i32,
+    #[prost_amino(string, tag = "2")]
+    pub string: String,
+    #[prost_amino(bytes, tag = "3")]
+    pub bytes: Vec<u8>,
+    #[prost_amino(sint64, repeated, tag = "4")]
+    pub packed: Vec<i64>,
+    #[prost_amino(map = "string, int64", tag = "5")]
+    pub map: HashMap<String, i64>,
+    #[prost_amino(message, optional, tag = "6")]
+    pub nested: Option<Nested>,
+    #[prost_amino(fixed64, tag = "7")]
+    pub fixed: u64,
+    #[prost_amino(duration, tag = "8")]
+    pub duration: Option<::prost::Duration>,
+}
+
+/// Returns `depth` messages nested in each other's `child` field.
+fn nested(depth: u32) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for _ in 0..depth {
+        let mut outer = vec![0x0a];
+        encode_varint(buf.len() as u64, &mut outer);
+        outer.extend_from_slice(&buf);
+        buf = outer;
+    }
+    buf
+}
+
+fn everything() -> Vec<u8> {
+    let mut map = HashMap::new();
+    map.insert("key".to_owned(), -3);
+    Everything {
+        int32: -1,
+        string: "hello".to_owned(),
+        bytes: vec![1, 2, 3],
+        packed: vec![-5, 0, 300],
+        map,
+        nested: Some(Nested {
+            child: None,
+            value: 7,
+        }),
+        fixed: 0xdead_beef,
+        duration: Some(::prost::Duration {
+            seconds: 1,
+            nanos: 2,
+        }),
+    }
+    .encode_to_vec()
+}
+
+#[test]
+fn nested_messages_up_to_the_recursion_limit() {
+    assert!(Nested::decode(&nested(RECURSION_LIMIT)[..]).is_ok());
+
+    let error = Nested::decode(&nested(RECURSION_LIMIT + 1)[..]).unwrap_err();
+    assert!(error.to_string().ends_with("recursion limit reached"));
+}
+
+#[test]
+fn deeply_nested_messages() {
+    // Deep enough to overflow the stack if the recursion were unbounded.
+    let error = Nested::decode(&nested(100_000)[..]).unwrap_err();
+    assert!(error.to_string().ends_with("recursion limit reached"));
+}
+
+#[test]
+fn deeply_nested_groups() {
+    let mut buf = Vec::new();
+    for _ in 0..100_000 {
+        buf.push(0x0b);
+    }
+    let error = NestedGroup::decode(&buf[..]).unwrap_err();
+    assert!(error.to_string().ends_with("recursion limit reached"));
+}
+
+#[test]
+fn truncated_input() {
+    let buf = everything();
+    for len in 0..buf.len() {
+        let _ = Everything::decode(&buf[..len]);
+    }
+}
+
+#[test]
+fn mutated_input() {
+    let buf = everything();
+    for index in 0..buf.len() {
+        for byte in 0..=255 {
+            let mut mutated = buf.clone();
+            mutated[index] = byte;
+            let _ = Everything::decode(&mutated[..]);
+        }
+    }
+}
+
+#[test]
+fn random_input() {
+    // A xorshift generator, so that failures are reproducible.
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    for _ in 0..10_000 {
+        let len = (next() % 64) as usize;
+        let buf = (0..len).map(|_| next() as u8).collect::<Vec<u8>>();
+        let _ = Everything::decode(&buf[..]);
+        let _ = Nested::decode(&buf[..]);
+        let _ = NestedGroup::decode(&buf[..]);
+    }
+}
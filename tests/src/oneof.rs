@@ -0,0 +1,87 @@
+//! Tests for oneof fields whose variants are registered messages.
+
+use prost::bytes::buf::BufExt;
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+#[amino_name = "tendermint/PubKeyEd25519"]
+pub struct PubKeyEd25519 {
+    #[prost_amino(bytes, tag = "1")]
+    pub key: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[amino_name = "tendermint/PubKeySecp256k1"]
+pub struct PubKeySecp256k1 {
+    #[prost_amino(bytes, tag = "1")]
+    pub key: Vec<u8>,
+}
+
+/// An interface value: both variants share a tag, and are told apart by their prefixes.
+#[derive(Clone, PartialEq, Oneof)]
+pub enum PubKey {
+    #[prost_amino(message, tag = "1")]
+    Ed25519(PubKeyEd25519),
+    #[prost_amino(message, tag = "1")]
+    Secp256k1(PubKeySecp256k1),
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Validator {
+    #[prost_amino(oneof = "PubKey", tags = "1")]
+    pub pub_key: Option<PubKey>,
+    #[prost_amino(int64, tag = "2")]
+    pub power: i64,
+}
+
+#[test]
+fn registered_variants_round_trip() {
+    let ed25519 = Validator {
+        pub_key: Some(PubKey::Ed25519(PubKeyEd25519 { key: vec![1; 32] })),
+        power: 10,
+    };
+    let secp256k1 = Validator {
+        pub_key: Some(PubKey::Secp256k1(PubKeySecp256k1 { key: vec![2; 33] })),
+        power: 20,
+    };
+    for msg in &[ed25519, secp256k1] {
+        let buf = msg.encode_to_vec();
+        assert_eq!(msg.encoded_len(), buf.len());
+        assert_eq!(&Validator::decode(&buf[..]).unwrap(), msg);
+    }
+}
+
+#[test]
+fn variant_prefix_follows_key_and_length() {
+    let msg = Validator {
+        pub_key: Some(PubKey::Ed25519(PubKeyEd25519 { key: vec![1; 32] })),
+        power: 0,
+    };
+    let buf = msg.encode_to_vec();
+    let mut want = vec![1 << 3 | 2, 38];
+    want.extend_from_slice(PubKeyEd25519::amino_prefix());
+    want.extend_from_slice(&[1 << 3 | 2, 32]);
+    want.extend_from_slice(&[1; 32]);
+    assert_eq!(buf, want);
+}
+
+#[test]
+fn unknown_variant_prefix() {
+    let mut buf = vec![1 << 3 | 2, 4];
+    buf.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+    assert!(Validator::decode(&buf[..]).is_err());
+}
+
+#[test]
+fn variant_prefix_split_across_chunks() {
+    let msg = Validator {
+        pub_key: Some(PubKey::Secp256k1(PubKeySecp256k1 { key: vec![2; 33] })),
+        power: 20,
+    };
+    let buf = msg.encode_to_vec();
+    // Splits within the length delimiter and the prefix, and one leaving an empty first chunk.
+    for i in 0..8 {
+        let (head, tail) = buf.split_at(i);
+        assert_eq!(Validator::decode(head.chain(tail)).unwrap(), msg);
+    }
+}
@@ -0,0 +1,60 @@
+//! Tests for the presence of optional message fields, which distinguishes an absent message from
+//! an empty one.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Header {
+    #[prost_amino(int64, tag = "1")]
+    pub height: i64,
+    #[prost_amino(string, tag = "2")]
+    pub chain_id: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Block {
+    #[prost_amino(message, optional, tag = "1")]
+    pub header: Option<Header>,
+    #[prost_amino(message, optional, boxed, tag = "2")]
+    pub last_header: Option<Box<Header>>,
+}
+
+fn roundtrip(block: &Block) -> Vec<u8> {
+    let buf = block.encode_to_vec();
+    assert_eq!(buf.len(), block.encoded_len());
+    assert_eq!(&Block::decode(&buf[..]).unwrap(), block);
+    buf
+}
+
+#[test]
+fn absent_message() {
+    let block = Block::default();
+    assert!(roundtrip(&block).is_empty());
+    assert!(block.is_empty());
+}
+
+#[test]
+fn empty_message_is_present() {
+    let block = Block {
+        header: Some(Header::default()),
+        last_header: Some(Box::new(Header::default())),
+    };
+    // Each empty message is encoded as a key with a zero length.
+    assert_eq!(roundtrip(&block), [1 << 3 | 2, 0, 2 << 3 | 2, 0]);
+    assert!(!block.is_empty());
+    assert_ne!(block, Block::default());
+}
+
+#[test]
+fn populated_message() {
+    let header = Header {
+        height: 3,
+        chain_id: "test".to_owned(),
+    };
+    let block = Block {
+        header: Some(header.clone()),
+        last_header: Some(Box::new(header)),
+    };
+    let buf = roundtrip(&block);
+    assert_eq!(&buf[..2], [1 << 3 | 2, 8]);
+}
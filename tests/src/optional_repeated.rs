@@ -0,0 +1,44 @@
+//! Tests for `optional_repeated` fields, which distinguish an empty list from an unset field.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Heights {
+    #[prost_amino(int64, optional_repeated, tag = "1")]
+    pub heights: Option<Vec<i64>>,
+}
+
+fn round_trip(heights: Option<Vec<i64>>) -> Vec<u8> {
+    let msg = Heights { heights };
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+    assert_eq!(msg.encoded_len(), buf.len());
+    assert_eq!(msg, Heights::decode(&buf[..]).unwrap());
+    buf
+}
+
+#[test]
+fn none() {
+    assert_eq!(round_trip(None), Vec::<u8>::new());
+    assert!(Heights::default().is_empty());
+}
+
+#[test]
+fn some_empty() {
+    assert_eq!(round_trip(Some(vec![])), vec![0x0a, 0x00]);
+    assert!(!Heights {
+        heights: Some(vec![])
+    }
+    .is_empty());
+}
+
+#[test]
+fn some_values() {
+    assert_eq!(round_trip(Some(vec![1, 2])), vec![0x0a, 0x02, 0x01, 0x02]);
+}
+
+#[test]
+fn unpacked_values_are_accepted() {
+    let msg = Heights::decode(&[0x08, 0x01, 0x08, 0x02][..]).unwrap();
+    assert_eq!(msg.heights, Some(vec![1, 2]));
+}
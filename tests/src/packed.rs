@@ -0,0 +1,54 @@
+//! Tests for packed repeated `float` and `double` fields.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Floats {
+    #[prost_amino(double, repeated, packed = "true", tag = "1")]
+    pub doubles: Vec<f64>,
+    #[prost_amino(float, repeated, packed = "true", tag = "2")]
+    pub floats: Vec<f32>,
+}
+
+#[test]
+fn empty_packed_floats_are_omitted() {
+    let msg = Floats::default();
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+    assert!(buf.is_empty());
+    assert_eq!(msg.encoded_len(), 0);
+    assert_eq!(Floats::decode(&buf[..]).unwrap(), msg);
+}
+
+#[test]
+fn packed_doubles_are_one_field() {
+    let msg = Floats {
+        doubles: vec![1.0, -2.5, 1e300],
+        floats: vec![0.5],
+    };
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+    assert_eq!(msg.encoded_len(), buf.len());
+
+    // A single key and length for the three eight byte doubles.
+    assert_eq!(&buf[..2], &[1 << 3 | 2, 24]);
+    assert_eq!(&buf[2..10], &1.0f64.to_le_bytes());
+    assert_eq!(&buf[26..28], &[2 << 3 | 2, 4]);
+    assert_eq!(buf.len(), 32);
+
+    assert_eq!(Floats::decode(&buf[..]).unwrap(), msg);
+}
+
+#[test]
+fn packed_length_must_be_a_multiple_of_the_width() {
+    let mut buf = vec![1 << 3 | 2, 12];
+    buf.extend_from_slice(&[0; 12]);
+    let error = Floats::decode(&buf[..]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "failed to decode Protobuf message: Floats.doubles: packed length 12 is not a multiple of 8"
+    );
+
+    let buf = [2 << 3 | 2, 3, 0, 0, 0];
+    assert!(Floats::decode(&buf[..]).is_err());
+}
@@ -0,0 +1,59 @@
+//! Tests for the `has_<field>` methods of messages with the `hazzers` attribute.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+#[prost_amino(hazzers)]
+pub struct Vote {
+    #[prost_amino(int64, tag = "1")]
+    pub height: i64,
+    #[prost_amino(uint32, required, tag = "2")]
+    pub round: u32,
+    #[prost_amino(string, tag = "3")]
+    pub validator: String,
+    #[prost_amino(int32, optional, tag = "4")]
+    pub index: Option<i32>,
+    #[prost_amino(presence)]
+    pub presence: u64,
+}
+
+#[test]
+fn decoded_zero_is_present() {
+    // An explicit zero height, which is never written by `encode`.
+    let vote = Vote::decode(&[1 << 3, 0][..]).unwrap();
+    assert_eq!(vote.height, 0);
+    assert!(vote.has_height());
+    assert!(!vote.has_round());
+    assert!(!vote.has_validator());
+
+    let vote = Vote::default();
+    assert_eq!(vote.height, 0);
+    assert!(!vote.has_height());
+}
+
+#[test]
+fn presence_is_not_encoded() {
+    let vote = Vote::decode(&[1 << 3, 5, 2 << 3, 1][..]).unwrap();
+    assert!(vote.has_height() && vote.has_round());
+    assert_eq!(vote.encode_to_vec(), [1 << 3, 5, 2 << 3, 1]);
+    assert_eq!(
+        format!("{:?}", vote),
+        "Vote { height: 5, round: 1, validator: \"\", index: None }"
+    );
+}
+
+#[test]
+fn clear_resets_presence() {
+    let mut vote = Vote::decode(&[1 << 3, 5, 2 << 3, 1, 3 << 3 | 2, 1, b'v'][..]).unwrap();
+    vote.clear_height();
+    assert!(!vote.has_height());
+    assert!(vote.has_round());
+
+    let mut other = Vote::decode(&[1 << 3, 7][..]).unwrap();
+    other.merge_from(vote.clone()).unwrap();
+    assert!(other.has_height() && other.has_round() && other.has_validator());
+
+    vote.clear();
+    assert!(!vote.has_round() && !vote.has_validator());
+    assert_eq!(vote, Vote::default());
+}
@@ -0,0 +1,125 @@
+//! Tests for integer fields with a `range`, which is checked by `validate`, and also when
+//! decoding for `checked` fields.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Order {
+    #[prost_amino(sint64, range = "-1000..=1000", checked, tag = "1")]
+    pub delta: i64,
+    #[prost_amino(uint32, optional, range = "1..=100", checked, tag = "2")]
+    pub quantity: Option<u32>,
+    #[prost_amino(int32, repeated, range = "0..=9", checked, tag = "3")]
+    pub digits: Vec<i32>,
+    #[prost_amino(uint64, range = "0..=10", tag = "4")]
+    pub priority: u64,
+}
+
+/// The same message without ranges, to encode out of range values.
+#[derive(Clone, PartialEq, Message)]
+pub struct UncheckedOrder {
+    #[prost_amino(sint64, tag = "1")]
+    pub delta: i64,
+    #[prost_amino(uint32, optional, tag = "2")]
+    pub quantity: Option<u32>,
+    #[prost_amino(int32, repeated, tag = "3")]
+    pub digits: Vec<i32>,
+    #[prost_amino(uint64, tag = "4")]
+    pub priority: u64,
+}
+
+fn unchecked() -> UncheckedOrder {
+    UncheckedOrder {
+        delta: -1000,
+        quantity: Some(100),
+        digits: vec![0, 5, 9],
+        priority: 10,
+    }
+}
+
+fn decode(msg: &UncheckedOrder) -> Result<Order, String> {
+    Order::decode(&msg.encode_to_vec()[..]).map_err(|error| error.to_string())
+}
+
+#[test]
+fn in_range_values_decode() {
+    let order = decode(&unchecked()).unwrap();
+    assert_eq!(order.delta, -1000);
+    assert_eq!(order.quantity, Some(100));
+    assert_eq!(order.digits, vec![0, 5, 9]);
+    order.validate().unwrap();
+    assert_eq!(order.encode_to_vec(), unchecked().encode_to_vec());
+}
+
+#[test]
+fn out_of_range_values_are_rejected_when_decoding() {
+    let msg = UncheckedOrder {
+        delta: 1001,
+        ..unchecked()
+    };
+    assert_eq!(
+        decode(&msg).unwrap_err(),
+        "failed to decode Protobuf message: Order.delta: value 1001 is out of range -1000..=1000"
+    );
+
+    let msg = UncheckedOrder {
+        quantity: Some(0),
+        ..unchecked()
+    };
+    assert_eq!(
+        decode(&msg).unwrap_err(),
+        "failed to decode Protobuf message: Order.quantity: value 0 is out of range 1..=100"
+    );
+
+    let msg = UncheckedOrder {
+        digits: vec![1, 2, 10],
+        ..unchecked()
+    };
+    assert_eq!(
+        decode(&msg).unwrap_err(),
+        "failed to decode Protobuf message: Order.digits[2]: value 10 is out of range 0..=9"
+    );
+}
+
+#[test]
+fn out_of_range_tag() {
+    let msg = UncheckedOrder {
+        quantity: Some(101),
+        ..unchecked()
+    };
+    let error = Order::decode(&msg.encode_to_vec()[..]).unwrap_err();
+    assert_eq!(error.out_of_range_tag(), Some(2));
+
+    let msg = UncheckedOrder {
+        digits: vec![-1],
+        ..unchecked()
+    };
+    let error = Order::decode(&msg.encode_to_vec()[..]).unwrap_err();
+    assert_eq!(error.out_of_range_tag(), Some(3));
+
+    // Other errors have no out of range tag.
+    let error = Order::decode(&[0x0a][..]).unwrap_err();
+    assert_eq!(error.out_of_range_tag(), None);
+}
+
+#[test]
+fn unchecked_ranges_are_validated() {
+    let msg = UncheckedOrder {
+        priority: 11,
+        ..unchecked()
+    };
+    let order = decode(&msg).unwrap();
+    assert_eq!(
+        order.validate().unwrap_err().to_string(),
+        "invalid Protobuf message: Order.priority: value 11 is out of range 0..=10"
+    );
+
+    let order = Order {
+        digits: vec![3, -1],
+        ..decode(&unchecked()).unwrap()
+    };
+    assert_eq!(
+        order.validate().unwrap_err().to_string(),
+        "invalid Protobuf message: Order.digits[1]: value -1 is out of range 0..=9"
+    );
+}
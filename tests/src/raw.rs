@@ -0,0 +1,54 @@
+//! Tests for framing messages with `Message::encode_raw` and `Message::merge_field`.
+
+use prost::encoding;
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Entry {
+    #[prost_amino(string, tag = "1")]
+    pub key: String,
+    #[prost_amino(uint64, tag = "2")]
+    pub version: u64,
+    #[prost_amino(bytes, repeated, tag = "3")]
+    pub values: Vec<Vec<u8>>,
+}
+
+fn entry() -> Entry {
+    Entry {
+        key: "key".to_owned(),
+        version: 300,
+        values: vec![b"a".to_vec(), b"bc".to_vec()],
+    }
+}
+
+#[test]
+fn encode_raw_writes_fields() {
+    let msg = entry();
+    let mut raw = Vec::new();
+    msg.encode_raw(&mut raw);
+
+    let mut fields = Vec::new();
+    encoding::string::encode(1, &msg.key, &mut fields);
+    encoding::uint64::encode(2, &msg.version, &mut fields);
+    encoding::bytes::encode_repeated(3, &msg.values, &mut fields);
+    assert_eq!(raw, fields);
+    assert_eq!(raw.len(), msg.encoded_len());
+    assert_eq!(raw, msg.encode_to_vec());
+}
+
+#[test]
+fn custom_framing() {
+    // A frame of a fixed32 length followed by the fields of the message.
+    let msg = entry();
+    let mut frame = Vec::new();
+    frame.extend_from_slice(&(msg.encoded_len() as u32).to_le_bytes());
+    msg.encode_raw(&mut frame);
+
+    let len = u32::from_le_bytes([frame[0], frame[1], frame[2], frame[3]]) as usize;
+    let mut buf = &frame[4..4 + len];
+    let mut decoded = Entry::default();
+    while !buf.is_empty() {
+        decoded.merge_field(&mut buf).unwrap();
+    }
+    assert_eq!(decoded, msg);
+}
@@ -0,0 +1,77 @@
+//! Tests for recursive message types, whose nested messages are held in a `Box` or a `Vec`.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Node {
+    #[prost_amino(int64, tag = "1")]
+    pub value: i64,
+    #[prost_amino(message, repeated, tag = "2")]
+    pub children: Vec<Node>,
+    #[prost_amino(message, optional, boxed, tag = "3")]
+    pub next: Option<Box<Node>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Leaf {
+    #[prost_amino(string, tag = "1")]
+    pub name: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Wrapper {
+    #[prost_amino(message, required, boxed, tag = "1")]
+    pub leaf: Box<Leaf>,
+}
+
+fn leaf(value: i64) -> Node {
+    Node {
+        value,
+        ..Node::default()
+    }
+}
+
+#[test]
+fn three_levels_deep() {
+    let tree = Node {
+        value: 1,
+        children: vec![
+            Node {
+                value: 2,
+                children: vec![leaf(4), leaf(5)],
+                next: Some(Box::new(leaf(6))),
+            },
+            leaf(3),
+        ],
+        next: Some(Box::new(Node {
+            value: 7,
+            children: Vec::new(),
+            next: Some(Box::new(Node {
+                value: 8,
+                children: vec![leaf(9)],
+                next: None,
+            })),
+        })),
+    };
+    let buf = tree.encode_to_vec();
+    assert_eq!(buf.len(), tree.encoded_len());
+    let decoded = Node::decode(&buf[..]).unwrap();
+    assert_eq!(decoded, tree);
+
+    let third = decoded.next.as_ref().and_then(|next| next.next.as_ref());
+    assert_eq!(third.map(|node| node.children[0].value), Some(9));
+}
+
+#[test]
+fn required_box() {
+    let msg = Wrapper {
+        leaf: Box::new(Leaf {
+            name: "leaf".to_owned(),
+        }),
+    };
+    let buf = msg.encode_to_vec();
+    assert_eq!(buf.len(), msg.encoded_len());
+    assert_eq!(Wrapper::decode(&buf[..]).unwrap(), msg);
+
+    assert!(Wrapper::default().encode_to_vec().is_empty());
+}
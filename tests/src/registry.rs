@@ -0,0 +1,140 @@
+//! Tests for decoding registered messages by their amino prefix with a `Registry`.
+
+use prost::{AminoMessage, Constructor, Message, Registry};
+
+#[derive(Clone, PartialEq, Message)]
+#[amino_name = "test/Send"]
+pub struct Send {
+    #[prost_amino(string, tag = "1")]
+    pub to: String,
+    #[prost_amino(int64, tag = "2")]
+    pub amount: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[amino_name = "test/Delegate"]
+pub struct Delegate {
+    #[prost_amino(string, tag = "1")]
+    pub validator: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[amino_name = "test/Vote"]
+pub struct Vote {
+    #[prost_amino(uint64, tag = "1")]
+    pub proposal: u64,
+    #[prost_amino(bool, tag = "2")]
+    pub yes: bool,
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[amino_name = "test/Withdraw"]
+pub struct Withdraw {
+    #[prost_amino(int64, tag = "1")]
+    pub amount: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[amino_name = "test/Unjail"]
+pub struct Unjail {
+    #[prost_amino(bytes, tag = "1")]
+    pub address: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Unregistered {
+    #[prost_amino(int64, tag = "1")]
+    pub value: i64,
+}
+
+fn registry() -> Registry {
+    prost::register_amino_types! {
+        "test/Send" => Send,
+        "test/Delegate" => Delegate,
+        "test/Vote" => Vote,
+        "test/Withdraw" => Withdraw,
+        "test/Unjail" => Unjail,
+    }
+    .unwrap()
+}
+
+/// Decodes the encoded message with the registry, and downcasts it to its type.
+fn dispatch<M>(registry: &Registry, name: &str, msg: &M) -> M
+where
+    M: Message + Clone + 'static,
+{
+    let buf = msg.encode_to_vec();
+    // The prefix follows the length delimiter.
+    assert_eq!(registry.name(&buf[1..5]), Some(name));
+    let decoded = registry.decode(&buf[..]).unwrap();
+    decoded.as_any().downcast_ref::<M>().unwrap().clone()
+}
+
+#[test]
+fn dispatch_by_prefix() {
+    let registry = registry();
+    assert_eq!(registry.len(), 5);
+
+    let send = Send {
+        to: "alice".to_owned(),
+        amount: 10,
+    };
+    assert_eq!(dispatch(&registry, "test/Send", &send), send);
+    let delegate = Delegate {
+        validator: "val".to_owned(),
+    };
+    assert_eq!(dispatch(&registry, "test/Delegate", &delegate), delegate);
+    let vote = Vote {
+        proposal: 7,
+        yes: true,
+    };
+    assert_eq!(dispatch(&registry, "test/Vote", &vote), vote);
+    let withdraw = Withdraw { amount: -3 };
+    assert_eq!(dispatch(&registry, "test/Withdraw", &withdraw), withdraw);
+    let unjail = Unjail {
+        address: vec![1, 2, 3],
+    };
+    assert_eq!(dispatch(&registry, "test/Unjail", &unjail), unjail);
+}
+
+#[test]
+fn unknown_prefix() {
+    let registry = Registry::from_entries(&[(
+        "test/Send",
+        (|| Box::new(Send::default()) as Box<dyn AminoMessage>) as Constructor,
+    )])
+    .unwrap();
+    let buf = Vote::default().encode_to_vec();
+    assert!(registry.decode(&buf[..]).is_err());
+}
+
+#[test]
+fn duplicate_entries_are_rejected() {
+    let error = prost::register_amino_types! {
+        "test/Send" => Send,
+        "test/Send" => Delegate,
+    }
+    .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "failed to register amino type: test/Send is already registered"
+    );
+
+    let error = prost::register_amino_types! {
+        "test/Send" => Send,
+        "test/Transfer" => Send,
+    }
+    .unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("test/Transfer has the same prefix"));
+
+    let error = prost::register_amino_types! {
+        "test/Unregistered" => Unregistered,
+    }
+    .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "failed to register amino type: test/Unregistered is not a registered message"
+    );
+}
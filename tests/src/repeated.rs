@@ -0,0 +1,37 @@
+//! Tests for the `push_` and `extend_` methods generated for repeated scalar fields.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Enumeration)]
+pub enum Color {
+    Red = 0,
+    Green = 1,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Repeated {
+    #[prost_amino(int64, repeated, tag = "1")]
+    pub heights: Vec<i64>,
+    #[prost_amino(string, repeated, tag = "2")]
+    pub names: Vec<String>,
+    #[prost_amino(enumeration = "Color", repeated, tag = "3")]
+    pub colors: Vec<i32>,
+    #[prost_amino(uint32, optional_repeated, tag = "4")]
+    pub rounds: Option<Vec<u32>>,
+}
+
+#[test]
+fn push_and_extend() {
+    let mut msg = Repeated::default();
+    msg.push_heights(1);
+    msg.extend_heights((2..5).map(|height| height * 10));
+    msg.extend_names(vec!["a".to_owned(), "b".to_owned()]);
+    msg.push_names("c".to_owned());
+    msg.extend_colors(vec![Color::Green, Color::Red]);
+    msg.extend_rounds(None);
+    assert_eq!(msg.rounds, Some(vec![]));
+    msg.push_rounds(3);
+
+    assert_eq!(msg.heights, vec![1, 20, 30, 40]);
+    assert_eq!(msg.names, vec!["a", "b", "c"]);
+    assert_eq!(msg.colors().collect::<Vec<_>>(), vec![Color::Green, Color::Red]);
+    assert_eq!(msg.rounds, Some(vec![3]));
+}
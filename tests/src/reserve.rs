@@ -0,0 +1,37 @@
+//! Tests for `Message::encode_append`, which reserves the encoded length of a message before
+//! writing it to a `Vec`.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Large {
+    #[prost_amino(string, repeated, tag = "1")]
+    pub names: Vec<String>,
+    #[prost_amino(int64, repeated, tag = "2")]
+    pub values: Vec<i64>,
+}
+
+fn large() -> Large {
+    Large {
+        names: (0..1000).map(|i| format!("name {}", i)).collect(),
+        values: (0..10_000).collect(),
+    }
+}
+
+#[test]
+fn append_reserves_once() {
+    let msg = large();
+    let len = msg.encoded_len();
+
+    // The buffer is allocated once, with exactly the encoded length.
+    let mut buf = Vec::new();
+    msg.encode_append(&mut buf);
+    assert_eq!(buf.capacity(), len);
+    assert_eq!(buf, msg.encode_to_vec());
+
+    // Appending keeps the existing contents.
+    let mut buf = vec![1, 2, 3];
+    msg.encode_append(&mut buf);
+    assert_eq!(&buf[..3], [1, 2, 3]);
+    assert_eq!(Large::decode(&buf[3..]).unwrap(), msg);
+}
@@ -0,0 +1,77 @@
+//! Tests for decoding many messages into one value with `Message::clear_and_decode`.
+
+use std::collections::HashMap;
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Record {
+    #[prost_amino(string, tag = "1")]
+    pub name: String,
+    #[prost_amino(bytes, tag = "2")]
+    pub payload: Vec<u8>,
+    #[prost_amino(uint64, repeated, tag = "3")]
+    pub values: Vec<u64>,
+    #[prost_amino(string, repeated, tag = "4")]
+    pub tags: Vec<String>,
+    #[prost_amino(map = "string, int32", tag = "5")]
+    pub counts: HashMap<String, i32>,
+}
+
+fn record(len: usize) -> Record {
+    Record {
+        name: "n".repeat(len),
+        payload: vec![0xAB; len],
+        values: (0..len as u64).collect(),
+        tags: vec!["tag".to_owned(); len],
+        counts: (0..len).map(|i| (i.to_string(), i as i32)).collect(),
+    }
+}
+
+#[test]
+fn clear_and_decode_retains_capacity() {
+    let mut msg = Record::default();
+    msg.clear_and_decode(&record(64).encode_to_vec()[..])
+        .unwrap();
+    assert_eq!(msg, record(64));
+    let capacities = (
+        msg.name.capacity(),
+        msg.payload.capacity(),
+        msg.values.capacity(),
+        msg.tags.capacity(),
+        msg.counts.capacity(),
+    );
+
+    for len in (0..8).rev() {
+        let buf = record(len).encode_to_vec();
+        msg.clear_and_decode(&buf[..]).unwrap();
+        assert_eq!(msg, record(len));
+        assert_eq!(
+            (
+                msg.name.capacity(),
+                msg.payload.capacity(),
+                msg.values.capacity(),
+                msg.tags.capacity(),
+                msg.counts.capacity(),
+            ),
+            capacities
+        );
+    }
+}
+
+#[test]
+fn clear_and_decode_replaces_previous_values() {
+    let mut msg = record(4);
+    msg.clear_and_decode(&[][..]).unwrap();
+    assert_eq!(msg, Record::default());
+
+    let mut msg = record(4);
+    let buf = Record {
+        name: "other".to_owned(),
+        ..Record::default()
+    }
+    .encode_to_vec();
+    msg.clear_and_decode(&buf[..]).unwrap();
+    assert_eq!(msg.name, "other");
+    assert!(msg.values.is_empty() && msg.tags.is_empty() && msg.counts.is_empty());
+}
@@ -0,0 +1,74 @@
+//! Tests for the `.proto` definitions rendered by `prost_build::to_proto_schema`.
+
+use std::collections::HashMap;
+
+use prost_build::to_proto_schema;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Enumeration)]
+pub enum Level {
+    Low = 0,
+    High = 1,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Header {
+    #[prost_amino(int64, tag = "1")]
+    pub height: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Block {
+    #[prost_amino(int64, tag = "1")]
+    pub height: i64,
+    #[prost_amino(bytes, repeated, tag = "2")]
+    pub txs: Vec<Vec<u8>>,
+    #[prost_amino(message, optional, boxed, tag = "3")]
+    pub header: Option<Box<Header>>,
+    #[prost_amino(enumeration = "Level", tag = "4")]
+    pub level: i32,
+    #[prost_amino(map = "string, message", tag = "5")]
+    pub headers: HashMap<String, Header>,
+    #[prost_amino(bytes, tag = "6", amino_name = "tendermint/PubKeyEd25519")]
+    pub pub_key: Vec<u8>,
+    #[prost_amino(uint32, required, tag = "7")]
+    pub round: u32,
+    #[prost_amino(duration, tag = "8")]
+    pub timeout: Option<::prost::Duration>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[amino_name = "tendermint/Heartbeat"]
+pub struct Heartbeat {
+    #[prost_amino(string, tag = "1")]
+    pub validator: String,
+}
+
+#[test]
+fn schema_of_message() {
+    assert_eq!(
+        to_proto_schema::<Block>(),
+        "message Block {
+  int64 height = 1;
+  repeated bytes txs = 2;
+  Header header = 3;
+  Level level = 4;
+  map<string, Header> headers = 5;
+  bytes pub_key = 6; // amino_name: \"tendermint/PubKeyEd25519\"
+  uint32 round = 7; // required
+  google.protobuf.Duration timeout = 8;
+}
+"
+    );
+}
+
+#[test]
+fn schema_of_registered_message() {
+    assert_eq!(
+        to_proto_schema::<Heartbeat>(),
+        "// amino prefix: [db, 32, 9c, d8]
+message Heartbeat {
+  string validator = 1;
+}
+"
+    );
+}
@@ -0,0 +1,78 @@
+//! Tests for `bytes` fields with the `shared` attribute, which share the buffer passed to
+//! `Message::decode_from_bytes` rather than copying it.
+
+use bytes::Bytes;
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Blob {
+    #[prost_amino(string, tag = "1")]
+    pub name: String,
+    #[prost_amino(bytes, shared, tag = "2")]
+    pub data: Bytes,
+    #[prost_amino(bytes, tag = "3")]
+    pub checksum: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Bundle {
+    #[prost_amino(message, optional, tag = "1")]
+    pub head: Option<Blob>,
+    #[prost_amino(message, repeated, tag = "2")]
+    pub blobs: Vec<Blob>,
+}
+
+fn blob(name: &str, data: &[u8]) -> Blob {
+    Blob {
+        name: name.to_owned(),
+        data: Bytes::from(data.to_vec()),
+        checksum: vec![1, 2, 3],
+    }
+}
+
+/// Asserts that `value` is a view of `buf`.
+fn assert_shares(value: &Bytes, buf: &Bytes) {
+    let start = buf.as_ptr() as usize;
+    let ptr = value.as_ptr() as usize;
+    assert!(ptr >= start && ptr + value.len() <= start + buf.len());
+}
+
+#[test]
+fn decode_from_bytes_shares_buffer() {
+    let msg = blob("blob", &[0xAB; 1024]);
+    let buf = Bytes::from(msg.encode_to_vec());
+
+    let decoded = Blob::decode_from_bytes(buf.clone()).unwrap();
+    assert_eq!(decoded, msg);
+    assert_shares(&decoded.data, &buf);
+
+    // Decoding from any other buffer copies the value.
+    let copied = Blob::decode(&buf[..]).unwrap();
+    assert_eq!(copied, msg);
+    assert!(copied.data.as_ptr() != decoded.data.as_ptr());
+}
+
+#[test]
+fn decode_from_bytes_shares_nested_buffers() {
+    let msg = Bundle {
+        head: Some(blob("head", b"first")),
+        blobs: vec![blob("a", b"second"), blob("b", b"third")],
+    };
+    let buf = Bytes::from(msg.encode_to_vec());
+
+    let decoded = Bundle::decode_from_bytes(buf.clone()).unwrap();
+    assert_eq!(decoded, msg);
+    assert_shares(&decoded.head.as_ref().unwrap().data, &buf);
+    for blob in &decoded.blobs {
+        assert_shares(&blob.data, &buf);
+    }
+}
+
+#[test]
+fn decode_from_bytes_truncated() {
+    let msg = blob("blob", b"data");
+    let buf = msg.encode_to_vec();
+    // Drop the checksum and the last two bytes of the data.
+    let truncated = Bytes::from(buf[..buf.len() - 7].to_vec());
+    assert!(Blob::decode_from_bytes(truncated).is_err());
+}
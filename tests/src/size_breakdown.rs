@@ -0,0 +1,141 @@
+//! Tests for `Message::size_breakdown`, which reports the encoded length of each field.
+
+use std::collections::HashMap;
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Header {
+    #[prost_amino(uint64, tag = "1")]
+    pub id: u64,
+    #[prost_amino(string, tag = "2")]
+    pub source: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Point {
+    #[prost_amino(sint32, tag = "1")]
+    pub x: i32,
+    #[prost_amino(sint32, tag = "2")]
+    pub y: i32,
+}
+
+#[derive(Clone, PartialEq, Oneof)]
+pub enum Payload {
+    #[prost_amino(string, tag = "8")]
+    Text(String),
+    #[prost_amino(bytes, tag = "9")]
+    Data(Vec<u8>),
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Report {
+    #[prost_amino(message, flatten)]
+    pub header: Header,
+    #[prost_amino(message, optional, tag = "3")]
+    pub origin: Option<Point>,
+    #[prost_amino(message, repeated, tag = "4")]
+    pub path: Vec<Point>,
+    #[prost_amino(double, repeated, tag = "5")]
+    pub readings: Vec<f64>,
+    #[prost_amino(map = "string, int64", tag = "6")]
+    pub counts: HashMap<String, i64>,
+    #[prost_amino(bool, tag = "7")]
+    pub done: bool,
+    #[prost_amino(oneof = "Payload", tags = "8, 9")]
+    pub payload: Option<Payload>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[amino_name = "test/SizeBreakdown"]
+pub struct Registered {
+    #[prost_amino(string, tag = "1")]
+    pub name: String,
+}
+
+fn report() -> Report {
+    let mut counts = HashMap::new();
+    counts.insert("errors".to_owned(), 3);
+    counts.insert("warnings".to_owned(), 12);
+    Report {
+        header: Header {
+            id: 300,
+            source: "sensor".to_owned(),
+        },
+        origin: Some(Point { x: -1, y: 1 }),
+        path: vec![Point { x: 1, y: 2 }, Point { x: 3, y: 4 }],
+        readings: vec![1.0, 2.0, 3.0],
+        counts,
+        done: false,
+        payload: Some(Payload::Data(vec![0; 200])),
+    }
+}
+
+fn total(breakdown: &[(u32, &'static str, usize)]) -> usize {
+    breakdown.iter().map(|&(_, _, len)| len).sum()
+}
+
+#[test]
+fn sizes_sum_to_encoded_len() {
+    let report = report();
+    let breakdown = report.size_breakdown();
+    assert_eq!(total(&breakdown), report.encoded_len());
+    assert_eq!(total(&breakdown), report.encode_to_vec().len());
+}
+
+#[test]
+fn fields_are_reported_in_encoding_order() {
+    // Fields are encoded in tag order, including the fields of flattened messages.
+    let breakdown = report().size_breakdown();
+    let fields = breakdown
+        .iter()
+        .map(|&(tag, name, _)| (tag, name))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        fields,
+        vec![
+            (1, "id"),
+            (2, "source"),
+            (3, "origin"),
+            (4, "path"),
+            (5, "readings"),
+            (6, "counts"),
+            (7, "done"),
+            (9, "Data"),
+        ]
+    );
+}
+
+#[test]
+fn sizes_include_keys_and_length_delimiters() {
+    let breakdown = report().size_breakdown();
+    // A 1 byte key and a 2 byte varint.
+    assert_eq!(breakdown[0], (1, "id", 3));
+    // A 1 byte key, a 1 byte length and three 8 byte values.
+    assert_eq!(breakdown[4], (5, "readings", 26));
+    // A default value is not encoded.
+    assert_eq!(breakdown[6], (7, "done", 0));
+    // A 1 byte key, a 2 byte length and 200 bytes.
+    assert_eq!(breakdown[7], (9, "Data", 203));
+}
+
+#[test]
+fn unset_oneof_is_not_reported() {
+    let report = Report {
+        payload: None,
+        ..report()
+    };
+    let breakdown = report.size_breakdown();
+    assert!(breakdown.iter().all(|&(tag, _, _)| tag != 8 && tag != 9));
+    assert_eq!(total(&breakdown), report.encoded_len());
+}
+
+#[test]
+fn registered_message_reports_its_prefix() {
+    let registered = Registered {
+        name: "name".to_owned(),
+    };
+    let breakdown = registered.size_breakdown();
+    assert_eq!(breakdown, vec![(0, "amino_prefix", 4), (1, "name", 6)]);
+    assert_eq!(total(&breakdown), registered.encoded_len());
+}
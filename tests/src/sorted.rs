@@ -0,0 +1,91 @@
+//! Tests for repeated fields with the `sorted` attribute, whose values are encoded in ascending
+//! order.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Allowlist {
+    #[prost_amino(string, repeated, sorted, tag = "1")]
+    pub addresses: Vec<String>,
+    #[prost_amino(uint64, repeated, sorted, tag = "2")]
+    pub heights: Vec<u64>,
+    #[prost_amino(sint32, repeated, packed = "false", sorted, tag = "3")]
+    pub offsets: Vec<i32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Unsorted {
+    #[prost_amino(string, repeated, tag = "1")]
+    pub addresses: Vec<String>,
+    #[prost_amino(uint64, repeated, tag = "2")]
+    pub heights: Vec<u64>,
+    #[prost_amino(sint32, repeated, packed = "false", tag = "3")]
+    pub offsets: Vec<i32>,
+}
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|&value| value.to_owned()).collect()
+}
+
+#[test]
+fn values_are_encoded_in_order() {
+    let allowlist = Allowlist {
+        addresses: strings(&["cosmos1c", "cosmos1a", "cosmos1b"]),
+        heights: vec![300, 1, 20],
+        offsets: vec![5, -7, 0],
+    };
+    let expected = Unsorted {
+        addresses: strings(&["cosmos1a", "cosmos1b", "cosmos1c"]),
+        heights: vec![1, 20, 300],
+        offsets: vec![-7, 0, 5],
+    };
+    let buf = allowlist.encode_to_vec();
+    assert_eq!(buf, expected.encode_to_vec());
+    assert_eq!(buf.len(), allowlist.encoded_len());
+
+    // The field itself is not reordered.
+    assert_eq!(allowlist.heights, vec![300, 1, 20]);
+}
+
+#[test]
+fn decoded_values_keep_their_wire_order() {
+    let unsorted = Unsorted {
+        heights: vec![3, 2, 1],
+        ..Unsorted::default()
+    };
+    let allowlist = Allowlist::decode(&unsorted.encode_to_vec()[..]).unwrap();
+    assert_eq!(allowlist.heights, vec![3, 2, 1]);
+}
+
+#[test]
+fn sorted_values_are_encoded_unchanged() {
+    let allowlist = Allowlist {
+        addresses: strings(&["a", "a", "b"]),
+        heights: vec![1, 2, 2, 3],
+        offsets: vec![],
+    };
+    let decoded = Allowlist::decode(&allowlist.encode_to_vec()[..]).unwrap();
+    assert_eq!(decoded, allowlist);
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct SortedChunks {
+    #[prost_amino(uint64, repeated, sorted, chunk = 13, tag = "1")]
+    pub values: Vec<u64>,
+}
+
+#[test]
+fn sorted_chunks_have_their_encoded_length() {
+    // Sorting moves the long values into the same blocks, which changes the lengths of the
+    // blocks.
+    let msg = SortedChunks {
+        values: (0..13)
+            .map(|i| if i % 2 == 0 { u64::MAX } else { 1 })
+            .collect(),
+    };
+    let buf = msg.encode_to_vec();
+    assert_eq!(buf.len(), msg.encoded_len());
+    let mut values = msg.values.clone();
+    values.sort();
+    assert_eq!(SortedChunks::decode(&buf[..]).unwrap().values, values);
+}
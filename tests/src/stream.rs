@@ -0,0 +1,78 @@
+//! Tests for the `decode_<field>_stream` methods generated for repeated fields with the `stream`
+//! attribute.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Samples {
+    #[prost_amino(string, tag = "1")]
+    pub name: String,
+    #[prost_amino(int64, repeated, stream, tag = "2")]
+    pub values: Vec<i64>,
+    #[prost_amino(fixed32, repeated, packed = "false", stream, tag = "3")]
+    pub ids: Vec<u32>,
+    #[prost_amino(string, repeated, stream, tag = "4")]
+    pub labels: Vec<String>,
+}
+
+#[test]
+fn stream_large_packed_field() {
+    let msg = Samples {
+        name: "samples".to_owned(),
+        values: (-100_000..100_000).collect(),
+        ids: Vec::new(),
+        labels: Vec::new(),
+    };
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+
+    let mut count = 0;
+    let mut sum = 0;
+    for value in Samples::decode_values_stream(&buf[..]) {
+        count += 1;
+        sum += value.unwrap();
+    }
+    assert_eq!(count, 200_000);
+    assert_eq!(sum, -100_000);
+}
+
+#[test]
+fn stream_unpacked_fields() {
+    let msg = Samples {
+        name: "samples".to_owned(),
+        values: vec![1, 2],
+        ids: vec![7, 8, 9],
+        labels: vec!["a".to_owned(), "b".to_owned()],
+    };
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+
+    let ids = Samples::decode_ids_stream(&buf[..])
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(ids, msg.ids);
+    let labels = Samples::decode_labels_stream(&buf[..])
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(labels, msg.labels);
+}
+
+#[test]
+fn stream_truncated_value() {
+    let msg = Samples {
+        name: String::new(),
+        values: vec![1, 2, 300],
+        ids: Vec::new(),
+        labels: Vec::new(),
+    };
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+    // Drop the last byte of the two byte varint `300`.
+    buf.pop();
+
+    let mut stream = Samples::decode_values_stream(&buf[..]);
+    assert_eq!(stream.next().unwrap().unwrap(), 1);
+    assert_eq!(stream.next().unwrap().unwrap(), 2);
+    assert!(stream.next().unwrap().is_err());
+    assert!(stream.next().is_none());
+}
@@ -0,0 +1,60 @@
+//! Tests for field tag inference.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+#[prost_amino(tag_prefix = 10)]
+pub struct Prefixed {
+    #[prost_amino(uint32)]
+    pub a: u32,
+    #[prost_amino(uint32)]
+    pub b: u32,
+    #[prost_amino(string)]
+    pub c: String,
+}
+
+#[test]
+fn inferred_tags_are_offset_by_prefix() {
+    let msg = Prefixed {
+        a: 1,
+        b: 2,
+        c: "c".to_owned(),
+    };
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+    // Tags 11, 12 and 13, i.e. `tag_prefix + position`.
+    assert_eq!(buf, vec![11 << 3, 0x01, 12 << 3, 0x02, 13 << 3 | 2, 0x01, b'c']);
+    assert_eq!(Prefixed::decode(&buf[..]).unwrap(), msg);
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct AutoTagged {
+    #[prost_amino(uint32, tag = "auto")]
+    pub a: u32,
+    #[prost_amino(uint32, tag = "2")]
+    pub b: u32,
+    #[prost_amino(uint32, tag = "auto")]
+    pub c: u32,
+    #[prost_amino(uint32, tag = "auto")]
+    pub d: u32,
+}
+
+#[test]
+fn auto_tags_fill_free_slots() {
+    let tags = AutoTagged::descriptor()
+        .iter()
+        .map(|field| (field.name, field.tag))
+        .collect::<Vec<_>>();
+    assert_eq!(tags, vec![("a", 1), ("b", 2), ("c", 3), ("d", 4)]);
+
+    let msg = AutoTagged {
+        a: 1,
+        b: 2,
+        c: 3,
+        d: 4,
+    };
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+    assert_eq!(buf, vec![1 << 3, 1, 2 << 3, 2, 3 << 3, 3, 4 << 3, 4]);
+    assert_eq!(AutoTagged::decode(&buf[..]).unwrap(), msg);
+}
@@ -0,0 +1,53 @@
+//! Tests for transparent newtype messages.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Coin {
+    #[prost_amino(string, tag = "1")]
+    pub denom: String,
+    #[prost_amino(uint64, tag = "2")]
+    pub amount: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[prost_amino(transparent)]
+pub struct Amount(u64);
+
+#[derive(Clone, PartialEq, Message)]
+#[prost_amino(transparent)]
+pub struct Fee {
+    pub coin: Coin,
+}
+
+fn encode<M: Message>(msg: &M) -> Vec<u8> {
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+    assert_eq!(msg.encoded_len(), buf.len());
+    buf
+}
+
+#[test]
+fn transparent_newtype_encodes_as_inner_value() {
+    let amount = Amount(300);
+    let buf = encode(&amount);
+    assert_eq!(buf, encode(&300u64));
+    assert_eq!(amount, Amount::decode(&buf[..]).unwrap());
+
+    let coin = Coin {
+        denom: "stake".to_owned(),
+        amount: 10,
+    };
+    let fee = Fee { coin: coin.clone() };
+    let buf = encode(&fee);
+    assert_eq!(buf, encode(&coin));
+    assert_eq!(fee, Fee::decode(&buf[..]).unwrap());
+    assert_eq!(Fee::descriptor(), Coin::descriptor());
+    assert_eq!(format!("{:?}", fee), format!("{:?}", coin));
+}
+
+#[test]
+fn transparent_default_is_empty() {
+    assert!(Amount::default().is_empty());
+    assert!(encode(&Fee::default()).is_empty());
+}
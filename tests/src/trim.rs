@@ -0,0 +1,46 @@
+//! Tests for `string` fields whose decoded values are stripped of surrounding whitespace.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Account {
+    #[prost_amino(string, trim, tag = "1")]
+    pub address: String,
+    #[prost_amino(string, optional, trim, tag = "2")]
+    pub memo: Option<String>,
+    #[prost_amino(string, tag = "3")]
+    pub name: String,
+}
+
+#[test]
+fn trim_decoded_strings() {
+    let msg = Account {
+        address: "  addr  ".to_owned(),
+        memo: Some("\tmemo \n".to_owned()),
+        name: " name ".to_owned(),
+    };
+    let buf = msg.encode_to_vec();
+    let decoded = Account::decode(&buf[..]).unwrap();
+    assert_eq!(decoded.address, "addr");
+    assert_eq!(decoded.memo, Some("memo".to_owned()));
+    assert_eq!(decoded.name, " name ");
+
+    // The trimmed values are encoded, so the encoding differs from the decoded bytes.
+    assert_ne!(decoded.encode_to_vec(), buf);
+    assert_eq!(
+        Account::decode(&decoded.encode_to_vec()[..]).unwrap(),
+        decoded
+    );
+}
+
+#[test]
+fn trim_to_empty() {
+    let msg = Account {
+        address: "   ".to_owned(),
+        memo: Some(" ".to_owned()),
+        name: String::new(),
+    };
+    let decoded = Account::decode(&msg.encode_to_vec()[..]).unwrap();
+    assert_eq!(decoded.address, "");
+    assert_eq!(decoded.memo, Some(String::new()));
+}
@@ -0,0 +1,64 @@
+//! Tests for the `validate` method generated for messages with required fields.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Coin {
+    #[prost_amino(string, required, tag = "1")]
+    pub denom: String,
+    #[prost_amino(string, tag = "2")]
+    pub amount: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct MsgSend {
+    #[prost_amino(bytes, required, tag = "1")]
+    pub from_address: Vec<u8>,
+    #[prost_amino(message, repeated, tag = "2")]
+    pub amount: Vec<Coin>,
+}
+
+fn coin(denom: &str) -> Coin {
+    Coin {
+        denom: denom.to_owned(),
+        amount: String::new(),
+    }
+}
+
+#[test]
+fn required_string_must_not_be_empty() {
+    assert!(coin("uatom").validate().is_ok());
+
+    let error = coin("").validate().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "invalid Protobuf message: Coin.denom: required field is empty"
+    );
+}
+
+#[test]
+fn nested_messages_are_validated() {
+    let msg = MsgSend {
+        from_address: vec![1, 2, 3],
+        amount: vec![coin("uatom"), coin("")],
+    };
+    let error = msg.validate().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "invalid Protobuf message: MsgSend.amount[1].denom: required field is empty"
+    );
+
+    let msg = MsgSend {
+        from_address: Vec::new(),
+        amount: vec![coin("uatom")],
+    };
+    assert!(msg.validate().is_err());
+}
+
+#[test]
+fn invalid_messages_still_encode() {
+    let msg = coin("");
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+    assert_eq!(Coin::decode(&buf[..]).unwrap(), msg);
+}
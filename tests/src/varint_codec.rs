@@ -0,0 +1,97 @@
+//! Tests for fields with the `varint_codec` attribute, whose values are encoded by a custom
+//! `VarintCodec`.
+
+use bytes::{Buf, BufMut};
+
+use prost::encoding::{decode_key, WireType};
+use prost::{DecodeError, Message, VarintCodec};
+
+/// Encodes values in groups of seven bits like a standard varint, but with the most significant
+/// group first.
+pub struct MostSignificantFirst;
+
+impl VarintCodec for MostSignificantFirst {
+    fn encode<B>(value: u64, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        let len = MostSignificantFirst::encoded_len(value);
+        for i in (1..len).rev() {
+            buf.put_u8(((value >> (7 * i)) & 0x7F) as u8 | 0x80);
+        }
+        buf.put_u8((value & 0x7F) as u8);
+    }
+
+    fn decode<B>(buf: &mut B) -> Result<u64, DecodeError>
+    where
+        B: Buf,
+    {
+        let mut value = 0u64;
+        for _ in 0..10 {
+            if !buf.has_remaining() {
+                return Err(DecodeError::new("truncated varint"));
+            }
+            let byte = buf.get_u8();
+            value = (value << 7) | u64::from(byte & 0x7F);
+            if byte < 0x80 {
+                return Ok(value);
+            }
+        }
+        Err(DecodeError::new("invalid varint"))
+    }
+
+    fn encoded_len(value: u64) -> usize {
+        let bits = 64 - (value | 1).leading_zeros() as usize;
+        (bits + 6) / 7
+    }
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Counters {
+    #[prost_amino(uint64, varint_codec = "MostSignificantFirst", tag = "1")]
+    pub total: u64,
+    #[prost_amino(sint32, optional, varint_codec = "MostSignificantFirst", tag = "2")]
+    pub delta: Option<i32>,
+    #[prost_amino(uint32, tag = "3")]
+    pub plain: u32,
+}
+
+#[test]
+fn values_round_trip() {
+    for &total in &[0, 1, 127, 128, 300, u64::max_value()] {
+        let counters = Counters {
+            total,
+            delta: Some(-65),
+            plain: 300,
+        };
+        let buf = counters.encode_to_vec();
+        assert_eq!(buf.len(), counters.encoded_len());
+        assert_eq!(Counters::decode(&buf[..]).unwrap(), counters);
+    }
+}
+
+#[test]
+fn values_are_written_by_the_codec() {
+    let counters = Counters {
+        total: 300,
+        delta: None,
+        plain: 300,
+    };
+    let buf = counters.encode_to_vec();
+    // 300 is 0b10_0101100, written most significant group first by the codec, and least
+    // significant group first by the standard encoding of the plain field.
+    assert_eq!(buf, vec![0x08, 0x82, 0x2c, 0x18, 0xac, 0x02]);
+
+    let mut slice = &buf[..];
+    assert_eq!(decode_key(&mut slice).unwrap(), (1, WireType::Varint));
+}
+
+#[test]
+fn default_values_are_not_encoded() {
+    assert!(Counters::default().encode_to_vec().is_empty());
+}
+
+#[test]
+fn codec_errors_are_reported() {
+    assert!(Counters::decode(&[0x08, 0x82][..]).is_err());
+}
@@ -0,0 +1,66 @@
+//! Tests for varint fields padded to a fixed width with the `varint_width` attribute.
+
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Padded {
+    #[prost_amino(uint32, varint_width = "5", tag = "1")]
+    pub id: u32,
+    #[prost_amino(sint64, optional, varint_width = "10", tag = "2")]
+    pub offset: Option<i64>,
+    #[prost_amino(bool, required, varint_width = "2", tag = "3")]
+    pub flag: bool,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Minimal {
+    #[prost_amino(uint32, tag = "1")]
+    pub id: u32,
+    #[prost_amino(sint64, optional, tag = "2")]
+    pub offset: Option<i64>,
+    #[prost_amino(bool, required, tag = "3")]
+    pub flag: bool,
+}
+
+#[test]
+fn varints_are_padded() {
+    let msg = Padded {
+        id: 1,
+        offset: Some(-1),
+        flag: true,
+    };
+    let buf = msg.encode_to_vec();
+    assert_eq!(
+        buf,
+        vec![
+            0x08, 0x81, 0x80, 0x80, 0x80, 0x00, // id
+            0x10, 0x81, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00, // offset
+            0x18, 0x81, 0x00, // flag
+        ]
+    );
+    assert_eq!(msg.encoded_len(), buf.len());
+    assert_eq!(Padded::decode(&buf[..]).unwrap(), msg);
+
+    // Padded and minimal encodings decode to the same values.
+    let minimal = Minimal::decode(&buf[..]).unwrap();
+    assert_eq!(
+        (minimal.id, minimal.offset, minimal.flag),
+        (1, Some(-1), true)
+    );
+    let decoded = Padded::decode(&minimal.encode_to_vec()[..]).unwrap();
+    assert_eq!(decoded, msg);
+}
+
+#[test]
+fn padded_values_use_full_width() {
+    let msg = Padded {
+        id: u32::max_value(),
+        offset: Some(i64::min_value()),
+        flag: false,
+    };
+    let buf = msg.encode_to_vec();
+    // Each value is exactly as wide as its padding, plus a one byte key.
+    assert_eq!(buf.len(), 6 + 11 + 3);
+    assert_eq!(msg.encoded_len(), buf.len());
+    assert_eq!(Padded::decode(&buf[..]).unwrap(), msg);
+}
@@ -0,0 +1,166 @@
+//! Tests for `Message::visit_fields`, which passes the fields of a message to a `FieldVisitor`.
+
+use std::collections::BTreeMap;
+
+use prost::{FieldVisitor, Message, ScalarValue};
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Inner {
+    #[prost_amino(int64, tag = "1")]
+    pub value: i64,
+    #[prost_amino(string, tag = "2")]
+    pub note: String,
+}
+
+#[derive(Clone, PartialEq, Oneof)]
+pub enum Choice {
+    #[prost_amino(uint32, tag = "10")]
+    Count(u32),
+    #[prost_amino(message, tag = "11")]
+    Nested(Inner),
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Outer {
+    #[prost_amino(sint32, tag = "1")]
+    pub delta: i32,
+    #[prost_amino(uint64, optional, tag = "2")]
+    pub limit: Option<u64>,
+    #[prost_amino(int32, repeated, tag = "3")]
+    pub values: Vec<i32>,
+    #[prost_amino(message, optional, tag = "4")]
+    pub inner: Option<Inner>,
+    #[prost_amino(message, repeated, tag = "5")]
+    pub inners: Vec<Inner>,
+    #[prost_amino(btree_map = "string, fixed32", tag = "6")]
+    pub weights: BTreeMap<String, u32>,
+    #[prost_amino(oneof = "Choice", tags = "10, 11")]
+    pub choice: Option<Choice>,
+    #[prost_amino(bool, tag = "7")]
+    pub flag: bool,
+}
+
+/// Sums every integer value, including map keys and the fields of nested messages.
+#[derive(Default)]
+struct Sum {
+    total: i128,
+    visited: Vec<(u32, &'static str)>,
+}
+
+impl Sum {
+    fn add(&mut self, value: ScalarValue) {
+        match value {
+            ScalarValue::Int(value) => self.total += i128::from(value),
+            ScalarValue::Uint(value) => self.total += i128::from(value),
+            _ => (),
+        }
+    }
+}
+
+impl FieldVisitor for Sum {
+    fn visit_scalar(&mut self, tag: u32, name: &'static str, value: ScalarValue) {
+        self.visited.push((tag, name));
+        self.add(value);
+    }
+
+    fn visit_map_key(&mut self, _tag: u32, _name: &'static str, key: ScalarValue) {
+        self.add(key);
+    }
+}
+
+fn outer() -> Outer {
+    let mut weights = BTreeMap::new();
+    weights.insert("a".to_owned(), 1000);
+    weights.insert("b".to_owned(), 2000);
+    Outer {
+        delta: -5,
+        limit: Some(100),
+        values: vec![1, 2, 3],
+        inner: Some(Inner {
+            value: 10,
+            note: "ten".to_owned(),
+        }),
+        inners: vec![
+            Inner {
+                value: 20,
+                note: String::new(),
+            },
+            Inner {
+                value: 30,
+                note: String::new(),
+            },
+        ],
+        weights,
+        choice: Some(Choice::Count(7)),
+        flag: true,
+    }
+}
+
+#[test]
+fn sums_integer_fields() {
+    let mut sum = Sum::default();
+    outer().visit_fields(&mut sum);
+    assert_eq!(sum.total, -5 + 100 + 6 + 10 + 20 + 30 + 3000 + 7);
+}
+
+#[test]
+fn fields_are_visited_in_tag_order() {
+    let mut sum = Sum::default();
+    outer().visit_fields(&mut sum);
+    assert_eq!(
+        sum.visited,
+        [
+            (1, "delta"),
+            (2, "limit"),
+            (3, "values"),
+            (3, "values"),
+            (3, "values"),
+            (1, "value"),
+            (2, "note"),
+            (1, "value"),
+            (2, "note"),
+            (1, "value"),
+            (2, "note"),
+            (6, "weights"),
+            (6, "weights"),
+            (7, "flag"),
+            (10, "Count"),
+        ]
+    );
+}
+
+#[test]
+fn unset_fields_are_not_visited() {
+    let mut sum = Sum::default();
+    Outer::default().visit_fields(&mut sum);
+    // Only the singular scalars, which always hold a value.
+    assert_eq!(sum.visited, [(1, "delta"), (7, "flag")]);
+    assert_eq!(sum.total, 0);
+}
+
+/// Counts nested messages without descending into them.
+#[derive(Default)]
+struct Messages(Vec<(u32, &'static str)>);
+
+impl FieldVisitor for Messages {
+    fn visit_scalar(&mut self, _tag: u32, _name: &'static str, _value: ScalarValue) {}
+
+    fn visit_message<M>(&mut self, tag: u32, name: &'static str, _value: &M)
+    where
+        M: Message,
+    {
+        self.0.push((tag, name));
+    }
+}
+
+#[test]
+fn visit_message_may_skip_nested_fields() {
+    let mut outer = outer();
+    outer.choice = Some(Choice::Nested(Inner::default()));
+    let mut messages = Messages::default();
+    outer.visit_fields(&mut messages);
+    assert_eq!(
+        messages.0,
+        [(4, "inner"), (5, "inners"), (5, "inners"), (11, "Nested")]
+    );
+}
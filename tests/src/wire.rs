@@ -0,0 +1,88 @@
+//! Tests for overriding the wire encoding of scalar fields.
+
+use prost::Message;
+
+/// An `int64` field which a non-standard producer emits as a `fixed64`.
+#[derive(Clone, PartialEq, Message)]
+pub struct FixedInt64 {
+    #[prost_amino(int64, wire = "fixed", tag = "1")]
+    pub value: i64,
+}
+
+#[test]
+fn int64_as_fixed64() {
+    let msg = FixedInt64 { value: -2 };
+
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+    // Key (tag 1, wire type 1) followed by the little-endian two's complement value.
+    assert_eq!(
+        buf,
+        vec![0x09, 0xfe, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]
+    );
+    assert_eq!(msg.encoded_len(), buf.len());
+
+    assert_eq!(FixedInt64::decode(&buf[..]).unwrap(), msg);
+}
+
+/// An `int64` field which a non-standard producer emits with zigzag encoding, like a `sint64`.
+#[derive(Clone, PartialEq, Message)]
+pub struct ZigzagInt64 {
+    #[prost_amino(int64, signed_varint, tag = "1")]
+    pub value: i64,
+    #[prost_amino(int64, zigzag, repeated, tag = "2")]
+    pub values: Vec<i64>,
+}
+
+#[test]
+fn int64_as_sint64() {
+    let msg = ZigzagInt64 {
+        value: -2,
+        values: vec![-1, 1, ::std::i64::MIN],
+    };
+
+    let mut buf = Vec::new();
+    msg.encode(&mut buf).unwrap();
+    // Key (tag 1, wire type 0) followed by the zigzag encoded value.
+    assert_eq!(&buf[..2], &[0x08, 0x03]);
+    assert_eq!(msg.encoded_len(), buf.len());
+
+    assert_eq!(ZigzagInt64::decode(&buf[..]).unwrap(), msg);
+}
+
+/// Fixed width fields which a non-standard producer emits in big-endian byte order.
+#[derive(Clone, PartialEq, Message)]
+pub struct BigEndian {
+    #[prost_amino(fixed32, endian = "big", tag = "1")]
+    pub id: u32,
+    #[prost_amino(sfixed64, endian = "big", repeated, tag = "2")]
+    pub offsets: Vec<i64>,
+    #[prost_amino(double, endian = "little", tag = "3")]
+    pub ratio: f64,
+    #[prost_amino(uint64, wire = "fixed", endian = "big", tag = "4")]
+    pub count: u64,
+}
+
+#[test]
+fn big_endian_fixed_width() {
+    let msg = BigEndian {
+        id: 0x0102_0304,
+        offsets: vec![-2],
+        ratio: 0.5,
+        count: 0x0a0b,
+    };
+
+    let buf = msg.encode_to_vec();
+    assert_eq!(
+        buf,
+        vec![
+            0x0d, 0x01, 0x02, 0x03, 0x04, // id
+            0x12, 0x08, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe, // offsets, packed
+            0x19, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xe0, 0x3f, // ratio
+            0x21, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0a, 0x0b, // count
+        ]
+    );
+    assert_eq!(msg.encoded_len(), buf.len());
+
+    assert_eq!(BigEndian::decode(&buf[..]).unwrap(), msg);
+}
@@ -0,0 +1,111 @@
+//! Tests for `bytes` fields with the `zstd` attribute, whose values are compressed on the wire.
+
+use prost::encoding::zstd_bytes;
+use prost::Message;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Blob {
+    #[prost_amino(bytes, zstd, tag = "1")]
+    pub data: Vec<u8>,
+    #[prost_amino(bytes, zstd, optional, tag = "2")]
+    pub extra: Option<Vec<u8>>,
+    #[prost_amino(bytes, zstd, repeated, tag = "3")]
+    pub chunks: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Capped {
+    #[prost_amino(bytes, zstd, max_len = "1024", tag = "1")]
+    pub data: Vec<u8>,
+}
+
+/// The same fields as `Blob`, without compression.
+#[derive(Clone, PartialEq, Message)]
+pub struct Raw {
+    #[prost_amino(bytes, tag = "1")]
+    pub data: Vec<u8>,
+}
+
+fn blob() -> Blob {
+    Blob {
+        data: b"amino ".repeat(1000),
+        extra: Some(Vec::new()),
+        chunks: vec![vec![0; 4096], b"short".to_vec()],
+    }
+}
+
+#[test]
+fn round_trip() {
+    let blob = blob();
+    let buf = blob.encode_to_vec();
+    assert_eq!(buf.len(), blob.encoded_len());
+    assert_eq!(Blob::decode(&buf[..]).unwrap(), blob);
+
+    assert_eq!(Blob::decode(&[][..]).unwrap(), Blob::default());
+}
+
+#[test]
+fn wire_bytes_are_compressed() {
+    let blob = Blob {
+        data: b"amino ".repeat(1000),
+        ..Blob::default()
+    };
+    let buf = blob.encode_to_vec();
+    assert!(buf.len() < 100, "{} bytes", buf.len());
+
+    let raw = Raw::decode(&buf[..]).unwrap();
+    assert_eq!(raw.data, zstd_bytes::compress(&blob.data));
+    assert_eq!(
+        zstd_bytes::decompress(&raw.data, blob.data.len()).unwrap(),
+        blob.data
+    );
+}
+
+#[test]
+fn decompressed_length_is_capped() {
+    let data = vec![7; 1025];
+    let buf = Raw {
+        data: zstd_bytes::compress(&data),
+    }
+    .encode_to_vec();
+    // The compressed value is far shorter than the cap, which applies to the decompressed value.
+    assert!(buf.len() < 1024);
+    let error = Capped::decode(&buf[..]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "failed to decode Protobuf message: Capped.data: \
+         decompressed value too large: exceeds maximum of 1024 bytes"
+    );
+    assert_eq!(error.max_len_exceeded(), Some((1, 1024)));
+
+    let capped = Capped {
+        data: vec![7; 1024],
+    };
+    let buf = capped.encode_to_vec();
+    assert_eq!(Capped::decode(&buf[..]).unwrap(), capped);
+}
+
+#[test]
+fn invalid_compressed_value() {
+    let buf = Raw {
+        data: b"not zstd".to_vec(),
+    }
+    .encode_to_vec();
+    let error = Blob::decode(&buf[..]).unwrap_err().to_string();
+    assert!(
+        error.starts_with("failed to decode Protobuf message: Blob.data: invalid zstd value: "),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn encoding_after_encoded_len_of_changed_value() {
+    // A value which changes after its encoded length is computed is compressed again.
+    let mut blob = blob();
+    blob.encoded_len();
+    blob.data = b"changed ".repeat(1000);
+    let buf = blob.encode_to_vec();
+    assert_eq!(buf.len(), blob.encoded_len());
+    assert_eq!(Blob::decode(&buf[..]).unwrap(), blob);
+}